@@ -0,0 +1,38 @@
+// build.rs
+//
+// Records the locked version of every dependency for `rsimg formats`, which
+// names the codec libraries compiled into the binary, and the target triple
+// `rsimg self-update` picks release binaries by. Builds without a Cargo.lock
+// next to the manifest get an empty version list.
+
+use std::path::Path;
+
+fn main() {
+    let target = std::env::var("TARGET").expect("set by cargo");
+    println!("cargo:rustc-env=RSIMG_TARGET={target}");
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").expect("set by cargo");
+    let lock = Path::new(&manifest_dir).join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock.display());
+
+    // Packages are `name = "..."` lines each followed by `version = "..."`
+    let text = std::fs::read_to_string(&lock).unwrap_or_default();
+    let mut versions = String::new();
+    let mut name = None;
+    for line in text.lines() {
+        if let Some(value) = line.strip_prefix("name = ") {
+            name = Some(value);
+        } else if let Some(version) = line.strip_prefix("version = ")
+            && let Some(name) = name.take()
+        {
+            versions.push_str(&format!("({name}, {version}),"));
+        }
+    }
+
+    let out_dir = std::env::var("OUT_DIR").expect("set by cargo");
+    std::fs::write(
+        Path::new(&out_dir).join("versions.rs"),
+        format!("&[{versions}]"),
+    )
+    .expect("OUT_DIR is writable");
+}
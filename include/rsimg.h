@@ -0,0 +1,52 @@
+/* include/rsimg.h
+ *
+ * C API of the rsimg library (librsimg.so / librsimg.a, built with
+ * `cargo build --release --lib`). See src/ffi.rs.
+ */
+
+#ifndef RSIMG_H
+#define RSIMG_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Return values of rsimg_optimize (the same as the CLI exit codes) */
+#define RSIMG_OK 0
+#define RSIMG_FAILURE 1
+#define RSIMG_INVALID_CONFIG 4
+
+typedef struct RsimgOptions {
+    /* Comma-separated output formats ("jpg,webp,png"); NULL for jpg,webp */
+    const char *formats;
+    /* Scale percentages (10-100); NULL or empty for 75,50,25 */
+    const uint32_t *scales;
+    size_t scales_len;
+    /* 0-100, or negative for the default (80) */
+    int quality;
+    /* Passed back unchanged to the callback */
+    void *user_data;
+} RsimgOptions;
+
+/* Receives one output; `format` and `data` are only valid during the call */
+typedef void (*RsimgOutputCallback)(void *user_data, uint32_t scale,
+                                    const char *format, const uint8_t *data,
+                                    size_t len);
+
+/* Decodes the image in buf[0..len] and calls `callback` for every
+ * scale/format combination, scale-major. `options` may be NULL. */
+int rsimg_optimize(const uint8_t *buf, size_t len, const RsimgOptions *options,
+                   RsimgOutputCallback callback);
+
+/* Message of the last failure on this thread, or NULL; valid until the next
+ * rsimg_optimize call on the same thread */
+const char *rsimg_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RSIMG_H */
@@ -0,0 +1,35 @@
+/* include/rsimg_plugin.h
+ *
+ * Interface of native transform plugins, loaded with `rsimg --plugins FILE`.
+ * Build a shared library exporting rsimg_transform; see src/plugin.rs for
+ * the config file and the WebAssembly variant.
+ */
+
+#ifndef RSIMG_PLUGIN_H
+#define RSIMG_PLUGIN_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct RsimgPluginContext {
+    /* Source file of the image */
+    const char *source;
+    /* Scale percentage of the variant, or 0 for "before-scale" plugins */
+    uint32_t scale;
+    /* The plugin's `options` string from the config file ("" if unset) */
+    const char *options;
+} RsimgPluginContext;
+
+/* Edits width * height RGBA8 pixels (row-major) in place. Called from several
+ * threads at once. Returns 0 on success; anything else fails the image. */
+int rsimg_transform(uint8_t *rgba, uint32_t width, uint32_t height,
+                    const RsimgPluginContext *ctx);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RSIMG_PLUGIN_H */
@@ -0,0 +1,164 @@
+// src/budget.rs
+//
+// Total output size budgets (`--total-budget 50MB`) for asset packs and
+// email campaigns with a hard limit on what they may weigh. The batch runs
+// once as configured; while its outputs add up to more than the budget it
+// runs again, first at lower qualities and, once those bottom out (or no
+// lossy format is requested), without its largest scale. Outputs of dropped
+// scales are removed at the end.
+
+use crate::encode::{QualityLevel, QualityPreset, tiles_dir};
+use crate::i18n::{Msg, tr};
+use crate::paths::long_path;
+use crate::processor::{self, Inputs, ProcessOptions};
+use crate::progress::Progress;
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Quality never goes below this while fitting a budget
+const MIN_QUALITY: u8 = 30;
+/// Quality is lowered by this much per pass
+const QUALITY_STEP: u8 = 10;
+
+/// Clap value parser for sizes such as `50MB`, `800 KB` or `1.5GiB`
+///
+/// Units are binary (1 MB = 1024 KB), matching how sizes are reported.
+pub fn parse_size_arg(s: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid size '{s}' (expected e.g. 50MB, 800KB or 1.5GB)");
+    let trimmed = s.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let factor = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1u64,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        _ => return Err(invalid()),
+    };
+    let bytes = (number * factor as f64).round();
+    if bytes < 1.0 {
+        return Err(invalid());
+    }
+    Ok(bytes as u64)
+}
+
+/// Human-readable byte count
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// Processes `files` until their outputs fit within `budget` bytes,
+/// lowering `opts`' quality and dropping scales as needed
+pub fn process_within_budget(
+    files: &[PathBuf],
+    opts: &mut ProcessOptions,
+    progress: &Progress,
+    budget: u64,
+) -> Result<()> {
+    let mut written = HashSet::new();
+    loop {
+        let outputs =
+            processor::process_all(Inputs::Files(files.to_vec()), opts, progress)?.outputs;
+        let total = outputs
+            .iter()
+            .map(|path| output_size(path))
+            .sum::<Result<u64>>()?;
+        written.extend(outputs.iter().cloned());
+
+        if total <= budget {
+            // Outputs of scales dropped along the way
+            let kept: HashSet<_> = outputs.into_iter().collect();
+            for stale in written.difference(&kept) {
+                remove_output(stale)?;
+            }
+            return Ok(());
+        }
+
+        let (total, budget) = (format_size(total), format_size(budget));
+        let change = if let Some(quality) = lower_quality(opts) {
+            tr!(Msg::BudgetQuality, total, budget, quality)
+        } else if opts.scales.len() > 1 {
+            let largest = opts.scales.iter().copied().max().unwrap_or_default();
+            opts.scales.retain(|&scale| scale != largest);
+            tr!(Msg::BudgetScale, total, budget, largest)
+        } else {
+            anyhow::bail!(tr!(Msg::BudgetUnreachable, total, budget));
+        };
+        eprintln!("\n  {} {}\n", "⚖️ ".yellow(), change);
+    }
+}
+
+/// Lowers the quality of lossy outputs one step, returning the new JPEG
+/// quality, or `None` when there's nothing left to lower
+fn lower_quality(opts: &mut ProcessOptions) -> Option<u8> {
+    let lossy = opts
+        .formats
+        .iter()
+        .any(|fmt| matches!(fmt.to_lowercase().as_str(), "jpg" | "jpeg" | "webp"));
+    if !lossy {
+        return None;
+    }
+    let step = |quality: u8| quality.saturating_sub(QUALITY_STEP).max(MIN_QUALITY);
+
+    let encode = &mut opts.encode;
+    match &mut encode.preset {
+        Some(preset) => {
+            if preset.jpg <= MIN_QUALITY && preset.webp <= MIN_QUALITY {
+                return None;
+            }
+            // Lossless WebP can't shrink: continue from the high level
+            if preset.level == QualityLevel::Lossless {
+                *preset = QualityPreset::builtin(QualityLevel::High);
+            } else {
+                (preset.jpg, preset.webp) = (step(preset.jpg), step(preset.webp));
+            }
+            Some(preset.jpg)
+        }
+        None => {
+            if encode.quality <= MIN_QUALITY {
+                return None;
+            }
+            encode.quality = step(encode.quality);
+            Some(encode.quality)
+        }
+    }
+}
+
+/// Bytes taken by an output: the file, or every tile of a tiled one
+fn output_size(path: &Path) -> Result<u64> {
+    let tiles = tiles_dir(path);
+    if !long_path(path).is_file() && long_path(&tiles).is_dir() {
+        let entries = std::fs::read_dir(long_path(&tiles))
+            .with_context(|| format!("Failed to read tile directory: {}", tiles.display()))?;
+        return entries
+            .map(|entry| Ok(entry?.metadata()?.len()))
+            .sum::<std::io::Result<u64>>()
+            .with_context(|| format!("Failed to read tile directory: {}", tiles.display()));
+    }
+    Ok(std::fs::metadata(long_path(path))
+        .with_context(|| format!("Failed to read file: {}", path.display()))?
+        .len())
+}
+
+/// Deletes an output, tiled or not
+fn remove_output(path: &Path) -> Result<()> {
+    let tiles = tiles_dir(path);
+    if long_path(&tiles).is_dir() {
+        std::fs::remove_dir_all(long_path(&tiles))
+            .with_context(|| format!("Failed to remove {}", tiles.display()))?;
+    }
+    if long_path(path).is_file() {
+        std::fs::remove_file(long_path(path))
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
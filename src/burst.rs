@@ -0,0 +1,126 @@
+// src/burst.rs
+//
+// `--dedupe-bursts`: event dumps are full of bursts, runs of near-identical
+// frames shot fractions of a second apart, of which one is wanted. Inputs
+// are ordered by when they were taken (EXIF DateTimeOriginal with its
+// sub-seconds, else the file's modification time); consecutive frames
+// taken within the gap of each other whose difference hashes differ in few
+// bits form a burst, and only its sharpest frame, by the variance of the
+// Laplacian of a reduced copy, goes on to be processed. Every input is
+// decoded once for this, so the culling pays off on bursts, not on
+// collections of distinct shots.
+
+use crate::decode;
+use crate::screen::{laplacian_variance, reduce};
+use image::GrayImage;
+use image::imageops::{self, FilterType};
+use rayon::iter::Either;
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Most bits two frames' difference hashes may differ in to be one scene
+const MAX_DISTANCE: u32 = 10;
+
+/// Inputs left after culling bursts
+pub struct Culled {
+    /// The sharpest frame of each burst and every input outside one, in
+    /// path order
+    pub kept: Vec<PathBuf>,
+    pub bursts: usize,
+    /// Frames left out
+    pub dropped: usize,
+}
+
+/// What a frame is compared by
+struct Frame {
+    path: PathBuf,
+    /// Capture time in seconds
+    time: Option<f64>,
+    hash: u64,
+    sharpness: f64,
+}
+
+/// Keeps the sharpest frame of each burst among `files`, frames at most
+/// `gap` seconds apart; inputs that don't decode are kept, to fail later
+pub fn keep_sharpest(files: Vec<PathBuf>, gap: f64) -> Culled {
+    let (mut frames, unread): (Vec<Frame>, Vec<PathBuf>) =
+        files
+            .into_par_iter()
+            .partition_map(|path| match analyze(&path) {
+                Some(frame) => Either::Left(frame),
+                None => Either::Right(path),
+            });
+    // Frames without a time come last, each alone
+    frames.sort_by(|a, b| {
+        let time = |frame: &Frame| frame.time.unwrap_or(f64::INFINITY);
+        time(a)
+            .total_cmp(&time(b))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+
+    let mut kept = unread;
+    let (mut bursts, mut dropped) = (0, 0);
+    let mut run: Vec<Frame> = Vec::new();
+    let mut close = |run: &mut Vec<Frame>| {
+        if run.len() > 1 {
+            bursts += 1;
+            dropped += run.len() - 1;
+        }
+        if let Some(best) = run
+            .drain(..)
+            .max_by(|a, b| a.sharpness.total_cmp(&b.sharpness))
+        {
+            kept.push(best.path);
+        }
+    };
+    for frame in frames {
+        let joins = run.last().is_some_and(|last| {
+            matches!((last.time, frame.time), (Some(a), Some(b)) if b - a <= gap)
+                && (last.hash ^ frame.hash).count_ones() <= MAX_DISTANCE
+        });
+        if !joins {
+            close(&mut run);
+        }
+        run.push(frame);
+    }
+    close(&mut run);
+
+    kept.sort_unstable();
+    Culled {
+        kept,
+        bursts,
+        dropped,
+    }
+}
+
+fn analyze(path: &Path) -> Option<Frame> {
+    let img = decode::load_image(path).ok()?;
+    let gray = reduce(&img);
+    let time = decode::load_metadata(path)
+        .and_then(|meta| meta.capture_info().seconds)
+        .or_else(|| {
+            let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+            Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs_f64())
+        });
+    Some(Frame {
+        path: path.to_path_buf(),
+        time,
+        hash: difference_hash(&gray),
+        sharpness: laplacian_variance(&gray),
+    })
+}
+
+/// 64 bits, each whether a pixel of a 9x8 reduction is brighter than its
+/// right neighbour
+fn difference_hash(gray: &GrayImage) -> u64 {
+    let small = imageops::resize(gray, 9, 8, FilterType::Triangle);
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let bit = small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0];
+            hash = hash << 1 | u64::from(bit);
+        }
+    }
+    hash
+}
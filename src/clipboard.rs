@@ -0,0 +1,94 @@
+// src/clipboard.rs
+//
+// `--from-clipboard` and `--to-clipboard`, for optimizing a screenshot
+// without saving it first. The clipboard is an input source beside file
+// paths: its image is staged as a lossless PNG named `clipboard` in a
+// temporary directory, and goes through the batch like any file. Clipboards
+// hold bitmaps rather than encoded files, so the largest output of the batch
+// is decoded and copied back as pixels, with the resize and any compression
+// artifacts of its format. On X11 and Wayland the copy outlives rsimg only
+// when a clipboard manager takes it over.
+
+use anyhow::{Context, Result};
+use arboard::{Clipboard, ImageData};
+use image::{ImageFormat, RgbaImage};
+use rsimg::decode;
+use rsimg::i18n::{Msg, tr};
+use rsimg::scratch::ScratchDir;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// The clipboard's image, staged as a file for the batch; removed on drop
+pub struct ClipboardInput {
+    dir: ScratchDir,
+    path: PathBuf,
+}
+
+impl ClipboardInput {
+    /// Stages the image on the clipboard in a directory under `tmpdir`
+    pub fn read(tmpdir: &Path) -> Result<Self> {
+        let image = open()?.get_image().map_err(|err| match err {
+            arboard::Error::ContentNotAvailable => anyhow::anyhow!(tr!(Msg::ClipboardEmpty)),
+            err => anyhow::Error::new(err).context(tr!(Msg::ClipboardReadFailed)),
+        })?;
+        let image = RgbaImage::from_raw(
+            image.width as u32,
+            image.height as u32,
+            image.bytes.into_owned(),
+        )
+        .with_context(|| tr!(Msg::ClipboardSize))?;
+
+        // Owning the directory from here on removes it on failure, too
+        let dir = ScratchDir::new(tmpdir, "rsimg-clipboard")?;
+        let input = Self {
+            path: dir.path().join("clipboard.png"),
+            dir,
+        };
+        image
+            .save_with_format(&input.path, ImageFormat::Png)
+            .with_context(|| format!("Failed to write {}", input.path.display()))?;
+        Ok(input)
+    }
+
+    /// The staged image
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where outputs go without --output: the staging directory when
+    /// they're only copied back, the current directory otherwise
+    pub fn output_dir(&self, to_clipboard: bool) -> PathBuf {
+        match to_clipboard {
+            true => self.dir.path().to_path_buf(),
+            false => PathBuf::from("."),
+        }
+    }
+}
+
+/// Copies the output with the most pixels to the clipboard, returning it
+pub fn copy_largest(outputs: &[PathBuf]) -> Result<&Path> {
+    let largest = outputs
+        .iter()
+        .filter_map(|path| {
+            let (width, height) = decode::load_dimensions(path).ok()?;
+            Some((u64::from(width) * u64::from(height), path))
+        })
+        .max_by_key(|&(pixels, _)| pixels)
+        .map(|(_, path)| path)
+        .with_context(|| tr!(Msg::ClipboardNoOutput))?;
+
+    let image = decode::load_image(largest)?.to_rgba8();
+    let data = ImageData {
+        width: image.width() as usize,
+        height: image.height() as usize,
+        bytes: Cow::Owned(image.into_raw()),
+    };
+    open()?
+        .set_image(data)
+        .with_context(|| tr!(Msg::ClipboardCopyFailed))?;
+    Ok(largest)
+}
+
+fn open() -> Result<Clipboard> {
+    Clipboard::new().with_context(|| tr!(Msg::ClipboardOpenFailed))
+}
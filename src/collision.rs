@@ -0,0 +1,106 @@
+// src/collision.rs
+//
+// Output name collisions. Every output of an input is named after its file
+// stem, so `a/logo.png` and `b/logo.jpg` written to one `--output` directory
+// (or `logo.png` and `logo.jpg` side by side) would overwrite each other.
+// Stems are compared case-insensitively, as macOS and Windows filesystems
+// do. Before a batch starts, colliding inputs either stop it with a list of
+// the clashes or get distinct stems (`--on-collision`).
+
+use crate::exit::InvalidConfig;
+use crate::i18n::{Msg, tr};
+use anyhow::Result;
+use clap::ValueEnum;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// What to do when inputs would write the same output files
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OnCollision {
+    /// Stop before processing, listing the colliding inputs
+    #[default]
+    Error,
+    /// Number the later inputs (`logo-2`, `logo-3`, ...)
+    Suffix,
+    /// Add a short hash of each colliding input's path (`logo-3fa2b1c0`)
+    Hash,
+}
+
+/// Output stem of every input whose outputs would collide with another's,
+/// in input order; inputs missing from the map keep their file stem
+pub fn output_stems(
+    inputs: &[&Path],
+    output_dir: Option<&Path>,
+    strategy: OnCollision,
+) -> Result<HashMap<PathBuf, String>> {
+    // Inputs by output directory and lowercased stem; the same path listed
+    // twice (`--jobs` rows) isn't a collision
+    let mut groups: Vec<(PathBuf, Vec<&Path>)> = Vec::new();
+    let mut index: HashMap<(PathBuf, String), usize> = HashMap::new();
+    for &input in inputs {
+        let Some(key) = output_key(input, output_dir) else {
+            continue;
+        };
+        let dir = key.0.clone();
+        let group = *index.entry(key).or_insert_with(|| {
+            groups.push((dir, Vec::new()));
+            groups.len() - 1
+        });
+        if !groups[group].1.contains(&input) {
+            groups[group].1.push(input);
+        }
+    }
+
+    let clashes: Vec<&(PathBuf, Vec<&Path>)> =
+        groups.iter().filter(|(_, paths)| paths.len() > 1).collect();
+    if clashes.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    if strategy == OnCollision::Error {
+        let mut message = tr!(Msg::Collisions, clashes.len());
+        for (dir, paths) in &clashes {
+            let stem = stem(paths[0]).unwrap_or_default();
+            message.push_str(&format!("\n  {}:", dir.join(format!("{stem}_*")).display()));
+            for path in paths {
+                message.push_str(&format!("\n    {}", path.display()));
+            }
+        }
+        return Err(InvalidConfig(message).into());
+    }
+
+    // Stems already taken in each directory, so a new one can't clash with
+    // another input's real stem
+    let mut taken: HashSet<(PathBuf, String)> = index.into_keys().collect();
+    let mut stems = HashMap::new();
+    for (dir, paths) in clashes {
+        for (i, &path) in paths.iter().enumerate() {
+            let stem = stem(path).unwrap_or_default();
+            let renamed = match strategy {
+                OnCollision::Suffix if i == 0 => continue,
+                OnCollision::Suffix => (i + 1..)
+                    .map(|n| format!("{stem}-{n}"))
+                    .find(|name| !taken.contains(&(dir.clone(), name.to_lowercase())))
+                    .expect("unbounded range"),
+                _ => {
+                    let hash = blake3::hash(path.as_os_str().as_encoded_bytes());
+                    format!("{stem}-{}", &hash.to_hex()[..8])
+                }
+            };
+            taken.insert((dir.clone(), renamed.to_lowercase()));
+            stems.insert(path.to_path_buf(), renamed);
+        }
+    }
+    Ok(stems)
+}
+
+/// Directory and lowercased stem an input's outputs are named by; inputs
+/// sharing them collide
+pub fn output_key(input: &Path, output_dir: Option<&Path>) -> Option<(PathBuf, String)> {
+    let dir = output_dir.or(input.parent()).unwrap_or(Path::new(""));
+    Some((dir.to_path_buf(), stem(input)?.to_lowercase()))
+}
+
+fn stem(path: &Path) -> Option<&str> {
+    path.file_stem().and_then(|s| s.to_str())
+}
@@ -0,0 +1,82 @@
+// src/completions.rs
+//
+// `rsimg completions <shell>` and `rsimg man`: shell completion scripts and
+// roff man pages generated from the argument definitions, so they never
+// drift from the flags. Values the shells can offer come from the parsers:
+// --formats lists the encoders compiled into this binary, and options that
+// also take free-form values (--quality, --breakpoints) suggest their named
+// ones through `Suggest`.
+
+use anyhow::{Context, Result};
+use clap::builder::{PossibleValue, TypedValueParser};
+use clap::{Arg, Command};
+use clap_complete::Shell;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate the completion script for
+    #[arg(value_enum, value_name = "SHELL", help = "Target shell")]
+    pub shell: Shell,
+}
+
+#[derive(clap::Args)]
+pub struct ManArgs {
+    /// Directory to write `rsimg.1` and a page per subcommand to, instead of
+    /// printing the main page
+    #[arg(long, value_name = "DIR", help = "Write every man page into DIR")]
+    pub out_dir: Option<PathBuf>,
+}
+
+/// Prints the completion script for a shell on stdout
+pub fn completions(args: CompletionsArgs, mut command: Command) -> Result<()> {
+    clap_complete::generate(args.shell, &mut command, "rsimg", &mut std::io::stdout());
+    Ok(())
+}
+
+/// Prints the man page, or writes all of them into a directory
+pub fn man(args: ManArgs, command: Command) -> Result<()> {
+    match args.out_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+            clap_mangen::generate_to(command, &dir)
+                .with_context(|| format!("Failed to write man pages to {}", dir.display()))
+        }
+        None => clap_mangen::Man::new(command)
+            .render(&mut std::io::stdout())
+            .context("Failed to write the man page"),
+    }
+}
+
+/// A value parser offering `values` to shell completion while still
+/// accepting whatever `parser` does
+#[derive(Clone)]
+pub struct Suggest<P> {
+    parser: P,
+    values: &'static [&'static str],
+}
+
+impl<P> Suggest<P> {
+    pub fn new(parser: P, values: &'static [&'static str]) -> Self {
+        Self { parser, values }
+    }
+}
+
+impl<P: TypedValueParser> TypedValueParser for Suggest<P> {
+    type Value = P::Value;
+
+    fn parse_ref(
+        &self,
+        cmd: &Command,
+        arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        self.parser.parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(self.values.iter().map(PossibleValue::new)))
+    }
+}
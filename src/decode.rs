@@ -0,0 +1,662 @@
+// src/decode.rs
+//
+// Input decoding. Files are memory-mapped and decoded straight from the
+// mapping, which avoids a second in-memory copy of large sources; when the
+// filesystem refuses the mapping we fall back to ordinary buffered reads.
+// The format comes from the file's leading bytes when they identify it,
+// and from the extension otherwise. CMYK JPEGs from print workflows take
+// their own path, as their ink values may or may not be stored inverted.
+// Animated GIF, WebP and APNG inputs become the single frame picked with
+// `--frame` (the first by default). With `--use-embedded-preview` a JPEG
+// preview stored in the file stands in for the image when it's large enough,
+// and is the only way camera RAW files are read.
+
+use crate::encode::{AnimationFrame, Metadata};
+#[cfg(feature = "native")]
+use crate::encode::{embedded_previews, find_marker};
+use crate::i18n::{Msg, tr};
+use crate::paths::long_path;
+#[cfg(feature = "native")]
+use crate::pipeline::scaled_size;
+use anyhow::{Context, Result};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::metadata::Orientation;
+use image::{
+    AnimationDecoder, DynamicImage, ImageDecoder, ImageFormat, ImageReader, ImageResult, RgbImage,
+    RgbaImage,
+};
+#[cfg(feature = "native")]
+use memmap2::Mmap;
+#[cfg(feature = "native")]
+use std::fs::File;
+use std::io::Cursor;
+#[cfg(feature = "native")]
+use std::io::Read;
+use std::path::Path;
+use zune_core::bytestream::ZCursor;
+use zune_core::colorspace::ColorSpace;
+use zune_core::options::DecoderOptions;
+use zune_jpeg::JpegDecoder;
+
+/// Camera RAW formats, read through their embedded previews; the TIFF-based
+/// ones
+pub const RAW_EXTENSIONS: &[&str] = &["dng", "cr2", "nef", "arw", "pef"];
+/// How far a preview's aspect ratio may stray from the image's, as a
+/// fraction; letterboxed thumbnails are further off
+#[cfg(feature = "native")]
+const PREVIEW_ASPECT_TOLERANCE: f64 = 0.01;
+
+/// Frame of an animated input used as the still image
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Frame {
+    #[default]
+    First,
+    Middle,
+    /// Zero-based; past the end means the last frame
+    Index(usize),
+}
+
+/// Clap value parser for `--frame first|middle|N`
+pub fn parse_frame_arg(s: &str) -> Result<Frame, String> {
+    match s.trim().to_lowercase().as_str() {
+        "first" => Ok(Frame::First),
+        "middle" => Ok(Frame::Middle),
+        n => n
+            .parse()
+            .map(Frame::Index)
+            .map_err(|_| format!("invalid frame '{s}' (expected first, middle or a frame number)")),
+    }
+}
+
+/// Loads an image from disk, preferring a memory-mapped read
+pub fn load_image(path: &Path) -> Result<DynamicImage> {
+    load_frame(path, Frame::First)
+}
+
+/// Loads an image from disk, taking `frame` of animated files
+#[cfg(feature = "native")]
+#[tracing::instrument(name = "decode", skip_all, fields(path = %path.display()))]
+pub fn load_frame(path: &Path, frame: Frame) -> Result<DynamicImage> {
+    let file = File::open(long_path(path)).with_context(|| tr!(Msg::OpenFailed, path.display()))?;
+
+    // Safety: the mapping is read-only and dropped before this function
+    // returns. A concurrent writer truncating the file could still fault,
+    // the same caveat every mmap-based reader accepts.
+    match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => decode_frame(&mmap, path, frame),
+        // Some filesystems (FUSE mounts, certain network shares) and empty
+        // files can't be mapped: read through a regular buffer instead
+        Err(_) => {
+            let bytes = std::fs::read(long_path(path))
+                .with_context(|| tr!(Msg::OpenFailed, path.display()))?;
+            decode_frame(&bytes, path, frame)
+        }
+    }
+}
+
+/// Loads an image from disk, taking `frame` of animated files
+#[cfg(not(feature = "native"))]
+#[tracing::instrument(name = "decode", skip_all, fields(path = %path.display()))]
+pub fn load_frame(path: &Path, frame: Frame) -> Result<DynamicImage> {
+    let bytes =
+        std::fs::read(long_path(path)).with_context(|| tr!(Msg::OpenFailed, path.display()))?;
+    decode_frame(&bytes, path, frame)
+}
+
+/// An embedded preview standing in for an image
+pub struct Preview {
+    /// The preview, upright
+    pub image: DynamicImage,
+    /// Upright size of the image it previews, `None` for camera RAW files
+    pub source: Option<(u32, u32)>,
+}
+
+/// The smallest embedded JPEG preview at least as large as the image at
+/// `scale` percent, and of its aspect ratio; the largest preview of camera
+/// RAW files, which aren't read otherwise. `None` when there's no such
+/// preview
+#[cfg(feature = "native")]
+pub fn load_preview(path: &Path, scale: u32) -> Result<Option<Preview>> {
+    let file = File::open(long_path(path)).with_context(|| tr!(Msg::OpenFailed, path.display()))?;
+    // Safety: as in `load_image`
+    match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => decode_preview(&mmap, path, scale),
+        Err(_) => {
+            let bytes = std::fs::read(long_path(path))
+                .with_context(|| tr!(Msg::OpenFailed, path.display()))?;
+            decode_preview(&bytes, path, scale)
+        }
+    }
+}
+
+/// Picks and decodes the preview `load_preview` describes
+#[cfg(feature = "native")]
+fn decode_preview(bytes: &[u8], path: &Path, scale: u32) -> Result<Option<Preview>> {
+    let (previews, orientation) = embedded_previews(bytes);
+    let orientation = orientation
+        .and_then(|tag| Orientation::from_exif(u8::try_from(tag).ok()?))
+        .unwrap_or(Orientation::NoTransforms);
+    let sideways = matches!(
+        orientation,
+        Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH
+    );
+    let is_raw = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| RAW_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+    let source = match is_raw {
+        true => None,
+        false => Some(load_dimensions(path)?),
+    };
+
+    // Upright size of each preview, from its header
+    let sized = previews.into_iter().filter_map(|preview| {
+        let reader = ImageReader::with_format(Cursor::new(preview), ImageFormat::Jpeg);
+        let (width, height) = reader.into_dimensions().ok()?;
+        let size = if sideways {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        Some((preview, size))
+    });
+    let area = |(width, height): (u32, u32)| u64::from(width) * u64::from(height);
+    let picked = match source {
+        None => sized.max_by_key(|&(_, size)| area(size)),
+        Some(source) => {
+            let needed = scaled_size(source, scale);
+            let aspect = |(width, height): (u32, u32)| f64::from(width) / f64::from(height.max(1));
+            sized
+                .filter(|&(_, (width, height))| width >= needed.0 && height >= needed.1)
+                .filter(|&(_, size)| {
+                    (aspect(size) / aspect(source) - 1.0).abs() <= PREVIEW_ASPECT_TOLERANCE
+                })
+                .min_by_key(|&(_, size)| area(size))
+        }
+    };
+    let Some((preview, _)) = picked else {
+        return Ok(None);
+    };
+
+    // The preview's own orientation tag, if any, isn't the image's
+    let mut image = image::load_from_memory_with_format(preview, ImageFormat::Jpeg)
+        .with_context(|| tr!(Msg::DecodeFormatFailed, "embedded preview", path.display()))?;
+    image.apply_orientation(orientation);
+    Ok(Some(Preview { image, source }))
+}
+
+/// Reads a file's EXIF and XMP without decoding its pixels
+#[cfg(feature = "native")]
+pub fn load_metadata(path: &Path) -> Option<Metadata> {
+    let file = File::open(long_path(path)).ok()?;
+    // Safety: as in `load_image`
+    match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => read_metadata(&mmap, path),
+        Err(_) => read_metadata(&std::fs::read(long_path(path)).ok()?, path),
+    }
+}
+
+/// The orientation a file is turned upright with when decoded, from its
+/// EXIF or, for camera RAW, its TIFF structure
+#[cfg(feature = "native")]
+pub fn load_orientation(path: &Path) -> Orientation {
+    let read = |bytes: &[u8]| {
+        read_metadata(bytes, path)
+            .and_then(|meta| meta.orientation())
+            .or_else(|| embedded_previews(bytes).1)
+    };
+    let Ok(file) = File::open(long_path(path)) else {
+        return Orientation::NoTransforms;
+    };
+    // Safety: as in `load_image`
+    let tag = match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => read(&mmap),
+        Err(_) => read(&std::fs::read(long_path(path)).unwrap_or_default()),
+    };
+    tag.and_then(|tag| Orientation::from_exif(u8::try_from(tag).ok()?))
+        .unwrap_or(Orientation::NoTransforms)
+}
+
+/// Whether rsimg wrote the file, as its marker says (`--skip-marked`)
+#[cfg(feature = "native")]
+pub fn is_marked(path: &Path) -> bool {
+    let Ok(file) = File::open(long_path(path)) else {
+        return false;
+    };
+    // Safety: as in `load_image`
+    match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => find_marker(&mmap).is_some(),
+        Err(_) => std::fs::read(long_path(path)).is_ok_and(|bytes| find_marker(&bytes).is_some()),
+    }
+}
+
+/// Upright width and height of an image, read from its header when the
+/// format allows and by decoding it otherwise (PSD, XCF)
+#[cfg(feature = "native")]
+pub fn load_dimensions(path: &Path) -> Result<(u32, u32)> {
+    let header = ImageReader::open(long_path(path))
+        .ok()
+        .and_then(|reader| reader.with_guessed_format().ok())
+        .and_then(|reader| reader.into_decoder().ok());
+    let Some(mut decoder) = header else {
+        let img = load_image(path)?;
+        return Ok((img.width(), img.height()));
+    };
+    let (width, height) = decoder.dimensions();
+    match decoder.orientation().unwrap_or(Orientation::NoTransforms) {
+        Orientation::Rotate90
+        | Orientation::Rotate270
+        | Orientation::Rotate90FlipH
+        | Orientation::Rotate270FlipH => Ok((height, width)),
+        _ => Ok((width, height)),
+    }
+}
+
+/// Reads a written output back (`--verify`): its contents must be the
+/// format its extension names, its header must give `expected` as the size
+/// when that's known, and its pixels must decode to the end
+///
+/// DDS and KTX2 textures aren't read back, as no decoder here covers the
+/// block formats they're written in.
+#[cfg(feature = "native")]
+pub fn verify(path: &Path, expected: Option<(u32, u32)>) -> Result<()> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let format = match extension.as_str() {
+        "apng" => ImageFormat::Png,
+        "dds" | "ktx2" => return Ok(()),
+        _ => ImageFormat::from_extension(&extension)
+            .with_context(|| format!("No decoder for .{extension} files"))?,
+    };
+    let reader = ImageReader::open(long_path(path))
+        .and_then(|reader| reader.with_guessed_format())
+        .context("Can't open the file")?;
+    if reader.format() != Some(format) {
+        anyhow::bail!("The contents aren't {}", extension.to_uppercase());
+    }
+    let decoder = reader.into_decoder().context("Invalid header")?;
+    let size = decoder.dimensions();
+    if let Some(expected) = expected
+        && size != expected
+    {
+        anyhow::bail!(
+            "The header says {}x{}, {}x{} were written",
+            size.0,
+            size.1,
+            expected.0,
+            expected.1
+        );
+    }
+    DynamicImage::from_decoder(decoder).context("The pixel data doesn't decode")?;
+    Ok(())
+}
+
+/// Format of an encoded image recognized by its magic bytes, as the
+/// extension rsimg reads it under
+pub fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"8BPS") {
+        return Some("psd");
+    }
+    if bytes.starts_with(b"gimp xcf") {
+        return Some("xcf");
+    }
+    match image::guess_format(bytes).ok()? {
+        ImageFormat::Jpeg => Some("jpg"),
+        ImageFormat::Png => Some("png"),
+        ImageFormat::Gif => Some("gif"),
+        ImageFormat::WebP => Some("webp"),
+        ImageFormat::Bmp => Some("bmp"),
+        ImageFormat::Tiff => Some("tiff"),
+        ImageFormat::Ico => Some("ico"),
+        ImageFormat::OpenExr => Some("exr"),
+        ImageFormat::Hdr => Some("hdr"),
+        _ => None,
+    }
+}
+
+/// Format of a file recognized by its first bytes
+#[cfg(feature = "native")]
+pub fn sniff_file(path: &Path) -> Option<&'static str> {
+    let mut head = Vec::with_capacity(64);
+    File::open(long_path(path))
+        .ok()?
+        .take(64)
+        .read_to_end(&mut head)
+        .ok()?;
+    sniff(&head)
+}
+
+/// The extension the bytes should have been saved with, falling back to
+/// the one they were
+fn format_hint(bytes: &[u8], path: &Path) -> Option<String> {
+    sniff(bytes).map(str::to_string).or_else(|| {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase)
+    })
+}
+
+/// Reads the EXIF and XMP blocks of an encoded image, if it has any
+pub fn read_metadata(bytes: &[u8], path: &Path) -> Option<Metadata> {
+    let format = format_hint(bytes, path).and_then(ImageFormat::from_extension);
+    let reader = match format {
+        Some(format) => ImageReader::with_format(Cursor::new(bytes), format),
+        None => ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .ok()?,
+    };
+    let mut decoder = reader.into_decoder().ok()?;
+    let exif = decoder.exif_metadata().ok().flatten();
+    let xmp = decoder.xmp_metadata().ok().flatten();
+    Some(Metadata::new(exif, xmp)).filter(|meta| !meta.is_empty())
+}
+
+/// Loads every frame of an animated GIF, WebP or PNG, or `None` for still
+/// images
+#[tracing::instrument(name = "decode", skip_all, fields(path = %path.display(), animated = true))]
+pub fn load_animation(path: &Path) -> Result<Option<Vec<AnimationFrame>>> {
+    let bytes =
+        std::fs::read(long_path(path)).with_context(|| tr!(Msg::OpenFailed, path.display()))?;
+    decode_animation(&bytes, path)
+}
+
+/// Decodes every frame of an in-memory animation, composited on the full
+/// canvas, or returns `None` for still images
+pub fn decode_animation(bytes: &[u8], path: &Path) -> Result<Option<Vec<AnimationFrame>>> {
+    let decode = || -> ImageResult<Option<Vec<AnimationFrame>>> {
+        let Some(frames) = animation_frames(bytes, format_hint(bytes, path).as_deref())? else {
+            return Ok(None);
+        };
+        let frames = frames
+            .map(|frame| {
+                let frame = frame?;
+                let (numer, denom) = frame.delay().numer_denom_ms();
+                Ok(AnimationFrame {
+                    delay_ms: numer / denom.max(1),
+                    image: frame.into_buffer(),
+                })
+            })
+            .collect::<ImageResult<Vec<_>>>()?;
+        // A single-frame GIF is a still image
+        Ok(Some(frames).filter(|frames| frames.len() > 1))
+    };
+    decode().with_context(|| tr!(Msg::DecodeFailed, path.display()))
+}
+
+/// Decodes an in-memory encoded image, using the path extension as a format
+/// hint when the content isn't recognized
+pub fn decode_bytes(bytes: &[u8], path: &Path) -> Result<DynamicImage> {
+    decode_frame(bytes, path, Frame::First)
+}
+
+/// Decodes an in-memory encoded image, taking `frame` if it's animated
+pub fn decode_frame(bytes: &[u8], path: &Path, frame: Frame) -> Result<DynamicImage> {
+    // Layered design files are flattened by their own decoders
+    let ext = format_hint(bytes, path);
+    if frame != Frame::First
+        && let Some(img) = animation_frame(bytes, ext.as_deref(), frame)
+            .with_context(|| tr!(Msg::DecodeFailed, path.display()))?
+    {
+        return Ok(img);
+    }
+    match ext.as_deref() {
+        Some("psd") => return decode_psd(bytes, path),
+        Some("xcf") => return decode_xcf(bytes, path),
+        Some("jpg" | "jpeg") => {
+            if let Some(img) = decode_cmyk_jpeg(bytes, path)? {
+                return Ok(img);
+            }
+        }
+        _ => {}
+    }
+
+    let mut reader = ImageReader::new(Cursor::new(bytes));
+
+    match ext.and_then(ImageFormat::from_extension) {
+        Some(format) => reader.set_format(format),
+        None => {
+            reader = reader
+                .with_guessed_format()
+                .with_context(|| tr!(Msg::DetectFailed, path.display()))?
+        }
+    }
+
+    let mut decoder = reader
+        .into_decoder()
+        .with_context(|| tr!(Msg::DecodeFailed, path.display()))?;
+
+    // Camera shots are stored sideways with an EXIF hint: make them upright
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder)
+        .with_context(|| tr!(Msg::DecodeFailed, path.display()))?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+/// Frames of an animated GIF, WebP or PNG, or `None` for formats without
+/// animation and still WebP/PNG files
+fn animation_frames<'a>(
+    bytes: &'a [u8],
+    ext: Option<&str>,
+) -> ImageResult<Option<image::Frames<'a>>> {
+    let cursor = Cursor::new(bytes);
+    Ok(match ext {
+        Some("gif") => Some(GifDecoder::new(cursor)?.into_frames()),
+        Some("webp") => {
+            let decoder = WebPDecoder::new(cursor)?;
+            decoder.has_animation().then(|| decoder.into_frames())
+        }
+        Some("png" | "apng") => {
+            let decoder = PngDecoder::new(cursor)?;
+            match decoder.is_apng()? {
+                true => Some(decoder.apng()?.into_frames()),
+                false => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Composites frame `frame` of an animated GIF, WebP or PNG, or returns
+/// `None` for still images
+fn animation_frame(
+    bytes: &[u8],
+    ext: Option<&str>,
+    frame: Frame,
+) -> ImageResult<Option<DynamicImage>> {
+    let frames = || animation_frames(bytes, ext);
+
+    // Counting decodes every frame once more, but holds only one at a time
+    let index = match frame {
+        Frame::First => 0,
+        Frame::Index(index) => index,
+        Frame::Middle => match frames()? {
+            Some(all) => all.count() / 2,
+            None => return Ok(None),
+        },
+    };
+    let Some(all) = frames()? else {
+        return Ok(None);
+    };
+    let mut picked = None;
+    for (i, decoded) in all.enumerate() {
+        picked = Some(decoded?);
+        if i == index {
+            break;
+        }
+    }
+    Ok(picked.map(|picked| DynamicImage::ImageRgba8(picked.into_buffer())))
+}
+
+/// Decodes a CMYK JPEG, or returns `None` for any other kind (YCCK
+/// included, which the regular decoder converts correctly)
+///
+/// Photoshop and most print tools store inverted inks and say so with an
+/// Adobe APP14 marker; files without the marker hold plain ink values. Inks
+/// are converted without the embedded ICC profile, which is close enough for
+/// web derivatives.
+fn decode_cmyk_jpeg(bytes: &[u8], path: &Path) -> Result<Option<DynamicImage>> {
+    let options = DecoderOptions::default()
+        .set_strict_mode(false)
+        .set_max_width(usize::MAX)
+        .set_max_height(usize::MAX)
+        .jpeg_set_out_colorspace(ColorSpace::CMYK);
+    let mut decoder = JpegDecoder::new_with_options(ZCursor::new(bytes), options);
+    if decoder.decode_headers().is_err() || decoder.input_colorspace() != Some(ColorSpace::CMYK) {
+        return Ok(None);
+    }
+    let (width, height) = decoder.dimensions().expect("headers were decoded");
+    let inks = decoder
+        .decode()
+        .map_err(|e| anyhow::anyhow!("{e:?}"))
+        .with_context(|| tr!(Msg::DecodeFormatFailed, "CMYK JPEG", path.display()))?;
+
+    // Light left by each ink: the stored value when inverted
+    let inverted = has_adobe_marker(bytes);
+    let rgb = inks
+        .chunks_exact(4)
+        .flat_map(|ink| {
+            let light = |v: u8| if inverted { v as u32 } else { 255 - v as u32 };
+            let k = light(ink[3]);
+            [0, 1, 2].map(|i| ((light(ink[i]) * k + 127) / 255) as u8)
+        })
+        .collect();
+    let rgb = RgbImage::from_raw(width as u32, height as u32, rgb)
+        .ok_or_else(|| anyhow::anyhow!("Corrupt CMYK JPEG: {}", path.display()))?;
+
+    let orientation = ImageReader::with_format(Cursor::new(bytes), ImageFormat::Jpeg)
+        .into_decoder()
+        .ok()
+        .and_then(|mut decoder| decoder.orientation().ok())
+        .unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::ImageRgb8(rgb);
+    img.apply_orientation(orientation);
+    Ok(Some(img))
+}
+
+/// Whether a JPEG has an Adobe APP14 marker before its scan data
+fn has_adobe_marker(jpeg: &[u8]) -> bool {
+    let mut pos = 2;
+    while let Some(&[0xFF, marker, high, low]) = jpeg.get(pos..pos + 4) {
+        match marker {
+            0xDA => break,
+            0xEE if jpeg.get(pos + 4..pos + 9) == Some(b"Adobe") => return true,
+            _ => pos += 2 + u16::from_be_bytes([high, low]) as usize,
+        }
+    }
+    false
+}
+
+/// Decodes the flattened composite stored in a Photoshop document
+fn decode_psd(bytes: &[u8], path: &Path) -> Result<DynamicImage> {
+    let psd = psd::Psd::from_bytes(bytes)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| tr!(Msg::DecodeFormatFailed, "PSD", path.display()))?;
+
+    let rgba = RgbaImage::from_raw(psd.width(), psd.height(), psd.rgba())
+        .ok_or_else(|| anyhow::anyhow!("Corrupt PSD composite: {}", path.display()))?;
+    Ok(DynamicImage::ImageRgba8(rgba))
+}
+
+/// Flattens a GIMP document by compositing its visible layers
+#[cfg(feature = "xcf")]
+fn decode_xcf(bytes: &[u8], path: &Path) -> Result<DynamicImage> {
+    use image::imageops;
+    use xcf::{PropertyIdentifier, PropertyPayload, Xcf};
+
+    let doc = Xcf::load(Cursor::new(bytes))
+        .map_err(|e| anyhow::anyhow!("{e:?}"))
+        .with_context(|| tr!(Msg::DecodeFormatFailed, "XCF", path.display()))?;
+    let mut canvas = RgbaImage::new(doc.width(), doc.height());
+
+    // Layers are stored top-most first
+    for layer in doc.layers.iter().rev() {
+        let property = |kind: PropertyIdentifier| {
+            layer.properties.iter().find_map(|p| match &p.payload {
+                PropertyPayload::Unknown(data) if p.kind == kind => Some(data.as_slice()),
+                _ => None,
+            })
+        };
+        let be_u32 = |data: &[u8], at: usize| {
+            data.get(at..at + 4)
+                .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        };
+
+        let visible = property(PropertyIdentifier::PropVisible)
+            .and_then(|d| be_u32(d, 0))
+            .is_none_or(|v| v != 0);
+        if !visible {
+            continue;
+        }
+        let opacity = property(PropertyIdentifier::PropOpacity)
+            .and_then(|d| be_u32(d, 0))
+            .map_or(255, |v| v.min(255));
+        let (x, y) = property(PropertyIdentifier::PropOffsets)
+            .and_then(|d| Some((be_u32(d, 0)? as i32, be_u32(d, 4)? as i32)))
+            .unwrap_or((0, 0));
+
+        let raw: Vec<u8> = layer.raw_rgba_buffer().iter().flat_map(|p| p.0).collect();
+        let Some(mut pixels) = RgbaImage::from_raw(layer.width, layer.height, raw) else {
+            continue;
+        };
+        if opacity < 255 {
+            for pixel in pixels.pixels_mut() {
+                pixel[3] = (pixel[3] as u32 * opacity / 255) as u8;
+            }
+        }
+        imageops::overlay(&mut canvas, &pixels, x as i64, y as i64);
+    }
+
+    Ok(DynamicImage::ImageRgba8(canvas))
+}
+
+#[cfg(not(feature = "xcf"))]
+fn decode_xcf(_bytes: &[u8], path: &Path) -> Result<DynamicImage> {
+    anyhow::bail!(
+        "XCF input requires building rsimg with the `xcf` feature: {}",
+        path.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Both fixtures are 24x8: a white, a cyan and a half-black 8x8 block
+    const BLOCKS: [[u8; 3]; 3] = [[255, 255, 255], [0, 255, 255], [127, 127, 127]];
+
+    /// Decodes a CMYK fixture and checks the middle of each block
+    fn check(bytes: &[u8], marked: bool) {
+        assert_eq!(has_adobe_marker(bytes), marked);
+        let img = decode_bytes(bytes, Path::new("cmyk.jpg"))
+            .unwrap()
+            .to_rgb8();
+        assert_eq!(img.dimensions(), (24, 8));
+        for (block, expected) in BLOCKS.iter().enumerate() {
+            let pixel = img.get_pixel(block as u32 * 8 + 4, 4).0;
+            for (got, want) in pixel.iter().zip(expected) {
+                assert!(
+                    got.abs_diff(*want) <= 2,
+                    "block {block}: {pixel:?} != {expected:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inverted_inks_with_adobe_marker() {
+        check(include_bytes!("../tests/fixtures/cmyk-adobe.jpg"), true);
+    }
+
+    #[test]
+    fn plain_inks_without_adobe_marker() {
+        check(include_bytes!("../tests/fixtures/cmyk-plain.jpg"), false);
+    }
+}
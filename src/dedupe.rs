@@ -0,0 +1,108 @@
+// src/dedupe.rs
+//
+// Duplicate input detection. Files with identical content (hard links,
+// copies of the same asset across locale folders, ...) are grouped so each
+// distinct image is decoded and encoded once; the remaining copies reuse the
+// primary's outputs.
+
+use crate::paths::long_path;
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// A set of byte-identical input files
+#[derive(Clone)]
+pub struct DuplicateGroup {
+    /// File that is actually decoded and encoded
+    pub primary: PathBuf,
+    /// Files whose outputs are linked or copied from the primary's
+    pub duplicates: Vec<PathBuf>,
+}
+
+/// Groups input files by content, preserving the original input order
+pub fn group_duplicates(files: Vec<PathBuf>) -> Vec<DuplicateGroup> {
+    // Only files sharing a size can be identical, so hash just those
+    let mut by_size: HashMap<u64, usize> = HashMap::new();
+    let sizes: Vec<Option<u64>> = files
+        .iter()
+        .map(|path| std::fs::metadata(path).ok().map(|m| m.len()))
+        .collect();
+    for size in sizes.iter().flatten() {
+        *by_size.entry(*size).or_default() += 1;
+    }
+
+    let keys: Vec<Option<[u8; 32]>> = files
+        .par_iter()
+        .zip(sizes.par_iter())
+        .map(|(path, size)| match size {
+            Some(size) if by_size[size] > 1 => content_hash(path).ok(),
+            _ => None,
+        })
+        .collect();
+
+    let mut groups: Vec<DuplicateGroup> = Vec::with_capacity(files.len());
+    let mut index_of: HashMap<[u8; 32], usize> = HashMap::new();
+
+    for (path, key) in files.into_iter().zip(keys) {
+        match key {
+            Some(key) => match index_of.get(&key) {
+                Some(&i) => groups[i].duplicates.push(path),
+                None => {
+                    index_of.insert(key, groups.len());
+                    groups.push(DuplicateGroup {
+                        primary: path,
+                        duplicates: Vec::new(),
+                    });
+                }
+            },
+            // Unique size (or unreadable): nothing to share
+            None => groups.push(DuplicateGroup {
+                primary: path,
+                duplicates: Vec::new(),
+            }),
+        }
+    }
+
+    groups
+}
+
+/// Hashes the full content of a file
+fn content_hash(path: &Path) -> Result<[u8; 32]> {
+    let file = File::open(long_path(path))
+        .with_context(|| format!("Failed to open file: {}", path.display()))?;
+    let mut hasher = blake3::Hasher::new();
+    hasher
+        .update_reader(file)
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    Ok(*hasher.finalize().as_bytes())
+}
+
+/// Places `target` as a copy of `source`, hard-linking when requested and
+/// the filesystem allows it
+pub fn link_or_copy(source: &Path, target: &Path, link: bool) -> Result<()> {
+    if source == target {
+        return Ok(());
+    }
+
+    // Replace stale outputs from previous runs. Removing first also keeps a
+    // copy from truncating the source through an existing hard link.
+    let (source_io, target_io) = (long_path(source), long_path(target));
+    if target_io.exists() {
+        std::fs::remove_file(&target_io)
+            .with_context(|| format!("Failed to replace file: {}", target.display()))?;
+    }
+
+    if !link || std::fs::hard_link(&source_io, &target_io).is_err() {
+        std::fs::copy(&source_io, &target_io).with_context(|| {
+            format!(
+                "Failed to copy {} to {}",
+                source.display(),
+                target.display()
+            )
+        })?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,556 @@
+// src/diff.rs
+//
+// `rsimg diff`: visual regression between two directories of rendered
+// images, e.g. screenshots or exported assets before and after a change.
+// Files are matched by their path within each directory and compared in
+// parallel, as RGBA: a pixel differs when any channel is further apart
+// than `--tolerance`, and the structural similarity (SSIM) of their luma,
+// over 8x8 blocks, tells how visible the change is. Pairs of different
+// sizes are compared over their common area, the rest counting as changed.
+//
+// As a CI check, BEFORE is the baseline: a pair fails when more of its
+// pixels differ than `--max-changed` allows or its SSIM is below
+// `--min-ssim`, and so does a file only one side has; any failure exits
+// with `Exit::Mismatch`. `--update-baseline` instead makes the baseline
+// match, copying over it the current images that failed or are new and
+// deleting those no longer rendered.
+//
+// With `--output`, every failing pair gets a montage of the baseline, the
+// current image and the diff, the baseline faded to gray with its
+// differing pixels in red and the area holding them boxed in yellow; and
+// `summary.csv` has a row for every file:
+//
+//   out/{path}_diff.png
+//   out/summary.csv   path,status,passed,width,height,changed_pixels,max_difference,ssim
+
+use crate::font;
+use anyhow::{Context, Result};
+use image::{GenericImage, GrayImage, Rgba, RgbaImage};
+use owo_colors::OwoColorize;
+use rayon::prelude::*;
+use rsimg::decode;
+use rsimg::exit::{InvalidConfig, Mismatch, NoInputs};
+use rsimg::i18n::{self, Msg, tr};
+use rsimg::processor::{self, ScanOptions};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Side of the blocks SSIM is computed over
+const BLOCK: u32 = 8;
+/// Montage labels are drawn at this multiple of the 5×7 font
+const LABEL_SCALE: u32 = 2;
+/// Gap around montage panels
+const PADDING: u32 = 8;
+const BACKGROUND: Rgba<u8> = Rgba([240, 240, 240, 255]);
+const TEXT: Rgba<u8> = Rgba([40, 40, 40, 255]);
+
+const HEADER: [&str; 8] = [
+    "path",
+    "status",
+    "passed",
+    "width",
+    "height",
+    "changed_pixels",
+    "max_difference",
+    "ssim",
+];
+
+#[derive(clap::Args)]
+pub struct DiffArgs {
+    /// Directory of reference images
+    #[arg(value_name = "BEFORE", help = "Reference directory")]
+    pub before: PathBuf,
+
+    /// Directory of the images compared against the reference
+    #[arg(value_name = "AFTER", help = "Directory to compare")]
+    pub after: PathBuf,
+
+    /// Directory the diff images and summary.csv are written to; without
+    /// it, only the summary is printed
+    #[arg(short, long, value_name = "DIR", help = "Write diff images to DIR")]
+    pub output: Option<PathBuf>,
+
+    /// How far apart (0-255) a pixel's channels may be and still count as
+    /// unchanged, to ignore encoder and antialiasing noise
+    #[arg(
+        long,
+        default_value_t = 0,
+        value_name = "LEVELS",
+        help = "Channel difference ignored per pixel"
+    )]
+    pub tolerance: u8,
+
+    /// Share of a pair's pixels (0-100) that may differ before it fails
+    #[arg(
+        long,
+        default_value_t = 0.0,
+        value_name = "PERCENT",
+        help = "Percentage of pixels allowed to differ"
+    )]
+    pub max_changed: f64,
+
+    /// SSIM (0-1) a pair must reach not to fail; not checked without it
+    #[arg(long, value_name = "SSIM", help = "Lowest SSIM a pair may have")]
+    pub min_ssim: Option<f64>,
+
+    /// Copy the current images that fail or are new over the baseline
+    /// (BEFORE), and delete baselines no longer in AFTER, instead of failing
+    #[arg(long, help = "Accept the current images as the new baseline")]
+    pub update_baseline: bool,
+
+    /// Descend into subdirectories
+    #[arg(short, long, help = "Scan directories recursively")]
+    pub recursive: bool,
+
+    /// Identify images by their magic bytes instead of their extension
+    #[arg(long, help = "Detect image files by content instead of extension")]
+    pub sniff: bool,
+}
+
+/// How one pair of images compared
+struct Comparison {
+    /// Path within both directories
+    path: PathBuf,
+    /// Size of the image before and after
+    old_size: (u32, u32),
+    size: (u32, u32),
+    /// Compared pixels, and how many of them differ
+    pixels: u64,
+    changed: u64,
+    /// Largest channel difference
+    max_difference: u8,
+    ssim: f64,
+    /// Within the thresholds
+    passed: bool,
+    /// The diff image written, if any
+    written: Option<PathBuf>,
+}
+
+impl Comparison {
+    fn is_identical(&self) -> bool {
+        self.changed == 0
+    }
+
+    fn is_resized(&self) -> bool {
+        self.size != self.old_size
+    }
+
+    fn share(&self) -> f64 {
+        self.changed as f64 * 100.0 / self.pixels.max(1) as f64
+    }
+}
+
+/// Runs the `diff` subcommand
+pub fn run(args: DiffArgs) -> Result<()> {
+    if !(0.0..=100.0).contains(&args.max_changed) {
+        return Err(InvalidConfig(tr!(Msg::MaxChangedRange, args.max_changed)).into());
+    }
+    if let Some(ssim) = args.min_ssim
+        && !(0.0..=1.0).contains(&ssim)
+    {
+        return Err(InvalidConfig(tr!(Msg::MinSsimRange, ssim)).into());
+    }
+    // A first run creates the baseline
+    if args.update_baseline {
+        std::fs::create_dir_all(&args.before)
+            .with_context(|| format!("Failed to create directory: {}", args.before.display()))?;
+    }
+    for dir in [&args.before, &args.after] {
+        if !dir.is_dir() {
+            return Err(InvalidConfig(tr!(Msg::PathInvalid, dir.display())).into());
+        }
+    }
+    let scan = ScanOptions {
+        recursive: args.recursive,
+        respect_ignore: true,
+        sniff: args.sniff,
+        raw: false,
+        extensions: Vec::new(),
+    };
+    let before = relative_files(&args.before, &scan)?;
+    let after = relative_files(&args.after, &scan)?;
+    if before.is_empty() && after.is_empty() {
+        return Err(NoInputs(tr!(Msg::NoImages)).into());
+    }
+    if let Some(output) = &args.output {
+        std::fs::create_dir_all(output)
+            .with_context(|| format!("Failed to create directory: {}", output.display()))?;
+    }
+
+    let pairs: Vec<(&PathBuf, &PathBuf, &PathBuf)> = before
+        .iter()
+        .filter_map(|(path, old)| Some((path, old, after.get(path)?)))
+        .collect();
+    let comparisons = pairs
+        .par_iter()
+        .map(|(path, old, new)| compare(path, old, new, &args))
+        .collect::<Result<Vec<_>>>()?;
+    let only_before: Vec<&PathBuf> = before.keys().filter(|p| !after.contains_key(*p)).collect();
+    let only_after: Vec<&PathBuf> = after.keys().filter(|p| !before.contains_key(*p)).collect();
+
+    report(&comparisons, &only_before, &only_after, &args);
+    if let Some(output) = &args.output {
+        let summary = output.join("summary.csv");
+        let file = std::fs::File::create(&summary)
+            .with_context(|| format!("Failed to create {}", summary.display()))?;
+        write_csv(
+            std::io::BufWriter::new(file),
+            &comparisons,
+            &only_before,
+            &only_after,
+        )
+        .with_context(|| format!("Failed to write {}", summary.display()))?;
+    }
+
+    let failed =
+        comparisons.iter().filter(|c| !c.passed).count() + only_before.len() + only_after.len();
+    if args.update_baseline {
+        return update_baseline(&comparisons, &only_before, &only_after, &args);
+    }
+    match failed {
+        0 => Ok(()),
+        failed => Err(Mismatch(failed).into()),
+    }
+}
+
+/// Makes the baseline match the current images: copies those that failed
+/// or are new over it and deletes those without a current image
+fn update_baseline(
+    comparisons: &[Comparison],
+    only_before: &[&PathBuf],
+    only_after: &[&PathBuf],
+    args: &DiffArgs,
+) -> Result<()> {
+    let failed = comparisons.iter().filter(|c| !c.passed).map(|c| &c.path);
+    let mut copied = 0;
+    for path in failed.chain(only_after.iter().copied()) {
+        let (source, target) = (args.after.join(path), args.before.join(path));
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        std::fs::copy(&source, &target).with_context(|| {
+            format!(
+                "Failed to copy {} to {}",
+                source.display(),
+                target.display()
+            )
+        })?;
+        copied += 1;
+    }
+    for path in only_before {
+        let target = args.before.join(path);
+        std::fs::remove_file(&target)
+            .with_context(|| format!("Failed to delete {}", target.display()))?;
+    }
+    println!(
+        "  {} {}",
+        "📌".bright_white(),
+        tr!(Msg::BaselineUpdated, copied, only_before.len())
+    );
+    Ok(())
+}
+
+/// The images under `dir` by their path within it
+fn relative_files(dir: &Path, scan: &ScanOptions) -> Result<BTreeMap<PathBuf, PathBuf>> {
+    Ok(processor::collect_image_files(dir, scan)?
+        .into_iter()
+        .filter_map(|path| Some((path.strip_prefix(dir).ok()?.to_path_buf(), path)))
+        .collect())
+}
+
+/// Compares the images `old` and `new`, found at `path` in each directory,
+/// writing the diff image if they differ and there's an output directory
+fn compare(path: &Path, old: &Path, new: &Path, args: &DiffArgs) -> Result<Comparison> {
+    let load = |file: &Path| {
+        decode::load_image(file).with_context(|| format!("Failed to decode {}", file.display()))
+    };
+    let (old, new) = rayon::join(|| load(old), || load(new));
+    let (a, b) = (old?.to_rgba8(), new?.to_rgba8());
+    let width = a.width().max(b.width());
+    let height = a.height().max(b.height());
+
+    let mut overlay = faded(&luma(&a, a.width(), a.height()), width, height);
+    let red = [255.0, 0.0, 0.0];
+    let (mut changed, mut max_difference) = (0, 0);
+    // Left, top, right and bottom of the changed area
+    let mut bounds: Option<[u32; 4]> = None;
+    for y in 0..height {
+        for x in 0..width {
+            let difference = match (a.get_pixel_checked(x, y), b.get_pixel_checked(x, y)) {
+                (Some(p), Some(q)) => (0..4).map(|c| p[c].abs_diff(q[c])).max().unwrap_or(0),
+                _ => u8::MAX,
+            };
+            max_difference = max_difference.max(difference);
+            if difference <= args.tolerance {
+                continue;
+            }
+            changed += 1;
+            // Faint differences still stand out
+            let strength = 0.4 + 0.6 * f32::from(difference) / 255.0;
+            let pixel = overlay.get_pixel_mut(x, y);
+            for (c, target) in red.into_iter().enumerate() {
+                let v = f32::from(pixel[c]);
+                pixel[c] = (v + (target - v) * strength).round() as u8;
+            }
+            bounds = Some(match bounds {
+                Some([left, top, right, bottom]) => {
+                    [left.min(x), top.min(y), right.max(x), bottom.max(y)]
+                }
+                None => [x, y, x, y],
+            });
+        }
+    }
+
+    let (common_width, common_height) = (a.width().min(b.width()), a.height().min(b.height()));
+    let ssim = ssim(
+        &luma(&a, common_width, common_height),
+        &luma(&b, common_width, common_height),
+    );
+    let pixels = u64::from(width) * u64::from(height);
+    let share = changed as f64 * 100.0 / pixels.max(1) as f64;
+    let passed = share <= args.max_changed && args.min_ssim.is_none_or(|min_ssim| ssim >= min_ssim);
+
+    let written = match (&args.output, bounds) {
+        (Some(output), Some(bounds)) if !passed => {
+            outline(&mut overlay, bounds);
+            let name = format!(
+                "{}_diff.png",
+                path.file_stem().unwrap_or_default().to_string_lossy()
+            );
+            let target = output.join(path).with_file_name(name);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+            }
+            montage([&a, &b, &overlay], share)
+                .save(&target)
+                .with_context(|| format!("Failed to write {}", target.display()))?;
+            Some(target)
+        }
+        _ => None,
+    };
+
+    Ok(Comparison {
+        path: path.to_path_buf(),
+        old_size: a.dimensions(),
+        size: b.dimensions(),
+        pixels,
+        changed,
+        max_difference,
+        ssim,
+        passed,
+        written,
+    })
+}
+
+/// `gray` lightened on a white `width`×`height` canvas, for differences
+/// to show over
+fn faded(gray: &GrayImage, width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        let v = gray.get_pixel_checked(x, y).map_or(255, |p| p[0]);
+        let v = (160 + u32::from(v) * 95 / 255) as u8;
+        Rgba([v, v, v, 255])
+    })
+}
+
+/// The baseline, the current image and the diff side by side, labeled
+/// above, in panels the size of the diff
+fn montage(panels: [&RgbaImage; 3], share: f64) -> RgbaImage {
+    let (width, height) = panels[2].dimensions();
+    let label_height = font::text_size("", LABEL_SCALE).1 + PADDING;
+    let mut montage = RgbaImage::from_pixel(
+        3 * (width + PADDING) + PADDING,
+        height + label_height + 2 * PADDING,
+        BACKGROUND,
+    );
+    let labels = [
+        "BASELINE".to_string(),
+        "CURRENT".to_string(),
+        format!("DIFF {share:.2}%"),
+    ];
+    for (i, (panel, mut label)) in panels.into_iter().zip(labels).enumerate() {
+        let x = PADDING + i as u32 * (width + PADDING);
+        while font::text_size(&label, LABEL_SCALE).0 > width {
+            label.pop();
+        }
+        font::draw_text(&mut montage, x, PADDING, &label, LABEL_SCALE, TEXT);
+        let _ = montage.copy_from(panel, x, PADDING + label_height);
+    }
+    montage
+}
+
+/// Draws a two-pixel yellow box just outside `bounds`
+fn outline(img: &mut RgbaImage, [left, top, right, bottom]: [u32; 4]) {
+    let (width, height) = img.dimensions();
+    let (left, top) = (left.saturating_sub(2), top.saturating_sub(2));
+    let (right, bottom) = ((right + 2).min(width - 1), (bottom + 2).min(height - 1));
+    let yellow = Rgba([255, 200, 0, 255]);
+    for x in left..=right {
+        for y in [top, top + 1, bottom.saturating_sub(1), bottom] {
+            img.put_pixel(x, y.min(height - 1), yellow);
+        }
+    }
+    for y in top..=bottom {
+        for x in [left, left + 1, right.saturating_sub(1), right] {
+            img.put_pixel(x.min(width - 1), y, yellow);
+        }
+    }
+}
+
+/// The top-left `width`×`height` of `img` as luma, composited on white
+fn luma(img: &RgbaImage, width: u32, height: u32) -> GrayImage {
+    GrayImage::from_fn(width, height, |x, y| {
+        let [r, g, b, a] = img.get_pixel(x, y).0;
+        let alpha = f32::from(a) / 255.0;
+        let y = 0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b);
+        image::Luma([(y * alpha + 255.0 * (1.0 - alpha)).round() as u8])
+    })
+}
+
+/// Mean SSIM of two images of one size over `BLOCK`×`BLOCK` blocks: 1 when
+/// they're the same
+fn ssim(a: &GrayImage, b: &GrayImage) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let (width, height) = a.dimensions();
+    let (mut total, mut blocks) = (0.0, 0);
+    for top in (0..height).step_by(BLOCK as usize) {
+        for left in (0..width).step_by(BLOCK as usize) {
+            let (mut sa, mut sb, mut saa, mut sbb, mut sab, mut n) = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+            for y in top..(top + BLOCK).min(height) {
+                for x in left..(left + BLOCK).min(width) {
+                    let (p, q) = (
+                        f64::from(a.get_pixel(x, y)[0]),
+                        f64::from(b.get_pixel(x, y)[0]),
+                    );
+                    sa += p;
+                    sb += q;
+                    saa += p * p;
+                    sbb += q * q;
+                    sab += p * q;
+                    n += 1.0;
+                }
+            }
+            let (ma, mb) = (sa / n, sb / n);
+            let (va, vb) = (saa / n - ma * ma, sbb / n - mb * mb);
+            let covariance = sab / n - ma * mb;
+            total += ((2.0 * ma * mb + C1) * (2.0 * covariance + C2))
+                / ((ma * ma + mb * mb + C1) * (va + vb + C2));
+            blocks += 1;
+        }
+    }
+    match blocks {
+        0 => 1.0,
+        _ => total / f64::from(blocks),
+    }
+}
+
+/// Prints every changed pair, marked by whether it passed, and the files
+/// only one directory has
+fn report(
+    comparisons: &[Comparison],
+    only_before: &[&PathBuf],
+    only_after: &[&PathBuf],
+    args: &DiffArgs,
+) {
+    let identical = comparisons.iter().filter(|c| c.is_identical()).count();
+    println!(
+        "\n  {} {}",
+        "🔍".bright_white(),
+        tr!(
+            Msg::DiffCompared,
+            comparisons.len(),
+            identical,
+            comparisons.len() - identical
+        )
+    );
+    for comparison in comparisons.iter().filter(|c| !c.is_identical()) {
+        let mut line = tr!(
+            Msg::DiffChanged,
+            format!("{:.2}", comparison.share()),
+            comparison.max_difference,
+            format!("{:.4}", comparison.ssim)
+        );
+        if comparison.is_resized() {
+            let (old_width, old_height) = comparison.old_size;
+            let (width, height) = comparison.size;
+            line.push_str("; ");
+            line.push_str(&tr!(Msg::DiffResized, old_width, old_height, width, height));
+        }
+        let mark = match comparison.passed {
+            true => "≈".green().to_string(),
+            false => "✗".red().to_string(),
+        };
+        println!(
+            "    {mark} {}: {line}",
+            comparison.path.display().to_string().bright_yellow()
+        );
+        if let Some(written) = &comparison.written {
+            println!("      → {}", written.display());
+        }
+    }
+    for (dir, only) in [(&args.before, only_before), (&args.after, only_after)] {
+        for path in only {
+            println!(
+                "    {} {}",
+                "∅".red(),
+                tr!(Msg::DiffOnlyIn, path.display(), dir.display())
+            );
+        }
+    }
+    if let Some(output) = &args.output {
+        println!(
+            "  {} {}: {}",
+            "💾".bright_white(),
+            i18n::text(Msg::Output),
+            output.display().to_string().bright_yellow()
+        );
+    }
+}
+
+fn write_csv(
+    out: impl Write,
+    comparisons: &[Comparison],
+    only_before: &[&PathBuf],
+    only_after: &[&PathBuf],
+) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(HEADER)?;
+    for comparison in comparisons {
+        let (width, height) = comparison.size;
+        writer.write_record([
+            comparison.path.display().to_string(),
+            match (comparison.is_identical(), comparison.is_resized()) {
+                (true, _) => "identical",
+                (false, true) => "resized",
+                (false, false) => "changed",
+            }
+            .to_string(),
+            comparison.passed.to_string(),
+            width.to_string(),
+            height.to_string(),
+            comparison.changed.to_string(),
+            comparison.max_difference.to_string(),
+            format!("{:.6}", comparison.ssim),
+        ])?;
+    }
+    for (status, only) in [("removed", only_before), ("added", only_after)] {
+        for path in only {
+            let row = [
+                &path.display().to_string(),
+                status,
+                "false",
+                "",
+                "",
+                "",
+                "",
+                "",
+            ];
+            writer.write_record(row)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,118 @@
+// src/encode/alpha.rs
+//
+// Transparent sources written to formats without an alpha channel (JPEG,
+// PPM, PGM). Encoders left to themselves drop the alpha and expose whatever
+// color transparent pixels happen to store, often black; `--alpha` picks
+// what shows through instead: a solid color, a matte of the image's own
+// average color, or no such output at all.
+
+use crate::transform::color::Color;
+use image::{DynamicImage, Rgb, RgbImage, Rgba, RgbaImage};
+
+/// How transparency is written to formats that can't store it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// Composite onto a solid color
+    Flatten(Color),
+    /// Leave those outputs out; done by the batch, encoders flatten on white
+    Skip,
+    /// Composite onto the average color of the visible pixels
+    Matte,
+}
+
+impl Default for AlphaMode {
+    fn default() -> Self {
+        AlphaMode::Flatten(Color::WHITE)
+    }
+}
+
+/// Clap value parser for `--alpha`: `flatten[:COLOR]`, `skip` or `matte`
+pub fn parse_alpha_arg(s: &str) -> Result<AlphaMode, String> {
+    let (mode, color) = match s.trim().split_once(':') {
+        Some((mode, color)) => (mode, Some(color)),
+        None => (s.trim(), None),
+    };
+    match (mode.to_lowercase().as_str(), color) {
+        ("flatten", None) => Ok(AlphaMode::default()),
+        ("flatten", Some(color)) => {
+            let color: Color = color.parse()?;
+            if !color.is_opaque() {
+                return Err("the flatten color must be opaque".into());
+            }
+            Ok(AlphaMode::Flatten(color))
+        }
+        ("skip", None) => Ok(AlphaMode::Skip),
+        ("matte", None) => Ok(AlphaMode::Matte),
+        _ => Err(format!(
+            "invalid alpha mode '{s}' (expected flatten[:COLOR], skip or matte)"
+        )),
+    }
+}
+
+/// Whether a format can store an alpha channel
+pub fn supports_alpha(format: &str) -> bool {
+    !matches!(
+        format.to_lowercase().as_str(),
+        "jpg" | "jpeg" | "ppm" | "pgm"
+    )
+}
+
+/// Whether any pixel is less than fully opaque
+pub fn has_transparency(img: &DynamicImage) -> bool {
+    match img {
+        DynamicImage::ImageLumaA8(img) => img.pixels().any(|p| p[1] < u8::MAX),
+        DynamicImage::ImageRgba8(img) => img.pixels().any(|p| p[3] < u8::MAX),
+        DynamicImage::ImageLumaA16(img) => img.pixels().any(|p| p[1] < u16::MAX),
+        DynamicImage::ImageRgba16(img) => img.pixels().any(|p| p[3] < u16::MAX),
+        DynamicImage::ImageRgba32F(img) => img.pixels().any(|p| p[3] < 1.0),
+        _ => false,
+    }
+}
+
+/// The image composited according to `mode`, or `None` when it's opaque
+pub fn flatten(img: &DynamicImage, mode: AlphaMode) -> Option<DynamicImage> {
+    if !has_transparency(img) {
+        return None;
+    }
+    let rgba = img.to_rgba8();
+    let Rgba([r, g, b, _]) = match mode {
+        AlphaMode::Flatten(color) => color.0,
+        AlphaMode::Skip => Color::WHITE.0,
+        AlphaMode::Matte => average_color(&rgba),
+    };
+    let background = [r, g, b].map(u32::from);
+
+    let mut out = RgbImage::new(rgba.width(), rgba.height());
+    for (dst, src) in out.pixels_mut().zip(rgba.pixels()) {
+        let alpha = u32::from(src[3]);
+        let blend = |i: usize| {
+            ((u32::from(src[i]) * alpha + background[i] * (255 - alpha) + 127) / 255) as u8
+        };
+        *dst = Rgb([blend(0), blend(1), blend(2)]);
+    }
+    let out = DynamicImage::ImageRgb8(out);
+    // Grayscale sources stay single-channel in JPEG
+    Some(match img.color().has_color() {
+        true => out,
+        false => DynamicImage::ImageLuma8(out.to_luma8()),
+    })
+}
+
+/// Mean color of the pixels, weighted by their opacity; white when nothing
+/// is visible
+fn average_color(img: &RgbaImage) -> Rgba<u8> {
+    let mut sums = [0u64; 3];
+    let mut weight = 0u64;
+    for pixel in img.pixels() {
+        let alpha = u64::from(pixel[3]);
+        for (sum, &channel) in sums.iter_mut().zip(&pixel.0[..3]) {
+            *sum += u64::from(channel) * alpha;
+        }
+        weight += alpha;
+    }
+    if weight == 0 {
+        return Color::WHITE.0;
+    }
+    let [r, g, b] = sums.map(|sum| (sum / weight) as u8);
+    Rgba([r, g, b, 255])
+}
@@ -0,0 +1,66 @@
+// src/encode/apng.rs
+//
+// Animated PNG output (`--formats apng`) for animated GIF, WebP and APNG
+// inputs, for targets that take APNG but not animated WebP (Safari
+// stickers, some messengers). Frames arrive composited on the full canvas,
+// so each one is stored whole; still inputs are written as ordinary PNGs.
+
+use super::{EncodeOptions, Metadata, marker, metadata, write_output};
+use anyhow::{Context, Result};
+use image::RgbaImage;
+use std::path::Path;
+
+/// One composited frame of an animation
+#[derive(Clone)]
+pub struct AnimationFrame {
+    pub image: RgbaImage,
+    /// How long the frame shows, in milliseconds
+    pub delay_ms: u32,
+}
+
+/// Encodes frames of equal size as a looping APNG
+#[tracing::instrument(name = "encode", skip_all, fields(format = "apng", frames = frames.len()))]
+pub fn encode_apng(frames: &[AnimationFrame]) -> Result<Vec<u8>> {
+    let first = frames.first().context("Animation has no frames")?;
+    let (width, height) = first.image.dimensions();
+
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::High);
+    // Zero plays: loop forever, as GIF and WebP animations usually do
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .with_context(|| "Error during APNG encoding")?;
+
+    let mut writer = encoder
+        .write_header()
+        .with_context(|| "Error during APNG encoding")?;
+    for frame in frames {
+        if frame.image.dimensions() != (width, height) {
+            anyhow::bail!("APNG frames must all be {width}x{height}");
+        }
+        writer
+            .set_frame_delay(frame.delay_ms.min(u16::MAX as u32) as u16, 1000)
+            .with_context(|| "Error during APNG encoding")?;
+        writer
+            .write_image_data(frame.image.as_raw())
+            .with_context(|| "Error during APNG encoding")?;
+    }
+    writer
+        .finish()
+        .with_context(|| "Error during APNG encoding")?;
+
+    Ok(bytes)
+}
+
+/// Encodes frames as an APNG file at `path`, marked unless `opts` say not
+pub fn save_apng(frames: &[AnimationFrame], path: &Path, opts: &EncodeOptions) -> Result<()> {
+    let mut bytes = encode_apng(frames)?;
+    if opts.mark {
+        let text = marker::text("apng", opts);
+        bytes = metadata::embed_png(bytes, &Metadata::default(), Some(&text));
+    }
+    write_output(path, &bytes).with_context(|| format!("Failed to write file: {}", path.display()))
+}
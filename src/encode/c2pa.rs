@@ -0,0 +1,790 @@
+// src/encode/c2pa.rs
+//
+// `--c2pa-sign`: C2PA content credentials recording how each output was
+// derived from its source. A manifest store (JUMBF boxes holding CBOR) is
+// spliced into the finished file, like the EXIF/XMP of `metadata`: APP11
+// segments in JPEG, a caBX chunk in PNG. Its single manifest has three
+// assertions, the source as the parent ingredient, the actions (opening
+// the source, the transforms in the order they ran, the resize and the
+// encoding) and a SHA-256 of the file's bytes outside the manifest, and a
+// claim listing their hashes, signed as a COSE_Sign1 whose protected
+// header carries the certificate chain.
+//
+// The hard binding excludes exactly the bytes the manifest is inserted
+// as, so what it hashes is the encoded file as it was before; only the
+// manifest's own length has to settle, which it does in a pass or two.
+
+use crate::exit::InvalidConfig;
+use crate::i18n::{Msg, tr};
+use crate::transform::geometry::ResizeMode;
+use crate::transform::{Stage, Transforms};
+use anyhow::{Context, Result};
+use base64::Engine;
+use ring::digest::{SHA256, digest};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{
+    ECDSA_P256_SHA256_FIXED_SIGNING, ECDSA_P384_SHA384_FIXED_SIGNING, EcdsaKeyPair, Ed25519KeyPair,
+    RSA_PSS_SHA256, RsaKeyPair,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Largest JPEG marker payload, as in `metadata`
+const MAX_SEGMENT: usize = 65533;
+/// APP11 payload header: common identifier "JP", box instance and packet
+/// sequence numbers
+const APP11_HEADER: usize = 2 + 2 + 4;
+
+/// COSE algorithm identifiers
+const ES256: i64 = -7;
+const ES384: i64 = -35;
+const EDDSA: i64 = -8;
+const PS256: i64 = -37;
+/// COSE header label of the X.509 certificate chain
+const X5CHAIN: i64 = 33;
+
+const GENERATOR: &str = concat!("rsimg/", env!("CARGO_PKG_VERSION"));
+
+/// A private key and the certificate chain vouching for it
+pub struct Signer {
+    key: Key,
+    /// DER certificates, the signer's first
+    certs: Vec<Vec<u8>>,
+    rng: SystemRandom,
+}
+
+enum Key {
+    Ecdsa(EcdsaKeyPair, i64),
+    Ed25519(Ed25519KeyPair),
+    Rsa(RsaKeyPair),
+}
+
+impl Signer {
+    /// Reads a PKCS#8 private key (ECDSA P-256 or P-384, Ed25519 or RSA)
+    /// from a PEM file, and the certificates from `certs` or, without it,
+    /// the same file
+    pub fn load(key: &Path, certs: Option<&Path>) -> Result<Self> {
+        let invalid =
+            |path: &Path, reason: &str| InvalidConfig(format!("{}: {reason}", path.display()));
+        let pem = read_pem(key)?;
+        let Some((_, der)) = pem.iter().find(|(label, _)| label == "PRIVATE KEY") else {
+            let reason = match pem.iter().any(|(label, _)| label.ends_with("PRIVATE KEY")) {
+                true => "convert the key to PKCS#8 (openssl pkcs8 -topk8 -nocrypt)",
+                false => "no PRIVATE KEY block found",
+            };
+            return Err(invalid(key, reason).into());
+        };
+        let rng = SystemRandom::new();
+        let key_pair = if let Ok(pair) =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, der, &rng)
+        {
+            Key::Ecdsa(pair, ES256)
+        } else if let Ok(pair) =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P384_SHA384_FIXED_SIGNING, der, &rng)
+        {
+            Key::Ecdsa(pair, ES384)
+        } else if let Ok(pair) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der) {
+            Key::Ed25519(pair)
+        } else if let Ok(pair) = RsaKeyPair::from_pkcs8(der) {
+            Key::Rsa(pair)
+        } else {
+            return Err(invalid(
+                key,
+                "unsupported key; use ECDSA P-256/P-384, Ed25519 or RSA",
+            )
+            .into());
+        };
+
+        let certs_path = certs.unwrap_or(key);
+        let certs: Vec<Vec<u8>> = match certs {
+            Some(path) => read_pem(path)?,
+            None => pem,
+        }
+        .into_iter()
+        .filter(|(label, _)| label == "CERTIFICATE")
+        .map(|(_, der)| der)
+        .collect();
+        if certs.is_empty() {
+            return Err(invalid(
+                certs_path,
+                "no CERTIFICATE block found; pass the chain with --c2pa-cert",
+            )
+            .into());
+        }
+        Ok(Self {
+            key: key_pair,
+            certs,
+            rng,
+        })
+    }
+
+    fn algorithm(&self) -> i64 {
+        match &self.key {
+            Key::Ecdsa(_, algorithm) => *algorithm,
+            Key::Ed25519(_) => EDDSA,
+            Key::Rsa(_) => PS256,
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+        let failed = |_| anyhow::anyhow!("Failed to sign the C2PA claim");
+        match &self.key {
+            Key::Ecdsa(pair, _) => Ok(pair
+                .sign(&self.rng, message)
+                .map_err(failed)?
+                .as_ref()
+                .to_vec()),
+            Key::Ed25519(pair) => Ok(pair.sign(message).as_ref().to_vec()),
+            Key::Rsa(pair) => {
+                let mut signature = vec![0; pair.public().modulus_len()];
+                pair.sign(&RSA_PSS_SHA256, &self.rng, message, &mut signature)
+                    .map_err(failed)?;
+                Ok(signature)
+            }
+        }
+    }
+}
+
+/// The PEM blocks of a file, as (label, DER)
+fn read_pem(path: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read PEM file: {}", path.display()))?;
+    let mut blocks = Vec::new();
+    let mut open: Option<(&str, String)> = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(label) = line
+            .strip_prefix("-----BEGIN ")
+            .and_then(|l| l.strip_suffix("-----"))
+        {
+            open = Some((label, String::new()));
+        } else if line.starts_with("-----END ")
+            && let Some((label, body)) = open.take()
+        {
+            let der = base64::engine::general_purpose::STANDARD
+                .decode(body)
+                .map_err(|err| InvalidConfig(format!("{}: {label}: {err}", path.display())))?;
+            blocks.push((label.to_string(), der));
+        } else if let Some((_, body)) = &mut open {
+            body.push_str(line);
+        }
+    }
+    Ok(blocks)
+}
+
+/// One step of how an output came to be, as a C2PA action
+#[derive(Clone, Debug)]
+struct Action {
+    action: &'static str,
+    description: String,
+}
+
+fn action(action: &'static str, description: impl Into<String>) -> Action {
+    Action {
+        action,
+        description: description.into(),
+    }
+}
+
+/// What the manifests of a batch's outputs record, and who signs them
+#[derive(Clone)]
+pub struct Provenance {
+    signer: Arc<Signer>,
+    /// Transforms run on the source before scaling
+    edits: Vec<Action>,
+    /// Transforms run on every scaled variant
+    effects: Vec<Action>,
+    /// The output's own source and variant, once known
+    output: Option<Output>,
+}
+
+#[derive(Clone)]
+struct Output {
+    source: PathBuf,
+    scale: u32,
+    simulated: Option<&'static str>,
+}
+
+impl Provenance {
+    pub fn new(signer: Signer, transforms: &Transforms) -> Self {
+        Self {
+            signer: Arc::new(signer),
+            edits: edits(transforms),
+            effects: effects(transforms),
+            output: None,
+        }
+    }
+
+    /// The provenance of one output: a variant of `source` at `scale`
+    /// percent, seen with the color vision deficiency `simulated`
+    pub fn for_output(&self, source: &Path, scale: u32, simulated: Option<&'static str>) -> Self {
+        Self {
+            output: Some(Output {
+                source: source.to_path_buf(),
+                scale,
+                simulated,
+            }),
+            ..self.clone()
+        }
+    }
+
+    /// Every action, in the order it was done
+    fn actions(&self, format: &str) -> Vec<Action> {
+        let mut actions = self.edits.clone();
+        if let Some(output) = &self.output
+            && output.scale != 100
+        {
+            actions.push(action(
+                "c2pa.resized",
+                format!("scaled to {}%", output.scale),
+            ));
+        }
+        actions.extend(self.effects.iter().cloned());
+        if let Some(deficiency) = self.output.as_ref().and_then(|output| output.simulated) {
+            actions.push(action(
+                "c2pa.color_adjustments",
+                format!("simulated {deficiency}"),
+            ));
+        }
+        actions.push(action(
+            "c2pa.transcoded",
+            format!("encoded as {}", format.to_uppercase()),
+        ));
+        actions
+    }
+}
+
+/// The transforms of `Transforms::apply`, as actions
+fn edits(t: &Transforms) -> Vec<Action> {
+    let mut actions = Vec::new();
+    if t.sidecars {
+        actions.push(action("c2pa.edited", "crop and rotation from XMP sidecar"));
+    }
+    if t.document {
+        actions.push(action(
+            "c2pa.filtered",
+            match t.binarize {
+                true => "page deskewed and reduced to black and white",
+                false => "page deskewed and whitened",
+            },
+        ));
+    }
+    if let Some(degrees) = t.rotate {
+        actions.push(action("c2pa.orientation", format!("rotated {degrees}°")));
+    }
+    if t.auto_straighten {
+        actions.push(action("c2pa.orientation", "straightened"));
+    }
+    if t.trim.is_some() {
+        actions.push(action("c2pa.cropped", "uniform borders trimmed"));
+    }
+    if let Some(rect) = t.crop {
+        actions.push(action(
+            "c2pa.cropped",
+            format!(
+                "cropped to {}x{}+{}+{}",
+                rect.width, rect.height, rect.x, rect.y
+            ),
+        ));
+    }
+    if let Some(aspect) = t.aspect {
+        let ratio = format!("{}:{}", aspect.width, aspect.height);
+        actions.push(match t.resize_mode {
+            ResizeMode::Crop => action("c2pa.cropped", format!("cropped to {ratio}")),
+            ResizeMode::Seam => action("c2pa.resized", format!("seam carved to {ratio}")),
+            ResizeMode::Pad => action("c2pa.edited", format!("padded to {ratio}")),
+        });
+    }
+    if t.denoise.is_some() {
+        actions.push(action("c2pa.filtered", "noise reduced"));
+    }
+    if t.fix_redeye {
+        actions.push(action("c2pa.edited", "red eyes removed"));
+    }
+    if t.auto_enhance {
+        actions.push(action(
+            "c2pa.color_adjustments",
+            "levels, white balance and saturation",
+        ));
+    }
+    if t.remove_background.is_some() {
+        actions.push(action("c2pa.edited", "background removed"));
+    }
+    if let Some((width, height)) = t.extent {
+        actions.push(action(
+            "c2pa.edited",
+            format!("canvas set to {width}x{height}"),
+        ));
+    }
+    if let Some(width) = t.max_width {
+        actions.push(action(
+            "c2pa.resized",
+            format!("shrunk to at most {width}px wide"),
+        ));
+    }
+    actions.extend(custom(t, Stage::BeforeScale));
+    actions
+}
+
+/// The transforms of `Transforms::apply_scaled`, as actions
+fn effects(t: &Transforms) -> Vec<Action> {
+    let mut actions = Vec::new();
+    if t.lut.is_some() {
+        actions.push(action("c2pa.color_adjustments", "3D LUT color grade"));
+    }
+    if t.vignette.is_some() {
+        actions.push(action("c2pa.filtered", "vignette"));
+    }
+    if let Some(levels) = t.posterize {
+        actions.push(action(
+            "c2pa.color_adjustments",
+            format!("posterized to {levels} levels"),
+        ));
+    }
+    if let Some(palette) = &t.palette {
+        actions.push(action(
+            "c2pa.color_adjustments",
+            format!("mapped onto a {}-color palette", palette.len()),
+        ));
+    }
+    if t.watermark.is_some() {
+        actions.push(action("c2pa.watermarked", "invisible watermark"));
+    }
+    actions.extend(custom(t, Stage::AfterScale));
+    actions
+}
+
+/// Plugins and models of a stage, which say nothing about what they do
+fn custom(t: &Transforms, stage: Stage) -> impl Iterator<Item = Action> + '_ {
+    t.custom
+        .iter()
+        .filter(move |(s, _)| *s == stage)
+        .map(|_| action("c2pa.edited", "custom transform"))
+}
+
+/// Adds a signed manifest of `provenance` to an encoded JPEG or PNG
+pub fn embed(bytes: Vec<u8>, format: &str, provenance: &Provenance) -> Result<Vec<u8>> {
+    let (mime, at) = match format.to_lowercase().as_str() {
+        "jpg" | "jpeg" => ("image/jpeg", jpeg_insertion(&bytes)),
+        // Signature, then IHDR: length, type, 13 bytes of data and CRC
+        "png" => ("image/png", 8 + 8 + 13 + 4),
+        other => anyhow::bail!(tr!(Msg::C2paFormat, other)),
+    };
+    anyhow::ensure!(
+        at <= bytes.len(),
+        "Truncated {} output",
+        format.to_uppercase()
+    );
+    let hash = digest(&SHA256, &bytes);
+    let ids = Ids {
+        manifest: uuid(&provenance.signer.rng)?,
+        output: uuid(&provenance.signer.rng)?,
+        source: uuid(&provenance.signer.rng)?,
+    };
+
+    // The length is part of the manifest: rebuild until it fits itself
+    let mut length = 0;
+    loop {
+        let store = manifest_store(provenance, mime, hash.as_ref(), (at, length), &ids)?;
+        let wrapped = match mime {
+            "image/jpeg" => app11_segments(&store),
+            _ => png_chunk(b"caBX", &store),
+        };
+        if wrapped.len() == length {
+            let mut out = Vec::with_capacity(bytes.len() + length);
+            out.extend_from_slice(&bytes[..at]);
+            out.extend_from_slice(&wrapped);
+            out.extend_from_slice(&bytes[at..]);
+            return Ok(out);
+        }
+        length = wrapped.len();
+    }
+}
+
+/// Where the manifest goes in a JPEG: after SOI and the APPn segments
+/// (JFIF, EXIF, XMP) that lead it
+fn jpeg_insertion(jpeg: &[u8]) -> usize {
+    let mut at = 2;
+    while let Some(&[0xFF, marker, high, low]) = jpeg.get(at..at + 4)
+        && (0xE0..=0xEF).contains(&marker)
+    {
+        at += 2 + u16::from_be_bytes([high, low]) as usize;
+    }
+    at.min(jpeg.len())
+}
+
+/// The manifest store in as many APP11 segments as it needs; every one
+/// after the first repeats the store's box header
+fn app11_segments(store: &[u8]) -> Vec<u8> {
+    let header = &store[..8];
+    let mut out = Vec::new();
+    let mut rest = store;
+    let mut sequence = 1u32;
+    while !rest.is_empty() {
+        let repeated: &[u8] = if sequence == 1 { &[] } else { header };
+        let room = MAX_SEGMENT - APP11_HEADER - repeated.len();
+        let (data, remaining) = rest.split_at(rest.len().min(room));
+        out.extend_from_slice(&[0xFF, 0xEB]);
+        out.extend_from_slice(
+            &((2 + APP11_HEADER + repeated.len() + data.len()) as u16).to_be_bytes(),
+        );
+        out.extend_from_slice(b"JP");
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&sequence.to_be_bytes());
+        out.extend_from_slice(repeated);
+        out.extend_from_slice(data);
+        rest = remaining;
+        sequence += 1;
+    }
+    out
+}
+
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(12 + data.len());
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    chunk.extend_from_slice(&hasher.finalize().to_be_bytes());
+    chunk
+}
+
+/// Identifiers of one manifest, fixed while its length settles
+struct Ids {
+    manifest: String,
+    output: String,
+    source: String,
+}
+
+/// The JUMBF manifest store for an output of type `mime` whose bytes
+/// outside `exclusion` (start, length) hash to `hash`
+fn manifest_store(
+    provenance: &Provenance,
+    mime: &str,
+    hash: &[u8],
+    exclusion: (usize, usize),
+    ids: &Ids,
+) -> Result<Vec<u8>> {
+    let ingredient = provenance.output.as_ref().map(|output| {
+        let mut cbor = Cbor::default();
+        cbor.map(4)
+            .text("dc:title")
+            .text(
+                &output
+                    .source
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+            )
+            .text("dc:format")
+            .text(source_mime(&output.source))
+            .text("instanceID")
+            .text(&format!("xmp:iid:{}", ids.source))
+            .text("relationship")
+            .text("parentOf");
+        assertion("c2pa.ingredient", cbor)
+    });
+    let ingredient_ref = ingredient
+        .as_ref()
+        .map(|ingredient| reference("c2pa.ingredient", ingredient));
+
+    let actions = provenance.actions(mime.trim_start_matches("image/"));
+    let mut cbor = Cbor::default();
+    cbor.map(1)
+        .text("actions")
+        .array(actions.len() as u64 + u64::from(ingredient_ref.is_some()));
+    if let Some((url, hash)) = &ingredient_ref {
+        cbor.map(3)
+            .text("action")
+            .text("c2pa.opened")
+            .text("softwareAgent")
+            .text(GENERATOR)
+            .text("parameters")
+            .map(1)
+            .text("ingredient")
+            .map(2)
+            .text("url")
+            .text(url)
+            .text("hash")
+            .bytes(hash);
+    }
+    for action in &actions {
+        cbor.map(3)
+            .text("action")
+            .text(action.action)
+            .text("softwareAgent")
+            .text(GENERATOR)
+            .text("description")
+            .text(&action.description);
+    }
+    let actions = assertion("c2pa.actions", cbor);
+
+    let mut cbor = Cbor::default();
+    cbor.map(5)
+        .text("exclusions")
+        .array(1)
+        .map(2)
+        .text("start")
+        .uint(exclusion.0 as u64)
+        .text("length")
+        .uint(exclusion.1 as u64)
+        .text("name")
+        .text("jumbf manifest")
+        .text("alg")
+        .text("sha256")
+        .text("hash")
+        .bytes(hash)
+        .text("pad")
+        .bytes(&[]);
+    let hash_data = assertion("c2pa.hash.data", cbor);
+
+    let assertions: Vec<(&str, &Vec<u8>)> = ingredient
+        .iter()
+        .map(|ingredient| ("c2pa.ingredient", ingredient))
+        .chain([("c2pa.actions", &actions), ("c2pa.hash.data", &hash_data)])
+        .collect();
+    let mut claim = Cbor::default();
+    claim
+        .map(7)
+        .text("claim_generator")
+        .text(GENERATOR)
+        .text("claim_generator_info")
+        .array(1)
+        .map(2)
+        .text("name")
+        .text("rsimg")
+        .text("version")
+        .text(env!("CARGO_PKG_VERSION"))
+        .text("signature")
+        .text("self#jumbf=c2pa.signature")
+        .text("assertions")
+        .array(assertions.len() as u64);
+    for (label, assertion) in &assertions {
+        let (url, hash) = reference(label, assertion);
+        claim
+            .map(2)
+            .text("url")
+            .text(&url)
+            .text("hash")
+            .bytes(&hash);
+    }
+    claim
+        .text("dc:format")
+        .text(mime)
+        .text("instanceID")
+        .text(&format!("xmp:iid:{}", ids.output))
+        .text("alg")
+        .text("sha256");
+    let claim = claim.0;
+    let signature = cose_sign1(&provenance.signer, &claim)?;
+
+    let assertion_store = superbox(
+        c2pa_uuid(b"c2as"),
+        "c2pa.assertions",
+        &assertions
+            .iter()
+            .map(|(_, assertion)| assertion.as_slice())
+            .collect::<Vec<_>>(),
+    );
+    let manifest = superbox(
+        c2pa_uuid(b"c2ma"),
+        &format!("urn:uuid:{}", ids.manifest),
+        &[
+            &assertion_store,
+            &superbox(
+                c2pa_uuid(b"c2cl"),
+                "c2pa.claim",
+                &[&jumbf_box(b"cbor", &claim)],
+            ),
+            &superbox(
+                c2pa_uuid(b"c2cs"),
+                "c2pa.signature",
+                &[&jumbf_box(b"cbor", &signature)],
+            ),
+        ],
+    );
+    Ok(superbox(c2pa_uuid(b"c2pa"), "c2pa", &[&manifest]))
+}
+
+/// An assertion superbox holding `cbor`
+fn assertion(label: &str, cbor: Cbor) -> Vec<u8> {
+    superbox(c2pa_uuid(b"cbor"), label, &[&jumbf_box(b"cbor", &cbor.0)])
+}
+
+/// The URL and hash the claim refers to an assertion by; the hash covers
+/// the superbox less its own header
+fn reference(label: &str, assertion: &[u8]) -> (String, Vec<u8>) {
+    (
+        format!("self#jumbf=c2pa.assertions/{label}"),
+        digest(&SHA256, &assertion[8..]).as_ref().to_vec(),
+    )
+}
+
+/// `claim` signed as a detached COSE_Sign1 (tag 18)
+fn cose_sign1(signer: &Signer, claim: &[u8]) -> Result<Vec<u8>> {
+    let mut protected = Cbor::default();
+    protected.map(2).int(1).int(signer.algorithm()).int(X5CHAIN);
+    match signer.certs.as_slice() {
+        [cert] => protected.bytes(cert),
+        certs => {
+            protected.array(certs.len() as u64);
+            certs
+                .iter()
+                .fold(&mut protected, |cbor, cert| cbor.bytes(cert))
+        }
+    };
+    let mut to_sign = Cbor::default();
+    to_sign
+        .array(4)
+        .text("Signature1")
+        .bytes(&protected.0)
+        .bytes(&[])
+        .bytes(claim);
+    let signature = signer.sign(&to_sign.0)?;
+
+    let mut cose = Cbor::default();
+    cose.tag(18)
+        .array(4)
+        .bytes(&protected.0)
+        .map(0)
+        .null()
+        .bytes(&signature);
+    Ok(cose.0)
+}
+
+/// A JUMBF superbox: the description box (type, requestable and labelled)
+/// followed by the content boxes
+fn superbox(kind: [u8; 16], label: &str, content: &[&[u8]]) -> Vec<u8> {
+    let mut description = kind.to_vec();
+    description.push(0x03);
+    description.extend_from_slice(label.as_bytes());
+    description.push(0);
+    let mut payload = jumbf_box(b"jumd", &description);
+    for boxed in content {
+        payload.extend_from_slice(boxed);
+    }
+    jumbf_box(b"jumb", &payload)
+}
+
+fn jumbf_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut boxed = Vec::with_capacity(8 + payload.len());
+    boxed.extend_from_slice(&(8 + payload.len() as u32).to_be_bytes());
+    boxed.extend_from_slice(kind);
+    boxed.extend_from_slice(payload);
+    boxed
+}
+
+/// The JUMBF type UUID C2PA gives a four-letter tag
+fn c2pa_uuid(tag: &[u8; 4]) -> [u8; 16] {
+    let mut uuid = [0; 16];
+    uuid[..4].copy_from_slice(tag);
+    uuid[4..].copy_from_slice(&[
+        0x00, 0x11, 0x00, 0x10, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+    ]);
+    uuid
+}
+
+/// A random (version 4) UUID in its text form
+fn uuid(rng: &SystemRandom) -> Result<String> {
+    let mut bytes = [0u8; 16];
+    rng.fill(&mut bytes)
+        .map_err(|_| anyhow::anyhow!("No randomness for the C2PA manifest"))?;
+    bytes[6] = bytes[6] & 0x0F | 0x40;
+    bytes[8] = bytes[8] & 0x3F | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+/// MIME type of a source, by its extension
+fn source_mime(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        "tif" | "tiff" => "image/tiff",
+        "avif" => "image/avif",
+        "heic" | "heif" => "image/heic",
+        "bmp" => "image/bmp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A CBOR writer for just the types manifests use
+#[derive(Default)]
+struct Cbor(Vec<u8>);
+
+impl Cbor {
+    fn head(&mut self, major: u8, value: u64) -> &mut Self {
+        let major = major << 5;
+        match value {
+            0..=23 => self.0.push(major | value as u8),
+            24..=0xFF => self.0.extend_from_slice(&[major | 24, value as u8]),
+            0x100..=0xFFFF => {
+                self.0.push(major | 25);
+                self.0.extend_from_slice(&(value as u16).to_be_bytes());
+            }
+            0x1_0000..=0xFFFF_FFFF => {
+                self.0.push(major | 26);
+                self.0.extend_from_slice(&(value as u32).to_be_bytes());
+            }
+            _ => {
+                self.0.push(major | 27);
+                self.0.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        self
+    }
+
+    fn uint(&mut self, value: u64) -> &mut Self {
+        self.head(0, value)
+    }
+
+    fn int(&mut self, value: i64) -> &mut Self {
+        match value {
+            0.. => self.head(0, value as u64),
+            _ => self.head(1, !value as u64),
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.head(2, bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn text(&mut self, text: &str) -> &mut Self {
+        self.head(3, text.len() as u64);
+        self.0.extend_from_slice(text.as_bytes());
+        self
+    }
+
+    fn array(&mut self, len: u64) -> &mut Self {
+        self.head(4, len)
+    }
+
+    fn map(&mut self, len: u64) -> &mut Self {
+        self.head(5, len)
+    }
+
+    fn tag(&mut self, tag: u64) -> &mut Self {
+        self.head(6, tag)
+    }
+
+    fn null(&mut self) -> &mut Self {
+        self.0.push(0xF6);
+        self
+    }
+}
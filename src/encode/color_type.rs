@@ -0,0 +1,137 @@
+// src/encode/color_type.rs
+//
+// PNG color type and bit depth selection. `auto` looks for the cheapest
+// representation that is still lossless: unused alpha and 16-bit samples
+// that only hold 8-bit values are dropped, gray content becomes a gray PNG
+// and images with few colors become indexed.
+
+use super::palette::{self, Dither};
+use anyhow::Result;
+use clap::ValueEnum;
+use image::{DynamicImage, ImageFormat};
+use std::collections::HashSet;
+
+/// PNG color type
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PngColorType {
+    /// Cheapest lossless representation of the pixels
+    Auto,
+    /// Truecolor, alpha dropped
+    Rgb,
+    /// Truecolor with alpha
+    Rgba,
+    /// Grayscale, with alpha if the image has any
+    Gray,
+    /// 8-bit indexed (quantized if it has more than --colors colors)
+    Palette,
+}
+
+/// PNG sample depth
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PngBitDepth {
+    #[value(name = "8")]
+    Eight,
+    #[value(name = "16")]
+    Sixteen,
+}
+
+/// Pixel properties that decide the cheapest lossless color type
+struct Analysis {
+    /// Some pixel isn't fully opaque
+    alpha: bool,
+    /// Every pixel has equal red, green and blue
+    gray: bool,
+    /// Some 16-bit sample can't be represented in 8 bits
+    deep: bool,
+    /// At most 256 distinct colors
+    few_colors: bool,
+}
+
+fn analyze(img: &DynamicImage) -> Analysis {
+    let mut analysis = Analysis {
+        alpha: false,
+        gray: true,
+        deep: false,
+        few_colors: false,
+    };
+
+    if super::is_high_depth(img) {
+        let pixels = img.to_rgba16();
+        for p in pixels.pixels() {
+            analysis.alpha |= p[3] != u16::MAX;
+            analysis.gray &= p[0] == p[1] && p[1] == p[2];
+            analysis.deep |= p.0.iter().any(|&v| v % 257 != 0);
+        }
+    } else {
+        let pixels = img.to_rgba8();
+        let mut colors = HashSet::new();
+        for p in pixels.pixels() {
+            analysis.alpha |= p[3] != u8::MAX;
+            analysis.gray &= p[0] == p[1] && p[1] == p[2];
+            if colors.len() <= 256 {
+                colors.insert(p.0);
+            }
+        }
+        analysis.few_colors = colors.len() <= 256;
+    }
+
+    // Gray can't have a palette advantage without alpha: both are one byte
+    // per pixel and gray needs no PLTE chunk
+    if analysis.deep || (analysis.gray && !analysis.alpha) {
+        analysis.few_colors = false;
+    }
+    analysis
+}
+
+/// Encodes a PNG in the requested (or cheapest lossless) color type and depth
+pub fn encode_png(
+    img: &DynamicImage,
+    color_type: PngColorType,
+    bit_depth: Option<PngBitDepth>,
+    colors: Option<u16>,
+    dither: Dither,
+) -> Result<Vec<u8>> {
+    // An explicit palette size asks for indexed output
+    let color_type = match (color_type, colors) {
+        (PngColorType::Auto, Some(_)) => PngColorType::Palette,
+        (color_type, _) => color_type,
+    };
+
+    let (color_type, alpha, mut deep) = match color_type {
+        PngColorType::Auto => {
+            let a = analyze(img);
+            let color_type = match (a.few_colors, a.gray, a.alpha) {
+                (true, ..) => PngColorType::Palette,
+                (false, true, _) => PngColorType::Gray,
+                (false, false, true) => PngColorType::Rgba,
+                (false, false, false) => PngColorType::Rgb,
+            };
+            (color_type, a.alpha, a.deep)
+        }
+        other => (other, img.color().has_alpha(), super::is_high_depth(img)),
+    };
+
+    if color_type == PngColorType::Palette {
+        if bit_depth == Some(PngBitDepth::Sixteen) {
+            anyhow::bail!("Palette PNGs can't be written with 16-bit samples");
+        }
+        return palette::encode_indexed_png(img, colors.unwrap_or(256), dither);
+    }
+
+    if let Some(depth) = bit_depth {
+        deep = depth == PngBitDepth::Sixteen;
+    }
+
+    let pixels = match (color_type, alpha, deep) {
+        (PngColorType::Gray, false, false) => DynamicImage::ImageLuma8(img.to_luma8()),
+        (PngColorType::Gray, false, true) => DynamicImage::ImageLuma16(img.to_luma16()),
+        (PngColorType::Gray, true, false) => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+        (PngColorType::Gray, true, true) => DynamicImage::ImageLumaA16(img.to_luma_alpha16()),
+        (PngColorType::Rgba, _, false) => DynamicImage::ImageRgba8(img.to_rgba8()),
+        (PngColorType::Rgba, _, true) => DynamicImage::ImageRgba16(img.to_rgba16()),
+        (_, _, false) => DynamicImage::ImageRgb8(img.to_rgb8()),
+        (_, _, true) => DynamicImage::ImageRgb16(img.to_rgb16()),
+    };
+
+    super::encode_as(&pixels, ImageFormat::Png)
+}
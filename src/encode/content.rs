@@ -0,0 +1,165 @@
+// src/encode/content.rs
+//
+// Content classification for `--classify`, the `--if-photo`, `--if-graphic`
+// and `--if-text` rules and `--preset screenshot`. Screenshots, diagrams and
+// text are mostly runs of identical pixels broken by hard edges, while
+// photos change a little from almost every pixel to the next; lossy JPEG
+// rings around exactly the edges the former are made of. Neighbouring
+// pixels along a sample of rows are compared: graphics are flat with few
+// soft steps, or use few colors altogether, and text is a graphic of
+// mostly two colors with many hard edges between them. With the `onnx`
+// feature a small model can take the decision instead (`--classify-model`).
+// Lossy outputs of graphics and text are routed to lossless WebP and to a
+// quantized PNG in place of JPEG.
+
+use anyhow::Result;
+use image::DynamicImage;
+use std::collections::HashMap;
+
+#[cfg(feature = "onnx")]
+use crate::onnx::OnnxModel;
+#[cfg(feature = "onnx")]
+use anyhow::Context;
+#[cfg(feature = "onnx")]
+use std::path::Path;
+#[cfg(feature = "onnx")]
+use std::sync::Arc;
+
+/// Rows compared at most, spread over the image
+const SAMPLE_ROWS: u32 = 256;
+/// Largest channel difference counted as a soft (photographic) step
+const SOFT_STEP: u8 = 24;
+/// Share of identical neighbours from which an image may be a graphic
+const MIN_FLAT: f64 = 0.6;
+/// Share of soft steps above which it's a photo after all
+const MAX_SOFT: f64 = 0.15;
+/// Colors an image can use and be a graphic however it looks
+const FEW_COLORS: usize = 256;
+/// Distinct colors counted at most; later ones only add to the total
+const MAX_TRACKED: usize = 4096;
+/// Share of hard edges from which a two-color graphic is text
+const MIN_HARD: f64 = 0.03;
+/// Share of the pixels the two most common colors (ink and paper) cover in text
+const MIN_INK_PAPER: f64 = 0.6;
+/// Input size of classification models
+#[cfg(feature = "onnx")]
+const MODEL_INPUT: (u32, u32) = (224, 224);
+
+/// What an image shows, as far as encoding goes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Content {
+    Photo,
+    /// UI or flat-color art
+    Graphic,
+    /// Documents, code, chat: text on a plain background
+    Text,
+}
+
+impl Content {
+    pub const ALL: [Content; 3] = [Content::Photo, Content::Graphic, Content::Text];
+
+    /// Label in reports and rule flags
+    pub fn name(self) -> &'static str {
+        match self {
+            Content::Photo => "photo",
+            Content::Graphic => "graphic",
+            Content::Text => "text",
+        }
+    }
+}
+
+/// Decides what images show: by pixel statistics, or with an ONNX model
+#[derive(Clone, Default)]
+pub struct Classifier {
+    #[cfg(feature = "onnx")]
+    model: Option<Arc<OnnxModel>>,
+}
+
+impl Classifier {
+    /// A classifier running a model that takes a 1x3x224x224 tensor of RGB
+    /// values in [0, 1] and returns a score for photo, graphic and text, in
+    /// that order
+    #[cfg(feature = "onnx")]
+    pub fn with_model(path: &Path) -> Result<Self> {
+        Ok(Self {
+            model: Some(Arc::new(OnnxModel::load(path, MODEL_INPUT)?)),
+        })
+    }
+
+    pub fn classify(&self, img: &DynamicImage) -> Result<Content> {
+        #[cfg(feature = "onnx")]
+        if let Some(model) = &self.model {
+            let scores = model.run(img)?;
+            let scores: Vec<f32> = scores.iter().copied().collect();
+            if scores.len() != Content::ALL.len() {
+                anyhow::bail!(
+                    "The classification model returned {} scores (expected photo, graphic and text)",
+                    scores.len()
+                );
+            }
+            let best = (0..scores.len())
+                .max_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+                .context("The classification model returned no scores")?;
+            return Ok(Content::ALL[best]);
+        }
+        Ok(classify(img))
+    }
+}
+
+/// Classifies an image by its colors and the steps between neighbouring
+/// pixels
+pub fn classify(img: &DynamicImage) -> Content {
+    let rgb = img.to_rgb8();
+    let (width, height) = rgb.dimensions();
+    if width < 2 {
+        return Content::Photo;
+    }
+
+    let mut colors: HashMap<[u8; 3], u64> = HashMap::new();
+    let (mut pairs, mut flat, mut soft) = (0u64, 0u64, 0u64);
+    let step = (height / SAMPLE_ROWS).max(1) as usize;
+    for y in (0..height).step_by(step) {
+        for x in 1..width {
+            let (a, b) = (rgb.get_pixel(x - 1, y).0, rgb.get_pixel(x, y).0);
+            let diff = (0..3).map(|i| a[i].abs_diff(b[i])).max().unwrap_or(0);
+            pairs += 1;
+            match diff {
+                0 => flat += 1,
+                1..=SOFT_STEP => soft += 1,
+                _ => {}
+            }
+            if let Some(count) = colors.get_mut(&b) {
+                *count += 1;
+            } else if colors.len() < MAX_TRACKED {
+                colors.insert(b, 1);
+            }
+        }
+    }
+
+    let share = |count: u64| count as f64 / pairs as f64;
+    let hard = pairs - flat - soft;
+    let graphic =
+        colors.len() <= FEW_COLORS || (share(flat) >= MIN_FLAT && share(soft) <= MAX_SOFT);
+    if !graphic {
+        return Content::Photo;
+    }
+    let mut counts: Vec<u64> = colors.into_values().collect();
+    counts.sort_unstable_by(|a, b| b.cmp(a));
+    let ink_paper: u64 = counts.iter().take(2).sum();
+    match share(hard) >= MIN_HARD && share(ink_paper) >= MIN_INK_PAPER {
+        true => Content::Text,
+        false => Content::Graphic,
+    }
+}
+
+/// Format `content` is written in when `format` is asked for (PNG for
+/// graphics and text in place of JPEG), and whether that's a lossless
+/// encoding in place of a lossy one
+pub fn route(format: &str, content: Content) -> (&str, bool) {
+    match (content, format.to_lowercase().as_str()) {
+        (Content::Photo, _) => (format, false),
+        (_, "jpg" | "jpeg") => ("png", true),
+        (_, "webp") => (format, true),
+        _ => (format, false),
+    }
+}
@@ -0,0 +1,574 @@
+// src/encode/exif.rs
+//
+// Just enough EXIF for JPEG outputs: reading and resetting the orientation
+// tag, and replacing the IFD1 thumbnail file managers show in their grids.
+// Output names also read a few capture details (date, camera, ISO, focal
+// length). `--use-embedded-preview` finds the JPEG previews cameras store
+// beside the image: the IFD1 thumbnail and MPF (CIPA multi-picture)
+// previews of JPEGs, and the JPEG-compressed IFDs and SubIFDs of TIFF-based
+// files, camera RAW included.
+
+use anyhow::{Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageReader};
+use std::io::Cursor;
+
+/// EXIF thumbnails are conventionally at most 160x120
+const THUMBNAIL_SIZE: (u32, u32) = (160, 120);
+const THUMBNAIL_QUALITY: u8 = 75;
+/// A marker segment holds at most 64 KiB, its length field included
+const MAX_PAYLOAD: usize = 65533;
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const MPF_HEADER: &[u8] = b"MPF\0";
+const JPEG_SOI: &[u8] = &[0xFF, 0xD8];
+
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_ORIENTATION: u16 = 0x0112;
+/// Star rating, as Windows writes it
+const TAG_RATING: u16 = 0x4746;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_SUB_IFDS: u16 = 0x014A;
+const TAG_THUMBNAIL_OFFSET: u16 = 0x0201;
+const TAG_THUMBNAIL_LENGTH: u16 = 0x0202;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_ISO: u16 = 0x8827;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_SUB_SEC_TIME_ORIGINAL: u16 = 0x9291;
+const TAG_FOCAL_LENGTH: u16 = 0x920A;
+const TAG_MP_ENTRY: u16 = 0xB002;
+
+/// TIFF compression of JPEG data: the 6.0 scheme and the one DNG uses
+const COMPRESSION_JPEG: [u32; 2] = [6, 7];
+/// IFDs followed at most, against loops in corrupt files
+const MAX_IFDS: usize = 64;
+
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+
+/// Capture details of a photo, for output names
+#[derive(Clone, Debug, Default)]
+pub struct CaptureInfo {
+    /// When the shot was taken, `YYYY-MM-DD`
+    pub date: Option<String>,
+    /// When the shot was taken to the sub-second, as seconds since 1970 in
+    /// the camera's unknown time zone, for ordering shots
+    pub seconds: Option<f64>,
+    /// Camera model as recorded, e.g. `Canon EOS 5D Mark IV`
+    pub camera: Option<String>,
+    pub iso: Option<u32>,
+    /// Focal length in millimeters
+    pub focal: Option<f64>,
+}
+
+/// Byte-order aware view of the TIFF structure inside an APP1 payload
+struct Tiff<'a> {
+    data: &'a [u8],
+    le: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn parse(payload: &'a [u8]) -> Option<Self> {
+        Self::new(payload.strip_prefix(EXIF_HEADER)?)
+    }
+
+    /// The structure starting at a TIFF header
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let le = match data.get(..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        Some(Self { data, le })
+    }
+
+    fn u16_at(&self, pos: usize) -> Option<u16> {
+        let b = self.data.get(pos..pos + 2)?;
+        let b = [b[0], b[1]];
+        Some(if self.le {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32_at(&self, pos: usize) -> Option<u32> {
+        let b = self.data.get(pos..pos + 4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Some(if self.le {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    fn ifd0(&self) -> Option<usize> {
+        self.u32_at(4).map(|offset| offset as usize)
+    }
+
+    /// Offset of the IFD's link to the next IFD
+    fn next_link(&self, ifd: usize) -> Option<usize> {
+        Some(ifd + 2 + self.u16_at(ifd)? as usize * 12)
+    }
+
+    /// Offset of the entry for `tag` in the IFD at `ifd`
+    fn entry(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let count = self.u16_at(ifd)? as usize;
+        (0..count)
+            .map(|i| ifd + 2 + i * 12)
+            .find(|&entry| self.u16_at(entry) == Some(tag))
+    }
+
+    /// Value of a SHORT or LONG entry
+    fn value(&self, ifd: usize, tag: u16) -> Option<u32> {
+        let entry = self.entry(ifd, tag)?;
+        match self.u16_at(entry + 2)? {
+            SHORT => self.u16_at(entry + 8).map(u32::from),
+            LONG => self.u32_at(entry + 8),
+            _ => None,
+        }
+    }
+
+    /// Offset of an entry's value: inline when it fits in 4 bytes
+    fn value_offset(&self, entry: usize, size: usize) -> Option<usize> {
+        match size {
+            0..=4 => Some(entry + 8),
+            _ => self.u32_at(entry + 8).map(|offset| offset as usize),
+        }
+    }
+
+    /// Text of an ASCII entry, without the terminating NUL and padding
+    fn text(&self, ifd: usize, tag: u16) -> Option<String> {
+        let entry = self.entry(ifd, tag)?;
+        if self.u16_at(entry + 2)? != ASCII {
+            return None;
+        }
+        let count = self.u32_at(entry + 4)? as usize;
+        let offset = self.value_offset(entry, count)?;
+        let bytes = self.data.get(offset..offset.checked_add(count)?)?;
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_end_matches('\0').trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    /// Value of a RATIONAL entry
+    fn rational(&self, ifd: usize, tag: u16) -> Option<f64> {
+        let entry = self.entry(ifd, tag)?;
+        if self.u16_at(entry + 2)? != RATIONAL {
+            return None;
+        }
+        let offset = self.value_offset(entry, 8)?;
+        let (num, den) = (self.u32_at(offset)?, self.u32_at(offset + 4)?);
+        (den != 0).then(|| num as f64 / den as f64)
+    }
+
+    /// Every value of a SHORT or LONG entry
+    fn values(&self, ifd: usize, tag: u16) -> Vec<u32> {
+        let Some(entry) = self.entry(ifd, tag) else {
+            return Vec::new();
+        };
+        let (Some(kind), Some(count)) = (self.u16_at(entry + 2), self.u32_at(entry + 4)) else {
+            return Vec::new();
+        };
+        let count = (count as usize).min(self.data.len() / 2);
+        let size = match kind {
+            SHORT => 2,
+            LONG => 4,
+            _ => return Vec::new(),
+        };
+        let Some(offset) = self.value_offset(entry, count * size) else {
+            return Vec::new();
+        };
+        (0..count)
+            .map_while(|i| match kind {
+                SHORT => self.u16_at(offset + i * 2).map(u32::from),
+                _ => self.u32_at(offset + i * 4),
+            })
+            .collect()
+    }
+
+    /// Byte ranges of the JPEG data an IFD holds or points to
+    fn jpegs(&self, ifd: usize) -> Vec<(usize, usize)> {
+        let range = |offset: u32, length: u32| {
+            let (start, length) = (offset as usize, length as usize);
+            let end = start.checked_add(length)?;
+            self.data
+                .get(start..end)
+                .filter(|bytes| bytes.starts_with(JPEG_SOI))
+                .map(|_| (start, end))
+        };
+        let mut ranges = Vec::new();
+        if let (Some(offset), Some(length)) = (
+            self.value(ifd, TAG_THUMBNAIL_OFFSET),
+            self.value(ifd, TAG_THUMBNAIL_LENGTH),
+        ) {
+            ranges.extend(range(offset, length));
+        }
+        // Single-strip JPEG data is a complete file
+        let offsets = self.values(ifd, TAG_STRIP_OFFSETS);
+        let lengths = self.values(ifd, TAG_STRIP_BYTE_COUNTS);
+        if let (&[offset], &[length]) = (offsets.as_slice(), lengths.as_slice())
+            && self
+                .value(ifd, TAG_COMPRESSION)
+                .is_some_and(|compression| COMPRESSION_JPEG.contains(&compression))
+        {
+            ranges.extend(range(offset, length));
+        }
+        ranges
+    }
+
+    /// Byte ranges of the JPEG data in every IFD of the chain and their
+    /// SubIFDs
+    fn all_jpegs(&self) -> Vec<(usize, usize)> {
+        let mut pending: Vec<usize> = self.ifd0().into_iter().collect();
+        let mut seen = Vec::new();
+        let mut ranges = Vec::new();
+        while let Some(ifd) = pending.pop() {
+            if ifd == 0 || seen.contains(&ifd) || seen.len() >= MAX_IFDS {
+                continue;
+            }
+            seen.push(ifd);
+            ranges.extend(self.jpegs(ifd));
+            let sub_ifds = self.values(ifd, TAG_SUB_IFDS);
+            pending.extend(sub_ifds.into_iter().map(|offset| offset as usize));
+            if let Some(next) = self.next_link(ifd).and_then(|link| self.u32_at(link)) {
+                pending.push(next as usize);
+            }
+        }
+        ranges
+    }
+
+    /// Byte range of the JPEG thumbnail referenced by IFD1
+    fn thumbnail(&self) -> Option<(usize, usize)> {
+        let ifd1 = self.u32_at(self.next_link(self.ifd0()?)?)? as usize;
+        if ifd1 == 0 {
+            return None;
+        }
+        let offset = self.value(ifd1, TAG_THUMBNAIL_OFFSET)? as usize;
+        let length = self.value(ifd1, TAG_THUMBNAIL_LENGTH)? as usize;
+        (offset + length <= self.data.len()).then_some((offset, offset + length))
+    }
+
+    fn put16(&self, out: &mut Vec<u8>, value: u16) {
+        out.extend_from_slice(&if self.le {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        });
+    }
+
+    fn put32(&self, out: &mut Vec<u8>, value: u32) {
+        out.extend_from_slice(&if self.le {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        });
+    }
+}
+
+/// True for APP1 payloads carrying EXIF (rather than XMP)
+pub fn is_exif(payload: &[u8]) -> bool {
+    Tiff::parse(payload).is_some()
+}
+
+/// Reads the orientation tag (1-8) from an EXIF payload
+pub fn orientation(payload: &[u8]) -> Option<u16> {
+    let tiff = Tiff::parse(payload)?;
+    tiff.value(tiff.ifd0()?, TAG_ORIENTATION)
+        .and_then(|value| u16::try_from(value).ok())
+}
+
+/// Reads the star rating (0-5) from an EXIF payload
+pub fn rating(payload: &[u8]) -> Option<u16> {
+    let tiff = Tiff::parse(payload)?;
+    tiff.value(tiff.ifd0()?, TAG_RATING)
+        .and_then(|value| u16::try_from(value).ok())
+}
+
+/// The embedded JPEG previews of a JPEG or TIFF-based file, and the
+/// file's orientation tag (1-8)
+pub fn embedded_previews(file: &[u8]) -> (Vec<&[u8]>, Option<u16>) {
+    let mut previews = Vec::new();
+    let mut orientation = None;
+    let orientation_of = |tiff: &Tiff| {
+        tiff.value(tiff.ifd0()?, TAG_ORIENTATION)
+            .and_then(|value| u16::try_from(value).ok())
+    };
+
+    if let Some(tiff) = Tiff::new(file) {
+        previews.extend(
+            tiff.all_jpegs()
+                .into_iter()
+                .map(|(start, end)| &file[start..end]),
+        );
+        return (previews, orientation_of(&tiff));
+    }
+    if !file.starts_with(JPEG_SOI) {
+        return (previews, orientation);
+    }
+
+    // Markers up to the scan data
+    let mut pos = 2;
+    while let Some(&[0xFF, marker, high, low]) = file.get(pos..pos + 4) {
+        let end = pos + 2 + u16::from_be_bytes([high, low]) as usize;
+        let Some(payload) = file.get(pos + 4..end) else {
+            break;
+        };
+        match marker {
+            0xDA => break,
+            0xE1 => {
+                if let Some(tiff) = Tiff::parse(payload) {
+                    let base = pos + 4 + EXIF_HEADER.len();
+                    orientation = orientation.or_else(|| orientation_of(&tiff));
+                    previews.extend(
+                        tiff.thumbnail()
+                            .and_then(|(start, end)| file.get(base + start..base + end)),
+                    );
+                }
+            }
+            0xE2 if payload.starts_with(MPF_HEADER) => {
+                previews.extend(mpf_previews(file, pos + 4 + MPF_HEADER.len(), end));
+            }
+            _ => {}
+        }
+        pos = end;
+    }
+    (previews, orientation)
+}
+
+/// The images of an MPF index at `base..end` after the first (the file's
+/// own), whose offsets count from the index's TIFF header
+fn mpf_previews(file: &[u8], base: usize, end: usize) -> Vec<&[u8]> {
+    let Some(tiff) = Tiff::new(&file[base..end]) else {
+        return Vec::new();
+    };
+    let Some((offset, count)) = tiff
+        .ifd0()
+        .and_then(|ifd| tiff.entry(ifd, TAG_MP_ENTRY))
+        .and_then(|entry| {
+            let size = tiff.u32_at(entry + 4)? as usize;
+            Some((tiff.value_offset(entry, size)?, size / 16))
+        })
+    else {
+        return Vec::new();
+    };
+    (1..count)
+        .map_while(|i| {
+            // Attributes, size, offset and two dependent images
+            let entry = offset + i * 16;
+            Some((tiff.u32_at(entry + 4)?, tiff.u32_at(entry + 8)?))
+        })
+        .filter_map(|(size, start)| {
+            let start = base.checked_add(start as usize)?;
+            file.get(start..start.checked_add(size as usize)?)
+        })
+        .filter(|bytes| bytes.starts_with(JPEG_SOI))
+        .collect()
+}
+
+/// Reads the capture date, camera model, ISO and focal length
+pub fn capture_info(payload: &[u8]) -> CaptureInfo {
+    let Some(tiff) = Tiff::parse(payload) else {
+        return CaptureInfo::default();
+    };
+    let Some(ifd0) = tiff.ifd0() else {
+        return CaptureInfo::default();
+    };
+    let exif_ifd = tiff.value(ifd0, TAG_EXIF_IFD).map(|offset| offset as usize);
+
+    // `YYYY:MM:DD HH:MM:SS`; editors often only keep IFD0's DateTime
+    let original = exif_ifd.and_then(|ifd| tiff.text(ifd, TAG_DATE_TIME_ORIGINAL));
+    // Sub-seconds of the original time, as the digits after the point
+    let subsec = original
+        .as_ref()
+        .and(exif_ifd)
+        .and_then(|ifd| tiff.text(ifd, TAG_SUB_SEC_TIME_ORIGINAL));
+    let stamp = original.or_else(|| tiff.text(ifd0, TAG_DATE_TIME));
+    let date = stamp.as_deref().and_then(|date| {
+        let day = date.get(..10)?.replace(':', "-");
+        let digits = day.bytes().filter(u8::is_ascii_digit).count();
+        (digits == 8 && !day.starts_with("0000")).then_some(day)
+    });
+    CaptureInfo {
+        seconds: date
+            .as_ref()
+            .and(stamp.as_deref())
+            .and_then(|stamp| seconds(stamp, subsec.as_deref())),
+        date,
+        camera: tiff.text(ifd0, TAG_MODEL),
+        iso: exif_ifd.and_then(|ifd| tiff.value(ifd, TAG_ISO)),
+        focal: exif_ifd.and_then(|ifd| tiff.rational(ifd, TAG_FOCAL_LENGTH)),
+    }
+}
+
+/// Seconds since 1970 of a `YYYY:MM:DD HH:MM:SS` stamp, plus `subsec`
+fn seconds(stamp: &str, subsec: Option<&str>) -> Option<f64> {
+    let field = |range: std::ops::Range<usize>| stamp.get(range)?.parse::<i64>().ok();
+    let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+    let (hour, minute, second) = (field(11..13)?, field(14..16)?, field(17..19)?);
+    if !(1..=12).contains(&month) {
+        return None;
+    }
+    // Days since 1970 of the proleptic Gregorian date
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let fraction = subsec
+        .map(|digits| digits.trim())
+        .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|digits| format!("0.{digits}").parse::<f64>().ok())
+        .unwrap_or(0.0);
+    Some((days * 86_400 + hour * 3600 + minute * 60 + second) as f64 + fraction)
+}
+
+/// Marks the image as upright once its pixels have been rotated
+pub fn reset_orientation(payload: &mut [u8]) {
+    let Some(tiff) = Tiff::parse(payload) else {
+        return;
+    };
+    let Some(entry) = tiff.ifd0().and_then(|ifd| tiff.entry(ifd, TAG_ORIENTATION)) else {
+        return;
+    };
+    if tiff.u16_at(entry + 2) == Some(SHORT) {
+        let one = if tiff.le { [1, 0] } else { [0, 1] };
+        let at = EXIF_HEADER.len() + entry + 8;
+        payload[at..at + 2].copy_from_slice(&one);
+    }
+}
+
+/// True when the embedded thumbnail is larger than EXIF thumbnails should be
+pub fn has_oversized_thumbnail(payload: &[u8]) -> bool {
+    let Some(tiff) = Tiff::parse(payload) else {
+        return false;
+    };
+    let Some((start, end)) = tiff.thumbnail() else {
+        return false;
+    };
+    ImageReader::new(Cursor::new(&tiff.data[start..end]))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok())
+        .is_some_and(|(w, h)| w.max(h) > THUMBNAIL_SIZE.0 || w.min(h) > THUMBNAIL_SIZE.1)
+}
+
+/// Encodes a thumbnail of the image as a small baseline JPEG
+pub fn encode_thumbnail(img: &DynamicImage) -> Result<Vec<u8>> {
+    let (w, h) = if img.width() >= img.height() {
+        THUMBNAIL_SIZE
+    } else {
+        (THUMBNAIL_SIZE.1, THUMBNAIL_SIZE.0)
+    };
+    let thumb = img.thumbnail(w, h).to_rgb8();
+
+    let mut bytes = Vec::new();
+    JpegEncoder::new_with_quality(&mut bytes, THUMBNAIL_QUALITY)
+        .encode_image(&thumb)
+        .context("Error during thumbnail encoding")?;
+    Ok(bytes)
+}
+
+/// A fresh EXIF payload holding nothing but the thumbnail
+pub fn thumbnail_exif(thumbnail: &[u8]) -> Vec<u8> {
+    // Big-endian TIFF header and an IFD0 with just Orientation = 1
+    let mut payload = EXIF_HEADER.to_vec();
+    payload.extend_from_slice(b"MM\0\x2a\0\0\0\x08");
+    payload.extend_from_slice(&[0, 1]);
+    payload.extend_from_slice(&TAG_ORIENTATION.to_be_bytes());
+    payload.extend_from_slice(&[0, 3, 0, 0, 0, 1, 0, 1, 0, 0]);
+    payload.extend_from_slice(&[0, 0, 0, 0]);
+    replace_thumbnail(&payload, Some(thumbnail))
+}
+
+/// Rewrites an EXIF payload with a new thumbnail, or none at all
+///
+/// A previous thumbnail is unlinked and, when it sits at the end of the
+/// block (where cameras put it), cut off. Returns the payload without a
+/// thumbnail if the new one wouldn't fit in the segment.
+pub fn replace_thumbnail(payload: &[u8], thumbnail: Option<&[u8]>) -> Vec<u8> {
+    let Some(tiff) = Tiff::parse(payload) else {
+        return payload.to_vec();
+    };
+    let Some(link) = tiff.ifd0().and_then(|ifd| tiff.next_link(ifd)) else {
+        return payload.to_vec();
+    };
+    if link + 4 > tiff.data.len() {
+        return payload.to_vec();
+    }
+
+    let mut data = tiff.data.to_vec();
+    if let Some((start, end)) = tiff.thumbnail()
+        && start > link
+        && end + 4 > data.len()
+    {
+        data.truncate(start);
+    }
+    data[link..link + 4].fill(0);
+
+    let unlinked = [EXIF_HEADER, &data].concat();
+    let Some(thumbnail) = thumbnail else {
+        return unlinked;
+    };
+
+    // New IFD1: Compression = 6 (JPEG), then the thumbnail's offset and length
+    if data.len() % 2 == 1 {
+        data.push(0);
+    }
+    let ifd1 = data.len();
+    let thumb_offset = ifd1 + 2 + 3 * 12 + 4;
+    tiff.put16(&mut data, 3);
+    for (tag, kind, value) in [
+        (TAG_COMPRESSION, SHORT, 6),
+        (TAG_THUMBNAIL_OFFSET, LONG, thumb_offset as u32),
+        (TAG_THUMBNAIL_LENGTH, LONG, thumbnail.len() as u32),
+    ] {
+        tiff.put16(&mut data, tag);
+        tiff.put16(&mut data, kind);
+        tiff.put32(&mut data, 1);
+        if kind == SHORT {
+            tiff.put16(&mut data, value as u16);
+            tiff.put16(&mut data, 0);
+        } else {
+            tiff.put32(&mut data, value);
+        }
+    }
+    tiff.put32(&mut data, 0);
+    data.extend_from_slice(thumbnail);
+
+    let mut link_bytes = Vec::with_capacity(4);
+    tiff.put32(&mut link_bytes, ifd1 as u32);
+    data[link..link + 4].copy_from_slice(&link_bytes);
+
+    if EXIF_HEADER.len() + data.len() > MAX_PAYLOAD {
+        return unlinked;
+    }
+    [EXIF_HEADER, &data].concat()
+}
+
+/// Inserts an APP1 segment into an encoded JPEG, after SOI and any JFIF header
+pub fn insert_app1(jpeg: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut at = 2;
+    if jpeg.get(2..4) == Some(&[0xFF, 0xE0])
+        && let Some(len) = jpeg.get(4..6)
+    {
+        at = (4 + u16::from_be_bytes([len[0], len[1]]) as usize).min(jpeg.len());
+    }
+
+    let mut out = Vec::with_capacity(jpeg.len() + payload.len() + 4);
+    out.extend_from_slice(&jpeg[..at]);
+    out.extend_from_slice(&[0xFF, 0xE1]);
+    out.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+    out.extend_from_slice(payload);
+    out.extend_from_slice(&jpeg[at..]);
+    out
+}
@@ -0,0 +1,1193 @@
+// src/encode/jpegtran.rs
+//
+// Lossless JPEG transforms in the spirit of jpegtran. The entropy-coded DCT
+// coefficients are decoded, rearranged in the frequency domain and written
+// back with optimized Huffman tables, so orienting or cropping a JPEG never
+// touches its pixels and costs no further generation of quantization loss.
+//
+// Only Huffman-coded sequential (baseline/extended) 8-bit files are handled;
+// for anything else `transform` returns `None` and the caller re-encodes.
+
+use super::{exif, marker};
+use crate::paths::long_path;
+use crate::transform::geometry::Rect;
+use anyhow::{Context, Result};
+use image::ImageFormat;
+use std::path::Path;
+
+/// Natural (row-major) index of each zigzag position
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+const SOI: u8 = 0xD8;
+const EOI: u8 = 0xD9;
+const SOS: u8 = 0xDA;
+const DQT: u8 = 0xDB;
+const DHT: u8 = 0xC4;
+const DRI: u8 = 0xDD;
+const APP0: u8 = 0xE0;
+const APP1: u8 = 0xE1;
+const APP2: u8 = 0xE2;
+const APP14: u8 = 0xEE;
+
+/// Quantized DCT coefficients of one 8x8 block, in natural order
+type Block = [i16; 64];
+
+/// What the lossless path does to a JPEG
+pub struct LosslessJpeg {
+    /// Rectangle of the oriented image to keep, widened to MCU boundaries
+    pub crop: Option<Rect>,
+    /// Drop EXIF, XMP and comments (JFIF, ICC and Adobe markers are kept)
+    pub strip: bool,
+    /// Regenerate the EXIF thumbnail from the transformed image
+    pub thumbnail: bool,
+    /// Keep only the luminance channel
+    pub grayscale: bool,
+    /// MCUs between restart markers (0: none)
+    pub restart_interval: u16,
+    /// Add the rsimg marker (off with `--no-marker`)
+    pub mark: bool,
+}
+
+/// Rewrites `source` into `path` without decoding pixels
+///
+/// Returns `false`, writing nothing, when the source uses a coding process
+/// the lossless path doesn't handle.
+pub fn save_lossless_jpeg(source: &Path, path: &Path, op: &LosslessJpeg) -> Result<bool> {
+    let data = std::fs::read(long_path(source))
+        .with_context(|| format!("Failed to open image: {}", source.display()))?;
+    let Some(bytes) = transform(&data, op)
+        .with_context(|| format!("Failed to transform JPEG losslessly: {}", source.display()))?
+    else {
+        return Ok(false);
+    };
+    let bytes = match op.mark {
+        true => marker::embed_jpeg(bytes, &marker::lossless_jpeg_text()),
+        false => bytes,
+    };
+
+    super::write_output(path, &bytes)
+        .with_context(|| format!("Failed to write JPEG file: {}", path.display()))?;
+    Ok(true)
+}
+
+/// Re-codes a freshly encoded JPEG with optimized Huffman tables and,
+/// with a nonzero `restart_interval`, restart markers every that many MCUs
+///
+/// Streams the lossless path can't handle are returned unchanged.
+pub fn optimize_jpeg(data: Vec<u8>, restart_interval: u16) -> Result<Vec<u8>> {
+    let Some(Parsed {
+        frame,
+        quant,
+        markers,
+    }) = parse(&data).context("Failed to optimize JPEG")?
+    else {
+        return Ok(data);
+    };
+
+    let body = write(&frame, &quant, restart_interval as usize);
+    let mut out = vec![0xFF, SOI];
+    for (marker, payload) in &markers {
+        segment(&mut out, *marker, payload);
+    }
+    out.extend_from_slice(&body[2..]);
+    Ok(out)
+}
+
+/// One frame component and its coefficient blocks, padded to whole MCUs
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    tq: u8,
+    blocks_w: usize,
+    blocks_h: usize,
+    blocks: Vec<Block>,
+}
+
+/// A quantization table, values in zigzag order
+#[derive(Clone)]
+struct QuantTable {
+    precision: u8,
+    values: [u16; 64],
+}
+
+/// Geometry and contents of a decoded frame
+struct Frame {
+    sof: u8,
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+}
+
+impl Frame {
+    fn max_sampling(&self) -> (usize, usize) {
+        let h = self.components.iter().map(|c| c.h).max().unwrap_or(1);
+        let v = self.components.iter().map(|c| c.v).max().unwrap_or(1);
+        (h, v)
+    }
+
+    /// Blocks actually covering the image in a component (no MCU padding)
+    fn coded_blocks(&self, c: &Component) -> (usize, usize) {
+        let (hmax, vmax) = self.max_sampling();
+        let w = (self.width * c.h).div_ceil(hmax);
+        let h = (self.height * c.v).div_ceil(vmax);
+        (w.div_ceil(8), h.div_ceil(8))
+    }
+
+    fn mcus(&self) -> (usize, usize) {
+        let (hmax, vmax) = self.max_sampling();
+        (
+            self.width.div_ceil(8 * hmax),
+            self.height.div_ceil(8 * vmax),
+        )
+    }
+}
+
+/// EXIF orientation as a mapping from output to source coordinates: the
+/// source is mirrored along the flagged axes, then optionally transposed
+#[derive(Clone, Copy, Default)]
+struct Orientation {
+    transpose: bool,
+    mirror_x: bool,
+    mirror_y: bool,
+}
+
+impl Orientation {
+    fn from_exif(tag: u16) -> Self {
+        let (transpose, mirror_x, mirror_y) = match tag {
+            2 => (false, true, false),
+            3 => (false, true, true),
+            4 => (false, false, true),
+            5 => (true, false, false),
+            6 => (true, false, true),
+            7 => (true, true, true),
+            8 => (true, true, false),
+            _ => (false, false, false),
+        };
+        Self {
+            transpose,
+            mirror_x,
+            mirror_y,
+        }
+    }
+
+    fn is_identity(&self) -> bool {
+        !(self.transpose || self.mirror_x || self.mirror_y)
+    }
+
+    fn block(&self, src: &Block) -> Block {
+        let mut out = [0; 64];
+        for v in 0..8 {
+            for u in 0..8 {
+                let mut c = src[v * 8 + u];
+                // Mirroring a block flips the sign of its odd frequencies
+                if (self.mirror_x && u % 2 == 1) != (self.mirror_y && v % 2 == 1) {
+                    c = -c;
+                }
+                let (ov, ou) = if self.transpose { (u, v) } else { (v, u) };
+                out[ov * 8 + ou] = c;
+            }
+        }
+        out
+    }
+}
+
+/// Applies EXIF orientation, crop and marker stripping to a JPEG stream
+fn transform(data: &[u8], op: &LosslessJpeg) -> Result<Option<Vec<u8>>> {
+    let Some(parsed) = parse(data)? else {
+        return Ok(None);
+    };
+    let Parsed {
+        mut frame,
+        mut quant,
+        mut markers,
+    } = parsed;
+
+    let orientation = markers
+        .iter()
+        .find_map(|(marker, payload)| {
+            (*marker == APP1)
+                .then(|| exif::orientation(payload))
+                .flatten()
+        })
+        .map(Orientation::from_exif)
+        .unwrap_or_default();
+
+    if op.grayscale && frame.components.len() > 1 {
+        // Only YCbCr keeps its luminance in the first component
+        let adobe_rgb = markers.iter().any(|(marker, payload)| {
+            *marker == APP14 && payload.starts_with(b"Adobe") && payload.get(11) == Some(&0)
+        });
+        if frame.components.len() != 3 || adobe_rgb {
+            return Ok(None);
+        }
+        frame.components.truncate(1);
+        let luma = &mut frame.components[0];
+        (luma.h, luma.v) = (1, 1);
+    }
+
+    let Some(oriented) = reorient(&frame, orientation, op.crop)? else {
+        return Ok(None);
+    };
+    frame = oriented;
+
+    // Baseline MCUs hold at most ten blocks
+    let mcu_blocks: usize = frame.components.iter().map(|c| c.h * c.v).sum();
+    if frame.components.len() > 1 && mcu_blocks > 10 {
+        return Ok(None);
+    }
+
+    if orientation.transpose {
+        for table in quant.iter_mut().flatten() {
+            table.values = transpose_zigzag(&table.values);
+        }
+    }
+
+    if op.strip {
+        markers.retain(|(marker, payload)| match *marker {
+            APP0 => payload.starts_with(b"JFIF\0"),
+            APP2 => payload.starts_with(b"ICC_PROFILE\0"),
+            APP14 => payload.starts_with(b"Adobe"),
+            _ => false,
+        });
+    } else if !orientation.is_identity() {
+        // The pixels now are upright: keep viewers from rotating them again
+        for (marker, payload) in &mut markers {
+            if *marker == APP1 {
+                exif::reset_orientation(payload);
+            }
+        }
+    }
+
+    let body = write(&frame, &quant, op.restart_interval as usize);
+
+    let is_exif = |marker: u8, payload: &[u8]| marker == APP1 && exif::is_exif(payload);
+    if op.thumbnail {
+        let img = image::load_from_memory_with_format(&body, ImageFormat::Jpeg)
+            .context("Failed to decode transformed JPEG")?;
+        let thumbnail = exif::encode_thumbnail(&img)?;
+        match markers.iter_mut().find(|(m, p)| is_exif(*m, p)) {
+            Some((_, payload)) => *payload = exif::replace_thumbnail(payload, Some(&thumbnail)),
+            None => {
+                let at = markers.iter().take_while(|(m, _)| *m == APP0).count();
+                markers.insert(at, (APP1, exif::thumbnail_exif(&thumbnail)));
+            }
+        }
+    } else {
+        // A carried-over thumbnail no longer matches a rotated or cropped image
+        let stale = !orientation.is_identity() || op.crop.is_some();
+        for (marker, payload) in &mut markers {
+            if is_exif(*marker, payload) && (stale || exif::has_oversized_thumbnail(payload)) {
+                *payload = exif::replace_thumbnail(payload, None);
+            }
+        }
+    }
+
+    let mut out = vec![0xFF, SOI];
+    for (marker, payload) in &markers {
+        segment(&mut out, *marker, payload);
+    }
+    out.extend_from_slice(&body[2..]);
+    Ok(Some(out))
+}
+
+struct Parsed {
+    frame: Frame,
+    quant: [Option<QuantTable>; 4],
+    /// APPn and COM segments, in file order
+    markers: Vec<(u8, Vec<u8>)>,
+}
+
+/// Reads every segment and entropy-decodes the scans into coefficients
+fn parse(data: &[u8]) -> Result<Option<Parsed>> {
+    if data.len() < 4 || data[0] != 0xFF || data[1] != SOI {
+        anyhow::bail!("Not a JPEG file");
+    }
+
+    let mut quant: [Option<QuantTable>; 4] = Default::default();
+    let mut dc_tables: [Option<HuffmanDecoder>; 4] = Default::default();
+    let mut ac_tables: [Option<HuffmanDecoder>; 4] = Default::default();
+    let mut markers = Vec::new();
+    let mut frame: Option<Frame> = None;
+    let mut restart_interval = 0;
+    let mut pos = 2;
+
+    loop {
+        // Markers may be preceded by any number of fill bytes
+        while pos < data.len() && data[pos] != 0xFF {
+            pos += 1;
+        }
+        while pos < data.len() && data[pos] == 0xFF {
+            pos += 1;
+        }
+        let Some(&marker) = data.get(pos) else {
+            anyhow::bail!("Truncated JPEG: missing end of image");
+        };
+        pos += 1;
+
+        if marker == EOI {
+            break;
+        }
+        if (0xD0..=0xD7).contains(&marker) || marker == 0x01 {
+            continue;
+        }
+
+        let len = be16(data, pos)? as usize;
+        let payload = data
+            .get(pos + 2..pos + len)
+            .ok_or_else(|| anyhow::anyhow!("Truncated JPEG segment"))?;
+        pos += len;
+
+        match marker {
+            DQT => parse_dqt(payload, &mut quant)?,
+            DHT => parse_dht(payload, &mut dc_tables, &mut ac_tables)?,
+            DRI => restart_interval = be16(payload, 0)? as usize,
+            0xC0 | 0xC1 => match parse_sof(marker, payload)? {
+                Some(parsed) => frame = Some(parsed),
+                None => return Ok(None),
+            },
+            // Progressive, lossless and arithmetic-coded processes
+            0xC2 | 0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => return Ok(None),
+            SOS => {
+                let frame = frame
+                    .as_mut()
+                    .ok_or_else(|| anyhow::anyhow!("Scan before frame header"))?;
+                let end = entropy_end(data, pos);
+                let scan = ScanTables {
+                    dc: &dc_tables,
+                    ac: &ac_tables,
+                };
+                if !decode_scan(frame, payload, &data[pos..end], &scan, restart_interval)? {
+                    return Ok(None);
+                }
+                pos = end;
+            }
+            APP0..=0xEF | 0xFE => markers.push((marker, payload.to_vec())),
+            _ => {}
+        }
+    }
+
+    let frame = frame.ok_or_else(|| anyhow::anyhow!("JPEG has no frame header"))?;
+    if frame
+        .components
+        .iter()
+        .any(|c| quant.get(c.tq as usize).is_none_or(Option::is_none))
+    {
+        anyhow::bail!("JPEG references a missing quantization table");
+    }
+
+    Ok(Some(Parsed {
+        frame,
+        quant,
+        markers,
+    }))
+}
+
+fn be16(data: &[u8], pos: usize) -> Result<u16> {
+    data.get(pos..pos + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| anyhow::anyhow!("Truncated JPEG segment"))
+}
+
+fn parse_dqt(mut payload: &[u8], quant: &mut [Option<QuantTable>; 4]) -> Result<()> {
+    while let Some(&pq_tq) = payload.first() {
+        let (precision, id) = (pq_tq >> 4, (pq_tq & 15) as usize);
+        let size = if precision == 0 { 64 } else { 128 };
+        let raw = payload
+            .get(1..1 + size)
+            .filter(|_| id < 4)
+            .ok_or_else(|| anyhow::anyhow!("Invalid quantization table"))?;
+
+        let mut values = [0u16; 64];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = if precision == 0 {
+                raw[i] as u16
+            } else {
+                u16::from_be_bytes([raw[2 * i], raw[2 * i + 1]])
+            };
+        }
+        quant[id] = Some(QuantTable { precision, values });
+        payload = &payload[1 + size..];
+    }
+    Ok(())
+}
+
+fn parse_dht(
+    mut payload: &[u8],
+    dc: &mut [Option<HuffmanDecoder>; 4],
+    ac: &mut [Option<HuffmanDecoder>; 4],
+) -> Result<()> {
+    while let Some(&tc_th) = payload.first() {
+        let (class, id) = (tc_th >> 4, (tc_th & 15) as usize);
+        let counts = payload
+            .get(1..17)
+            .filter(|_| id < 4 && class < 2)
+            .ok_or_else(|| anyhow::anyhow!("Invalid Huffman table"))?;
+        let total: usize = counts.iter().map(|&n| n as usize).sum();
+        let values = payload
+            .get(17..17 + total)
+            .ok_or_else(|| anyhow::anyhow!("Invalid Huffman table"))?;
+
+        let table = HuffmanDecoder::new(counts, values);
+        if class == 0 {
+            dc[id] = Some(table);
+        } else {
+            ac[id] = Some(table);
+        }
+        payload = &payload[17 + total..];
+    }
+    Ok(())
+}
+
+fn parse_sof(marker: u8, payload: &[u8]) -> Result<Option<Frame>> {
+    let header = payload
+        .get(..6)
+        .ok_or_else(|| anyhow::anyhow!("Invalid frame header"))?;
+    let precision = header[0];
+    let height = u16::from_be_bytes([header[1], header[2]]) as usize;
+    let width = u16::from_be_bytes([header[3], header[4]]) as usize;
+    let count = header[5] as usize;
+
+    // 12-bit samples and heights deferred to a DNL marker aren't handled
+    if precision != 8 || height == 0 || width == 0 {
+        return Ok(None);
+    }
+
+    let specs = payload
+        .get(6..6 + 3 * count)
+        .ok_or_else(|| anyhow::anyhow!("Invalid frame header"))?;
+    let components: Vec<Component> = specs
+        .chunks_exact(3)
+        .map(|spec| Component {
+            id: spec[0],
+            h: (spec[1] >> 4) as usize,
+            v: (spec[1] & 15) as usize,
+            tq: spec[2],
+            blocks_w: 0,
+            blocks_h: 0,
+            blocks: Vec::new(),
+        })
+        .collect();
+    // A single output scan carries at most four components
+    if components.len() > 4 {
+        return Ok(None);
+    }
+    if components.is_empty()
+        || components
+            .iter()
+            .any(|c| !(1..=4).contains(&c.h) || !(1..=4).contains(&c.v) || c.tq > 3)
+    {
+        anyhow::bail!("Invalid frame header");
+    }
+
+    let mut frame = Frame {
+        sof: marker,
+        width,
+        height,
+        components,
+    };
+    let (mcus_x, mcus_y) = frame.mcus();
+    for c in &mut frame.components {
+        c.blocks_w = mcus_x * c.h;
+        c.blocks_h = mcus_y * c.v;
+        c.blocks = vec![[0; 64]; c.blocks_w * c.blocks_h];
+    }
+    Ok(Some(frame))
+}
+
+/// Offset of the first marker after entropy-coded data (skipping stuffed
+/// bytes and restart markers)
+fn entropy_end(data: &[u8], mut pos: usize) -> usize {
+    while pos + 1 < data.len() {
+        if data[pos] == 0xFF && data[pos + 1] != 0 && !(0xD0..=0xD7).contains(&data[pos + 1]) {
+            return pos;
+        }
+        pos += 1;
+    }
+    data.len()
+}
+
+struct ScanTables<'a> {
+    dc: &'a [Option<HuffmanDecoder>; 4],
+    ac: &'a [Option<HuffmanDecoder>; 4],
+}
+
+/// Decodes one sequential scan into the frame's coefficient blocks
+fn decode_scan(
+    frame: &mut Frame,
+    header: &[u8],
+    entropy: &[u8],
+    tables: &ScanTables,
+    restart_interval: usize,
+) -> Result<bool> {
+    let count = *header
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("Invalid scan header"))? as usize;
+    let specs = header
+        .get(1..1 + 2 * count + 3)
+        .ok_or_else(|| anyhow::anyhow!("Invalid scan header"))?;
+    let (ss, se, a) = (specs[2 * count], specs[2 * count + 1], specs[2 * count + 2]);
+    if ss != 0 || se != 63 || a != 0 {
+        return Ok(false);
+    }
+
+    // (component index, DC table, AC table) for each scan component
+    let mut members = Vec::with_capacity(count);
+    for spec in specs[..2 * count].chunks_exact(2) {
+        let index = frame
+            .components
+            .iter()
+            .position(|c| c.id == spec[0])
+            .ok_or_else(|| anyhow::anyhow!("Scan references an unknown component"))?;
+        let dc = tables.dc[(spec[1] >> 4) as usize & 3]
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Scan references a missing Huffman table"))?;
+        let ac = tables.ac[(spec[1] & 15) as usize & 3]
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Scan references a missing Huffman table"))?;
+        members.push((index, dc, ac));
+    }
+
+    let mut reader = BitReader::new(entropy);
+    let mut preds = vec![0i32; count];
+    let mut mcu = 0;
+    let restart = |reader: &mut BitReader, preds: &mut [i32], mcu: &mut usize| {
+        if restart_interval > 0 && *mcu > 0 && (*mcu).is_multiple_of(restart_interval) {
+            reader.restart();
+            preds.fill(0);
+        }
+        *mcu += 1;
+    };
+
+    if count == 1 {
+        // Non-interleaved: blocks in raster order over the component alone
+        let (index, dc, ac) = members[0];
+        let (coded_w, coded_h) = frame.coded_blocks(&frame.components[index]);
+        let c = &mut frame.components[index];
+        for by in 0..coded_h {
+            for bx in 0..coded_w {
+                restart(&mut reader, &mut preds, &mut mcu);
+                let block = &mut c.blocks[by * c.blocks_w + bx];
+                decode_block(&mut reader, block, &mut preds[0], dc, ac)?;
+            }
+        }
+    } else {
+        let (mcus_x, mcus_y) = frame.mcus();
+        for my in 0..mcus_y {
+            for mx in 0..mcus_x {
+                restart(&mut reader, &mut preds, &mut mcu);
+                for (slot, &(index, dc, ac)) in members.iter().enumerate() {
+                    let c = &mut frame.components[index];
+                    for v in 0..c.v {
+                        for h in 0..c.h {
+                            let at = (my * c.v + v) * c.blocks_w + mx * c.h + h;
+                            decode_block(&mut reader, &mut c.blocks[at], &mut preds[slot], dc, ac)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+fn decode_block(
+    reader: &mut BitReader,
+    block: &mut Block,
+    pred: &mut i32,
+    dc: &HuffmanDecoder,
+    ac: &HuffmanDecoder,
+) -> Result<()> {
+    let size = dc.decode(reader)?;
+    *pred += extend(reader.receive(size), size);
+    block[0] = *pred as i16;
+
+    let mut k = 1;
+    while k < 64 {
+        let rs = ac.decode(reader)?;
+        let (run, size) = (rs >> 4, rs & 15);
+        if size == 0 {
+            if run != 15 {
+                break;
+            }
+            k += 16;
+            continue;
+        }
+        k += run as usize;
+        if k > 63 {
+            anyhow::bail!("Corrupt JPEG data: coefficient index out of range");
+        }
+        block[ZIGZAG[k]] = extend(reader.receive(size), size) as i16;
+        k += 1;
+    }
+    Ok(())
+}
+
+/// Sign-extends a `size`-bit magnitude category value
+fn extend(bits: u32, size: u8) -> i32 {
+    if size == 0 {
+        0
+    } else if bits < 1 << (size - 1) {
+        bits as i32 - (1 << size) + 1
+    } else {
+        bits as i32
+    }
+}
+
+/// Canonical Huffman decoding table
+struct HuffmanDecoder {
+    /// Largest code of each length (-1 when there is none)
+    max_code: [i32; 17],
+    /// Index into `values` of the first code of each length, minus that code
+    offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl HuffmanDecoder {
+    fn new(counts: &[u8], values: &[u8]) -> Self {
+        let mut max_code = [-1; 17];
+        let mut offset = [0; 17];
+        let (mut code, mut index) = (0i32, 0i32);
+        for len in 1..=16 {
+            let n = counts[len - 1] as i32;
+            offset[len] = index - code;
+            if n > 0 {
+                max_code[len] = code + n - 1;
+            }
+            code = (code + n) << 1;
+            index += n;
+        }
+        Self {
+            max_code,
+            offset,
+            values: values.to_vec(),
+        }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u8> {
+        let mut code = 0i32;
+        for len in 1..=16 {
+            code = (code << 1) | reader.bit() as i32;
+            if code <= self.max_code[len] {
+                return self
+                    .values
+                    .get((self.offset[len] + code) as usize)
+                    .copied()
+                    .ok_or_else(|| anyhow::anyhow!("Corrupt JPEG data: bad Huffman code"));
+            }
+        }
+        anyhow::bail!("Corrupt JPEG data: bad Huffman code")
+    }
+}
+
+/// Reads entropy-coded bits, undoing byte stuffing. Past the end of the data
+/// (or at a restart marker) it yields zeros, as libjpeg does.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    byte: u8,
+    bits: u8,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            byte: 0,
+            bits: 0,
+        }
+    }
+
+    fn bit(&mut self) -> u32 {
+        if self.bits == 0 {
+            self.byte = match self.data.get(self.pos..self.pos + 2) {
+                Some([0xFF, 0x00]) => {
+                    self.pos += 2;
+                    0xFF
+                }
+                Some([0xFF, _]) => 0,
+                _ => match self.data.get(self.pos) {
+                    Some(&byte) => {
+                        self.pos += 1;
+                        byte
+                    }
+                    None => 0,
+                },
+            };
+            self.bits = 8;
+        }
+        self.bits -= 1;
+        ((self.byte >> self.bits) & 1) as u32
+    }
+
+    fn receive(&mut self, size: u8) -> u32 {
+        (0..size).fold(0, |acc, _| (acc << 1) | self.bit())
+    }
+
+    /// Discards the partial byte and steps over the next restart marker
+    fn restart(&mut self) {
+        self.bits = 0;
+        if let Some([0xFF, 0xD0..=0xD7]) = self.data.get(self.pos..self.pos + 2) {
+            self.pos += 2;
+        }
+    }
+}
+
+/// Builds the oriented and cropped frame by moving whole coefficient blocks
+///
+/// Partial MCUs on an edge that mirroring would move to the top or left
+/// can't be relocated losslessly, so they are trimmed (like `jpegtran
+/// -trim`). Returns `None` if that leaves nothing of the image.
+fn reorient(frame: &Frame, o: Orientation, crop: Option<Rect>) -> Result<Option<Frame>> {
+    let (hmax, vmax) = frame.max_sampling();
+    let (mcu_w, mcu_h) = (8 * hmax, 8 * vmax);
+
+    let width = if o.mirror_x {
+        frame.width / mcu_w * mcu_w
+    } else {
+        frame.width
+    };
+    let height = if o.mirror_y {
+        frame.height / mcu_h * mcu_h
+    } else {
+        frame.height
+    };
+    if width == 0 || height == 0 {
+        return Ok(None);
+    }
+
+    let (out_w, out_h) = if o.transpose {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let (out_mcu_w, out_mcu_h) = if o.transpose {
+        (mcu_h, mcu_w)
+    } else {
+        (mcu_w, mcu_h)
+    };
+
+    // Crop origin snaps down to the MCU grid; the far edge stays put
+    let (x0, y0, right, bottom) = match crop {
+        Some(rect) => {
+            let right = (rect.x as usize)
+                .saturating_add(rect.width as usize)
+                .min(out_w);
+            let bottom = (rect.y as usize)
+                .saturating_add(rect.height as usize)
+                .min(out_h);
+            if rect.x as usize >= right || rect.y as usize >= bottom {
+                anyhow::bail!(
+                    "Crop rectangle {},{},{}x{} lies outside the {}x{} image",
+                    rect.x,
+                    rect.y,
+                    rect.width,
+                    rect.height,
+                    out_w,
+                    out_h
+                );
+            }
+            (
+                rect.x as usize / out_mcu_w * out_mcu_w,
+                rect.y as usize / out_mcu_h * out_mcu_h,
+                right,
+                bottom,
+            )
+        }
+        None => (0, 0, out_w, out_h),
+    };
+
+    let mut out = Frame {
+        sof: frame.sof,
+        width: right - x0,
+        height: bottom - y0,
+        components: Vec::with_capacity(frame.components.len()),
+    };
+    let (mcus_x, mcus_y) = (
+        out.width.div_ceil(out_mcu_w),
+        out.height.div_ceil(out_mcu_h),
+    );
+
+    for c in &frame.components {
+        let (h, v) = if o.transpose { (c.v, c.h) } else { (c.h, c.v) };
+        let (blocks_w, blocks_h) = (mcus_x * h, mcus_y * v);
+        let (bx0, by0) = (x0 / out_mcu_w * h, y0 / out_mcu_h * v);
+        // Source extent in blocks along mirrored axes (whole MCUs only)
+        let (src_w, src_h) = (width / mcu_w * c.h, height / mcu_h * c.v);
+
+        let mut blocks = vec![[0; 64]; blocks_w * blocks_h];
+        for by in 0..blocks_h {
+            for bx in 0..blocks_w {
+                let (gx, gy) = (bx0 + bx, by0 + by);
+                let (mut sx, mut sy) = if o.transpose { (gy, gx) } else { (gx, gy) };
+                if o.mirror_x {
+                    let Some(x) = src_w.checked_sub(sx + 1) else {
+                        continue;
+                    };
+                    sx = x;
+                }
+                if o.mirror_y {
+                    let Some(y) = src_h.checked_sub(sy + 1) else {
+                        continue;
+                    };
+                    sy = y;
+                }
+                if sx < c.blocks_w && sy < c.blocks_h {
+                    blocks[by * blocks_w + bx] = o.block(&c.blocks[sy * c.blocks_w + sx]);
+                }
+            }
+        }
+
+        out.components.push(Component {
+            id: c.id,
+            h,
+            v,
+            tq: c.tq,
+            blocks_w,
+            blocks_h,
+            blocks,
+        });
+    }
+
+    Ok(Some(out))
+}
+
+fn transpose_zigzag(values: &[u16; 64]) -> [u16; 64] {
+    let mut natural = [0; 64];
+    for (k, &value) in values.iter().enumerate() {
+        natural[ZIGZAG[k]] = value;
+    }
+    std::array::from_fn(|k| {
+        let (v, u) = (ZIGZAG[k] / 8, ZIGZAG[k] % 8);
+        natural[u * 8 + v]
+    })
+}
+
+/// Huffman table used by a component: luma gets table 0, chroma table 1
+fn table_for(index: usize) -> usize {
+    index.min(1)
+}
+
+/// Calls `emit(mcu, class, table, symbol, extra_bits, extra_len)` for every
+/// Huffman symbol of the frame's single output scan, in stream order; DC
+/// predictions restart every `restart_interval` MCUs (0: never)
+fn for_each_symbol(
+    frame: &Frame,
+    restart_interval: usize,
+    mut emit: impl FnMut(usize, usize, usize, u8, u32, u8),
+) {
+    let mut preds = vec![0i32; frame.components.len()];
+    let restarts = |mcu: usize| restart_interval > 0 && mcu.is_multiple_of(restart_interval);
+    let mut block = |mcu: usize, index: usize, block: &Block, pred: &mut i32| {
+        let table = table_for(index);
+        let (size, bits) = magnitude(block[0] as i32 - *pred);
+        *pred = block[0] as i32;
+        emit(mcu, 0, table, size, bits, size);
+
+        let mut run = 0;
+        for &natural in &ZIGZAG[1..] {
+            let coef = block[natural];
+            if coef == 0 {
+                run += 1;
+                continue;
+            }
+            while run > 15 {
+                emit(mcu, 1, table, 0xF0, 0, 0);
+                run -= 16;
+            }
+            let (size, bits) = magnitude(coef as i32);
+            emit(mcu, 1, table, (run << 4) | size, bits, size);
+            run = 0;
+        }
+        if run > 0 {
+            emit(mcu, 1, table, 0x00, 0, 0);
+        }
+    };
+
+    // A non-interleaved scan's MCU is a single block
+    if frame.components.len() == 1 {
+        let c = &frame.components[0];
+        let (coded_w, coded_h) = frame.coded_blocks(c);
+        for by in 0..coded_h {
+            for bx in 0..coded_w {
+                let mcu = by * coded_w + bx;
+                if restarts(mcu) {
+                    preds.fill(0);
+                }
+                block(mcu, 0, &c.blocks[by * c.blocks_w + bx], &mut preds[0]);
+            }
+        }
+    } else {
+        let (mcus_x, mcus_y) = frame.mcus();
+        for my in 0..mcus_y {
+            for mx in 0..mcus_x {
+                let mcu = my * mcus_x + mx;
+                if restarts(mcu) {
+                    preds.fill(0);
+                }
+                for (index, c) in frame.components.iter().enumerate() {
+                    for v in 0..c.v {
+                        for h in 0..c.h {
+                            let at = (my * c.v + v) * c.blocks_w + mx * c.h + h;
+                            block(mcu, index, &c.blocks[at], &mut preds[index]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Magnitude category and its extra bits (one's complement for negatives)
+fn magnitude(value: i32) -> (u8, u32) {
+    let size = (32 - value.unsigned_abs().leading_zeros()) as u8;
+    let bits = if value < 0 { value - 1 } else { value };
+    (size, bits as u32 & ((1u32 << size) - 1))
+}
+
+/// Code lengths (as DHT counts) and symbols of an optimal length-limited
+/// Huffman code, following the procedure of JPEG Annex K.2
+fn optimal_table(counts: &[u32; 256]) -> ([u8; 16], Vec<u8>) {
+    let mut freq: Vec<u64> = counts.iter().map(|&n| n as u64).collect();
+    // A reserved symbol guarantees no code consists solely of one bits
+    freq.push(1);
+    let mut code_size = [0usize; 257];
+    let mut others = [usize::MAX; 257];
+
+    loop {
+        let smallest = |skip: usize| {
+            (0..257)
+                .filter(|&i| freq[i] > 0 && i != skip)
+                .min_by(|&a, &b| freq[a].cmp(&freq[b]).then(b.cmp(&a)))
+        };
+        let Some(mut c1) = smallest(usize::MAX) else {
+            break;
+        };
+        let Some(mut c2) = smallest(c1) else {
+            break;
+        };
+
+        freq[c1] += freq[c2];
+        freq[c2] = 0;
+        code_size[c1] += 1;
+        while others[c1] != usize::MAX {
+            c1 = others[c1];
+            code_size[c1] += 1;
+        }
+        others[c1] = c2;
+        code_size[c2] += 1;
+        while others[c2] != usize::MAX {
+            c2 = others[c2];
+            code_size[c2] += 1;
+        }
+    }
+
+    let mut bits = [0u32; 258];
+    for &size in &code_size {
+        if size > 0 {
+            bits[size] += 1;
+        }
+    }
+    // Shorten codes longer than 16 bits
+    for i in (17..bits.len()).rev() {
+        while bits[i] > 0 {
+            let mut j = i - 2;
+            while bits[j] == 0 {
+                j -= 1;
+            }
+            bits[i] -= 2;
+            bits[i - 1] += 1;
+            bits[j + 1] += 2;
+            bits[j] -= 1;
+        }
+    }
+    // Drop the reserved symbol's code
+    if let Some(longest) = (1..=16).rev().find(|&i| bits[i] > 0) {
+        bits[longest] -= 1;
+    }
+
+    let mut symbols: Vec<u8> = (0..256)
+        .filter(|&s| code_size[s] > 0)
+        .map(|s| s as u8)
+        .collect();
+    symbols.sort_by_key(|&s| code_size[s as usize]);
+    (std::array::from_fn(|i| bits[i + 1] as u8), symbols)
+}
+
+/// Huffman code and length of each symbol
+struct HuffmanEncoder {
+    codes: [(u16, u8); 256],
+}
+
+impl HuffmanEncoder {
+    fn new(bits: &[u8; 16], symbols: &[u8]) -> Self {
+        let mut codes = [(0, 0); 256];
+        let (mut code, mut k) = (0u16, 0);
+        for (len, &n) in bits.iter().enumerate() {
+            for _ in 0..n {
+                codes[symbols[k] as usize] = (code, len as u8 + 1);
+                code += 1;
+                k += 1;
+            }
+            code <<= 1;
+        }
+        Self { codes }
+    }
+}
+
+/// Writes entropy-coded bits with 0xFF byte stuffing
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, len: u8) {
+        for i in (0..len).rev() {
+            self.acc = (self.acc << 1) | ((value >> i) & 1);
+            self.bits += 1;
+            if self.bits == 8 {
+                self.push_byte();
+            }
+        }
+    }
+
+    fn push_byte(&mut self) {
+        let byte = self.acc as u8;
+        self.out.push(byte);
+        if byte == 0xFF {
+            self.out.push(0);
+        }
+        self.acc = 0;
+        self.bits = 0;
+    }
+
+    /// Pads the partial byte with one bits
+    fn pad(&mut self) {
+        if self.bits > 0 {
+            let pad = 8 - self.bits;
+            self.put((1 << pad) - 1, pad);
+        }
+    }
+
+    /// Byte-aligns and writes restart marker RST`n` (mod 8)
+    fn restart(&mut self, n: usize) {
+        self.pad();
+        self.out.extend_from_slice(&[0xFF, 0xD0 + (n % 8) as u8]);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.pad();
+        self.out
+    }
+}
+
+/// Serializes the frame as a baseline (or extended) sequential JPEG with no
+/// APPn segments, with restart markers every `restart_interval` MCUs (0: none)
+fn write(frame: &Frame, quant: &[Option<QuantTable>; 4], restart_interval: usize) -> Vec<u8> {
+    // First pass: symbol statistics for optimized Huffman tables
+    let mut counts = [[[0u32; 256]; 2]; 2];
+    for_each_symbol(frame, restart_interval, |_, class, table, symbol, _, _| {
+        counts[class][table][symbol as usize] += 1;
+    });
+
+    let tables_used = if frame.components.len() > 1 { 2 } else { 1 };
+    let mut dht = Vec::new();
+    let mut encoders: Vec<[HuffmanEncoder; 2]> = Vec::new();
+    for table in 0..tables_used {
+        let mut pair = Vec::with_capacity(2);
+        for (class, class_counts) in counts.iter().enumerate() {
+            let (bits, symbols) = optimal_table(&class_counts[table]);
+            dht.push((class << 4 | table) as u8);
+            dht.extend_from_slice(&bits);
+            dht.extend_from_slice(&symbols);
+            pair.push(HuffmanEncoder::new(&bits, &symbols));
+        }
+        let ac = pair.pop().expect("two classes");
+        let dc = pair.pop().expect("two classes");
+        encoders.push([dc, ac]);
+    }
+
+    // Second pass: the entropy-coded scan itself
+    let mut writer = BitWriter {
+        out: Vec::new(),
+        acc: 0,
+        bits: 0,
+    };
+    let mut current_mcu = 0;
+    for_each_symbol(
+        frame,
+        restart_interval,
+        |mcu, class, table, symbol, extra, extra_len| {
+            if mcu != current_mcu {
+                current_mcu = mcu;
+                if restart_interval > 0 && mcu.is_multiple_of(restart_interval) {
+                    writer.restart(mcu / restart_interval - 1);
+                }
+            }
+            let (code, len) = encoders[table][class].codes[symbol as usize];
+            writer.put(code as u32, len);
+            writer.put(extra, extra_len);
+        },
+    );
+    let entropy = writer.finish();
+
+    let mut out = vec![0xFF, SOI];
+    let mut dqt = Vec::new();
+    for (id, table) in quant.iter().enumerate() {
+        let Some(table) = table else {
+            continue;
+        };
+        if !frame.components.iter().any(|c| c.tq as usize == id) {
+            continue;
+        }
+        dqt.push(table.precision << 4 | id as u8);
+        for &value in &table.values {
+            if table.precision == 0 {
+                dqt.push(value as u8);
+            } else {
+                dqt.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+    }
+    segment(&mut out, DQT, &dqt);
+
+    let mut sof = vec![8];
+    sof.extend_from_slice(&(frame.height as u16).to_be_bytes());
+    sof.extend_from_slice(&(frame.width as u16).to_be_bytes());
+    sof.push(frame.components.len() as u8);
+    for c in &frame.components {
+        sof.extend_from_slice(&[c.id, (c.h << 4 | c.v) as u8, c.tq]);
+    }
+    segment(&mut out, frame.sof, &sof);
+    segment(&mut out, DHT, &dht);
+    if restart_interval > 0 {
+        segment(&mut out, DRI, &(restart_interval as u16).to_be_bytes());
+    }
+
+    let mut sos = vec![frame.components.len() as u8];
+    for (index, c) in frame.components.iter().enumerate() {
+        let table = table_for(index) as u8;
+        sos.extend_from_slice(&[c.id, table << 4 | table]);
+    }
+    sos.extend_from_slice(&[0, 63, 0]);
+    segment(&mut out, SOS, &sos);
+
+    out.extend_from_slice(&entropy);
+    out.extend_from_slice(&[0xFF, EOI]);
+    out
+}
+
+/// Appends a marker segment with its length field
+fn segment(out: &mut Vec<u8>, marker: u8, payload: &[u8]) {
+    out.extend_from_slice(&[0xFF, marker]);
+    out.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+    out.extend_from_slice(payload);
+}
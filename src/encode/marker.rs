@@ -0,0 +1,177 @@
+// src/encode/marker.rs
+//
+// The note rsimg leaves in its JPEG, PNG and WebP outputs, naming the
+// version and the encoder settings that wrote them (`rsimg 0.1.0; jpg q80`),
+// so `--skip-marked` can leave already-optimized files alone whatever
+// they're called: re-encoding a lossy output only stacks more loss on it.
+// JPEG carries it in a COM segment after the APPn ones, PNG in a `Software`
+// tEXt chunk after IHDR and WebP in its XMP packet, as an `rsimg:Optimized`
+// property beside whatever source XMP was kept. `--no-marker` leaves it out.
+
+use super::EncodeOptions;
+
+const PREFIX: &str = "rsimg ";
+const PNG_KEYWORD: &[u8] = b"Software\0";
+const XMP_NAMESPACE: &str = "https://github.com/pankaspe/rsimg/ns/1.0/";
+/// Start of the rdf:Description holding the marker in XMP
+const XMP_DESCRIPTION: &str = "<rdf:Description rdf:about=\"\" xmlns:rsimg=";
+const XMP_PROPERTY: &str = "rsimg:Optimized=\"";
+
+/// The marker for an output in `format` written with `opts`
+pub(super) fn text(format: &str, opts: &EncodeOptions) -> String {
+    let settings = match format.to_lowercase().as_str() {
+        "jpg" | "jpeg" => format!(
+            "jpg q{}",
+            opts.preset.map_or(opts.quality, |preset| preset.jpg)
+        ),
+        "webp" => match opts.preset.map_or(Some(opts.quality), |p| p.webp_quality()) {
+            Some(quality) => format!("webp q{quality}"),
+            None => "webp lossless".to_string(),
+        },
+        "png" | "apng" => match opts.colors {
+            Some(colors) => format!("png {colors} colors"),
+            None => "png".to_string(),
+        },
+        format => format.to_string(),
+    };
+    format!("{PREFIX}{}; {settings}", env!("CARGO_PKG_VERSION"))
+}
+
+/// The marker for a JPEG transcoded by the lossless path, which keeps the
+/// quality of its source
+pub(super) fn lossless_jpeg_text() -> String {
+    format!("{PREFIX}{}; jpg lossless", env!("CARGO_PKG_VERSION"))
+}
+
+/// Adds a COM segment after the JPEG's leading APPn segments
+pub(super) fn embed_jpeg(jpeg: Vec<u8>, text: &str) -> Vec<u8> {
+    let mut at = 2;
+    while let Some(&[0xFF, marker, hi, lo]) = jpeg.get(at..at + 4)
+        && (0xE0..=0xEF).contains(&marker)
+    {
+        at += 2 + u16::from_be_bytes([hi, lo]) as usize;
+    }
+    if at > jpeg.len() {
+        return jpeg;
+    }
+    let mut out = Vec::with_capacity(jpeg.len() + text.len() + 4);
+    out.extend_from_slice(&jpeg[..at]);
+    out.extend_from_slice(&[0xFF, 0xFE]);
+    out.extend_from_slice(&(text.len() as u16 + 2).to_be_bytes());
+    out.extend_from_slice(text.as_bytes());
+    out.extend_from_slice(&jpeg[at..]);
+    out
+}
+
+/// The `Software` tEXt chunk's data
+pub(super) fn png_text(text: &str) -> Vec<u8> {
+    [PNG_KEYWORD, text.as_bytes()].concat()
+}
+
+/// `xmp` with the marker in an rdf:Description of its own, replacing an
+/// earlier one; a fresh packet without `xmp`. XMP that isn't RDF is kept
+/// as it is, unmarked.
+pub(super) fn with_xmp(xmp: Option<&[u8]>, text: &str) -> Vec<u8> {
+    let description = format!(
+        "{XMP_DESCRIPTION}\"{XMP_NAMESPACE}\" {XMP_PROPERTY}{}\"/>",
+        escape(text)
+    );
+    let Some(xmp) = xmp else {
+        return format!(
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\
+             <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\
+             {description}</rdf:RDF></x:xmpmeta>"
+        )
+        .into_bytes();
+    };
+    let Ok(xmp) = std::str::from_utf8(xmp) else {
+        return xmp.to_vec();
+    };
+    let mut xmp = xmp.to_string();
+    if let Some(start) = xmp.find(XMP_DESCRIPTION)
+        && let Some(len) = xmp[start..].find("/>")
+    {
+        xmp.replace_range(start..start + len + 2, "");
+    }
+    match xmp.rfind("</rdf:RDF>") {
+        Some(end) => xmp.insert_str(end, &description),
+        None => return xmp.into_bytes(),
+    }
+    xmp.into_bytes()
+}
+
+/// The marker of an encoded JPEG, PNG or WebP file, if rsimg wrote it
+pub fn find_marker(bytes: &[u8]) -> Option<String> {
+    let marker = if bytes.starts_with(&[0xFF, 0xD8]) {
+        find_jpeg(bytes)?
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        find_png(bytes)?
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        find_webp(bytes)?
+    } else {
+        return None;
+    };
+    marker.starts_with(PREFIX).then_some(marker)
+}
+
+/// COM segments up to the first scan
+fn find_jpeg(jpeg: &[u8]) -> Option<String> {
+    let mut at = 2;
+    while let Some(&[0xFF, marker, hi, lo]) = jpeg.get(at..at + 4) {
+        let len = u16::from_be_bytes([hi, lo]) as usize;
+        let data = jpeg.get(at + 4..at + 2 + len.max(2))?;
+        match marker {
+            0xFE if data.starts_with(PREFIX.as_bytes()) => {
+                return Some(String::from_utf8_lossy(data).into_owned());
+            }
+            0xDA | 0xD9 => return None,
+            _ => at += 2 + len,
+        }
+    }
+    None
+}
+
+/// tEXt chunks up to the image data
+fn find_png(png: &[u8]) -> Option<String> {
+    let mut at = 8;
+    while let Some(header) = png.get(at..at + 8) {
+        let len = u32::from_be_bytes(header[..4].try_into().ok()?) as usize;
+        let data = png.get(at + 8..at + 8 + len)?;
+        match &header[4..] {
+            b"tEXt" if data.starts_with(PNG_KEYWORD) => {
+                return Some(String::from_utf8_lossy(&data[PNG_KEYWORD.len()..]).into_owned());
+            }
+            b"IDAT" | b"IEND" => return None,
+            _ => at += 12 + len,
+        }
+    }
+    None
+}
+
+/// The XMP chunk, wherever it is
+fn find_webp(webp: &[u8]) -> Option<String> {
+    let mut at = 12;
+    while let Some(header) = webp.get(at..at + 8) {
+        let len = u32::from_le_bytes(header[4..].try_into().ok()?) as usize;
+        if &header[..4] == b"XMP " {
+            let xmp = String::from_utf8_lossy(webp.get(at + 8..at + 8 + len)?);
+            let start = xmp.find(XMP_PROPERTY)? + XMP_PROPERTY.len();
+            let len = xmp[start..].find('"')?;
+            return Some(unescape(&xmp[start..start + len]));
+        }
+        at += 8 + len + len % 2;
+    }
+    None
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,240 @@
+// src/encode/metadata.rs
+//
+// EXIF and XMP carried over from the source into re-encoded outputs. The
+// encoders write bare pixels, so the blocks are spliced into the finished
+// files: APP1 segments in JPEG, eXIf/iTXt chunks in PNG and EXIF/XMP chunks
+// in WebP (converting a simple VP8/VP8L file to the extended VP8X layout).
+
+use super::{exif, marker};
+
+/// Largest JPEG marker payload, as in `exif`
+const MAX_SEGMENT: usize = 65533;
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+
+const VP8X_ALPHA: u8 = 1 << 4;
+const VP8X_EXIF: u8 = 1 << 3;
+const VP8X_XMP: u8 = 1 << 2;
+
+/// Source metadata to copy into outputs
+#[derive(Clone, Debug, Default)]
+pub struct Metadata {
+    /// EXIF as a JPEG APP1 payload (`Exif\0\0` and the TIFF block)
+    exif: Option<Vec<u8>>,
+    /// XMP packet (XML)
+    xmp: Option<Vec<u8>>,
+}
+
+impl Metadata {
+    /// Metadata read from a decoded source, as raw TIFF or APP1 EXIF
+    ///
+    /// The source is decoded upright and resized, so the orientation is reset
+    /// and the embedded thumbnail dropped.
+    pub fn new(exif: Option<Vec<u8>>, xmp: Option<Vec<u8>>) -> Self {
+        let exif = exif.and_then(|data| {
+            let mut payload = if data.starts_with(EXIF_HEADER) {
+                data
+            } else {
+                [EXIF_HEADER, &data].concat()
+            };
+            if !exif::is_exif(&payload) {
+                return None;
+            }
+            exif::reset_orientation(&mut payload);
+            Some(exif::replace_thumbnail(&payload, None))
+        });
+        let xmp = xmp.filter(|xmp| !xmp.is_empty());
+        Self { exif, xmp }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exif.is_none() && self.xmp.is_none()
+    }
+
+    /// The EXIF APP1 payload
+    pub(super) fn exif(&self) -> Option<&[u8]> {
+        self.exif.as_deref()
+    }
+
+    /// The EXIF orientation tag (1-8)
+    pub fn orientation(&self) -> Option<u16> {
+        self.exif().and_then(exif::orientation)
+    }
+
+    /// The EXIF star rating (0-5)
+    pub fn rating(&self) -> Option<u16> {
+        self.exif().and_then(exif::rating)
+    }
+
+    /// Capture date, camera, ISO and focal length from the EXIF
+    pub fn capture_info(&self) -> exif::CaptureInfo {
+        self.exif().map(exif::capture_info).unwrap_or_default()
+    }
+
+    /// The same metadata with `xmp` as the XMP packet
+    pub(super) fn with_xmp(&self, xmp: Vec<u8>) -> Self {
+        Self {
+            exif: self.exif.clone(),
+            xmp: Some(xmp),
+        }
+    }
+
+    /// The XMP packet
+    pub fn xmp(&self) -> Option<&[u8]> {
+        self.xmp.as_deref()
+    }
+
+    /// EXIF without the APP1 header, as PNG and WebP store it
+    fn tiff(&self) -> Option<&[u8]> {
+        self.exif.as_deref().map(|exif| &exif[EXIF_HEADER.len()..])
+    }
+}
+
+/// Adds the XMP packet to an encoded JPEG; EXIF is written by the JPEG
+/// encoder itself, together with the thumbnail
+pub fn embed_jpeg_xmp(jpeg: Vec<u8>, meta: &Metadata) -> Vec<u8> {
+    match &meta.xmp {
+        // Extended XMP split across segments isn't worth it for derivatives
+        Some(xmp) if XMP_HEADER.len() + xmp.len() <= MAX_SEGMENT => {
+            exif::insert_app1(&jpeg, &[XMP_HEADER, xmp].concat())
+        }
+        _ => jpeg,
+    }
+}
+
+/// Adds eXIf and iTXt chunks to an encoded PNG, right after IHDR, and the
+/// tEXt chunk with the rsimg marker
+pub fn embed_png(png: Vec<u8>, meta: &Metadata, marker: Option<&str>) -> Vec<u8> {
+    // Signature, then IHDR: length, type, 13 bytes of data and CRC
+    const IHDR_END: usize = 8 + 8 + 13 + 4;
+    if png.len() < IHDR_END || &png[12..16] != b"IHDR" {
+        return png;
+    }
+
+    let mut chunks = Vec::new();
+    if let Some(tiff) = meta.tiff() {
+        png_chunk(&mut chunks, b"eXIf", tiff);
+    }
+    if let Some(xmp) = &meta.xmp {
+        // Keyword, uncompressed, no language or translated keyword
+        let data = [XMP_KEYWORD, b"\0\0\0\0\0", xmp].concat();
+        png_chunk(&mut chunks, b"iTXt", &data);
+    }
+    if let Some(text) = marker {
+        png_chunk(&mut chunks, b"tEXt", &marker::png_text(text));
+    }
+
+    let mut out = Vec::with_capacity(png.len() + chunks.len());
+    out.extend_from_slice(&png[..IHDR_END]);
+    out.extend_from_slice(&chunks);
+    out.extend_from_slice(&png[IHDR_END..]);
+    out
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+/// Adds EXIF and XMP chunks to an encoded WebP, switching a simple file to
+/// the extended layout that can hold them
+pub fn embed_webp(webp: Vec<u8>, meta: &Metadata) -> Vec<u8> {
+    if webp.len() < 12 || &webp[..4] != b"RIFF" || &webp[8..12] != b"WEBP" {
+        return webp;
+    }
+
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= webp.len() {
+        let kind: [u8; 4] = webp[pos..pos + 4].try_into().expect("4 bytes");
+        let len = u32::from_le_bytes(webp[pos + 4..pos + 8].try_into().expect("4 bytes")) as usize;
+        let Some(data) = webp.get(pos + 8..pos + 8 + len) else {
+            return webp;
+        };
+        chunks.push((kind, data));
+        pos += 8 + len + len % 2;
+    }
+
+    let mut header = match chunks.first() {
+        Some((kind, data)) if kind == b"VP8X" && data.len() >= 10 => {
+            let mut header: [u8; 10] = data[..10].try_into().expect("10 bytes");
+            header[0] &= !(VP8X_EXIF | VP8X_XMP);
+            chunks.remove(0);
+            header
+        }
+        Some((kind, data)) => match canvas(kind, data) {
+            Some((width, height, alpha)) => {
+                let mut header = [0; 10];
+                header[0] = if alpha { VP8X_ALPHA } else { 0 };
+                header[4..7].copy_from_slice(&(width.saturating_sub(1)).to_le_bytes()[..3]);
+                header[7..10].copy_from_slice(&(height.saturating_sub(1)).to_le_bytes()[..3]);
+                header
+            }
+            None => return webp,
+        },
+        None => return webp,
+    };
+    chunks.retain(|(kind, _)| kind != b"EXIF" && kind != b"XMP ");
+
+    if let Some(tiff) = meta.tiff() {
+        header[0] |= VP8X_EXIF;
+        chunks.push((*b"EXIF", tiff));
+    }
+    if let Some(xmp) = &meta.xmp {
+        header[0] |= VP8X_XMP;
+        chunks.push((*b"XMP ", xmp));
+    }
+
+    let mut body = b"WEBP".to_vec();
+    webp_chunk(&mut body, b"VP8X", &header);
+    for (kind, data) in &chunks {
+        webp_chunk(&mut body, kind, data);
+    }
+    let mut out = Vec::with_capacity(body.len() + 8);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    out
+}
+
+fn webp_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(kind);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Canvas size and alpha of a simple WebP's bitstream chunk
+fn canvas(kind: &[u8; 4], data: &[u8]) -> Option<(u32, u32, bool)> {
+    match kind {
+        // Frame tag, start code, then 14-bit width and height
+        b"VP8 " if data.get(3..6) == Some(&[0x9d, 0x01, 0x2a]) => {
+            let dim = |at: usize| Some(u16::from_le_bytes([*data.get(at)?, *data.get(at + 1)?]));
+            Some((
+                u32::from(dim(6)? & 0x3fff),
+                u32::from(dim(8)? & 0x3fff),
+                false,
+            ))
+        }
+        // Signature, then width - 1 and height - 1 in 14 bits each and the
+        // alpha hint
+        b"VP8L" if data.first() == Some(&0x2f) => {
+            let bits = u32::from_le_bytes(data.get(1..5)?.try_into().ok()?);
+            Some((
+                (bits & 0x3fff) + 1,
+                ((bits >> 14) & 0x3fff) + 1,
+                bits >> 28 & 1 == 1,
+            ))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,404 @@
+// src/encode/mod.rs
+//
+// Output encoders, dispatched on the requested format name.
+
+mod alpha;
+mod apng;
+#[cfg(feature = "c2pa")]
+mod c2pa;
+mod color_type;
+mod content;
+mod exif;
+mod jpegtran;
+mod marker;
+mod metadata;
+mod palette;
+mod print;
+mod quality;
+mod reduce;
+mod texture;
+mod tiles;
+
+use crate::paths::long_path;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use image::{DynamicImage, ImageFormat};
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub use alpha::{AlphaMode, has_transparency, parse_alpha_arg, supports_alpha};
+pub use apng::{AnimationFrame, encode_apng, save_apng};
+#[cfg(feature = "c2pa")]
+pub use c2pa::{Provenance, Signer};
+pub use color_type::{PngBitDepth, PngColorType};
+pub use content::{Classifier, Content, classify, route};
+pub use exif::{CaptureInfo, embedded_previews};
+pub use jpegtran::{LosslessJpeg, optimize_jpeg, save_lossless_jpeg};
+pub use marker::find_marker;
+pub use metadata::Metadata;
+pub use palette::Dither;
+pub use print::{Intent, OutputProfile};
+pub use quality::{Quality, QualityLevel, QualityPreset, parse_quality_arg};
+pub use reduce::{Reduction, reduce};
+pub use tiles::{save_tiled, tiles_dir};
+
+/// Encoder settings shared by every output
+#[derive(Clone)]
+pub struct EncodeOptions {
+    /// JPEG/WebP quality (0-100)
+    pub quality: u8,
+    /// Named `--quality` level, replacing `quality` with per-format settings
+    pub preset: Option<QualityPreset>,
+    /// Palette size; switches PNG to 8-bit indexed output (GIF is always indexed)
+    pub colors: Option<u16>,
+    /// Dithering used when reducing to a palette
+    pub dither: Dither,
+    /// Full mipmap chains in texture (DDS/KTX2) outputs
+    pub mipmaps: bool,
+    /// Fresh EXIF thumbnail in JPEG outputs
+    pub embed_thumbnail: bool,
+    /// Optimized Huffman tables in JPEG outputs
+    pub jpeg_optimize: bool,
+    /// MCUs between restart markers in JPEG outputs (implies optimized tables)
+    pub jpeg_restart: Option<u16>,
+    /// Channel layout forced onto JPEG/PNG outputs (default: as decoded)
+    pub channels: Option<Channels>,
+    /// PNG color type (`auto` picks the cheapest lossless one)
+    pub png_color_type: PngColorType,
+    /// PNG sample depth (default: 8 unless the pixels need 16)
+    pub png_bit_depth: Option<PngBitDepth>,
+    /// Source EXIF/XMP written into JPEG, PNG and WebP outputs
+    pub metadata: Option<Arc<Metadata>>,
+    /// What shows through transparency in JPEG, PPM and PGM outputs
+    pub alpha: AlphaMode,
+    /// Note the rsimg version and settings in JPEG, PNG and WebP outputs
+    pub mark: bool,
+    /// CMYK press profile JPEG and TIFF outputs are separated into
+    pub output_profile: Option<Arc<OutputProfile>>,
+    /// Signed C2PA manifest added to JPEG and PNG outputs
+    #[cfg(feature = "c2pa")]
+    pub provenance: Option<Arc<Provenance>>,
+}
+
+impl Default for EncodeOptions {
+    /// The command-line defaults
+    fn default() -> Self {
+        Self {
+            quality: 80,
+            preset: None,
+            colors: None,
+            dither: Dither::FloydSteinberg,
+            mipmaps: false,
+            embed_thumbnail: false,
+            jpeg_optimize: false,
+            jpeg_restart: None,
+            channels: None,
+            png_color_type: PngColorType::Auto,
+            png_bit_depth: None,
+            metadata: None,
+            alpha: AlphaMode::default(),
+            mark: true,
+            output_profile: None,
+            #[cfg(feature = "c2pa")]
+            provenance: None,
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// True when writing `format` with these options leaves the pixels of
+    /// a source already in it as they are, so `--passthrough` may copy it;
+    /// quality and metadata settings are what passthrough skips on purpose
+    pub fn is_default_for(&self, format: &str) -> bool {
+        if self.output_profile.is_some() || self.is_signed() {
+            return false;
+        }
+        match format.to_lowercase().as_str() {
+            "png" | "apng" => {
+                self.colors.is_none()
+                    && self.channels.is_none()
+                    && self.png_color_type == PngColorType::Auto
+                    && self.png_bit_depth.is_none()
+            }
+            "gif" => self.colors.is_none(),
+            "jpg" | "jpeg" => self.channels.is_none(),
+            _ => true,
+        }
+    }
+
+    /// Whether outputs get a C2PA manifest, which only encoded files carry
+    pub fn is_signed(&self) -> bool {
+        #[cfg(feature = "c2pa")]
+        return self.provenance.is_some();
+        #[cfg(not(feature = "c2pa"))]
+        false
+    }
+}
+
+/// Color channels written to JPEG and PNG outputs
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Channels {
+    /// Single luminance channel (plus alpha in PNGs)
+    Gray,
+    /// Red, green and blue (plus alpha in PNGs)
+    Rgb,
+}
+
+/// Saves an image to disk in the specified format and quality
+pub fn save_image(
+    img: &DynamicImage,
+    path: &Path,
+    format: &str,
+    opts: &EncodeOptions,
+) -> Result<()> {
+    let bytes = encode_image(img, format, opts)?;
+    write_output(path, &bytes).with_context(|| format!("Failed to write file: {}", path.display()))
+}
+
+/// Encodes an image in the specified format and quality
+#[tracing::instrument(name = "encode", skip(img, opts))]
+pub fn encode_image(img: &DynamicImage, format: &str, opts: &EncodeOptions) -> Result<Vec<u8>> {
+    let bytes = encode_format(img, format, opts)?;
+    // Signed last: the manifest hashes the finished file
+    #[cfg(feature = "c2pa")]
+    if let Some(provenance) = &opts.provenance {
+        return c2pa::embed(bytes, format, provenance);
+    }
+    Ok(bytes)
+}
+
+/// Runs the encoder of `format`, splicing in the metadata and marker
+fn encode_format(img: &DynamicImage, format: &str, opts: &EncodeOptions) -> Result<Vec<u8>> {
+    let metadata = opts.metadata.as_deref();
+    let img = match supports_alpha(format) {
+        true => Cow::Borrowed(img),
+        false => alpha::flatten(img, opts.alpha).map_or(Cow::Borrowed(img), Cow::Owned),
+    };
+    let (img, _) = reduce(&img, format, opts);
+    let img: &DynamicImage = &img;
+    let marker = opts.mark.then(|| marker::text(format, opts));
+    match format.to_lowercase().as_str() {
+        "jpg" | "jpeg" => {
+            let img = with_channels(img, opts.channels, false);
+            let quality = opts.preset.map_or(opts.quality, |preset| preset.jpg);
+            let mut bytes = match &opts.output_profile {
+                Some(profile) => jpeg_metadata(
+                    profile.encode_jpeg(&img, quality, opts.alpha)?,
+                    &img,
+                    opts.embed_thumbnail,
+                    metadata,
+                )?,
+                None => encode_jpeg(&img, quality, opts.embed_thumbnail, metadata)?,
+            };
+            if opts.jpeg_optimize || opts.jpeg_restart.is_some() {
+                bytes = optimize_jpeg(bytes, opts.jpeg_restart.unwrap_or(0))?;
+            }
+            if let Some(text) = &marker {
+                bytes = marker::embed_jpeg(bytes, text);
+            }
+            Ok(bytes)
+        }
+        "webp" => {
+            let quality = match opts.preset {
+                Some(preset) => preset.webp_quality(),
+                None => Some(opts.quality),
+            };
+            let bytes = encode_webp(img, quality)?;
+            let marked = marker.as_deref().map(|text| {
+                let meta = metadata.cloned().unwrap_or_default();
+                meta.with_xmp(marker::with_xmp(meta.xmp(), text))
+            });
+            Ok(match marked.as_ref().or(metadata) {
+                Some(meta) => metadata::embed_webp(bytes, meta),
+                None => bytes,
+            })
+        }
+        // Animated inputs are written by `save_apng`; stills are plain PNGs
+        "png" | "apng" => {
+            let bytes = color_type::encode_png(
+                &with_channels(img, opts.channels, true),
+                opts.png_color_type,
+                opts.png_bit_depth,
+                opts.colors,
+                opts.dither,
+            )?;
+            Ok(match (metadata, marker.as_deref()) {
+                (None, None) => bytes,
+                (meta, marker) => {
+                    metadata::embed_png(bytes, meta.unwrap_or(&Metadata::default()), marker)
+                }
+            })
+        }
+        "gif" => palette::encode_gif(img, opts.colors.unwrap_or(256), opts.dither),
+        "dds" => texture::encode_dds(img, opts.mipmaps),
+        "ktx2" => texture::encode_ktx2(img, opts.mipmaps),
+        "ppm" => encode_ppm(img),
+        "pgm" => encode_pgm(img),
+        "ff" | "farbfeld" => encode_as(
+            &DynamicImage::ImageRgba16(img.to_rgba16()),
+            ImageFormat::Farbfeld,
+        ),
+        "tga" => encode_tga(img),
+        "tif" | "tiff" => print::encode_tiff(img, opts.output_profile.as_deref(), opts.alpha),
+        _ => Err(anyhow::anyhow!("Unsupported format: {}", format)),
+    }
+}
+
+/// Converts to the requested channel layout, keeping bit depth and (when
+/// the format can store it) alpha
+fn with_channels(
+    img: &DynamicImage,
+    channels: Option<Channels>,
+    keep_alpha: bool,
+) -> Cow<'_, DynamicImage> {
+    let alpha = keep_alpha && img.color().has_alpha();
+    let deep = keep_alpha && is_high_depth(img);
+
+    Cow::Owned(match (channels, alpha, deep) {
+        (None, ..) => return Cow::Borrowed(img),
+        (Some(Channels::Gray), false, false) => DynamicImage::ImageLuma8(img.to_luma8()),
+        (Some(Channels::Gray), false, true) => DynamicImage::ImageLuma16(img.to_luma16()),
+        (Some(Channels::Gray), true, false) => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+        (Some(Channels::Gray), true, true) => DynamicImage::ImageLumaA16(img.to_luma_alpha16()),
+        (Some(Channels::Rgb), false, false) => DynamicImage::ImageRgb8(img.to_rgb8()),
+        (Some(Channels::Rgb), false, true) => DynamicImage::ImageRgb16(img.to_rgb16()),
+        (Some(Channels::Rgb), true, false) => DynamicImage::ImageRgba8(img.to_rgba8()),
+        (Some(Channels::Rgb), true, true) => DynamicImage::ImageRgba16(img.to_rgba16()),
+    })
+}
+
+/// Writes an output to a temporary file renamed over `path`, so readers
+/// (and other rsimg processes) never see it half-written. Renaming replaces
+/// a previous output rather than truncating it: a hard link left by
+/// --passthrough or duplicate sharing would otherwise write through to the
+/// source file.
+#[tracing::instrument(name = "write", skip_all, fields(path = %path.display(), bytes = bytes.len()))]
+fn write_output(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let temp = path.with_file_name(format!(".{name}.{}-{n}.tmp", std::process::id()));
+    std::fs::write(long_path(&temp), bytes)?;
+    std::fs::rename(long_path(&temp), long_path(path)).inspect_err(|_| {
+        let _ = std::fs::remove_file(long_path(&temp));
+    })
+}
+
+/// Encodes image as JPEG with the given quality, optionally with an EXIF
+/// thumbnail and the source's metadata
+fn encode_jpeg(
+    img: &DynamicImage,
+    quality: u8,
+    thumbnail: bool,
+    metadata: Option<&Metadata>,
+) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, quality);
+    // Encoding the DynamicImage itself would expand grayscale to three channels
+    match img {
+        DynamicImage::ImageLuma8(gray) => encoder.encode_image(gray),
+        _ => encoder.encode_image(img),
+    }
+    .with_context(|| "Error during JPEG encoding")?;
+    jpeg_metadata(bytes, img, thumbnail, metadata)
+}
+
+/// Adds the source's XMP and EXIF, and optionally a fresh thumbnail, to an
+/// encoded JPEG
+fn jpeg_metadata(
+    mut bytes: Vec<u8>,
+    img: &DynamicImage,
+    thumbnail: bool,
+    metadata: Option<&Metadata>,
+) -> Result<Vec<u8>> {
+    if let Some(meta) = metadata {
+        bytes = metadata::embed_jpeg_xmp(bytes, meta);
+    }
+    let source_exif = metadata.and_then(Metadata::exif);
+    let exif = if thumbnail {
+        let thumbnail = exif::encode_thumbnail(img)?;
+        Some(match source_exif {
+            Some(source) => exif::replace_thumbnail(source, Some(&thumbnail)),
+            None => exif::thumbnail_exif(&thumbnail),
+        })
+    } else {
+        source_exif.map(<[u8]>::to_vec)
+    };
+    if let Some(exif) = exif {
+        bytes = exif::insert_app1(&bytes, &exif);
+    }
+
+    Ok(bytes)
+}
+
+/// Encodes image as WebP with the given quality, or losslessly for `None`
+#[cfg(feature = "webp")]
+fn encode_webp(img: &DynamicImage, quality: Option<u8>) -> Result<Vec<u8>> {
+    use webp::Encoder;
+
+    let encode = |encoder: Encoder| match quality {
+        Some(quality) => encoder.encode(quality as f32),
+        None => encoder.encode_lossless(),
+    };
+    // Convert to RGB8/RGBA8 for WebP encoder, keeping transparency if present
+    let webp_data = if img.color().has_alpha() {
+        let rgba = img.to_rgba8();
+        encode(Encoder::from_rgba(&rgba, rgba.width(), rgba.height()))
+    } else {
+        let rgb = img.to_rgb8();
+        encode(Encoder::from_rgb(&rgb, rgb.width(), rgb.height()))
+    };
+
+    Ok(webp_data.to_vec())
+}
+
+#[cfg(not(feature = "webp"))]
+fn encode_webp(_img: &DynamicImage, _quality: Option<u8>) -> Result<Vec<u8>> {
+    anyhow::bail!("WebP output requires building rsimg with the `webp` feature")
+}
+
+/// Encodes image as binary PPM (RGB, 16-bit when the source has more than 8 bits)
+fn encode_ppm(img: &DynamicImage) -> Result<Vec<u8>> {
+    let rgb = if is_high_depth(img) {
+        DynamicImage::ImageRgb16(img.to_rgb16())
+    } else {
+        DynamicImage::ImageRgb8(img.to_rgb8())
+    };
+    encode_as(&rgb, ImageFormat::Pnm)
+}
+
+/// Encodes image as binary PGM (grayscale)
+fn encode_pgm(img: &DynamicImage) -> Result<Vec<u8>> {
+    let gray = if is_high_depth(img) {
+        DynamicImage::ImageLuma16(img.to_luma16())
+    } else {
+        DynamicImage::ImageLuma8(img.to_luma8())
+    };
+    encode_as(&gray, ImageFormat::Pnm)
+}
+
+/// Encodes image as TGA, keeping the alpha channel if present
+fn encode_tga(img: &DynamicImage) -> Result<Vec<u8>> {
+    let pixels = match img {
+        DynamicImage::ImageLuma8(_) => return encode_as(img, ImageFormat::Tga),
+        _ if img.color().has_alpha() => DynamicImage::ImageRgba8(img.to_rgba8()),
+        _ => DynamicImage::ImageRgb8(img.to_rgb8()),
+    };
+    encode_as(&pixels, ImageFormat::Tga)
+}
+
+/// Encodes with the `image` crate's encoder for a format
+fn encode_as(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut bytes), format)
+        .with_context(|| format!("Error during {format:?} encoding"))?;
+    Ok(bytes)
+}
+
+/// Whether the image carries more than 8 bits per channel
+fn is_high_depth(img: &DynamicImage) -> bool {
+    img.color().bytes_per_pixel() / img.color().channel_count() > 1
+}
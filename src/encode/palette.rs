@@ -0,0 +1,254 @@
+// src/encode/palette.rs
+//
+// Palette (indexed color) outputs: color quantization with optional
+// dithering, written as 8-bit PNG or GIF.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use color_quant::NeuQuant;
+use image::{DynamicImage, RgbaImage};
+use std::collections::{HashMap, HashSet};
+
+/// How quantization error is distributed across neighboring pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Dither {
+    /// Map every pixel to its nearest palette color (best for flat art)
+    None,
+    /// 8×8 Bayer threshold pattern
+    Ordered,
+    /// Floyd–Steinberg error diffusion
+    FloydSteinberg,
+}
+
+/// An image reduced to at most 256 palette entries
+struct Indexed {
+    palette: Vec<[u8; 4]>,
+    indices: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+/// 8×8 Bayer matrix used by ordered dithering
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Encodes an 8-bit palette PNG with up to `colors` entries
+pub fn encode_indexed_png(img: &DynamicImage, colors: u16, dither: Dither) -> Result<Vec<u8>> {
+    let indexed = quantize(&img.to_rgba8(), colors, dither);
+
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, indexed.width, indexed.height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(
+        indexed
+            .palette
+            .iter()
+            .flat_map(|c| [c[0], c[1], c[2]])
+            .collect::<Vec<_>>(),
+    );
+
+    // Only emit alpha entries up to the last non-opaque one
+    let alphas: Vec<u8> = indexed.palette.iter().map(|c| c[3]).collect();
+    if let Some(last) = alphas.iter().rposition(|&a| a != 255) {
+        encoder.set_trns(alphas[..=last].to_vec());
+    }
+
+    let mut writer = encoder
+        .write_header()
+        .with_context(|| "Error during PNG encoding")?;
+    writer
+        .write_image_data(&indexed.indices)
+        .with_context(|| "Error during PNG encoding")?;
+    writer
+        .finish()
+        .with_context(|| "Error during PNG encoding")?;
+
+    Ok(bytes)
+}
+
+/// Encodes a single-frame GIF with up to `colors` entries
+pub fn encode_gif(img: &DynamicImage, colors: u16, dither: Dither) -> Result<Vec<u8>> {
+    if img.width() > u16::MAX as u32 || img.height() > u16::MAX as u32 {
+        anyhow::bail!("Image too large for GIF: {}x{}", img.width(), img.height());
+    }
+
+    // GIF has a single on/off transparent color: collapse all
+    // mostly-transparent pixels into one fully transparent entry
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        pixel.0 = if pixel[3] < 128 {
+            [0, 0, 0, 0]
+        } else {
+            [pixel[0], pixel[1], pixel[2], 255]
+        };
+    }
+    let indexed = quantize(&rgba, colors, dither);
+    let transparent = indexed.palette.iter().position(|c| c[3] == 0);
+
+    let palette: Vec<u8> = indexed
+        .palette
+        .iter()
+        .flat_map(|c| [c[0], c[1], c[2]])
+        .collect();
+    let frame = gif::Frame::from_palette_pixels(
+        indexed.width as u16,
+        indexed.height as u16,
+        indexed.indices,
+        palette,
+        transparent.map(|i| i as u8),
+    );
+
+    let mut bytes = Vec::new();
+    let mut encoder = gif::Encoder::new(&mut bytes, frame.width, frame.height, &[])
+        .with_context(|| "Error during GIF encoding")?;
+    encoder
+        .write_frame(&frame)
+        .with_context(|| "Error during GIF encoding")?;
+    drop(encoder);
+
+    Ok(bytes)
+}
+
+/// Reduces an image to a palette of at most `colors` entries
+fn quantize(img: &RgbaImage, colors: u16, dither: Dither) -> Indexed {
+    let colors = colors.clamp(2, 256) as usize;
+    let palette = build_palette(img, colors);
+    let mut mapper = Mapper::new(palette);
+    let (width, height) = img.dimensions();
+
+    let indices = match dither {
+        Dither::None => img.pixels().map(|p| mapper.nearest(p.0)).collect(),
+        Dither::Ordered => {
+            // Threshold spread roughly matches the gap between palette levels
+            let spread = 255.0 / (colors as f32).cbrt();
+            img.enumerate_pixels()
+                .map(|(x, y, p)| {
+                    let threshold = BAYER_8X8[(y % 8) as usize][(x % 8) as usize] as f32;
+                    let offset = (threshold / 64.0 - 0.5) * spread;
+                    let shifted = [
+                        (p[0] as f32 + offset).round().clamp(0.0, 255.0) as u8,
+                        (p[1] as f32 + offset).round().clamp(0.0, 255.0) as u8,
+                        (p[2] as f32 + offset).round().clamp(0.0, 255.0) as u8,
+                        p[3],
+                    ];
+                    mapper.nearest(shifted)
+                })
+                .collect()
+        }
+        Dither::FloydSteinberg => floyd_steinberg(img, &mut mapper),
+    };
+
+    Indexed {
+        palette: mapper.palette,
+        indices,
+        width,
+        height,
+    }
+}
+
+/// Uses the exact colors when few enough, otherwise NeuQuant's palette
+fn build_palette(img: &RgbaImage, colors: usize) -> Vec<[u8; 4]> {
+    let mut unique: Vec<[u8; 4]> = Vec::new();
+    let mut seen = HashSet::new();
+    for pixel in img.pixels() {
+        if seen.insert(pixel.0) {
+            unique.push(pixel.0);
+            if unique.len() > colors {
+                break;
+            }
+        }
+    }
+    if unique.len() <= colors {
+        return unique;
+    }
+
+    // NeuQuant can drift alpha slightly off 255: pin it for opaque sources
+    let opaque = img.pixels().all(|p| p[3] == 255);
+    let quantizer = NeuQuant::new(10, colors, img.as_raw());
+    quantizer
+        .color_map_rgba()
+        .chunks_exact(4)
+        .map(|c| [c[0], c[1], c[2], if opaque { 255 } else { c[3] }])
+        .collect()
+}
+
+/// Error-diffusion dithering (Floyd–Steinberg) over the RGB channels
+fn floyd_steinberg(img: &RgbaImage, mapper: &mut Mapper) -> Vec<u8> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let mut indices = Vec::with_capacity(width * height);
+
+    // Accumulated error for the current and next row
+    let mut current = vec![[0.0f32; 3]; width + 2];
+    let mut next = vec![[0.0f32; 3]; width + 2];
+
+    for y in 0..height {
+        for x in 0..width {
+            let p = img.get_pixel(x as u32, y as u32);
+            let wanted: [f32; 3] = std::array::from_fn(|c| p[c] as f32 + current[x + 1][c]);
+            let clamped = wanted.map(|v| v.round().clamp(0.0, 255.0) as u8);
+
+            let index = mapper.nearest([clamped[0], clamped[1], clamped[2], p[3]]);
+            let chosen = mapper.palette[index as usize];
+            indices.push(index);
+
+            for c in 0..3 {
+                let err = wanted[c] - chosen[c] as f32;
+                current[x + 2][c] += err * 7.0 / 16.0;
+                next[x][c] += err * 3.0 / 16.0;
+                next[x + 1][c] += err * 5.0 / 16.0;
+                next[x + 2][c] += err / 16.0;
+            }
+        }
+        std::mem::swap(&mut current, &mut next);
+        next.iter_mut().for_each(|e| *e = [0.0; 3]);
+    }
+
+    indices
+}
+
+/// Nearest-palette-entry lookup with a per-color cache
+struct Mapper {
+    palette: Vec<[u8; 4]>,
+    cache: HashMap<[u8; 4], u8>,
+}
+
+impl Mapper {
+    fn new(palette: Vec<[u8; 4]>) -> Self {
+        Self {
+            palette,
+            cache: HashMap::new(),
+        }
+    }
+
+    fn nearest(&mut self, color: [u8; 4]) -> u8 {
+        if let Some(&index) = self.cache.get(&color) {
+            return index;
+        }
+
+        let distance = |entry: &[u8; 4]| -> u32 {
+            entry
+                .iter()
+                .zip(color)
+                .map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32)
+                .sum()
+        };
+        let index = self
+            .palette
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, entry)| distance(entry))
+            .map_or(0, |(i, _)| i as u8);
+
+        self.cache.insert(color, index);
+        index
+    }
+}
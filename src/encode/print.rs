@@ -0,0 +1,145 @@
+// src/encode/print.rs
+//
+// Print output. TIFF outputs are written Deflate-compressed, in RGB(A) or
+// gray as decoded; with `--output-profile` (a press condition's CMYK ICC
+// profile, e.g. FOGRA39 or GRACoL), JPEG and TIFF outputs are instead
+// converted from sRGB to that profile's inks through moxcms and carry the
+// profile, so prepress tools know which press they were separated for.
+// Transparency is flattened first, as `--alpha` says, since the inks have
+// no alpha.
+
+use super::alpha::{self, AlphaMode};
+use crate::exit::InvalidConfig;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use image::DynamicImage;
+use moxcms::{ColorProfile, DataColorSpace, Layout, RenderingIntent, TransformOptions};
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+use tiff::encoder::{Compression, DeflateLevel, TiffEncoder, colortype};
+use tiff::tags::Tag;
+
+/// How colors outside the press gamut are brought into it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Intent {
+    /// Compress the whole gamut, keeping the relations between colors
+    #[default]
+    Perceptual,
+    /// Keep colors inside the gamut exact and clip the rest
+    Relative,
+}
+
+/// A CMYK press profile and the sRGB transform into it
+pub struct OutputProfile {
+    /// The profile as read, embedded in every output
+    icc: Vec<u8>,
+    transform: Arc<moxcms::Transform8BitExecutor>,
+}
+
+impl OutputProfile {
+    /// Reads the ICC profile at `path`, which must describe CMYK
+    pub fn load(path: &Path, intent: Intent) -> Result<Self> {
+        let icc = std::fs::read(path)
+            .with_context(|| format!("Failed to read ICC profile: {}", path.display()))?;
+        let invalid = |reason: String| InvalidConfig(format!("{}: {reason}", path.display()));
+        let profile = ColorProfile::new_from_slice(&icc).map_err(|err| invalid(err.to_string()))?;
+        if profile.color_space != DataColorSpace::Cmyk {
+            return Err(invalid(format!(
+                "a CMYK profile is needed, not {:?}",
+                profile.color_space
+            ))
+            .into());
+        }
+        let options = TransformOptions {
+            rendering_intent: match intent {
+                Intent::Perceptual => RenderingIntent::Perceptual,
+                Intent::Relative => RenderingIntent::RelativeColorimetric,
+            },
+            ..TransformOptions::default()
+        };
+        // CMYK uses the RGBA layout, one byte per ink
+        let transform = ColorProfile::new_srgb()
+            .create_transform_8bit(Layout::Rgb, &profile, Layout::Rgba, options)
+            .map_err(|err| invalid(err.to_string()))?;
+        Ok(Self {
+            icc,
+            transform: Arc::from(transform),
+        })
+    }
+
+    /// Ink values of `img`, four bytes per pixel (0 is no ink)
+    fn separate(&self, img: &DynamicImage, alpha: AlphaMode) -> Result<Vec<u8>> {
+        let flat = alpha::flatten(img, alpha);
+        let rgb = flat.as_ref().unwrap_or(img).to_rgb8();
+        let mut cmyk = vec![0; rgb.len() / 3 * 4];
+        self.transform
+            .transform(&rgb, &mut cmyk)
+            .map_err(|err| anyhow::anyhow!("CMYK conversion failed: {err}"))?;
+        Ok(cmyk)
+    }
+
+    /// A CMYK JPEG of `img` with the profile embedded
+    pub(crate) fn encode_jpeg(
+        &self,
+        img: &DynamicImage,
+        quality: u8,
+        alpha: AlphaMode,
+    ) -> Result<Vec<u8>> {
+        let (width, height) = dimensions(img)?;
+        let cmyk = self.separate(img, alpha)?;
+        let mut bytes = Vec::new();
+        let mut encoder = jpeg_encoder::Encoder::new(&mut bytes, quality.max(1));
+        // Inks have no chroma to subsample
+        encoder.set_sampling_factor(jpeg_encoder::SamplingFactor::F_1_1);
+        encoder
+            .add_icc_profile(&self.icc)
+            .context("Failed to embed the ICC profile")?;
+        encoder
+            .encode(&cmyk, width, height, jpeg_encoder::ColorType::Cmyk)
+            .context("Error during CMYK JPEG encoding")?;
+        Ok(bytes)
+    }
+}
+
+/// A TIFF of `img`, separated into CMYK if there's a `profile`
+pub(crate) fn encode_tiff(
+    img: &DynamicImage,
+    profile: Option<&OutputProfile>,
+    alpha: AlphaMode,
+) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut tiff = TiffEncoder::new(Cursor::new(&mut bytes))?
+        .with_compression(Compression::Deflate(DeflateLevel::Balanced));
+    let (width, height) = (img.width(), img.height());
+    match (profile, img) {
+        (Some(profile), _) => {
+            let cmyk = profile.separate(img, alpha)?;
+            let mut image = tiff.new_image::<colortype::CMYK8>(width, height)?;
+            image
+                .encoder()
+                .write_tag(Tag::IccProfile, profile.icc.as_slice())?;
+            image.write_data(&cmyk)?;
+        }
+        (None, DynamicImage::ImageLuma8(gray)) => {
+            tiff.write_image::<colortype::Gray8>(width, height, gray.as_raw())?
+        }
+        (None, _) if img.color().has_alpha() => {
+            tiff.write_image::<colortype::RGBA8>(width, height, img.to_rgba8().as_raw())?
+        }
+        (None, _) => tiff.write_image::<colortype::RGB8>(width, height, img.to_rgb8().as_raw())?,
+    }
+    Ok(bytes)
+}
+
+/// Dimensions of `img` within what a JPEG holds
+fn dimensions(img: &DynamicImage) -> Result<(u16, u16)> {
+    match (u16::try_from(img.width()), u16::try_from(img.height())) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => anyhow::bail!(
+            "{}×{} is too large for a JPEG (65535 at most)",
+            img.width(),
+            img.height()
+        ),
+    }
+}
@@ -0,0 +1,122 @@
+// src/encode/quality.rs
+//
+// Named quality levels (`--quality high`) for people who don't know what
+// "80" means to a given codec. Each level maps to its own setting per lossy
+// format, tuned so JPEG and WebP outputs of the same level look about alike;
+// `lossless` writes lossless WebP (JPEG has no lossless mode and gets 100).
+// A TOML file passed to --quality-levels overrides any of the numbers:
+//
+//   [high]
+//   jpg = 90
+//   webp = 82
+
+use clap::ValueEnum;
+use std::fmt;
+
+/// A named quality level
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum QualityLevel {
+    Low,
+    Medium,
+    High,
+    Lossless,
+}
+
+/// `--quality` as given: a number for every format or a named level
+#[derive(Clone, Copy, Debug)]
+pub enum Quality {
+    Value(u8),
+    Level(QualityLevel),
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Quality::Value(quality) => write!(f, "{quality}%"),
+            Quality::Level(level) => {
+                let name = level.to_possible_value().expect("no skipped levels");
+                f.write_str(name.get_name())
+            }
+        }
+    }
+}
+
+/// Clap value parser for `--quality`: 0-100 or a level name
+pub fn parse_quality_arg(s: &str) -> Result<Quality, String> {
+    if let Ok(quality) = s.trim().parse::<u8>() {
+        return Ok(Quality::Value(quality));
+    }
+    QualityLevel::from_str(s.trim(), true)
+        .map(Quality::Level)
+        .map_err(|_| {
+            format!("invalid quality '{s}' (expected 0-100, low, medium, high or lossless)")
+        })
+}
+
+/// What a level means to each lossy format
+#[derive(Clone, Copy, Debug)]
+pub struct QualityPreset {
+    pub level: QualityLevel,
+    pub jpg: u8,
+    /// Ignored by `lossless`, which always writes lossless WebP
+    pub webp: u8,
+}
+
+impl QualityPreset {
+    /// The built-in settings of a level, as listed in `--help`
+    pub fn builtin(level: QualityLevel) -> Self {
+        let (jpg, webp) = match level {
+            QualityLevel::Low => (60, 50),
+            QualityLevel::Medium => (75, 70),
+            QualityLevel::High => (88, 85),
+            QualityLevel::Lossless => (100, 100),
+        };
+        Self { level, jpg, webp }
+    }
+
+    /// The settings of a level with the overrides of a --quality-levels file
+    #[cfg(feature = "native")]
+    pub fn load(level: QualityLevel, path: &std::path::Path) -> anyhow::Result<Self> {
+        use crate::i18n::{Msg, tr};
+        use anyhow::Context;
+
+        let text =
+            std::fs::read_to_string(path).with_context(|| tr!(Msg::LevelsRead, path.display()))?;
+        let config: toml::Table = text
+            .parse()
+            .with_context(|| tr!(Msg::LevelsInvalid, path.display()))?;
+
+        for (name, table) in &config {
+            if QualityLevel::from_str(name, false).is_err() {
+                anyhow::bail!(tr!(Msg::LevelUnknown, name, path.display()));
+            }
+            if !table.is_table() {
+                anyhow::bail!(tr!(Msg::LevelTable, name, path.display()));
+            }
+        }
+
+        let mut preset = Self::builtin(level);
+        let name = Quality::Level(level).to_string();
+        let Some(table) = config.get(&name).and_then(toml::Value::as_table) else {
+            return Ok(preset);
+        };
+        for (format, value) in table {
+            let target = match format.as_str() {
+                "jpg" | "jpeg" => &mut preset.jpg,
+                "webp" => &mut preset.webp,
+                _ => anyhow::bail!(tr!(Msg::LevelFormat, format, name, path.display())),
+            };
+            *target = value
+                .as_integer()
+                .and_then(|quality| u8::try_from(quality).ok())
+                .filter(|quality| *quality <= 100)
+                .with_context(|| tr!(Msg::LevelQuality, format, name, path.display()))?;
+        }
+        Ok(preset)
+    }
+
+    /// WebP quality, or `None` for lossless WebP
+    pub fn webp_quality(&self) -> Option<u8> {
+        (self.level != QualityLevel::Lossless).then_some(self.webp)
+    }
+}
@@ -0,0 +1,93 @@
+// src/encode/reduce.rs
+//
+// Cheaper channel layouts for images that don't use all of theirs: gray
+// content in an RGB buffer becomes single-channel (JPEG, PNG, TGA) and a
+// fully opaque alpha channel is dropped (PNG, WebP, TGA). Only 8-bit images
+// are looked at; PNG's `auto` color type covers deeper ones. JPEG output
+// tolerates the few levels of chroma noise a gray scan picks up from a
+// color JPEG, since it's lossy anyway; the lossless formats need exact gray.
+
+use super::{EncodeOptions, PngColorType};
+use image::{DynamicImage, GenericImageView};
+use std::borrow::Cow;
+
+/// Largest difference between channels JPEG outputs still count as gray
+const JPEG_GRAY_TOLERANCE: u8 = 3;
+
+/// Channels an output was encoded without
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Reduction {
+    /// Gray content written single-channel
+    pub gray: bool,
+    /// Fully opaque alpha channel left out
+    pub alpha: bool,
+}
+
+impl Reduction {
+    pub fn is_empty(self) -> bool {
+        !self.gray && !self.alpha
+    }
+}
+
+/// The image in the cheapest channel layout `format` stores it in without
+/// visible change, with what was dropped
+///
+/// `--channels` (JPEG, PNG) and an explicit PNG color type or palette take
+/// precedence.
+pub fn reduce<'a>(
+    img: &'a DynamicImage,
+    format: &str,
+    opts: &EncodeOptions,
+) -> (Cow<'a, DynamicImage>, Reduction) {
+    let format = format.to_lowercase();
+    let png_auto = opts.png_color_type == PngColorType::Auto && opts.colors.is_none();
+    // Tolerance for gray, `None` where gray isn't cheaper; and whether the
+    // format would store an alpha channel
+    let (tolerance, stores_alpha) = match format.as_str() {
+        "jpg" | "jpeg" if opts.channels.is_none() => (Some(JPEG_GRAY_TOLERANCE), false),
+        "png" | "apng" if opts.channels.is_none() && png_auto => (Some(0), true),
+        "tga" => (Some(0), true),
+        "webp" => (None, true),
+        _ => return (Cow::Borrowed(img), Reduction::default()),
+    };
+    if !matches!(
+        img,
+        DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) | DynamicImage::ImageLumaA8(_)
+    ) {
+        return (Cow::Borrowed(img), Reduction::default());
+    }
+
+    let (alpha_used, gray) = scan(img, tolerance);
+    let has_alpha = img.color().has_alpha();
+    let reduction = Reduction {
+        gray: gray && img.color().has_color(),
+        alpha: stores_alpha && has_alpha && !alpha_used,
+    };
+    if reduction.is_empty() {
+        return (Cow::Borrowed(img), reduction);
+    }
+    let keep_alpha = has_alpha && alpha_used && stores_alpha;
+    let reduced = match (img.color().has_color() && !reduction.gray, keep_alpha) {
+        (true, _) => DynamicImage::ImageRgb8(img.to_rgb8()),
+        (false, false) => DynamicImage::ImageLuma8(img.to_luma8()),
+        (false, true) => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+    };
+    (Cow::Owned(reduced), reduction)
+}
+
+/// Whether some pixel isn't fully opaque, and whether every pixel is gray
+/// within `tolerance` (`false` when not asked); stops once both are known
+fn scan(img: &DynamicImage, tolerance: Option<u8>) -> (bool, bool) {
+    let mut alpha_used = !img.color().has_alpha();
+    let mut gray = tolerance.is_some();
+    let tolerance = tolerance.unwrap_or_default();
+    for (_, _, pixel) in img.pixels() {
+        let [r, g, b, a] = pixel.0;
+        alpha_used |= a != u8::MAX;
+        gray &= r.abs_diff(g) <= tolerance && g.abs_diff(b) <= tolerance;
+        if alpha_used && !gray {
+            break;
+        }
+    }
+    (alpha_used, gray)
+}
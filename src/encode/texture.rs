@@ -0,0 +1,204 @@
+// src/encode/texture.rs
+//
+// GPU texture outputs: BCn-compressed DDS and KTX2 files with optional
+// mipmap chains. Opaque images use BC1 (8 bytes per 4×4 block); images
+// with alpha use BC3 (16 bytes per block).
+
+use anyhow::{Context, Result};
+use ddsfile::{D3DFormat, Dds, NewD3dParams};
+use image::imageops::FilterType;
+use image::{DynamicImage, RgbaImage};
+use texpresso::{Format, Params};
+
+/// KTX 2.0 file identifier
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// Block compression chosen for an image
+#[derive(Clone, Copy)]
+enum Compression {
+    Bc1,
+    Bc3,
+}
+
+impl Compression {
+    fn for_image(img: &DynamicImage) -> Self {
+        if img.color().has_alpha() {
+            Compression::Bc3
+        } else {
+            Compression::Bc1
+        }
+    }
+
+    fn format(self) -> Format {
+        match self {
+            Compression::Bc1 => Format::Bc1,
+            Compression::Bc3 => Format::Bc3,
+        }
+    }
+
+    fn block_bytes(self) -> usize {
+        match self {
+            Compression::Bc1 => 8,
+            Compression::Bc3 => 16,
+        }
+    }
+}
+
+/// Encodes a DDS texture (legacy DXT1/DXT5 header for broad tool support)
+pub fn encode_dds(img: &DynamicImage, mipmaps: bool) -> Result<Vec<u8>> {
+    let compression = Compression::for_image(img);
+    let levels = compress_levels(img, compression, mipmaps);
+
+    let mut dds = Dds::new_d3d(NewD3dParams {
+        height: img.height(),
+        width: img.width(),
+        depth: None,
+        format: match compression {
+            Compression::Bc1 => D3DFormat::DXT1,
+            Compression::Bc3 => D3DFormat::DXT5,
+        },
+        mipmap_levels: Some(levels.len() as u32),
+        caps2: None,
+    })
+    .map_err(|e| anyhow::anyhow!("{e}"))
+    .with_context(|| "Error during DDS encoding")?;
+    dds.data = levels.concat();
+
+    let mut bytes = Vec::new();
+    dds.write(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| "Error during DDS encoding")?;
+
+    Ok(bytes)
+}
+
+/// Encodes a KTX2 texture with an sRGB BCn format and basic data format descriptor
+pub fn encode_ktx2(img: &DynamicImage, mipmaps: bool) -> Result<Vec<u8>> {
+    let compression = Compression::for_image(img);
+    let levels = compress_levels(img, compression, mipmaps);
+    let block_bytes = compression.block_bytes();
+
+    // VK_FORMAT_BC1_RGB_SRGB_BLOCK / VK_FORMAT_BC3_SRGB_BLOCK
+    let vk_format: u32 = match compression {
+        Compression::Bc1 => 132,
+        Compression::Bc3 => 138,
+    };
+    let dfd = data_format_descriptor(compression);
+
+    // Layout: header (80) | level index | DFD | level data, smallest level
+    // first, each level aligned to the block size
+    let level_index_len = 24 * levels.len();
+    let dfd_offset = 80 + level_index_len;
+    let mut offset = dfd_offset + dfd.len();
+    let mut placements = vec![(0usize, 0usize); levels.len()];
+    for (i, level) in levels.iter().enumerate().rev() {
+        offset = offset.next_multiple_of(block_bytes);
+        placements[i] = (offset, level.len());
+        offset += level.len();
+    }
+
+    let mut out = Vec::with_capacity(offset);
+    out.extend_from_slice(&KTX2_IDENTIFIER);
+    for value in [
+        vk_format,
+        1, // typeSize
+        img.width(),
+        img.height(),
+        0, // pixelDepth
+        0, // layerCount
+        1, // faceCount
+        levels.len() as u32,
+        0, // supercompressionScheme
+        dfd_offset as u32,
+        dfd.len() as u32,
+        0, // kvdByteOffset
+        0, // kvdByteLength
+    ] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&0u64.to_le_bytes()); // sgdByteOffset
+    out.extend_from_slice(&0u64.to_le_bytes()); // sgdByteLength
+
+    for &(level_offset, len) in &placements {
+        out.extend_from_slice(&(level_offset as u64).to_le_bytes());
+        out.extend_from_slice(&(len as u64).to_le_bytes());
+        out.extend_from_slice(&(len as u64).to_le_bytes());
+    }
+    out.extend_from_slice(&dfd);
+
+    for (i, level) in levels.iter().enumerate().rev() {
+        out.resize(placements[i].0, 0);
+        out.extend_from_slice(level);
+    }
+
+    Ok(out)
+}
+
+/// Compresses the base image and, if requested, every mip level down to 1×1
+fn compress_levels(img: &DynamicImage, compression: Compression, mipmaps: bool) -> Vec<Vec<u8>> {
+    let base = img.to_rgba8();
+    let count = if mipmaps {
+        32 - img.width().max(img.height()).max(1).leading_zeros()
+    } else {
+        1
+    };
+
+    (0..count)
+        .map(|level| {
+            let (w, h) = (
+                (img.width() >> level).max(1),
+                (img.height() >> level).max(1),
+            );
+            // Each level is filtered from the full-resolution base
+            let pixels = if level == 0 {
+                base.clone()
+            } else {
+                image::imageops::resize(&base, w, h, FilterType::Lanczos3)
+            };
+            compress(&pixels, compression)
+        })
+        .collect()
+}
+
+/// BCn-compresses one RGBA level
+fn compress(pixels: &RgbaImage, compression: Compression) -> Vec<u8> {
+    let (w, h) = (pixels.width() as usize, pixels.height() as usize);
+    let format = compression.format();
+    let mut out = vec![0; format.compressed_size(w, h)];
+    format.compress(pixels.as_raw(), w, h, Params::default(), &mut out);
+    out
+}
+
+/// KTX2 basic data format descriptor for the BC1/BC3 sRGB block formats
+fn data_format_descriptor(compression: Compression) -> Vec<u8> {
+    // (bitOffset, bitLength - 1, channelType) per sample
+    let samples: &[(u32, u32, u32)] = match compression {
+        Compression::Bc1 => &[(0, 63, 0)],
+        Compression::Bc3 => &[(0, 63, 15), (64, 63, 0)],
+    };
+    let (model, bytes_plane0) = match compression {
+        Compression::Bc1 => (128u32, compression.block_bytes() as u32),
+        Compression::Bc3 => (130u32, compression.block_bytes() as u32),
+    };
+
+    let block_size = 24 + 16 * samples.len() as u32;
+    let mut words = vec![
+        block_size + 4, // dfdTotalSize
+        0,              // vendorId = Khronos, descriptorType = basic
+        2 | (block_size << 16),
+        model | (1 << 8) | (2 << 16), // BT.709 primaries, sRGB transfer
+        3 | (3 << 8),                 // 4×4 texel blocks
+        bytes_plane0,
+        0,
+    ];
+    for &(bit_offset, bit_length, channel) in samples {
+        words.push(bit_offset | (bit_length << 16) | (channel << 24));
+        words.push(0); // sample position
+        words.push(0); // sampleLower
+        words.push(u32::MAX); // sampleUpper
+    }
+
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
@@ -0,0 +1,76 @@
+// src/encode/tiles.rs
+//
+// Tiled outputs (`--tile WxH`) for deep-zoom viewers and other clients that
+// page through huge images. An output larger than one tile is written as a
+// directory of tiles named `{column}_{row}.{format}` plus an `index.json`
+// manifest of the grid:
+//
+//   {"width": 12000, "height": 8000, "tile_width": 512, "tile_height": 512,
+//    "columns": 24, "rows": 16, "format": "webp",
+//    "tiles": [{"file": "0_0.webp", "x": 0, "y": 0, "width": 512, "height": 512}, ...]}
+//
+// Tiles in the last column and row are smaller when the size isn't a
+// multiple of the tile size.
+
+use super::{EncodeOptions, save_image};
+use crate::paths::long_path;
+use anyhow::{Context, Result};
+use image::DynamicImage;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+const MANIFEST: &str = "index.json";
+
+/// Directory for the tiles of an output (`photo_50pct.webp` becomes
+/// `photo_50pct_webp/`)
+pub fn tiles_dir(output: &Path) -> PathBuf {
+    let name = output.file_name().unwrap_or_default().to_string_lossy();
+    output.with_file_name(name.replace('.', "_"))
+}
+
+/// Saves an image as tiles of at most `tile_width` x `tile_height`, or as a
+/// single file at `output` when it fits in one tile
+pub fn save_tiled(
+    img: &DynamicImage,
+    output: &Path,
+    format: &str,
+    (tile_width, tile_height): (u32, u32),
+    opts: &EncodeOptions,
+) -> Result<()> {
+    let (width, height) = (img.width(), img.height());
+    if width <= tile_width && height <= tile_height {
+        return save_image(img, output, format, opts);
+    }
+
+    let dir = tiles_dir(output);
+    std::fs::create_dir_all(long_path(&dir))
+        .with_context(|| format!("Failed to create tile directory: {}", dir.display()))?;
+
+    let (columns, rows) = (width.div_ceil(tile_width), height.div_ceil(tile_height));
+    let mut entries = Vec::with_capacity((columns * rows) as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let (x, y) = (column * tile_width, row * tile_height);
+            let (w, h) = (tile_width.min(width - x), tile_height.min(height - y));
+            let file = format!("{column}_{row}.{format}");
+            save_image(&img.crop_imm(x, y, w, h), &dir.join(&file), format, opts)?;
+            entries.push(format!(
+                r#"{{"file": "{file}", "x": {x}, "y": {y}, "width": {w}, "height": {h}}}"#
+            ));
+        }
+    }
+
+    let mut manifest = String::new();
+    let _ = writeln!(
+        manifest,
+        r#"{{"width": {width}, "height": {height}, "tile_width": {tile_width}, "tile_height": {tile_height},"#
+    );
+    let _ = writeln!(
+        manifest,
+        r#" "columns": {columns}, "rows": {rows}, "format": "{format}","#
+    );
+    let _ = writeln!(manifest, r#" "tiles": [{}]}}"#, entries.join(",\n  "));
+    let path = dir.join(MANIFEST);
+    super::write_output(&path, manifest.as_bytes())
+        .with_context(|| format!("Failed to write file: {}", path.display()))
+}
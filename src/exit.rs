@@ -0,0 +1,94 @@
+// src/exit.rs
+//
+// Process exit codes. Errors that should map to a specific code carry a
+// marker type at the root of their anyhow chain; anything else is a plain
+// failure.
+
+use crate::i18n::{Msg, tr};
+use std::fmt;
+use std::process::ExitCode;
+
+/// Exit statuses rsimg reports to its caller
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exit {
+    /// Every image was processed
+    Success = 0,
+    /// The run stopped on an unexpected error
+    Failure = 1,
+    /// Some images failed, the rest were processed
+    PartialFailure = 2,
+    /// No input images were found
+    NoInputs = 3,
+    /// Invalid arguments or option values
+    InvalidConfig = 4,
+    /// `rsimg diff` found images differing from their baseline
+    Mismatch = 5,
+}
+
+impl Exit {
+    /// Exit status for an error, from the marker type it was created with
+    pub fn for_error(err: &anyhow::Error) -> Self {
+        if err.downcast_ref::<InvalidConfig>().is_some() {
+            Exit::InvalidConfig
+        } else if err.downcast_ref::<NoInputs>().is_some() {
+            Exit::NoInputs
+        } else if err.downcast_ref::<PartialFailure>().is_some() {
+            Exit::PartialFailure
+        } else if err.downcast_ref::<Mismatch>().is_some() {
+            Exit::Mismatch
+        } else {
+            Exit::Failure
+        }
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit as u8)
+    }
+}
+
+/// An argument or option value that can't be used
+#[derive(Debug)]
+pub struct InvalidConfig(pub String);
+
+/// The input path holds no usable images
+#[derive(Debug)]
+pub struct NoInputs(pub String);
+
+/// Number of images that failed while others succeeded
+#[derive(Debug)]
+pub struct PartialFailure(pub usize);
+
+/// Number of images differing from their baseline beyond the thresholds
+#[derive(Debug)]
+pub struct Mismatch(pub usize);
+
+impl fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Display for NoInputs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Display for PartialFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&tr!(Msg::SomeFailed, self.0))
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&tr!(Msg::Mismatch, self.0))
+    }
+}
+
+impl std::error::Error for InvalidConfig {}
+impl std::error::Error for NoInputs {}
+impl std::error::Error for PartialFailure {}
+impl std::error::Error for Mismatch {}
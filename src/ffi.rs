@@ -0,0 +1,167 @@
+// src/ffi.rs
+//
+// C ABI over the in-memory pipeline, for applications that embed rsimg
+// instead of running the CLI. The declarations are in `include/rsimg.h`.
+//
+// `rsimg_optimize` decodes one image from a buffer and hands every encoded
+// variant to a callback; the buffers passed to the callback are only valid
+// during the call. Return values are the CLI exit codes (0 success,
+// 1 failure, 4 invalid options) and `rsimg_last_error` describes the last
+// failure on the calling thread.
+
+use crate::exit::{Exit, InvalidConfig};
+use crate::i18n::{Msg, tr};
+use crate::pipeline::{self, Options};
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Receives one encoded output: its scale, format name and bytes
+pub type OutputCallback = Option<
+    unsafe extern "C" fn(
+        user_data: *mut c_void,
+        scale: u32,
+        format: *const c_char,
+        data: *const u8,
+        len: usize,
+    ),
+>;
+
+/// Mirrors `RsimgOptions` in `include/rsimg.h`
+#[repr(C)]
+pub struct RsimgOptions {
+    /// Comma-separated output formats; null for the CLI default (jpg,webp)
+    pub formats: *const c_char,
+    /// Scale percentages; null or empty for the CLI default (75,50,25)
+    pub scales: *const u32,
+    pub scales_len: usize,
+    /// 0-100, or negative for the CLI default
+    pub quality: c_int,
+    /// Passed back unchanged to the callback
+    pub user_data: *mut c_void,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Optimizes the encoded image in `buf`, calling `callback` once per output
+///
+/// # Safety
+///
+/// `buf` must point to `len` readable bytes. `options` may be null;
+/// otherwise it must point to a valid `RsimgOptions` whose `formats` is null
+/// or a NUL-terminated string and whose `scales` is null or points to
+/// `scales_len` values.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rsimg_optimize(
+    buf: *const u8,
+    len: usize,
+    options: *const RsimgOptions,
+    callback: OutputCallback,
+) -> c_int {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // SAFETY: upheld by the caller as documented above
+        unsafe { optimize(buf, len, options, callback) }
+    }));
+    let result = result.unwrap_or_else(|_| Err(anyhow::anyhow!("Internal error")));
+
+    let exit = match &result {
+        Ok(()) => Exit::Success,
+        Err(err) => Exit::for_error(err),
+    };
+    set_last_error(result.err());
+    exit as c_int
+}
+
+/// Message of the last failed call on this thread, or null
+///
+/// The string stays valid until the next `rsimg_optimize` call on the same
+/// thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn rsimg_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+unsafe fn optimize(
+    buf: *const u8,
+    len: usize,
+    options: *const RsimgOptions,
+    callback: OutputCallback,
+) -> Result<()> {
+    let callback = callback.ok_or_else(|| InvalidConfig(tr!(Msg::FfiCallback)))?;
+    if buf.is_null() {
+        return Err(InvalidConfig(tr!(Msg::FfiBuffer)).into());
+    }
+    // SAFETY: the caller passes `len` readable bytes at `buf`
+    let bytes = unsafe { std::slice::from_raw_parts(buf, len) };
+    // SAFETY: null or a valid `RsimgOptions`, as documented
+    let raw = unsafe { options.as_ref() };
+    // SAFETY: the pointers inside `raw` are valid, as documented
+    let opts = unsafe { pipeline_options(raw) }?;
+    let user_data = raw.map_or(std::ptr::null_mut(), |raw| raw.user_data);
+
+    for output in pipeline::optimize(bytes, &opts)? {
+        let format = CString::new(output.format).context("Invalid format name")?;
+        // SAFETY: the callback is the caller's; the pointers are valid for the call
+        unsafe {
+            callback(
+                user_data,
+                output.scale,
+                format.as_ptr(),
+                output.bytes.as_ptr(),
+                output.bytes.len(),
+            )
+        };
+    }
+    Ok(())
+}
+
+/// Pipeline settings from the C options, with the CLI defaults for unset fields
+unsafe fn pipeline_options(raw: Option<&RsimgOptions>) -> Result<Options> {
+    let mut opts = Options::default();
+    let Some(raw) = raw else {
+        return Ok(opts);
+    };
+
+    if !raw.formats.is_null() {
+        // SAFETY: a NUL-terminated string, as documented
+        let formats = unsafe { CStr::from_ptr(raw.formats) }
+            .to_str()
+            .map_err(|_| InvalidConfig(tr!(Msg::FfiFormats)))?;
+        opts.formats = formats
+            .split(',')
+            .map(|f| f.trim().to_lowercase())
+            .filter(|f| !f.is_empty())
+            .collect();
+    }
+
+    if !raw.scales.is_null() && raw.scales_len > 0 {
+        // SAFETY: `scales_len` values at `scales`, as documented
+        let scales = unsafe { std::slice::from_raw_parts(raw.scales, raw.scales_len) };
+        if let Some(scale) = scales.iter().find(|&&s| !(10..=100).contains(&s)) {
+            return Err(InvalidConfig(tr!(Msg::ScaleInvalid, scale)).into());
+        }
+        opts.scales = scales.to_vec();
+    }
+
+    match raw.quality {
+        q if q < 0 => {}
+        q @ 0..=100 => opts.encode.quality = q as u8,
+        q => return Err(InvalidConfig(tr!(Msg::QualityInvalid, q)).into()),
+    }
+    Ok(opts)
+}
+
+fn set_last_error(err: Option<anyhow::Error>) {
+    let message = err.map(|err| {
+        // Interior NULs would cut the message short in C anyway
+        CString::new(format!("{err:#}").replace('\0', " ")).unwrap_or_default()
+    });
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
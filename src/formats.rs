@@ -0,0 +1,187 @@
+// src/formats.rs
+//
+// `rsimg formats`: the input decoders and output encoders compiled into this
+// binary, with the library behind each and the Cargo feature it needs, so
+// scripts can probe what a given build supports instead of parsing errors.
+// Codecs behind a feature that wasn't enabled are listed as unavailable.
+// `--json` prints one object:
+//
+//   {"version":"0.1.0","features":["native","webp"],
+//    "inputs":[{"format":"jpg","extensions":["jpg","jpeg"],"library":"zune-jpeg",
+//               "version":"0.5.5","feature":null,"available":true}, ...],
+//    "outputs":[...]}
+
+use anyhow::Result;
+use clap::builder::PossibleValuesParser;
+use owo_colors::OwoColorize;
+use rsimg::i18n::{self, Msg, tr};
+use serde_json::{Value, json};
+
+/// Locked dependency versions, written by build.rs
+const VERSIONS: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/versions.rs"));
+
+/// Cargo features and whether this build has them
+const FEATURES: &[(&str, bool)] = &[
+    ("native", cfg!(feature = "native")),
+    ("webp", cfg!(feature = "webp")),
+    ("onnx", cfg!(feature = "onnx")),
+    ("xcf", cfg!(feature = "xcf")),
+    ("http", cfg!(feature = "http")),
+    ("python", cfg!(feature = "python")),
+    ("wasm-plugins", cfg!(feature = "wasm-plugins")),
+    ("otlp", cfg!(feature = "otlp")),
+    ("clipboard", cfg!(feature = "clipboard")),
+    ("history", cfg!(feature = "history")),
+    ("c2pa", cfg!(feature = "c2pa")),
+];
+
+/// A decoder or encoder for one format
+struct Codec {
+    format: &'static str,
+    extensions: &'static [&'static str],
+    /// Crate doing the work
+    library: &'static str,
+    /// Feature the codec is compiled in with, if it's optional
+    feature: Option<&'static str>,
+}
+
+const fn codec(
+    format: &'static str,
+    extensions: &'static [&'static str],
+    library: &'static str,
+    feature: Option<&'static str>,
+) -> Codec {
+    Codec {
+        format,
+        extensions,
+        library,
+        feature,
+    }
+}
+
+const DECODERS: &[Codec] = &[
+    codec("jpg", &["jpg", "jpeg"], "zune-jpeg", None),
+    codec("png", &["png", "apng"], "png", None),
+    codec("gif", &["gif"], "gif", None),
+    codec("webp", &["webp"], "image-webp", None),
+    codec("bmp", &["bmp"], "image", None),
+    codec("tiff", &["tiff", "tif"], "tiff", None),
+    codec("ico", &["ico"], "image", None),
+    codec("exr", &["exr"], "exr", None),
+    codec("hdr", &["hdr"], "image", None),
+    codec("psd", &["psd"], "psd", None),
+    codec("xcf", &["xcf"], "xcf", Some("xcf")),
+];
+
+const ENCODERS: &[Codec] = &[
+    codec("jpg", &["jpg", "jpeg"], "image", None),
+    codec("webp", &["webp"], "libwebp-sys", Some("webp")),
+    codec("png", &["png"], "png", None),
+    codec("apng", &["apng"], "png", None),
+    codec("gif", &["gif"], "gif", None),
+    codec("dds", &["dds"], "texpresso", None),
+    codec("ktx2", &["ktx2"], "texpresso", None),
+    codec("ppm", &["ppm"], "image", None),
+    codec("pgm", &["pgm"], "image", None),
+    codec("ff", &["ff", "farbfeld"], "image", None),
+    codec("tga", &["tga"], "image", None),
+    codec("tiff", &["tiff", "tif"], "tiff", None),
+];
+
+#[derive(clap::Args)]
+pub struct FormatsArgs {
+    /// Print one JSON object instead of tables
+    #[arg(long, help = "Output as JSON")]
+    pub json: bool,
+}
+
+/// Runs the `formats` subcommand
+pub fn run(args: FormatsArgs) -> Result<()> {
+    if args.json {
+        let features: Vec<&str> = FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect();
+        let report = json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "features": features,
+            "inputs": DECODERS.iter().map(codec_json).collect::<Vec<_>>(),
+            "outputs": ENCODERS.iter().map(codec_json).collect::<Vec<_>>(),
+        });
+        println!("{report}");
+        return Ok(());
+    }
+
+    println!("rsimg {}", env!("CARGO_PKG_VERSION"));
+    let features: Vec<String> = FEATURES
+        .iter()
+        .map(|&(name, enabled)| match enabled {
+            true => name.green().to_string(),
+            false => name.dimmed().to_string(),
+        })
+        .collect();
+    println!("{}", tr!(Msg::Features, features.join(" ")));
+
+    for (title, codecs) in [(Msg::Inputs, DECODERS), (Msg::Outputs, ENCODERS)] {
+        let title = i18n::text(title);
+        println!("\n{}", title.bold());
+        for codec in codecs {
+            let line = format!(
+                "  {:<18}{:<12}{:<10}{}",
+                codec.extensions.join(", "),
+                codec.library,
+                version(codec.library).unwrap_or("?"),
+                codec
+                    .feature
+                    .map(|feature| format!("feature `{feature}`"))
+                    .unwrap_or_default()
+            );
+            let line = line.trim_end();
+            match available(codec) {
+                true => println!("{line}"),
+                false => println!("{} {}", line.dimmed(), i18n::text(Msg::NotCompiled)),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Value parser for --formats: the extensions of the encoders compiled in
+pub fn output_formats() -> PossibleValuesParser {
+    PossibleValuesParser::new(
+        ENCODERS
+            .iter()
+            .filter(|codec| available(codec))
+            .flat_map(|codec| codec.extensions.iter().copied()),
+    )
+}
+
+fn codec_json(codec: &Codec) -> Value {
+    json!({
+        "format": codec.format,
+        "extensions": codec.extensions,
+        "library": codec.library,
+        "version": version(codec.library),
+        "feature": codec.feature,
+        "available": available(codec),
+    })
+}
+
+/// Whether the codec's feature, if any, is enabled in this build
+fn available(codec: &Codec) -> bool {
+    codec.feature.is_none_or(|feature| {
+        FEATURES
+            .iter()
+            .any(|&(name, enabled)| name == feature && enabled)
+    })
+}
+
+/// Locked version of a crate, the newest when several are in the tree
+fn version(library: &str) -> Option<&'static str> {
+    VERSIONS
+        .iter()
+        .rev()
+        .find(|(name, _)| *name == library)
+        .map(|(_, version)| *version)
+}
@@ -0,0 +1,253 @@
+// src/gallery.rs
+//
+// `rsimg gallery`: a static gallery site from a directory of images in one
+// run. Every image is resized to the `--widths` breakpoints in each format
+// (never enlarged), and `index.html` lays them out as a grid of lazily
+// loaded thumbnails, each a `<picture>` whose `srcset` lets the browser
+// pick the width it needs, linking to the largest output.
+//
+//   site/index.html
+//   site/images/{stem}_{width}w.{format}
+
+use crate::package::{escape, href};
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use rsimg::collision::OnCollision;
+use rsimg::decode;
+use rsimg::encode::EncodeOptions;
+use rsimg::exit::{InvalidConfig, NoInputs};
+use rsimg::i18n::{self, Msg, tr};
+use rsimg::processor::{self, FileLog, Inputs, ProcessOptions, ScanOptions};
+use rsimg::progress::{BarStyle, Progress, Theme};
+use rsimg::transform::script::Script;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Folder of the site holding the images
+const IMAGES: &str = "images";
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem;color:#222}\
+    main{display:grid;grid-template-columns:repeat(auto-fill,minmax(280px,1fr));gap:1rem}\
+    img{display:block;width:100%;height:auto;border-radius:4px}";
+
+/// Width a thumbnail takes in the grid, for `sizes`
+const THUMB_SIZES: &str = "(min-width: 900px) 33vw, (min-width: 600px) 50vw, 100vw";
+
+#[derive(clap::Args)]
+pub struct GalleryArgs {
+    /// Directory of images to publish
+    #[arg(value_name = "INPUT", help = "Input directory")]
+    pub input: PathBuf,
+
+    /// Directory the site is written to
+    #[arg(short, long, value_name = "DIR", help = "Site output directory")]
+    pub output: PathBuf,
+
+    /// Image widths the browser picks from (comma-separated)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = vec![480, 960, 1920],
+        value_parser = clap::value_parser!(u32).range(16..),
+        value_name = "WIDTHS",
+        help = "Breakpoint widths in pixels"
+    )]
+    pub widths: Vec<u32>,
+
+    /// Formats of every width: browsers show the first they support, and
+    /// the last is the fallback for those supporting none of the others
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = vec!["webp".to_string(), "jpg".to_string()],
+        value_parser = ["jpg", "png", "webp", "gif"],
+        value_name = "FORMATS",
+        help = "Image formats (jpg, png, webp, gif)"
+    )]
+    pub formats: Vec<String>,
+
+    /// JPEG/WebP quality (0-100)
+    #[arg(
+        short,
+        long,
+        default_value_t = 80,
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        value_name = "QUALITY",
+        help = "Image quality"
+    )]
+    pub quality: u8,
+
+    /// Page title (default: the input directory's name)
+    #[arg(long, value_name = "TITLE", help = "Gallery title")]
+    pub title: Option<String>,
+
+    /// Descend into subdirectories
+    #[arg(short, long, help = "Scan directories recursively")]
+    pub recursive: bool,
+}
+
+/// Outputs of one format as (width, height, path), narrowest first
+type Widths = Vec<(u32, u32, PathBuf)>;
+
+/// One published image: its outputs by format
+struct Item {
+    stem: String,
+    sources: Vec<(String, Widths)>,
+}
+
+/// Runs the `gallery` subcommand
+pub fn run(args: GalleryArgs) -> Result<()> {
+    if !args.input.is_dir() {
+        return Err(InvalidConfig(tr!(Msg::PathInvalid, args.input.display())).into());
+    }
+    let files = processor::collect_image_files(
+        &args.input,
+        &ScanOptions {
+            recursive: args.recursive,
+            ..ScanOptions::default()
+        },
+    )?;
+    if files.is_empty() {
+        return Err(NoInputs(tr!(Msg::NoImages)).into());
+    }
+
+    let images = args.output.join(IMAGES);
+    std::fs::create_dir_all(&images)
+        .with_context(|| format!("Failed to create directory: {}", images.display()))?;
+    let log = Arc::new(FileLog::default());
+    let opts = ProcessOptions {
+        formats: args.formats.clone(),
+        scales: vec![100],
+        encode: EncodeOptions {
+            quality: args.quality,
+            ..Default::default()
+        },
+        output_dir: Some(images),
+        on_collision: OnCollision::Suffix,
+        script: Some(Script::breakpoints(&args.widths, &args.formats)),
+        log: Some(Arc::clone(&log)),
+        ..Default::default()
+    };
+    let progress = Progress::new(false, Theme::new(BarStyle::Detailed, None, None)?);
+    // Failed images are listed and left out of the page
+    let batch = processor::process_all(Inputs::Files(files), &opts, &progress);
+
+    let mut records = log.take();
+    records.sort_by(|a, b| a.input.cmp(&b.input));
+    let items: Vec<Item> = records
+        .into_iter()
+        .filter(|record| record.error.is_none())
+        .map(|record| item(&record.input, record.outputs, &args.formats))
+        .collect();
+    let title = match &args.title {
+        Some(title) => title.clone(),
+        None => args
+            .input
+            .canonicalize()
+            .ok()
+            .and_then(|dir| Some(dir.file_name()?.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "Gallery".to_string()),
+    };
+    let index = args.output.join("index.html");
+    std::fs::write(&index, page(&title, &items, &args.output))
+        .with_context(|| format!("Failed to write {}", index.display()))?;
+    println!(
+        "  {} {}: {} ({})",
+        "🖼".bright_white(),
+        i18n::text(Msg::Gallery),
+        index.display().to_string().bright_yellow(),
+        tr!(Msg::Images, items.len()).bright_cyan()
+    );
+    batch.map(|_| ())
+}
+
+/// Groups an input's outputs by format, in `formats` order
+fn item(input: &Path, outputs: Vec<(PathBuf, u64)>, formats: &[String]) -> Item {
+    let mut sources: Vec<(String, Widths)> = formats
+        .iter()
+        .map(|format| (format.clone(), Vec::new()))
+        .collect();
+    for (path, _) in outputs {
+        let format = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let Ok((width, height)) = decode::load_dimensions(&path) else {
+            continue;
+        };
+        if let Some((_, widths)) = sources.iter_mut().find(|(f, _)| *f == format) {
+            widths.push((width, height, path));
+        }
+    }
+    for (_, widths) in &mut sources {
+        widths.sort_by_key(|&(width, ..)| width);
+    }
+    sources.retain(|(_, widths)| !widths.is_empty());
+    Item {
+        stem: input
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        sources,
+    }
+}
+
+fn page(title: &str, items: &[Item], site: &Path) -> String {
+    // Links are relative to the site directory
+    let url = |path: &Path| href(&path.strip_prefix(site).unwrap_or(path).to_string_lossy());
+    let srcset = |widths: &Widths| {
+        widths
+            .iter()
+            .map(|(width, _, path)| format!("{} {width}w", url(path)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut body = String::new();
+    for item in items {
+        // The last format is the fallback <img>, the others <source>s
+        let Some(((_, fallback), others)) = item.sources.split_last() else {
+            continue;
+        };
+        let (width, height, smallest) = &fallback[0];
+        let largest = &item.sources[0]
+            .1
+            .last()
+            .expect("formats without outputs are dropped")
+            .2;
+        let _ = write!(body, "<a href=\"{}\"><picture>", url(largest));
+        for (format, widths) in others {
+            let _ = write!(
+                body,
+                "<source type=\"image/{}\" srcset=\"{}\" sizes=\"{THUMB_SIZES}\">",
+                mime(format),
+                srcset(widths)
+            );
+        }
+        let _ = writeln!(
+            body,
+            "<img src=\"{}\" srcset=\"{}\" sizes=\"{THUMB_SIZES}\" width=\"{width}\" height=\"{height}\" \
+             alt=\"{}\" loading=\"lazy\" decoding=\"async\"></picture></a>",
+            url(smallest),
+            srcset(fallback),
+            escape(&item.stem)
+        );
+    }
+
+    let title = escape(title);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n\
+         <main>\n{body}</main>\n</body>\n</html>\n"
+    )
+}
+
+/// Subtype of a format's MIME type
+fn mime(format: &str) -> &str {
+    match format {
+        "jpg" => "jpeg",
+        format => format,
+    }
+}
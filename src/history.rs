@@ -0,0 +1,397 @@
+// src/history.rs
+//
+// `--history DB` and `rsimg stats`: a SQLite record of every run, for
+// answering how much a pipeline has processed and saved over weeks of
+// cron jobs. A run is inserted before its batch starts; the inputs it
+// finishes are written every few seconds while it goes, so a run that's
+// killed keeps what it did, and its duration is set once the batch returns
+// (it stays NULL for interrupted runs). Concurrent runs can share a
+// database: it's in WAL mode and writers wait for each other.
+//
+//   runs(id, started, version, args, duration_ms)
+//     started: Unix seconds; args: the command line as a JSON array
+//   files(run_id, input, input_bytes, outputs, output_bytes, duration_ms, error)
+//   outputs(run_id, input, path, format, bytes)
+//
+// `stats` totals runs, files and bytes per day, week, month or run.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use owo_colors::OwoColorize;
+use rsimg::budget::format_size;
+use rsimg::exit::InvalidConfig;
+use rsimg::i18n::{Msg, tr};
+use rsimg::processor::{FileLog, FileRecord};
+use rusqlite::{Connection, OpenFlags, params};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How often finished inputs are written while a batch runs
+const CHECKPOINT: Duration = Duration::from_secs(5);
+/// How long a write waits for another run holding the database
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY,
+        started INTEGER NOT NULL,
+        version TEXT NOT NULL,
+        args TEXT NOT NULL,
+        duration_ms INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS files (
+        run_id INTEGER NOT NULL REFERENCES runs(id),
+        input TEXT NOT NULL,
+        input_bytes INTEGER NOT NULL,
+        outputs INTEGER NOT NULL,
+        output_bytes INTEGER NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        error TEXT
+    );
+    CREATE TABLE IF NOT EXISTS outputs (
+        run_id INTEGER NOT NULL REFERENCES runs(id),
+        input TEXT NOT NULL,
+        path TEXT NOT NULL,
+        format TEXT NOT NULL,
+        bytes INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS files_run ON files(run_id);
+    CREATE INDEX IF NOT EXISTS outputs_run ON outputs(run_id);
+";
+
+/// The run being recorded
+pub struct History {
+    conn: Connection,
+    path: PathBuf,
+    run: i64,
+    log: Arc<FileLog>,
+}
+
+impl History {
+    /// Opens (or creates) the database and inserts the run
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open history database: {}", path.display()))?;
+        let context = || format!("Failed to set up history database: {}", path.display());
+        conn.busy_timeout(BUSY_TIMEOUT).with_context(context)?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .with_context(context)?;
+        conn.execute_batch(SCHEMA).with_context(context)?;
+
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let args: Vec<String> = std::env::args_os()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        conn.execute(
+            "INSERT INTO runs (started, version, args) VALUES (?1, ?2, ?3)",
+            params![
+                started as i64,
+                env!("CARGO_PKG_VERSION"),
+                json!(args).to_string()
+            ],
+        )
+        .with_context(|| format!("Failed to record the run in {}", path.display()))?;
+        Ok(Self {
+            run: conn.last_insert_rowid(),
+            conn,
+            path: path.to_path_buf(),
+            log: Arc::default(),
+        })
+    }
+
+    /// Where the batch reports finished inputs (`ProcessOptions::log`)
+    pub fn log(&self) -> Arc<FileLog> {
+        self.log.clone()
+    }
+
+    /// Runs `batch`, writing the inputs it finishes as it goes and its
+    /// duration once it returns
+    ///
+    /// The batch's own error wins over one writing the history.
+    pub fn record<T>(self, batch: impl FnOnce() -> Result<T>) -> Result<T> {
+        let started = Instant::now();
+        let (done, stop) = mpsc::channel::<()>();
+        // The connection can't be shared, so the writer owns it until the
+        // batch is done
+        let (result, (history, written)) = std::thread::scope(|scope| {
+            let writer = scope.spawn(move || {
+                loop {
+                    let finished = !matches!(
+                        stop.recv_timeout(CHECKPOINT),
+                        Err(RecvTimeoutError::Timeout)
+                    );
+                    if let Err(err) = self.checkpoint() {
+                        return (self, Err(err));
+                    }
+                    if finished {
+                        return (self, Ok(()));
+                    }
+                }
+            });
+            let result = batch();
+            drop(done);
+            let written = writer
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            (result, written)
+        });
+
+        let duration = started.elapsed().as_millis() as i64;
+        let finished = written.and_then(|()| {
+            history
+                .conn
+                .execute(
+                    "UPDATE runs SET duration_ms = ?1 WHERE id = ?2",
+                    params![duration, history.run],
+                )
+                .map(drop)
+                .with_context(|| format!("Failed to record the run in {}", history.path.display()))
+        });
+        let value = result?;
+        finished?;
+        Ok(value)
+    }
+
+    /// Writes the inputs finished since the last checkpoint, in one
+    /// transaction
+    fn checkpoint(&self) -> Result<()> {
+        let records = self.log.take();
+        if records.is_empty() {
+            return Ok(());
+        }
+        self.write(&records)
+            .with_context(|| format!("Failed to write history to {}", self.path.display()))
+    }
+
+    fn write(&self, records: &[FileRecord]) -> rusqlite::Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut file = tx.prepare_cached(
+                "INSERT INTO files (run_id, input, input_bytes, outputs, output_bytes, duration_ms, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            let mut output = tx.prepare_cached(
+                "INSERT INTO outputs (run_id, input, path, format, bytes) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for record in records {
+                let input = record.input.to_string_lossy();
+                let output_bytes: u64 = record.outputs.iter().map(|(_, bytes)| bytes).sum();
+                file.execute(params![
+                    self.run,
+                    input,
+                    record.input_bytes as i64,
+                    record.outputs.len() as i64,
+                    output_bytes as i64,
+                    record.elapsed.as_millis() as i64,
+                    record.error,
+                ])?;
+                for (path, bytes) in &record.outputs {
+                    let format = path
+                        .extension()
+                        .map(|ext| ext.to_string_lossy().to_lowercase())
+                        .unwrap_or_default();
+                    output.execute(params![
+                        self.run,
+                        input,
+                        path.to_string_lossy(),
+                        format,
+                        *bytes as i64
+                    ])?;
+                }
+            }
+        }
+        tx.commit()
+    }
+}
+
+/// How `stats` groups runs
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    /// Every run on its own row
+    Run,
+}
+
+impl Period {
+    /// SQL for the row label and what rows are grouped by
+    fn columns(self) -> (&'static str, &'static str) {
+        match self {
+            Period::Day => ("date(started, 'unixepoch', 'localtime')", "1"),
+            Period::Week => ("strftime('%Y-W%W', started, 'unixepoch', 'localtime')", "1"),
+            Period::Month => ("strftime('%Y-%m', started, 'unixepoch', 'localtime')", "1"),
+            Period::Run => (
+                "strftime('%Y-%m-%d %H:%M', started, 'unixepoch', 'localtime')",
+                "runs.id",
+            ),
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub struct StatsArgs {
+    /// Database written by --history
+    #[arg(
+        value_name = "DB",
+        default_value = "rsimg.db",
+        help = "History database"
+    )]
+    pub db: PathBuf,
+
+    /// Rows to total runs in
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Period::Day,
+        value_name = "PERIOD",
+        help = "Group by day, week, month or run"
+    )]
+    pub by: Period,
+
+    /// First day counted, in local time
+    #[arg(
+        long,
+        value_name = "YYYY-MM-DD",
+        help = "Only count runs since this day"
+    )]
+    pub since: Option<String>,
+
+    /// Print one JSON object instead of a table
+    #[arg(long, help = "Output as JSON")]
+    pub json: bool,
+}
+
+/// Totals of one row of `stats`
+#[derive(Default)]
+struct Totals {
+    runs: u64,
+    files: u64,
+    failed: u64,
+    input_bytes: u64,
+    output_bytes: u64,
+    duration_ms: u64,
+}
+
+impl Totals {
+    fn add(&mut self, other: &Totals) {
+        self.runs += other.runs;
+        self.files += other.files;
+        self.failed += other.failed;
+        self.input_bytes += other.input_bytes;
+        self.output_bytes += other.output_bytes;
+        self.duration_ms += other.duration_ms;
+    }
+
+    fn json(&self) -> serde_json::Value {
+        json!({
+            "runs": self.runs,
+            "files": self.files,
+            "failed": self.failed,
+            "input_bytes": self.input_bytes,
+            "output_bytes": self.output_bytes,
+            "duration_ms": self.duration_ms,
+        })
+    }
+}
+
+/// Runs the `stats` subcommand
+pub fn run(args: StatsArgs) -> Result<()> {
+    let conn = Connection::open_with_flags(&args.db, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open history database: {}", args.db.display()))?;
+    if let Some(since) = &args.since {
+        let valid: Option<String> = conn.query_row("SELECT date(?1)", [since], |row| row.get(0))?;
+        if valid.as_deref() != Some(since.as_str()) {
+            return Err(InvalidConfig(tr!(Msg::SinceInvalid, since)).into());
+        }
+    }
+
+    let (label, group) = args.by.columns();
+    let sql = format!(
+        "SELECT {label} AS period, COUNT(*), SUM(f.files), SUM(f.failed), SUM(f.input_bytes),
+                SUM(f.output_bytes), SUM(runs.duration_ms)
+         FROM runs LEFT JOIN (
+             SELECT run_id, COUNT(*) AS files, SUM(error IS NOT NULL) AS failed,
+                    SUM(input_bytes) AS input_bytes, SUM(output_bytes) AS output_bytes
+             FROM files GROUP BY run_id
+         ) AS f ON f.run_id = runs.id
+         WHERE ?1 IS NULL OR date(started, 'unixepoch', 'localtime') >= ?1
+         GROUP BY period, {group}
+         ORDER BY MIN(started)"
+    );
+    let context = || format!("Failed to read history database: {}", args.db.display());
+    let mut statement = conn.prepare(&sql).with_context(context)?;
+    let rows = statement
+        .query_map([&args.since], |row| {
+            let count = |i| row.get::<_, Option<i64>>(i).map(|n| n.unwrap_or(0) as u64);
+            Ok((
+                row.get::<_, String>(0)?,
+                Totals {
+                    runs: count(1)?,
+                    files: count(2)?,
+                    failed: count(3)?,
+                    input_bytes: count(4)?,
+                    output_bytes: count(5)?,
+                    duration_ms: count(6)?,
+                },
+            ))
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .with_context(context)?;
+
+    let mut total = Totals::default();
+    for (_, totals) in &rows {
+        total.add(totals);
+    }
+
+    if args.json {
+        let periods: Vec<_> = rows
+            .iter()
+            .map(|(period, totals)| {
+                let mut row = totals.json();
+                row["period"] = json!(period);
+                row
+            })
+            .collect();
+        let report = json!({
+            "by": args.by.to_possible_value().map(|value| value.get_name().to_string()),
+            "periods": periods,
+            "total": total.json(),
+        });
+        println!("{report}");
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        format!(
+            "{:<18}{:>6}{:>8}{:>8}{:>12}{:>12}{:>10}",
+            "Period", "Runs", "Files", "Failed", "Input", "Output", "Time"
+        )
+        .bold()
+    );
+    for (period, totals) in &rows {
+        println!("{}", row(period, totals));
+    }
+    println!("{}", row("Total", &total).bold());
+    Ok(())
+}
+
+fn row(label: &str, totals: &Totals) -> String {
+    format!(
+        "{:<18}{:>6}{:>8}{:>8}{:>12}{:>12}{:>10}",
+        label,
+        totals.runs,
+        totals.files,
+        totals.failed,
+        format_size(totals.input_bytes),
+        format_size(totals.output_bytes),
+        format!("{:.1}s", totals.duration_ms as f64 / 1000.0)
+    )
+}
@@ -0,0 +1,201 @@
+// src/hook.rs
+//
+// User commands around a batch. Command lines are split like a shell would
+// (quotes, backslashes) and then each argument has its placeholders filled
+// in, so paths with spaces stay one argument and nothing is interpreted by
+// a shell; pipes and redirects need an explicit `sh -c '...'`. Output is
+// captured: a command exiting non-zero fails its input, with its stderr in
+// the error.
+//
+// `--exec-after` runs for every output once its input has finished, to
+// upload, chown or tag files without rsimg knowing how:
+//
+//   {output}  path of the output       {name}  its file name
+//   {dir}     its directory            {input} the input it was made from
+//
+// These run on a pool of their own, a few at a time, while the batch goes
+// on; the batch waits for the last of them before its summary.
+//
+// `--exec-decode` converts inputs rsimg can't decode itself: the tool reads
+// `{input}` and writes a PNG to `{tmp_png}`, a fresh path in a private
+// directory under `--tmpdir`, which is then scaled and encoded like any
+// other source and removed.
+
+use crate::decode::{self, Frame};
+use crate::exit::InvalidConfig;
+use crate::i18n::{Msg, tr};
+use crate::scratch::ScratchDir;
+use anyhow::{Context, Result};
+use image::DynamicImage;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Runs a command for every output of a batch
+pub struct ExecAfter {
+    /// Program and arguments, with placeholders
+    template: Vec<String>,
+    pool: ThreadPool,
+    state: Arc<(Mutex<State>, Condvar)>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Commands queued or running
+    pending: usize,
+    failures: Vec<anyhow::Error>,
+}
+
+impl ExecAfter {
+    /// Parses `command`, to be run at most `jobs` at a time
+    pub fn new(command: &str, jobs: usize) -> Result<Self> {
+        let template = split(command)?;
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .thread_name(|i| format!("rsimg-exec-{i}"))
+            .build()
+            .context("Failed to configure exec thread pool")?;
+        Ok(Self {
+            template,
+            pool,
+            state: Arc::default(),
+        })
+    }
+
+    /// Queues the command for each of `outputs`, made from `input`; the
+    /// first that fails is the input's failure
+    pub(crate) fn queue(&self, input: &Path, outputs: &[PathBuf]) {
+        let commands: Vec<(PathBuf, Vec<String>)> = outputs
+            .iter()
+            .map(|output| (output.clone(), self.args(input, output)))
+            .collect();
+        if commands.is_empty() {
+            return;
+        }
+
+        let state = Arc::clone(&self.state);
+        state.0.lock().unwrap_or_else(|e| e.into_inner()).pending += 1;
+        self.pool.spawn(move || {
+            let failure = commands.iter().find_map(|(output, args)| {
+                run(args)
+                    .map_err(|err| {
+                        anyhow::anyhow!("{}: {err:#}", tr!(Msg::ExecFailed, output.display()))
+                    })
+                    .err()
+            });
+            let (lock, done) = &*state;
+            let mut state = lock.lock().unwrap_or_else(|e| e.into_inner());
+            state.pending -= 1;
+            state.failures.extend(failure);
+            done.notify_all();
+        });
+    }
+
+    /// The command line for `output`, placeholders filled in
+    fn args(&self, input: &Path, output: &Path) -> Vec<String> {
+        let dir = output.parent().unwrap_or(Path::new(""));
+        let name = output.file_name().unwrap_or_default();
+        fill(
+            &self.template,
+            &[
+                ("{output}", output.as_os_str()),
+                ("{name}", name),
+                ("{dir}", dir.as_os_str()),
+                ("{input}", input.as_os_str()),
+            ],
+        )
+    }
+
+    /// Waits for every queued command, returning the failures since the
+    /// last wait
+    pub(crate) fn wait(&self) -> Vec<anyhow::Error> {
+        let (lock, done) = &*self.state;
+        let mut state = lock.lock().unwrap_or_else(|e| e.into_inner());
+        while state.pending > 0 {
+            state = done.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        std::mem::take(&mut state.failures)
+    }
+}
+
+/// Decodes inputs through an external tool
+pub struct ExecDecode {
+    /// Program and arguments, with placeholders
+    template: Vec<String>,
+    /// Where the tool's PNGs go
+    scratch: ScratchDir,
+}
+
+impl ExecDecode {
+    /// Parses `command`, which must write `{tmp_png}`, its PNGs going to
+    /// `tmpdir`
+    pub fn new(command: &str, tmpdir: &Path) -> Result<Self> {
+        let template = split(command)?;
+        if !template.iter().any(|arg| arg.contains("{tmp_png}")) {
+            return Err(InvalidConfig(tr!(Msg::ExecDecodeTmp, command)).into());
+        }
+        Ok(Self {
+            template,
+            scratch: ScratchDir::new(tmpdir, "rsimg-decode")?,
+        })
+    }
+
+    /// Converts `input` with the tool and loads `frame` of the PNG it wrote
+    pub(crate) fn decode(&self, input: &Path, frame: Frame) -> Result<DynamicImage> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let png = self.scratch.path().join(format!("{n}.png"));
+        let args = fill(
+            &self.template,
+            &[
+                ("{input}", input.as_os_str()),
+                ("{tmp_png}", png.as_os_str()),
+            ],
+        );
+        let decoded = run(&args).and_then(|()| decode::load_frame(&png, frame));
+        let _ = std::fs::remove_file(&png);
+        decoded.map_err(|err| {
+            anyhow::anyhow!("{}: {err:#}", tr!(Msg::ExecDecodeFailed, input.display()))
+        })
+    }
+}
+
+/// Splits a command line into its program and arguments
+fn split(command: &str) -> Result<Vec<String>> {
+    shlex::split(command)
+        .filter(|args| !args.is_empty())
+        .ok_or_else(|| InvalidConfig(tr!(Msg::ExecInvalid, command)).into())
+}
+
+/// `template` with each placeholder replaced by its value
+fn fill(template: &[String], values: &[(&str, &OsStr)]) -> Vec<String> {
+    template
+        .iter()
+        .map(|arg| {
+            values
+                .iter()
+                .fold(arg.clone(), |arg, (placeholder, value)| {
+                    arg.replace(placeholder, &value.to_string_lossy())
+                })
+        })
+        .collect()
+}
+
+fn run(args: &[String]) -> Result<()> {
+    let result = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("Failed to run {}", args[0]))?;
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        match stderr.trim() {
+            "" => anyhow::bail!("{} failed ({})", args[0], result.status),
+            stderr => anyhow::bail!("{} failed ({}): {stderr}", args[0], result.status),
+        }
+    }
+    Ok(())
+}
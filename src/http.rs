@@ -0,0 +1,417 @@
+// src/http.rs
+//
+// `rsimg serve --http ADDR`: a tiny image proxy for internal tools. One
+// endpoint optimizes a single image per request through the in-memory
+// pipeline shared with batch runs:
+//
+//   POST /optimize?w=640&fmt=webp&q=75         request body is the image
+//   GET  /optimize?url=https://...&w=640&h=480 the image is fetched first
+//
+// `w`/`h` bound the output size (never enlarged), `fmt` defaults to webp and
+// `q` to the CLI quality default. Errors are plain text with a 4xx/5xx status.
+//
+// Fetching by `url` is off unless hosts are allowed with `--http-allow-host`,
+// so the endpoint can't be used to reach arbitrary servers. Every lookup,
+// redirects included, goes through `GuardedResolver`: hosts must be on the
+// list, and private, loopback and link-local addresses are refused unless
+// the host is listed by name rather than by a wildcard.
+
+use anyhow::{Context, Result};
+use rsimg::encode::EncodeOptions;
+use rsimg::i18n::{Msg, tr};
+use rsimg::pipeline;
+use rsimg::transform::Transforms;
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response, Server};
+use ureq::Agent;
+use ureq::http::Uri;
+use ureq::unversioned::resolver::{DefaultResolver, ResolvedSocketAddrs, Resolver};
+use ureq::unversioned::transport::{DefaultConnector, NextTimeout};
+
+/// Largest accepted upload or fetched source
+const MAX_SOURCE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Formats an endpoint may be asked for
+const FORMATS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "tga", "ppm"];
+
+/// Serves requests until the process is stopped; `allow_hosts` are the
+/// `--http-allow-host` patterns, and `url` fetching is off when empty
+pub fn serve(addr: &str, allow_hosts: &[String]) -> Result<()> {
+    let server = Server::http(addr)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| tr!(Msg::ListenFailed, addr))?;
+    eprintln!("{}", tr!(Msg::Listening, format!("http://{addr}")));
+
+    let agent = (!allow_hosts.is_empty()).then(|| {
+        let resolver = GuardedResolver(Arc::new(HostList::new(allow_hosts)));
+        Agent::with_parts(
+            Agent::config_builder().build(),
+            DefaultConnector::default(),
+            resolver,
+        )
+    });
+
+    // Each worker takes the next request off the shared listener
+    let workers = rayon::current_num_threads().max(2);
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                for request in server.incoming_requests() {
+                    handle(request, agent.as_ref());
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// An HTTP error status with a plain-text message
+struct Failure(u16, String);
+
+fn handle(mut request: Request, agent: Option<&Agent>) {
+    let response = match respond(&mut request, agent) {
+        Ok((bytes, content_type)) => Response::from_data(bytes).with_header(
+            Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes())
+                .expect("static header is valid"),
+        ),
+        Err(Failure(status, message)) => {
+            Response::from_string(format!("{message}\n")).with_status_code(status)
+        }
+    };
+    let _ = request.respond(response);
+}
+
+/// The encoded image and its content type for one request
+fn respond(
+    request: &mut Request,
+    agent: Option<&Agent>,
+) -> Result<(Vec<u8>, &'static str), Failure> {
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    if path != "/optimize" {
+        return Err(Failure(404, format!("Unknown endpoint: {path}")));
+    }
+    let params = Params::parse(query)?;
+
+    let source = match (request.method(), &params.url) {
+        (Method::Get, Some(url)) => fetch(agent, url)?,
+        (Method::Post | Method::Put, _) => {
+            let mut body = Vec::new();
+            request
+                .as_reader()
+                .take(MAX_SOURCE_BYTES + 1)
+                .read_to_end(&mut body)
+                .map_err(|e| Failure(400, format!("Failed to read upload: {e}")))?;
+            if body.len() as u64 > MAX_SOURCE_BYTES {
+                return Err(Failure(413, "Image is too large".into()));
+            }
+            body
+        }
+        _ => {
+            return Err(Failure(
+                400,
+                "POST the image, or GET with a 'url' parameter".into(),
+            ));
+        }
+    };
+
+    let encode = EncodeOptions {
+        quality: params.quality,
+        ..Default::default()
+    };
+    let bytes = pipeline::optimize_to_fit(
+        &source,
+        params.width,
+        params.height,
+        &params.fmt,
+        &encode,
+        &Transforms::default(),
+    )
+    .map_err(|e| Failure(422, format!("{e:#}")))?;
+
+    Ok((bytes, content_type(&params.fmt)))
+}
+
+/// Query parameters of `/optimize`
+struct Params {
+    width: Option<u32>,
+    height: Option<u32>,
+    fmt: String,
+    quality: u8,
+    url: Option<String>,
+}
+
+impl Params {
+    fn parse(query: &str) -> Result<Self, Failure> {
+        let mut params = Params {
+            width: None,
+            height: None,
+            fmt: "webp".to_string(),
+            quality: EncodeOptions::default().quality,
+            url: None,
+        };
+        let invalid = |key: &str, value: &str| Failure(400, format!("Invalid {key}: '{value}'"));
+
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value);
+            match key {
+                "w" => params.width = Some(value.parse().map_err(|_| invalid(key, &value))?),
+                "h" => params.height = Some(value.parse().map_err(|_| invalid(key, &value))?),
+                "q" => {
+                    params.quality = value
+                        .parse()
+                        .ok()
+                        .filter(|q| *q <= 100)
+                        .ok_or_else(|| invalid(key, &value))?
+                }
+                "fmt" => {
+                    let fmt = value.to_lowercase();
+                    if !FORMATS.contains(&fmt.as_str()) {
+                        return Err(invalid(key, &value));
+                    }
+                    params.fmt = fmt;
+                }
+                "url" => params.url = Some(value),
+                _ => return Err(Failure(400, format!("Unknown parameter: {key}"))),
+            }
+        }
+
+        if params.width == Some(0) || params.height == Some(0) {
+            return Err(Failure(400, "Width and height must be positive".into()));
+        }
+        Ok(params)
+    }
+}
+
+/// Downloads a source image through the guarded agent, if fetching is on
+fn fetch(agent: Option<&Agent>, url: &str) -> Result<Vec<u8>, Failure> {
+    let Some(agent) = agent else {
+        return Err(Failure(
+            403,
+            "Fetching by URL is disabled; allow hosts with --http-allow-host".into(),
+        ));
+    };
+    let failed = |e: ureq::Error| match e {
+        ureq::Error::Io(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            Failure(403, format!("Refused to fetch {url}: {e}"))
+        }
+        e => Failure(502, format!("Failed to fetch {url}: {e}")),
+    };
+    let mut response = agent.get(url).call().map_err(failed)?;
+    response
+        .body_mut()
+        .with_config()
+        .limit(MAX_SOURCE_BYTES)
+        .read_to_vec()
+        .map_err(failed)
+}
+
+/// The `--http-allow-host` patterns: a host name or address, `*.domain` for
+/// its subdomains, or `*` for any host
+#[derive(Debug)]
+struct HostList(Vec<String>);
+
+/// How a host matched the allowlist
+#[derive(Debug, PartialEq)]
+enum Allowed {
+    No,
+    /// Through a wildcard, so only public addresses may be reached
+    Public,
+    /// By its own name, so any address may be reached
+    Any,
+}
+
+impl HostList {
+    fn new(patterns: &[String]) -> Self {
+        HostList(patterns.iter().map(|p| normalize_host(p)).collect())
+    }
+
+    fn check(&self, host: &str) -> Allowed {
+        let host = normalize_host(host);
+        let mut allowed = Allowed::No;
+        for pattern in &self.0 {
+            if *pattern == host {
+                return Allowed::Any;
+            }
+            let wildcard = match pattern.strip_prefix('*') {
+                Some("") => true,
+                Some(suffix) => suffix.starts_with('.') && host.ends_with(suffix),
+                None => false,
+            };
+            if wildcard {
+                allowed = Allowed::Public;
+            }
+        }
+        allowed
+    }
+}
+
+/// Lowercases a host and drops IPv6 brackets and a trailing root dot
+fn normalize_host(host: &str) -> String {
+    let host = host.trim().trim_end_matches('.');
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    host.to_lowercase()
+}
+
+/// Whether an address is outside the public internet: private, loopback,
+/// link-local, shared (CGNAT), multicast, unspecified or unique local
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_internal_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_internal_v4(ip),
+            None => is_internal_v6(ip),
+        },
+    }
+}
+
+fn is_internal_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+}
+
+fn is_internal_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || first & 0xfe00 == 0xfc00
+        || first & 0xffc0 == 0xfe80
+}
+
+/// Resolves only allowed hosts, and refuses internal addresses for hosts
+/// allowed through a wildcard. Checking the resolved addresses here, rather
+/// than before the request, covers redirects and DNS answers that change
+/// between lookups
+#[derive(Debug)]
+struct GuardedResolver(Arc<HostList>);
+
+impl Resolver for GuardedResolver {
+    fn resolve(
+        &self,
+        uri: &Uri,
+        config: &ureq::config::Config,
+        timeout: NextTimeout,
+    ) -> Result<ResolvedSocketAddrs, ureq::Error> {
+        let host = uri.host().unwrap_or_default();
+        let refused = |reason: String| {
+            ureq::Error::Io(io::Error::new(io::ErrorKind::PermissionDenied, reason))
+        };
+        let allowed = self.0.check(host);
+        if allowed == Allowed::No {
+            return Err(refused(format!("{host} is not an allowed host")));
+        }
+
+        let addrs = DefaultResolver::default().resolve(uri, config, timeout)?;
+        if allowed == Allowed::Public
+            && let Some(addr) = addrs.iter().find(|addr| is_internal(addr.ip()))
+        {
+            return Err(refused(format!(
+                "{host} resolves to internal address {}",
+                addr.ip()
+            )));
+        }
+        Ok(addrs)
+    }
+}
+
+/// Decodes `%XX` escapes and `+` (space) in a query value
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let escaped = bytes
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match escaped {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn content_type(fmt: &str) -> &'static str {
+    match fmt {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hosts(patterns: &[&str]) -> HostList {
+        HostList::new(&patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn hosts_match_names_and_wildcards() {
+        let list = hosts(&[
+            "Images.Example.com",
+            "*.cdn.example.net",
+            "10.0.0.5",
+            "[::1]",
+        ]);
+        assert_eq!(list.check("images.example.com."), Allowed::Any);
+        assert_eq!(list.check("a.cdn.example.net"), Allowed::Public);
+        assert_eq!(list.check("cdn.example.net"), Allowed::No);
+        assert_eq!(list.check("evilcdn.example.net"), Allowed::No);
+        assert_eq!(list.check("example.com"), Allowed::No);
+        assert_eq!(list.check("10.0.0.5"), Allowed::Any);
+        assert_eq!(list.check("[::1]"), Allowed::Any);
+        assert_eq!(hosts(&["*"]).check("anything.org"), Allowed::Public);
+        assert_eq!(hosts(&[]).check("anything.org"), Allowed::No);
+    }
+
+    #[test]
+    fn internal_addresses_are_recognized() {
+        for ip in [
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "127.0.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(is_internal(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["93.184.216.34", "100.128.0.1", "2606:2800:220:1::1"] {
+            assert!(!is_internal(ip.parse().unwrap()), "{ip}");
+        }
+    }
+}
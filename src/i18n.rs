@@ -0,0 +1,882 @@
+// src/i18n.rs
+//
+// Message tables for user-facing output. Each language is one exhaustive
+// match, so a message added without a translation fails to compile. The
+// language comes from --lang, or the locale environment when it isn't given.
+
+use clap::ValueEnum;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// Language of console output and error reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Lang {
+    /// English
+    En,
+    /// Italiano
+    It,
+    /// Deutsch
+    De,
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Selects the output language; without one, the locale decides
+pub fn init(lang: Option<Lang>) {
+    let _ = LANG.set(lang.unwrap_or_else(from_locale));
+}
+
+/// The language set by `init` (English before that)
+pub fn lang() -> Lang {
+    LANG.get().copied().unwrap_or(Lang::En)
+}
+
+/// Language of the first locale variable that names one we have
+fn from_locale() -> Lang {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| match value.get(..2) {
+            Some("it") => Some(Lang::It),
+            Some("de") => Some(Lang::De),
+            Some("en") => Some(Lang::En),
+            _ => None,
+        })
+        .unwrap_or(Lang::En)
+}
+
+/// User-facing messages; `{}` placeholders are filled in order
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(test, derive(ValueEnum))]
+pub enum Msg {
+    Title,
+    TuneTitle,
+    Found,
+    Images,
+    Output,
+    Formats,
+    Scales,
+    Pipeline,
+    Jobs,
+    Breakpoints,
+    Geometries,
+    Quality,
+    Threads,
+    Done,
+    Optimized,
+    NoImages,
+    Error,
+    ErrorsHeader,
+    NoneProcessed,
+    Collisions,
+    StreamCollision,
+    StreamSeq,
+    Streaming,
+    BudgetQuality,
+    BudgetScale,
+    BudgetUnreachable,
+    LockWaiting,
+    ProfileHeader,
+    UpToDate,
+    UpdateAvailable,
+    Updated,
+    AlphaSkipped,
+    ChannelsReduced,
+    RepeatsElided,
+    CopiedToClipboard,
+    GraphicsRouted,
+    Classified,
+    PreviewsUsed,
+    MarkedSkipped,
+    LimitReached,
+    Unselected,
+    Bursts,
+    Screened,
+    Blurry,
+    Underexposed,
+    Overexposed,
+    DiffCompared,
+    DiffChanged,
+    DiffResized,
+    DiffOnlyIn,
+    BaselineUpdated,
+    MaxChangedRange,
+    MinSsimRange,
+    WatermarkFound,
+    WatermarkNone,
+    WatermarkCarries,
+    WatermarkUnknown,
+    ResultsClosed,
+    ExecInvalid,
+    ExecFailed,
+    ExecDecodeTmp,
+    ExecDecodeFailed,
+    SpaceShort,
+    SpaceLow,
+    TmpdirMissing,
+    IconsSquare,
+    IconsSmall,
+    ProgressTemplate,
+    ProgressChars,
+    SomeFailed,
+    Mismatch,
+    QualityRange,
+    QualityInvalid,
+    TrimRange,
+    DenoiseRange,
+    VignetteRange,
+    FaceModelRequired,
+    ScaleInvalid,
+    ResizeInvalid,
+    ScalesInvalid,
+    SinceInvalid,
+    BreakpointsRead,
+    BreakpointsInvalid,
+    BreakpointsEmpty,
+    PipelineRead,
+    PipelineStep,
+    PipelineNoEncode,
+    OverlapRange,
+    JobsRead,
+    JobsInvalid,
+    JobsEmpty,
+    JobInvalid,
+    JobLine,
+    JobEntry,
+    JobsUnknownColumn,
+    JobsInputColumn,
+    JobsArray,
+    JobObject,
+    JobUnknownKey,
+    JobStrings,
+    JobValue,
+    JobNoInput,
+    JobOutputName,
+    RuleSetting,
+    RuleInput,
+    RuleUnknown,
+    LevelsRead,
+    LevelsInvalid,
+    LevelUnknown,
+    LevelTable,
+    LevelFormat,
+    LevelQuality,
+    ListenFailed,
+    Listening,
+    SocketUnix,
+    ClipboardEmpty,
+    ClipboardReadFailed,
+    ClipboardSize,
+    ClipboardNoOutput,
+    ClipboardCopyFailed,
+    ClipboardOpenFailed,
+    Features,
+    Inputs,
+    Outputs,
+    NotCompiled,
+    PluginConfigRead,
+    PluginConfigInvalid,
+    PluginList,
+    PluginPath,
+    PluginStage,
+    PluginLoadFailed,
+    PluginOptionsNul,
+    PluginWasm,
+    PluginExport,
+    PluginCode,
+    PluginFailed,
+    FfiCallback,
+    FfiBuffer,
+    FfiFormats,
+    PresetConflict,
+    ProfileFormat,
+    C2paFormat,
+    PathMissing,
+    PathInvalid,
+    Unsupported,
+    OpenFailed,
+    DetectFailed,
+    Misnamed,
+    DecodeFailed,
+    DecodeFormatFailed,
+    TransformFailed,
+    SaveFailed,
+    VerifyFailed,
+    DuplicateSkipped,
+    Cancelled,
+    OfSource,
+    Grid,
+    PyramidTitle,
+    Pyramid,
+    Metadata,
+    Package,
+    Gallery,
+    AppIconsTitle,
+    AppIcons,
+    Files,
+    Tiles,
+    Original,
+}
+
+/// Message text in the current language
+pub fn text(msg: Msg) -> &'static str {
+    match lang() {
+        Lang::En => en(msg),
+        Lang::It => it(msg),
+        Lang::De => de(msg),
+    }
+}
+
+/// Message text with its placeholders replaced by `args`
+pub fn fill(msg: Msg, args: &[&dyn Display]) -> String {
+    let mut parts = text(msg).split("{}");
+    let mut out = parts.next().unwrap_or_default().to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+/// `tr!(Msg::X)` or `tr!(Msg::X, a, b)`: a localized message as a `String`
+#[macro_export]
+macro_rules! tr {
+    ($msg:expr $(, $arg:expr)* $(,)?) => {
+        $crate::i18n::fill($msg, &[$(&$arg as &dyn ::std::fmt::Display),*])
+    };
+}
+pub use crate::tr;
+
+fn en(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Title => "Image Optimizer",
+        Msg::TuneTitle => "Quality Tuning",
+        Msg::Found => "Found",
+        Msg::Images => "{} images",
+        Msg::Output => "Output",
+        Msg::Formats => "Formats",
+        Msg::Scales => "Scales",
+        Msg::Pipeline => "Pipeline",
+        Msg::Jobs => "Jobs",
+        Msg::Breakpoints => "Breakpoints",
+        Msg::Geometries => "Sizes",
+        Msg::Quality => "Quality",
+        Msg::Threads => "Using {} decode + {} encode threads",
+        Msg::Done => "Processing completed successfully!",
+        Msg::Optimized => "{} images optimized",
+        Msg::NoImages => "No valid images found",
+        Msg::Error => "Error",
+        Msg::ErrorsHeader => "Errors during processing:",
+        Msg::NoneProcessed => "None of the {} images were processed",
+        Msg::BudgetQuality => "Outputs take {} of the {} budget; retrying at quality {}",
+        Msg::BudgetScale => "Outputs take {} of the {} budget; retrying without the {}% scale",
+        Msg::BudgetUnreachable => {
+            "Outputs take {} at the lowest quality and a single scale, over the {} budget"
+        }
+        Msg::LockWaiting => "Waiting for another rsimg writing to {}",
+        Msg::ProfileHeader => "Time per file in each stage ({} files):",
+        Msg::UpToDate => "rsimg {} is up to date",
+        Msg::UpdateAvailable => {
+            "rsimg {} is available (this is {}); run `rsimg self-update` to install it"
+        }
+        Msg::Updated => "Updated rsimg {} to {}",
+        Msg::AlphaSkipped => "Skipped {} output of transparent image: {}",
+        Msg::ChannelsReduced => {
+            "Smaller outputs: {} written as grayscale, {} without an unused alpha channel"
+        }
+        Msg::RepeatsElided => "Skipped {} outputs repeating another's size and format",
+        Msg::CopiedToClipboard => "Copied {} to the clipboard",
+        Msg::GraphicsRouted => {
+            "{} images look like screenshots or graphics: written as lossless WebP and palette PNG"
+        }
+        Msg::Classified => "Classified {} photos, {} graphics and {} text images",
+        Msg::PreviewsUsed => "{} images resized from their embedded previews",
+        Msg::MarkedSkipped => "{} images already optimized by rsimg were skipped",
+        Msg::LimitReached => "Limit reached: {} images were left for a later run",
+        Msg::Unselected => "{} images without the rating or keywords asked for were skipped",
+        Msg::Bursts => "Kept the sharpest frame of each of {} bursts, leaving out {} images",
+        Msg::Screened => "{} images failing --skip-blurry were skipped:",
+        Msg::Blurry => "blurry (sharpness {})",
+        Msg::Underexposed => "underexposed",
+        Msg::Overexposed => "overexposed",
+        Msg::DiffCompared => "{} pairs compared: {} identical, {} changed",
+        Msg::DiffChanged => "{}% of pixels differ, by up to {}; SSIM {}",
+        Msg::DiffResized => "resized from {}×{} to {}×{}",
+        Msg::DiffOnlyIn => "{} is only in {}",
+        Msg::BaselineUpdated => "Baseline updated: {} images copied, {} deleted",
+        Msg::MaxChangedRange => "--max-changed must be between 0 and 100, not {}",
+        Msg::MinSsimRange => "--min-ssim must be between 0 and 1, not {}",
+        Msg::WatermarkFound => "{}: watermark {} ({}% of blocks agree)",
+        Msg::WatermarkNone => "{}: no watermark found",
+        Msg::WatermarkCarries => "carries {}",
+        Msg::WatermarkUnknown => "carries none of the IDs given",
+        Msg::ResultsClosed => "No more results are written to {}: its reader went away",
+        Msg::ExecInvalid => "--exec-after '{}' is not a valid command line",
+        Msg::ExecFailed => "--exec-after failed for {}",
+        Msg::ExecDecodeTmp => "--exec-decode '{}' must write its PNG to {tmp_png}",
+        Msg::ExecDecodeFailed => "--exec-decode failed for {}",
+        Msg::SpaceShort => {
+            "Outputs need about {} in {}, but its volume has {} free (--no-space-check starts anyway)"
+        }
+        Msg::SpaceLow => "Outputs will take about {} of the {} free for {}",
+        Msg::TmpdirMissing => "--tmpdir '{}' is not a directory",
+        Msg::IconsSquare => "App icons need a square source, but {} is {}×{}",
+        Msg::IconsSmall => {
+            "{} is {}px wide, smaller than the largest icon ({}px): icons are enlarged"
+        }
+        Msg::ProgressTemplate => "Invalid progress template: {}",
+        Msg::ProgressChars => "Progress characters need at least two characters (filled and empty)",
+        Msg::Collisions => {
+            "{} output names are shared by several inputs (rename them, or use --on-collision suffix|hash):"
+        }
+        Msg::StreamCollision => {
+            "{} would overwrite the outputs of {} (rename one, or drop --stream to use --on-collision)"
+        }
+        Msg::StreamSeq => "{seq} numbers inputs in path order, which --stream doesn't wait for",
+        Msg::Streaming => "Processing images as they're found",
+        Msg::SomeFailed => "{} images were not processed correctly",
+        Msg::Mismatch => "{} images differ from their baseline",
+        Msg::QualityRange => "Quality must be between 0 and 100",
+        Msg::QualityInvalid => "Quality must be between 0 and 100 ({} is invalid)",
+        Msg::TrimRange => "Trim fuzz must be between 0 and 100",
+        Msg::DenoiseRange => "Denoise strength must be between 1 and 100",
+        Msg::VignetteRange => "Vignette strength must be between 0 and 100",
+        Msg::FaceModelRequired => "--gravity face needs --face-model (onnx feature)",
+        Msg::ScaleInvalid => "Scales must be between 10 and 100 ({}% is invalid)",
+        Msg::ResizeInvalid => "Invalid --resize: {}",
+        Msg::ScalesInvalid => "Invalid --scales: {}",
+        Msg::SinceInvalid => "Invalid --since day '{}' (expected YYYY-MM-DD)",
+        Msg::BreakpointsRead => "Failed to read breakpoints {}: {}",
+        Msg::BreakpointsInvalid => "Invalid breakpoints in {}: {} (expected a list of widths)",
+        Msg::BreakpointsEmpty => "{} must list widths above 0",
+        Msg::PipelineRead => "Failed to read pipeline {}: {}",
+        Msg::PipelineStep => "Invalid pipeline step '{}': {}",
+        Msg::PipelineNoEncode => "The pipeline has no encode(...) step",
+        Msg::OverlapRange => "Tile overlap must be less than half the tile size ({})",
+        Msg::JobsRead => "Failed to read jobs file: {}",
+        Msg::JobsInvalid => "Invalid jobs file {}: {}",
+        Msg::JobsEmpty => "Jobs file {} lists no jobs",
+        Msg::JobInvalid => "{}, {}: {}",
+        Msg::JobLine => "line {}",
+        Msg::JobEntry => "job {}",
+        Msg::JobsUnknownColumn => "unknown column '{}' (expected {})",
+        Msg::JobsInputColumn => "missing 'input' column",
+        Msg::JobsArray => "expected an array of jobs",
+        Msg::JobObject => "{}: expected an object",
+        Msg::JobUnknownKey => "{}: unknown key '{}' (expected {})",
+        Msg::JobStrings => "{}: '{}' must list strings",
+        Msg::JobValue => "{}: invalid value for '{}'",
+        Msg::JobNoInput => "no input given",
+        Msg::JobOutputName => "output name '{}' contains a path separator",
+        Msg::RuleSetting => "invalid setting '{}' (expected KEY=VALUE)",
+        Msg::RuleInput => "'input' can't be set by a rule",
+        Msg::RuleUnknown => "unknown setting '{}' (expected scales, {})",
+        Msg::LevelsRead => "Failed to read quality levels: {}",
+        Msg::LevelsInvalid => "Invalid quality levels: {}",
+        Msg::LevelUnknown => {
+            "Unknown quality level [{}] in {} (expected low, medium, high or lossless)"
+        }
+        Msg::LevelTable => "'{}' in {} must be a table",
+        Msg::LevelFormat => "Unknown format '{}' in [{}] of {} (expected jpg or webp)",
+        Msg::LevelQuality => "Quality for {} in [{}] of {} must be between 0 and 100",
+        Msg::ListenFailed => "Failed to listen on {}",
+        Msg::Listening => "Listening on {}",
+        Msg::SocketUnix => {
+            "--socket needs a unix platform; without it requests are read from stdin"
+        }
+        Msg::ClipboardEmpty => "The clipboard holds no image",
+        Msg::ClipboardReadFailed => "Failed to read the clipboard",
+        Msg::ClipboardSize => "The clipboard image has an unexpected size",
+        Msg::ClipboardNoOutput => "No output to copy to the clipboard",
+        Msg::ClipboardCopyFailed => "Failed to copy the image to the clipboard",
+        Msg::ClipboardOpenFailed => "Failed to open the clipboard",
+        Msg::Features => "Features: {}",
+        Msg::Inputs => "Inputs",
+        Msg::Outputs => "Outputs",
+        Msg::NotCompiled => "(not compiled in)",
+        Msg::PluginConfigRead => "Failed to read plugin config: {}",
+        Msg::PluginConfigInvalid => "Invalid plugin config: {}",
+        Msg::PluginList => "'plugin' must be a list of [[plugin]] tables",
+        Msg::PluginPath => "Plugin {} in {} has no 'path'",
+        Msg::PluginStage => "Unknown plugin stage '{}' (expected before-scale or after-scale)",
+        Msg::PluginLoadFailed => "Failed to load plugin: {}",
+        Msg::PluginOptionsNul => "Plugin options contain a NUL byte",
+        Msg::PluginWasm => "WebAssembly plugins need rsimg built with the 'wasm-plugins' feature",
+        Msg::PluginExport => "Missing {} export",
+        Msg::PluginCode => "Plugin {} failed with code {}",
+        Msg::PluginFailed => "Plugin {} failed: {}",
+        Msg::FfiCallback => "No output callback given",
+        Msg::FfiBuffer => "No input buffer given",
+        Msg::FfiFormats => "Formats are not valid UTF-8",
+        Msg::PresetConflict => "--preset {} can't be combined with {}",
+        Msg::ProfileFormat => "--output-profile separates JPEG and TIFF outputs only, not {}",
+        Msg::C2paFormat => "--c2pa-sign embeds manifests in JPEG and PNG outputs only, not {}",
+        Msg::PathMissing => "Path '{}' does not exist",
+        Msg::PathInvalid => "Path '{}' is not a valid file or directory",
+        Msg::Unsupported => "File '{}' is not a supported image format",
+        Msg::OpenFailed => "Failed to open image: {}",
+        Msg::DetectFailed => "Failed to detect format: {}",
+        Msg::Misnamed => "{} contains {} data; its extension is wrong",
+        Msg::DecodeFailed => "Failed to decode image: {}",
+        Msg::DecodeFormatFailed => "Failed to decode {}: {}",
+        Msg::TransformFailed => "Failed to transform image: {}",
+        Msg::SaveFailed => "Error saving: {}",
+        Msg::VerifyFailed => "Output can't be read back: {}",
+        Msg::DuplicateSkipped => "Skipped duplicate of failed image: {}",
+        Msg::Cancelled => "Cancelled",
+        Msg::OfSource => "{}% of source",
+        Msg::Grid => "Grid",
+        Msg::PyramidTitle => "Tile Pyramid",
+        Msg::Pyramid => "Pyramid",
+        Msg::Metadata => "Metadata",
+        Msg::Package => "Package",
+        Msg::Gallery => "Gallery",
+        Msg::AppIconsTitle => "App Icons",
+        Msg::AppIcons => "App icons",
+        Msg::Files => "{} files",
+        Msg::Tiles => "{} tiles",
+        Msg::Original => "original",
+    }
+}
+
+fn it(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Title => "Ottimizzatore di immagini",
+        Msg::TuneTitle => "Taratura della qualità",
+        Msg::Found => "Trovate",
+        Msg::Images => "{} immagini",
+        Msg::Output => "Destinazione",
+        Msg::Formats => "Formati",
+        Msg::Scales => "Scale",
+        Msg::Pipeline => "Pipeline",
+        Msg::Jobs => "Lavori",
+        Msg::Breakpoints => "Breakpoint",
+        Msg::Geometries => "Dimensioni",
+        Msg::Quality => "Qualità",
+        Msg::Threads => "Uso {} thread di decodifica + {} di codifica",
+        Msg::Done => "Elaborazione completata con successo!",
+        Msg::Optimized => "{} immagini ottimizzate",
+        Msg::NoImages => "Nessuna immagine valida trovata",
+        Msg::Error => "Errore",
+        Msg::ErrorsHeader => "Errori durante l'elaborazione:",
+        Msg::NoneProcessed => "Nessuna delle {} immagini è stata elaborata",
+        Msg::BudgetQuality => {
+            "Gli output occupano {} su un budget di {}; nuovo tentativo con qualità {}"
+        }
+        Msg::BudgetScale => {
+            "Gli output occupano {} su un budget di {}; nuovo tentativo senza la scala {}%"
+        }
+        Msg::BudgetUnreachable => {
+            "Gli output occupano {} alla qualità minima e con una sola scala, oltre il budget di {}"
+        }
+        Msg::LockWaiting => "In attesa di un altro rsimg che scrive in {}",
+        Msg::ProfileHeader => "Tempo per file in ogni fase ({} file):",
+        Msg::UpToDate => "rsimg {} è aggiornato",
+        Msg::UpdateAvailable => {
+            "È disponibile rsimg {} (questa è la {}); esegui `rsimg self-update` per installarlo"
+        }
+        Msg::Updated => "rsimg aggiornato da {} a {}",
+        Msg::AlphaSkipped => "Output {} saltato per l'immagine trasparente: {}",
+        Msg::ChannelsReduced => {
+            "Output più leggeri: {} in scala di grigi, {} senza un canale alfa inutilizzato"
+        }
+        Msg::RepeatsElided => "Saltati {} output con dimensioni e formato già scritti",
+        Msg::CopiedToClipboard => "{} copiato negli appunti",
+        Msg::GraphicsRouted => {
+            "{} immagini sembrano screenshot o grafiche: salvate come WebP lossless e PNG a palette"
+        }
+        Msg::Classified => "Classificate {} foto, {} grafiche e {} immagini di testo",
+        Msg::PreviewsUsed => "{} immagini ridimensionate dalle anteprime incorporate",
+        Msg::MarkedSkipped => "{} immagini già ottimizzate da rsimg sono state saltate",
+        Msg::Unselected => {
+            "{} immagini senza la valutazione o le parole chiave richieste sono state saltate"
+        }
+        Msg::Bursts => {
+            "Tenuto il fotogramma più nitido di ciascuna di {} raffiche, escluse {} immagini"
+        }
+        Msg::Screened => "{} immagini non superano --skip-blurry e sono state saltate:",
+        Msg::Blurry => "sfocata (nitidezza {})",
+        Msg::Underexposed => "sottoesposta",
+        Msg::Overexposed => "sovraesposta",
+        Msg::DiffCompared => "{} coppie confrontate: {} identiche, {} diverse",
+        Msg::DiffChanged => "{}% dei pixel diverso, fino a {}; SSIM {}",
+        Msg::DiffResized => "ridimensionata da {}×{} a {}×{}",
+        Msg::DiffOnlyIn => "{} c'è solo in {}",
+        Msg::BaselineUpdated => "Riferimento aggiornato: {} immagini copiate, {} eliminate",
+        Msg::MaxChangedRange => "--max-changed deve essere tra 0 e 100, non {}",
+        Msg::MinSsimRange => "--min-ssim deve essere tra 0 e 1, non {}",
+        Msg::WatermarkFound => "{}: filigrana {} (concorda il {}% dei blocchi)",
+        Msg::WatermarkNone => "{}: nessuna filigrana trovata",
+        Msg::WatermarkCarries => "contiene {}",
+        Msg::WatermarkUnknown => "non contiene nessuno degli ID indicati",
+        Msg::LimitReached => {
+            "Limite raggiunto: {} immagini sono rimaste per un'esecuzione successiva"
+        }
+        Msg::ResultsClosed => "Nessun altro risultato viene scritto in {}: il lettore si è chiuso",
+        Msg::ExecInvalid => "--exec-after '{}' non è una riga di comando valida",
+        Msg::ExecFailed => "--exec-after non riuscito per {}",
+        Msg::ExecDecodeTmp => "--exec-decode '{}' deve scrivere il suo PNG in {tmp_png}",
+        Msg::ExecDecodeFailed => "--exec-decode non riuscito per {}",
+        Msg::SpaceShort => {
+            "Le uscite richiedono circa {} in {}, ma il volume ha {} liberi (--no-space-check avvia comunque)"
+        }
+        Msg::SpaceLow => "Le uscite occuperanno circa {} dei {} liberi per {}",
+        Msg::TmpdirMissing => "--tmpdir '{}' non è una cartella",
+        Msg::IconsSquare => "Le icone richiedono un'origine quadrata, ma {} è {}×{}",
+        Msg::IconsSmall => {
+            "{} è largo {}px, meno dell'icona più grande ({}px): le icone sono ingrandite"
+        }
+        Msg::ProgressTemplate => "Modello di avanzamento non valido: {}",
+        Msg::ProgressChars => "I caratteri di avanzamento devono essere almeno due (pieno e vuoto)",
+        Msg::Collisions => {
+            "{} nomi di output sono condivisi da più input (rinominali o usa --on-collision suffix|hash):"
+        }
+        Msg::StreamCollision => {
+            "{} sovrascriverebbe gli output di {} (rinominane uno o togli --stream per usare --on-collision)"
+        }
+        Msg::StreamSeq => "{seq} numera gli input in ordine di percorso, che --stream non attende",
+        Msg::Streaming => "Elaborazione delle immagini man mano che vengono trovate",
+        Msg::SomeFailed => "{} immagini non sono state elaborate correttamente",
+        Msg::Mismatch => "{} immagini differiscono dal riferimento",
+        Msg::QualityRange => "La qualità deve essere compresa tra 0 e 100",
+        Msg::QualityInvalid => "La qualità deve essere compresa tra 0 e 100 ({} non è valido)",
+        Msg::TrimRange => "La tolleranza del ritaglio deve essere compresa tra 0 e 100",
+        Msg::DenoiseRange => {
+            "L'intensità della riduzione del rumore deve essere compresa tra 1 e 100"
+        }
+        Msg::VignetteRange => "L'intensità della vignettatura deve essere compresa tra 0 e 100",
+        Msg::FaceModelRequired => "--gravity face richiede --face-model (funzionalità onnx)",
+        Msg::ScaleInvalid => "Le scale devono essere comprese tra 10 e 100 ({}% non è valido)",
+        Msg::ResizeInvalid => "--resize non valido: {}",
+        Msg::ScalesInvalid => "--scales non valido: {}",
+        Msg::SinceInvalid => "Giorno --since '{}' non valido (formato AAAA-MM-GG)",
+        Msg::BreakpointsRead => "Impossibile leggere i breakpoint {}: {}",
+        Msg::BreakpointsInvalid => {
+            "Breakpoint non validi in {}: {} (attesa una lista di larghezze)"
+        }
+        Msg::BreakpointsEmpty => "{} deve elencare larghezze maggiori di 0",
+        Msg::PipelineRead => "Impossibile leggere la pipeline {}: {}",
+        Msg::PipelineStep => "Passo della pipeline '{}' non valido: {}",
+        Msg::PipelineNoEncode => "La pipeline non ha un passo encode(...)",
+        Msg::OverlapRange => {
+            "La sovrapposizione delle tile deve essere minore di metà della dimensione della tile ({})"
+        }
+        Msg::JobsRead => "Impossibile leggere il file dei job: {}",
+        Msg::JobsInvalid => "File dei job {} non valido: {}",
+        Msg::JobsEmpty => "Il file dei job {} non elenca alcun job",
+        Msg::JobInvalid => "{}, {}: {}",
+        Msg::JobLine => "riga {}",
+        Msg::JobEntry => "job {}",
+        Msg::JobsUnknownColumn => "colonna '{}' sconosciuta (attese {})",
+        Msg::JobsInputColumn => "manca la colonna 'input'",
+        Msg::JobsArray => "atteso un array di job",
+        Msg::JobObject => "{}: atteso un oggetto",
+        Msg::JobUnknownKey => "{}: chiave '{}' sconosciuta (attese {})",
+        Msg::JobStrings => "{}: '{}' deve elencare stringhe",
+        Msg::JobValue => "{}: valore non valido per '{}'",
+        Msg::JobNoInput => "nessun input indicato",
+        Msg::JobOutputName => "il nome di output '{}' contiene un separatore di percorso",
+        Msg::RuleSetting => "impostazione '{}' non valida (atteso CHIAVE=VALORE)",
+        Msg::RuleInput => "'input' non si può impostare con una regola",
+        Msg::RuleUnknown => "impostazione '{}' sconosciuta (attese scales, {})",
+        Msg::LevelsRead => "Impossibile leggere i livelli di qualità: {}",
+        Msg::LevelsInvalid => "Livelli di qualità non validi: {}",
+        Msg::LevelUnknown => {
+            "Livello di qualità [{}] sconosciuto in {} (attesi low, medium, high o lossless)"
+        }
+        Msg::LevelTable => "'{}' in {} deve essere una tabella",
+        Msg::LevelFormat => "Formato '{}' sconosciuto in [{}] di {} (attesi jpg o webp)",
+        Msg::LevelQuality => "La qualità per {} in [{}] di {} deve essere compresa tra 0 e 100",
+        Msg::ListenFailed => "Impossibile mettersi in ascolto su {}",
+        Msg::Listening => "In ascolto su {}",
+        Msg::SocketUnix => {
+            "--socket richiede una piattaforma unix; senza, le richieste si leggono da stdin"
+        }
+        Msg::ClipboardEmpty => "Gli appunti non contengono un'immagine",
+        Msg::ClipboardReadFailed => "Impossibile leggere gli appunti",
+        Msg::ClipboardSize => "L'immagine negli appunti ha una dimensione inattesa",
+        Msg::ClipboardNoOutput => "Nessun output da copiare negli appunti",
+        Msg::ClipboardCopyFailed => "Impossibile copiare l'immagine negli appunti",
+        Msg::ClipboardOpenFailed => "Impossibile aprire gli appunti",
+        Msg::Features => "Funzionalità: {}",
+        Msg::Inputs => "Input",
+        Msg::Outputs => "Output",
+        Msg::NotCompiled => "(non incluso nella build)",
+        Msg::PluginConfigRead => "Impossibile leggere la configurazione dei plugin: {}",
+        Msg::PluginConfigInvalid => "Configurazione dei plugin non valida: {}",
+        Msg::PluginList => "'plugin' deve essere una lista di tabelle [[plugin]]",
+        Msg::PluginPath => "Il plugin {} in {} non ha 'path'",
+        Msg::PluginStage => "Fase del plugin '{}' sconosciuta (attese before-scale o after-scale)",
+        Msg::PluginLoadFailed => "Impossibile caricare il plugin: {}",
+        Msg::PluginOptionsNul => "Le opzioni del plugin contengono un byte NUL",
+        Msg::PluginWasm => {
+            "I plugin WebAssembly richiedono rsimg compilato con la funzionalità 'wasm-plugins'"
+        }
+        Msg::PluginExport => "Export {} mancante",
+        Msg::PluginCode => "Il plugin {} è fallito con codice {}",
+        Msg::PluginFailed => "Il plugin {} è fallito: {}",
+        Msg::FfiCallback => "Nessuna callback di output indicata",
+        Msg::FfiBuffer => "Nessun buffer di input indicato",
+        Msg::FfiFormats => "I formati non sono UTF-8 valido",
+        Msg::PresetConflict => "--preset {} non si può combinare con {}",
+        Msg::ProfileFormat => "--output-profile separa solo output JPEG e TIFF, non {}",
+        Msg::C2paFormat => "--c2pa-sign incorpora manifest solo in output JPEG e PNG, non {}",
+        Msg::PathMissing => "Il percorso '{}' non esiste",
+        Msg::PathInvalid => "Il percorso '{}' non è un file o una cartella valida",
+        Msg::Unsupported => "Il file '{}' non è in un formato immagine supportato",
+        Msg::OpenFailed => "Impossibile aprire l'immagine: {}",
+        Msg::DetectFailed => "Impossibile riconoscere il formato: {}",
+        Msg::Misnamed => "{} contiene dati {}: l'estensione è sbagliata",
+        Msg::DecodeFailed => "Impossibile decodificare l'immagine: {}",
+        Msg::DecodeFormatFailed => "Impossibile decodificare il file {}: {}",
+        Msg::TransformFailed => "Impossibile trasformare l'immagine: {}",
+        Msg::SaveFailed => "Errore durante il salvataggio: {}",
+        Msg::VerifyFailed => "Output non rileggibile: {}",
+        Msg::DuplicateSkipped => "Duplicato di un'immagine non riuscita, saltato: {}",
+        Msg::Cancelled => "Annullato",
+        Msg::OfSource => "{}% dell'originale",
+        Msg::Grid => "Griglia",
+        Msg::PyramidTitle => "Piramide di tasselli",
+        Msg::Pyramid => "Piramide",
+        Msg::Metadata => "Metadati",
+        Msg::Package => "Pacchetto",
+        Msg::Gallery => "Galleria",
+        Msg::AppIconsTitle => "Icone delle app",
+        Msg::AppIcons => "Icone",
+        Msg::Files => "{} file",
+        Msg::Tiles => "{} tasselli",
+        Msg::Original => "originale",
+    }
+}
+
+fn de(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Title => "Bildoptimierer",
+        Msg::TuneTitle => "Qualitätsabstimmung",
+        Msg::Found => "Gefunden:",
+        Msg::Images => "{} Bilder",
+        Msg::Output => "Ausgabe",
+        Msg::Formats => "Formate",
+        Msg::Scales => "Skalierungen",
+        Msg::Pipeline => "Pipeline",
+        Msg::Jobs => "Aufträge",
+        Msg::Breakpoints => "Breakpoints",
+        Msg::Geometries => "Größen",
+        Msg::Quality => "Qualität",
+        Msg::Threads => "{} Threads zum Dekodieren + {} zum Kodieren",
+        Msg::Done => "Verarbeitung erfolgreich abgeschlossen!",
+        Msg::Optimized => "{} Bilder optimiert",
+        Msg::NoImages => "Keine gültigen Bilder gefunden",
+        Msg::Error => "Fehler",
+        Msg::ErrorsHeader => "Fehler bei der Verarbeitung:",
+        Msg::NoneProcessed => "Keines der {} Bilder wurde verarbeitet",
+        Msg::BudgetQuality => {
+            "Die Ausgaben belegen {} bei einem Budget von {}; neuer Versuch mit Qualität {}"
+        }
+        Msg::BudgetScale => {
+            "Die Ausgaben belegen {} bei einem Budget von {}; neuer Versuch ohne die Skalierung {}%"
+        }
+        Msg::BudgetUnreachable => {
+            "Die Ausgaben belegen {} bei niedrigster Qualität und einer Skalierung, über dem Budget von {}"
+        }
+        Msg::LockWaiting => "Warte auf ein anderes rsimg, das in {} schreibt",
+        Msg::ProfileHeader => "Zeit pro Datei in jeder Phase ({} Dateien):",
+        Msg::UpToDate => "rsimg {} ist aktuell",
+        Msg::UpdateAvailable => {
+            "rsimg {} ist verfügbar (dies ist {}); `rsimg self-update` installiert es"
+        }
+        Msg::Updated => "rsimg von {} auf {} aktualisiert",
+        Msg::AlphaSkipped => "{}-Ausgabe des transparenten Bildes übersprungen: {}",
+        Msg::ChannelsReduced => {
+            "Kleinere Ausgaben: {} in Graustufen, {} ohne ungenutzten Alphakanal"
+        }
+        Msg::RepeatsElided => "{} Ausgaben mit bereits geschriebener Größe und Format übersprungen",
+        Msg::CopiedToClipboard => "{} in die Zwischenablage kopiert",
+        Msg::GraphicsRouted => {
+            "{} Bilder sehen nach Screenshots oder Grafiken aus: als verlustfreies WebP und Paletten-PNG gespeichert"
+        }
+        Msg::Classified => "Klassifiziert: {} Fotos, {} Grafiken und {} Textbilder",
+        Msg::PreviewsUsed => "{} Bilder aus ihren eingebetteten Vorschauen verkleinert",
+        Msg::MarkedSkipped => "{} bereits von rsimg optimierte Bilder übersprungen",
+        Msg::LimitReached => "Limit erreicht: {} Bilder bleiben für einen späteren Lauf",
+        Msg::Unselected => "{} Bilder ohne die verlangte Bewertung oder Stichwörter übersprungen",
+        Msg::Bursts => "Von {} Serien jeweils das schärfste Bild behalten, {} Bilder ausgelassen",
+        Msg::Screened => "{} Bilder an --skip-blurry gescheitert und übersprungen:",
+        Msg::Blurry => "unscharf (Schärfe {})",
+        Msg::Underexposed => "unterbelichtet",
+        Msg::Overexposed => "überbelichtet",
+        Msg::DiffCompared => "{} Paare verglichen: {} identisch, {} verändert",
+        Msg::DiffChanged => "{} % der Pixel weichen ab, um bis zu {}; SSIM {}",
+        Msg::DiffResized => "Größe von {}×{} auf {}×{} geändert",
+        Msg::DiffOnlyIn => "{} gibt es nur in {}",
+        Msg::BaselineUpdated => "Referenz aktualisiert: {} Bilder kopiert, {} gelöscht",
+        Msg::MaxChangedRange => "--max-changed muss zwischen 0 und 100 liegen, nicht {}",
+        Msg::MinSsimRange => "--min-ssim muss zwischen 0 und 1 liegen, nicht {}",
+        Msg::WatermarkFound => "{}: Wasserzeichen {} ({} % der Blöcke stimmen überein)",
+        Msg::WatermarkNone => "{}: kein Wasserzeichen gefunden",
+        Msg::WatermarkCarries => "enthält {}",
+        Msg::WatermarkUnknown => "enthält keine der angegebenen IDs",
+        Msg::ResultsClosed => "Keine weiteren Ergebnisse nach {}: der Leser wurde beendet",
+        Msg::ExecInvalid => "--exec-after '{}' ist keine gültige Befehlszeile",
+        Msg::ExecFailed => "--exec-after für {} fehlgeschlagen",
+        Msg::ExecDecodeTmp => "--exec-decode '{}' muss sein PNG nach {tmp_png} schreiben",
+        Msg::ExecDecodeFailed => "--exec-decode für {} fehlgeschlagen",
+        Msg::SpaceShort => {
+            "Die Ausgaben brauchen etwa {} in {}, das Volume hat aber nur {} frei (--no-space-check startet trotzdem)"
+        }
+        Msg::SpaceLow => "Die Ausgaben belegen etwa {} der {} freien für {}",
+        Msg::TmpdirMissing => "--tmpdir '{}' ist kein Verzeichnis",
+        Msg::IconsSquare => "App-Icons brauchen eine quadratische Quelle, {} ist aber {}×{}",
+        Msg::IconsSmall => {
+            "{} ist {}px breit, kleiner als das größte Icon ({}px): die Icons werden vergrößert"
+        }
+        Msg::ProgressTemplate => "Ungültige Fortschrittsvorlage: {}",
+        Msg::ProgressChars => {
+            "Fortschrittszeichen brauchen mindestens zwei Zeichen (gefüllt und leer)"
+        }
+        Msg::Collisions => {
+            "{} Ausgabenamen werden von mehreren Eingaben geteilt (umbenennen oder --on-collision suffix|hash verwenden):"
+        }
+        Msg::StreamCollision => {
+            "{} würde die Ausgaben von {} überschreiben (eine umbenennen oder --stream weglassen, um --on-collision zu nutzen)"
+        }
+        Msg::StreamSeq => {
+            "{seq} nummeriert Eingaben in Pfadreihenfolge, auf die --stream nicht wartet"
+        }
+        Msg::Streaming => "Bilder werden verarbeitet, sobald sie gefunden werden",
+        Msg::SomeFailed => "{} Bilder wurden nicht korrekt verarbeitet",
+        Msg::Mismatch => "{} Bilder weichen von ihrer Referenz ab",
+        Msg::QualityRange => "Die Qualität muss zwischen 0 und 100 liegen",
+        Msg::QualityInvalid => "Die Qualität muss zwischen 0 und 100 liegen ({} ist ungültig)",
+        Msg::TrimRange => "Die Toleranz für --trim muss zwischen 0 und 100 liegen",
+        Msg::DenoiseRange => "Die Stärke der Rauschunterdrückung muss zwischen 1 und 100 liegen",
+        Msg::VignetteRange => "Die Stärke der Vignette muss zwischen 0 und 100 liegen",
+        Msg::FaceModelRequired => "--gravity face erfordert --face-model (Feature onnx)",
+        Msg::ScaleInvalid => "Skalierungen müssen zwischen 10 und 100 liegen ({}% ist ungültig)",
+        Msg::ResizeInvalid => "Ungültiges --resize: {}",
+        Msg::ScalesInvalid => "Ungültiges --scales: {}",
+        Msg::SinceInvalid => "Ungültiger --since-Tag '{}' (erwartet JJJJ-MM-TT)",
+        Msg::BreakpointsRead => "Breakpoints {} konnten nicht gelesen werden: {}",
+        Msg::BreakpointsInvalid => {
+            "Ungültige Breakpoints in {}: {} (erwartet: eine Liste von Breiten)"
+        }
+        Msg::BreakpointsEmpty => "{} muss Breiten über 0 auflisten",
+        Msg::PipelineRead => "Pipeline {} konnte nicht gelesen werden: {}",
+        Msg::PipelineStep => "Ungültiger Pipeline-Schritt '{}': {}",
+        Msg::PipelineNoEncode => "Die Pipeline hat keinen encode(...)-Schritt",
+        Msg::OverlapRange => {
+            "Die Kachelüberlappung muss kleiner als die halbe Kachelgröße sein ({})"
+        }
+        Msg::JobsRead => "Job-Datei konnte nicht gelesen werden: {}",
+        Msg::JobsInvalid => "Ungültige Job-Datei {}: {}",
+        Msg::JobsEmpty => "Job-Datei {} enthält keine Jobs",
+        Msg::JobInvalid => "{}, {}: {}",
+        Msg::JobLine => "Zeile {}",
+        Msg::JobEntry => "Job {}",
+        Msg::JobsUnknownColumn => "unbekannte Spalte '{}' (erwartet: {})",
+        Msg::JobsInputColumn => "Spalte 'input' fehlt",
+        Msg::JobsArray => "erwartet: ein Array von Jobs",
+        Msg::JobObject => "{}: erwartet: ein Objekt",
+        Msg::JobUnknownKey => "{}: unbekannter Schlüssel '{}' (erwartet: {})",
+        Msg::JobStrings => "{}: '{}' muss Zeichenketten auflisten",
+        Msg::JobValue => "{}: ungültiger Wert für '{}'",
+        Msg::JobNoInput => "keine Eingabe angegeben",
+        Msg::JobOutputName => "Ausgabename '{}' enthält ein Pfadtrennzeichen",
+        Msg::RuleSetting => "ungültige Einstellung '{}' (erwartet: SCHLÜSSEL=WERT)",
+        Msg::RuleInput => "'input' kann nicht durch eine Regel gesetzt werden",
+        Msg::RuleUnknown => "unbekannte Einstellung '{}' (erwartet: scales, {})",
+        Msg::LevelsRead => "Qualitätsstufen konnten nicht gelesen werden: {}",
+        Msg::LevelsInvalid => "Ungültige Qualitätsstufen: {}",
+        Msg::LevelUnknown => {
+            "Unbekannte Qualitätsstufe [{}] in {} (erwartet: low, medium, high oder lossless)"
+        }
+        Msg::LevelTable => "'{}' in {} muss eine Tabelle sein",
+        Msg::LevelFormat => "Unbekanntes Format '{}' in [{}] von {} (erwartet: jpg oder webp)",
+        Msg::LevelQuality => "Die Qualität für {} in [{}] von {} muss zwischen 0 und 100 liegen",
+        Msg::ListenFailed => "Lauschen auf {} fehlgeschlagen",
+        Msg::Listening => "Lausche auf {}",
+        Msg::SocketUnix => {
+            "--socket erfordert eine Unix-Plattform; ohne werden Anfragen von stdin gelesen"
+        }
+        Msg::ClipboardEmpty => "Die Zwischenablage enthält kein Bild",
+        Msg::ClipboardReadFailed => "Zwischenablage konnte nicht gelesen werden",
+        Msg::ClipboardSize => "Das Bild in der Zwischenablage hat eine unerwartete Größe",
+        Msg::ClipboardNoOutput => "Keine Ausgabe zum Kopieren in die Zwischenablage",
+        Msg::ClipboardCopyFailed => "Bild konnte nicht in die Zwischenablage kopiert werden",
+        Msg::ClipboardOpenFailed => "Zwischenablage konnte nicht geöffnet werden",
+        Msg::Features => "Features: {}",
+        Msg::Inputs => "Eingaben",
+        Msg::Outputs => "Ausgaben",
+        Msg::NotCompiled => "(nicht einkompiliert)",
+        Msg::PluginConfigRead => "Plugin-Konfiguration konnte nicht gelesen werden: {}",
+        Msg::PluginConfigInvalid => "Ungültige Plugin-Konfiguration: {}",
+        Msg::PluginList => "'plugin' muss eine Liste von [[plugin]]-Tabellen sein",
+        Msg::PluginPath => "Plugin {} in {} hat keinen 'path'",
+        Msg::PluginStage => {
+            "Unbekannte Plugin-Phase '{}' (erwartet: before-scale oder after-scale)"
+        }
+        Msg::PluginLoadFailed => "Plugin konnte nicht geladen werden: {}",
+        Msg::PluginOptionsNul => "Die Plugin-Optionen enthalten ein NUL-Byte",
+        Msg::PluginWasm => "WebAssembly-Plugins erfordern rsimg mit dem Feature 'wasm-plugins'",
+        Msg::PluginExport => "Export {} fehlt",
+        Msg::PluginCode => "Plugin {} ist mit Code {} fehlgeschlagen",
+        Msg::PluginFailed => "Plugin {} ist fehlgeschlagen: {}",
+        Msg::FfiCallback => "Kein Ausgabe-Callback angegeben",
+        Msg::FfiBuffer => "Kein Eingabepuffer angegeben",
+        Msg::FfiFormats => "Die Formate sind kein gültiges UTF-8",
+        Msg::PresetConflict => "--preset {} lässt sich nicht mit {} kombinieren",
+        Msg::ProfileFormat => "--output-profile separiert nur JPEG- und TIFF-Ausgaben, nicht {}",
+        Msg::C2paFormat => {
+            "--c2pa-sign bettet Manifeste nur in JPEG- und PNG-Ausgaben ein, nicht {}"
+        }
+        Msg::PathMissing => "Der Pfad '{}' existiert nicht",
+        Msg::PathInvalid => "Der Pfad '{}' ist weder eine Datei noch ein Verzeichnis",
+        Msg::Unsupported => "Die Datei '{}' hat kein unterstütztes Bildformat",
+        Msg::OpenFailed => "Bild konnte nicht geöffnet werden: {}",
+        Msg::DetectFailed => "Format konnte nicht erkannt werden: {}",
+        Msg::Misnamed => "{} enthält {}-Daten; die Dateiendung ist falsch",
+        Msg::DecodeFailed => "Bild konnte nicht dekodiert werden: {}",
+        Msg::DecodeFormatFailed => "{}-Datei konnte nicht dekodiert werden: {}",
+        Msg::TransformFailed => "Bild konnte nicht bearbeitet werden: {}",
+        Msg::SaveFailed => "Fehler beim Speichern: {}",
+        Msg::VerifyFailed => "Ausgabe nicht lesbar: {}",
+        Msg::DuplicateSkipped => "Duplikat eines fehlgeschlagenen Bildes übersprungen: {}",
+        Msg::Cancelled => "Abgebrochen",
+        Msg::OfSource => "{}% des Originals",
+        Msg::Grid => "Raster",
+        Msg::PyramidTitle => "Kachelpyramide",
+        Msg::Pyramid => "Pyramide",
+        Msg::Metadata => "Metadaten",
+        Msg::Package => "Paket",
+        Msg::Gallery => "Galerie",
+        Msg::AppIconsTitle => "App-Icons",
+        Msg::AppIcons => "App-Icons",
+        Msg::Files => "{} Dateien",
+        Msg::Tiles => "{} Kacheln",
+        Msg::Original => "Original",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_message_has_all_translations() {
+        for &msg in Msg::value_variants() {
+            let english = en(msg);
+            assert!(!english.is_empty(), "{msg:?} is empty in en");
+            for (lang, text) in [("it", it(msg)), ("de", de(msg))] {
+                assert!(!text.is_empty(), "{msg:?} is empty in {lang}");
+                assert_eq!(
+                    text.matches("{}").count(),
+                    english.matches("{}").count(),
+                    "{msg:?} has other placeholders in {lang}"
+                );
+            }
+        }
+    }
+}
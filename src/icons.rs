@@ -0,0 +1,368 @@
+// src/icons.rs
+//
+// `--app-icons`: every icon an app ships, from one square source, with the
+// files that declare them:
+//
+//   ios/AppIcon.appiconset/       Icon-{points}@{scale}x.png + Contents.json,
+//                                 opaque and full bleed (iOS rounds the corners)
+//   android/res/mipmap-{density}/ ic_launcher.png, ic_launcher_round.png and
+//                                 the adaptive ic_launcher_foreground.png
+//   android/res/mipmap-anydpi-v26/ic_launcher.xml (+ _round), pairing the
+//                                 foreground with values/ic_launcher_background.xml
+//   android/play_store_512.png
+//   web/                          favicons, apple-touch-icon.png, the PWA's
+//                                 icon-{size}.png and icon-maskable-{size}.png,
+//                                 and manifest.webmanifest listing them
+//
+// Icons a platform masks to a shape of its own keep the source inside the
+// shape's safe zone: the middle 66 of an adaptive icon's 108dp, the middle
+// 80% of a maskable one. --background fills what can't be transparent.
+
+use anyhow::{Context, Result};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, Rgba, RgbaImage};
+use owo_colors::OwoColorize;
+use rayon::prelude::*;
+use rsimg::decode;
+use rsimg::encode::{EncodeOptions, save_image};
+use rsimg::exit::InvalidConfig;
+use rsimg::i18n::{self, Msg, tr};
+use rsimg::transform::color::Color;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+/// Share of an adaptive icon the source takes: the 66dp safe zone of 108dp
+const ADAPTIVE_SAFE: f32 = 66.0 / 108.0;
+
+/// Share of a maskable icon the source takes: the safe zone's diameter
+const MASKABLE_SAFE: f32 = 0.8;
+
+/// iOS icons as (idiom, points, scales)
+const IOS: [(&str, f32, &[u32]); 9] = [
+    ("iphone", 20.0, &[2, 3]),
+    ("iphone", 29.0, &[2, 3]),
+    ("iphone", 40.0, &[2, 3]),
+    ("iphone", 60.0, &[2, 3]),
+    ("ipad", 20.0, &[1, 2]),
+    ("ipad", 29.0, &[1, 2]),
+    ("ipad", 40.0, &[1, 2]),
+    ("ipad", 76.0, &[1, 2]),
+    ("ipad", 83.5, &[2]),
+];
+
+/// Android densities as (name, pixels per dp)
+const DENSITIES: [(&str, f32); 5] = [
+    ("mdpi", 1.0),
+    ("hdpi", 1.5),
+    ("xhdpi", 2.0),
+    ("xxhdpi", 3.0),
+    ("xxxhdpi", 4.0),
+];
+
+/// Sizes of the PWA's manifest icons, plain and maskable
+const PWA: [u32; 2] = [192, 512];
+
+/// How an icon is drawn from the source
+#[derive(Clone, Copy)]
+enum Style {
+    /// Scaled, transparency kept
+    Plain,
+    /// Scaled onto the background, without an alpha channel
+    Opaque,
+    /// Cut to a circle
+    Round,
+    /// Scaled to a share of a transparent canvas, centered
+    Inset(f32),
+    /// Scaled to a share of a canvas filled with the background, centered
+    Padded(f32),
+}
+
+/// One icon of the set
+struct Icon {
+    /// Path below the output directory
+    path: String,
+    size: u32,
+    style: Style,
+}
+
+impl Icon {
+    fn new(path: impl Into<String>, size: u32, style: Style) -> Self {
+        Self {
+            path: path.into(),
+            size,
+            style,
+        }
+    }
+}
+
+/// Writes the icon sets of `input` to `output` (default: `{stem}_icons`
+/// next to it)
+pub fn run(input: &Path, output: Option<&Path>, background: Color) -> Result<()> {
+    let source = decode::load_image(input)?.into_rgba8();
+    let (width, height) = source.dimensions();
+    if width != height {
+        return Err(InvalidConfig(tr!(Msg::IconsSquare, input.display(), width, height)).into());
+    }
+    let dir = match output {
+        Some(dir) => dir.to_path_buf(),
+        None => default_dir(input)?,
+    };
+
+    println!(
+        "{}",
+        format!("\n=== RSIMG — {} ===\n", i18n::text(Msg::AppIconsTitle))
+            .bold()
+            .cyan()
+    );
+    let icons = icons();
+    let largest = icons.iter().map(|icon| icon.size).max().unwrap_or(0);
+    if width < largest {
+        eprintln!(
+            "  {} {}",
+            "⚠".yellow(),
+            tr!(Msg::IconsSmall, input.display(), width, largest)
+        );
+    }
+
+    icons.par_iter().try_for_each(|icon| {
+        let path = dir.join(&icon.path);
+        if let Some(parent) = path.parent() {
+            create_dir(parent)?;
+        }
+        let img = draw(&source, icon, background);
+        save_image(&img, &path, "png", &EncodeOptions::default())
+            .with_context(|| format!("Failed to save icon: {}", path.display()))
+    })?;
+
+    let declarations = [
+        ("ios/AppIcon.appiconset/Contents.json", contents_json()),
+        (
+            "android/res/mipmap-anydpi-v26/ic_launcher.xml",
+            ADAPTIVE_XML.to_string(),
+        ),
+        (
+            "android/res/mipmap-anydpi-v26/ic_launcher_round.xml",
+            ADAPTIVE_XML.to_string(),
+        ),
+        (
+            "android/res/values/ic_launcher_background.xml",
+            background_xml(background),
+        ),
+        ("web/manifest.webmanifest", manifest()),
+    ];
+    for (name, contents) in &declarations {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            create_dir(parent)?;
+        }
+        write(&path, contents)?;
+    }
+
+    println!(
+        "  {} {}: {} ({})\n",
+        "📱".bright_white(),
+        i18n::text(Msg::AppIcons),
+        dir.display().to_string().bright_yellow(),
+        tr!(Msg::Files, icons.len() + declarations.len()).bright_cyan()
+    );
+    Ok(())
+}
+
+/// Every icon of the three platforms
+fn icons() -> Vec<Icon> {
+    let mut icons = Vec::new();
+    for (_, points, scales) in IOS {
+        for &scale in scales {
+            let path = format!("ios/AppIcon.appiconset/{}", ios_name(points, scale));
+            // iPhone and iPad share the sizes they have in common
+            if icons.iter().all(|icon: &Icon| icon.path != path) {
+                icons.push(Icon::new(
+                    path,
+                    (points * scale as f32) as u32,
+                    Style::Opaque,
+                ));
+            }
+        }
+    }
+    icons.push(Icon::new(
+        format!("ios/AppIcon.appiconset/{}", ios_name(1024.0, 1)),
+        1024,
+        Style::Opaque,
+    ));
+
+    for (density, scale) in DENSITIES {
+        let mipmap = format!("android/res/mipmap-{density}");
+        let legacy = (48.0 * scale) as u32;
+        icons.push(Icon::new(
+            format!("{mipmap}/ic_launcher.png"),
+            legacy,
+            Style::Plain,
+        ));
+        icons.push(Icon::new(
+            format!("{mipmap}/ic_launcher_round.png"),
+            legacy,
+            Style::Round,
+        ));
+        icons.push(Icon::new(
+            format!("{mipmap}/ic_launcher_foreground.png"),
+            (108.0 * scale) as u32,
+            Style::Inset(ADAPTIVE_SAFE),
+        ));
+    }
+    icons.push(Icon::new("android/play_store_512.png", 512, Style::Plain));
+
+    for size in [16, 32] {
+        icons.push(Icon::new(
+            format!("web/favicon-{size}.png"),
+            size,
+            Style::Plain,
+        ));
+    }
+    icons.push(Icon::new("web/apple-touch-icon.png", 180, Style::Opaque));
+    for size in PWA {
+        icons.push(Icon::new(
+            format!("web/icon-{size}.png"),
+            size,
+            Style::Plain,
+        ));
+        icons.push(Icon::new(
+            format!("web/icon-maskable-{size}.png"),
+            size,
+            Style::Padded(MASKABLE_SAFE),
+        ));
+    }
+    icons
+}
+
+/// Draws `icon` from the square `source`
+fn draw(source: &RgbaImage, icon: &Icon, background: Color) -> DynamicImage {
+    let size = icon.size;
+    let scaled = |edge: u32| imageops::resize(source, edge, edge, FilterType::Lanczos3);
+    let centered = |share: f32, fill: Rgba<u8>| {
+        let edge = ((size as f32 * share).round() as u32).max(1);
+        let mut canvas = RgbaImage::from_pixel(size, size, fill);
+        let offset = i64::from((size - edge) / 2);
+        imageops::overlay(&mut canvas, &scaled(edge), offset, offset);
+        canvas
+    };
+    let transparent = Rgba([0, 0, 0, 0]);
+
+    match icon.style {
+        Style::Plain => DynamicImage::ImageRgba8(scaled(size)),
+        // The App Store rejects icons with transparency
+        Style::Opaque => {
+            let fill = match background.is_opaque() {
+                true => background,
+                false => Color::WHITE,
+            };
+            DynamicImage::ImageRgba8(centered(1.0, fill.0))
+                .to_rgb8()
+                .into()
+        }
+        Style::Round => {
+            let mut img = scaled(size);
+            let radius = size as f32 / 2.0;
+            for (x, y, pixel) in img.enumerate_pixels_mut() {
+                let (dx, dy) = (x as f32 + 0.5 - radius, y as f32 + 0.5 - radius);
+                // One pixel of antialiasing along the edge
+                let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+                pixel[3] = (f32::from(pixel[3]) * coverage).round() as u8;
+            }
+            DynamicImage::ImageRgba8(img)
+        }
+        Style::Inset(share) => DynamicImage::ImageRgba8(centered(share, transparent)),
+        Style::Padded(share) => DynamicImage::ImageRgba8(centered(share, background.0)),
+    }
+}
+
+/// `Icon-60@2x.png`, `Icon-83.5@2x.png`
+fn ios_name(points: f32, scale: u32) -> String {
+    format!("Icon-{points}@{scale}x.png")
+}
+
+/// The asset catalog entry of the iOS icons
+fn contents_json() -> String {
+    let mut images: Vec<_> = IOS
+        .iter()
+        .flat_map(|&(idiom, points, scales)| {
+            scales.iter().map(move |&scale| {
+                json!({
+                    "filename": ios_name(points, scale),
+                    "idiom": idiom,
+                    "scale": format!("{scale}x"),
+                    "size": format!("{points}x{points}"),
+                })
+            })
+        })
+        .collect();
+    images.push(json!({
+        "filename": ios_name(1024.0, 1),
+        "idiom": "ios-marketing",
+        "scale": "1x",
+        "size": "1024x1024",
+    }));
+    let contents = json!({
+        "images": images,
+        "info": { "author": "rsimg", "version": 1 },
+    });
+    format!("{contents:#}\n")
+}
+
+/// The `icons` member of a web app manifest
+fn manifest() -> String {
+    let icons: Vec<_> = PWA
+        .iter()
+        .flat_map(|size| {
+            [("icon", "any"), ("icon-maskable", "maskable")].map(|(name, purpose)| {
+                json!({
+                    "src": format!("{name}-{size}.png"),
+                    "sizes": format!("{size}x{size}"),
+                    "type": "image/png",
+                    "purpose": purpose,
+                })
+            })
+        })
+        .collect();
+    format!("{:#}\n", json!({ "icons": icons }))
+}
+
+/// Adaptive icon pairing the foreground with the background color
+const ADAPTIVE_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<adaptive-icon xmlns:android="http://schemas.android.com/apk/res/android">
+    <background android:drawable="@color/ic_launcher_background"/>
+    <foreground android:drawable="@mipmap/ic_launcher_foreground"/>
+</adaptive-icon>
+"#;
+
+/// The adaptive icons' background color resource, `#AARRGGBB` when it
+/// isn't opaque
+fn background_xml(background: Color) -> String {
+    let Rgba([r, g, b, a]) = background.0;
+    let color = match background.is_opaque() {
+        true => format!("#{r:02X}{g:02X}{b:02X}"),
+        false => format!("#{a:02X}{r:02X}{g:02X}{b:02X}"),
+    };
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>\n    \
+         <color name=\"ic_launcher_background\">{color}</color>\n</resources>\n"
+    )
+}
+
+/// `logo.png` → `logo_icons` in the same directory
+fn default_dir(input: &Path) -> Result<PathBuf> {
+    let stem = input
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Invalid filename: {}", input.display()))?;
+    Ok(input.with_file_name(format!("{stem}_icons")))
+}
+
+fn create_dir(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create directory: {}", dir.display()))
+}
+
+fn write(path: &Path, contents: &str) -> Result<()> {
+    std::fs::write(path, contents)
+        .with_context(|| format!("Failed to write file: {}", path.display()))
+}
@@ -0,0 +1,296 @@
+// src/jobs.rs
+//
+// `--jobs FILE`: per-image crops, sizes, formats and names for one batch,
+// as CSV with a header row or as a JSON array of objects with the same keys:
+//
+//   input,crop,sizes,formats,quality,output
+//   hero.jpg,"0,120,1920x800",1920x;960x,webp;jpg,82,hero-{width}
+//   team.png,,50%,png,,
+//
+// Only `input` is required; it's relative to the jobs file. `sizes` and
+// `formats` are lists separated by `;`, `,` or spaces (arrays in JSON) and
+// default to `--scales` and `--formats`. `output` names the files of a row
+// without their extension, using `{stem}`, `{scale}`, `{width}` and
+// `{height}`; rows sharing a source need different names.
+//
+// `--if-portrait` and `--if-landscape` build the same rows from `KEY=VALUE`
+// settings (the columns above, plus `scales` for percentages), one job per
+// input picked by its upright orientation, so a mixed gallery gets
+// different sizes or crops per orientation in one pass. `--if-photo`,
+// `--if-graphic` and `--if-text` do the same by what the input shows.
+
+use crate::decode;
+use crate::encode::{Classifier, Content};
+use crate::exit::InvalidConfig;
+use crate::i18n::{Msg, tr};
+use crate::processor::Job;
+use crate::transform::script::Script;
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+const COLUMNS: [&str; 6] = ["input", "crop", "sizes", "formats", "quality", "output"];
+
+/// One row as written, before validation
+#[derive(Clone, Default)]
+struct Row {
+    input: String,
+    crop: String,
+    sizes: String,
+    formats: String,
+    quality: String,
+    output: String,
+}
+
+/// Reads a jobs file; `.json` files are JSON, anything else CSV
+pub fn load(path: &Path, formats: &[String], scales: &[u32]) -> Result<Vec<Job>> {
+    let text = std::fs::read_to_string(path).with_context(|| tr!(Msg::JobsRead, path.display()))?;
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let rows = if is_json {
+        parse_json(&text)
+    } else {
+        parse_csv(&text)
+    }
+    .map_err(|e| InvalidConfig(tr!(Msg::JobsInvalid, path.display(), e)))?;
+
+    if rows.is_empty() {
+        return Err(InvalidConfig(tr!(Msg::JobsEmpty, path.display())).into());
+    }
+
+    let base = path.parent().unwrap_or(Path::new("."));
+    let default_sizes: Vec<String> = scales.iter().map(|s| format!("{s}%")).collect();
+    rows.into_iter()
+        .map(|(label, row)| {
+            let input = match row.input.as_str() {
+                "" => Err(tr!(Msg::JobNoInput)),
+                input => Ok(base.join(input)),
+            };
+            input
+                .and_then(|input| job(row, input, formats, &default_sizes))
+                .map_err(|e| InvalidConfig(tr!(Msg::JobInvalid, path.display(), label, e)).into())
+        })
+        .collect()
+}
+
+/// Settings for the inputs of one orientation (`--if-portrait`,
+/// `--if-landscape`) or class (`--if-photo`, `--if-graphic`, `--if-text`)
+pub struct Rule(Row);
+
+impl Rule {
+    /// Parses `KEY=VALUE` settings; values may hold several, separated by
+    /// whitespace (`"scales=60,30 formats=webp"`)
+    pub fn parse(settings: &[String]) -> Result<Self, String> {
+        let mut row = Row::default();
+        for setting in settings.iter().flat_map(|s| s.split_whitespace()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| tr!(Msg::RuleSetting, setting))?;
+            match key {
+                "scales" => {
+                    let scales = list(value)
+                        .into_iter()
+                        .map(|scale| match scale.parse::<u32>() {
+                            Ok(s @ 10..=100) => Ok(format!("{s}%")),
+                            _ => Err(tr!(Msg::ScaleInvalid, scale)),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    row.sizes = scales.join(";");
+                }
+                "input" => return Err(tr!(Msg::RuleInput)),
+                key if COLUMNS.contains(&key) => *field(&mut row, key) = value.to_string(),
+                _ => {
+                    return Err(tr!(Msg::RuleUnknown, key, COLUMNS[1..].join(", ")));
+                }
+            }
+        }
+        // Checked once here rather than for every input it applies to
+        job(row.clone(), PathBuf::new(), &[], &["100%".into()])?;
+        Ok(Self(row))
+    }
+}
+
+/// One job per input, with the settings of the rule matching its upright
+/// orientation; square inputs, and those without a rule, get the batch's
+pub fn by_orientation(
+    files: Vec<PathBuf>,
+    portrait: Option<&Rule>,
+    landscape: Option<&Rule>,
+    formats: &[String],
+    scales: &[u32],
+) -> Result<Vec<Job>> {
+    let default_sizes: Vec<String> = scales.iter().map(|s| format!("{s}%")).collect();
+    files
+        .into_par_iter()
+        .map(|input| {
+            // Unreadable inputs fail when they're decoded, with the usual error
+            let rule = match decode::load_dimensions(&input) {
+                Ok((width, height)) if height > width => portrait,
+                Ok((width, height)) if width > height => landscape,
+                _ => None,
+            };
+            let row = rule.map(|rule| rule.0.clone()).unwrap_or_default();
+            job(row, input, formats, &default_sizes).map_err(|e| InvalidConfig(e).into())
+        })
+        .collect()
+}
+
+/// One job per input, with the settings of the rule for what it shows;
+/// inputs without a rule get the batch's. The class is kept on the job so
+/// inputs aren't classified twice
+pub fn by_content(
+    files: Vec<PathBuf>,
+    rules: [Option<&Rule>; 3],
+    classifier: &Classifier,
+    formats: &[String],
+    scales: &[u32],
+) -> Result<Vec<Job>> {
+    let default_sizes: Vec<String> = scales.iter().map(|s| format!("{s}%")).collect();
+    files
+        .into_par_iter()
+        .map(|input| {
+            // Unreadable inputs fail when they're decoded, with the usual error
+            let content = match decode::load_image(&input) {
+                Ok(img) => Some(classifier.classify(&img)?),
+                Err(_) => None,
+            };
+            let rule = content.and_then(|content| {
+                let class = Content::ALL.iter().position(|&c| c == content)?;
+                rules[class]
+            });
+            let row = rule.map(|rule| rule.0.clone()).unwrap_or_default();
+            let mut job = job(row, input, formats, &default_sizes).map_err(InvalidConfig)?;
+            job.content = content;
+            Ok(job)
+        })
+        .collect()
+}
+
+fn job(
+    row: Row,
+    input: PathBuf,
+    formats: &[String],
+    default_sizes: &[String],
+) -> Result<Job, String> {
+    let crop = match row.crop.as_str() {
+        "" => None,
+        crop => Some(crop.parse()?),
+    };
+    let quality = match row.quality.as_str() {
+        "" => None,
+        q => Some(
+            q.parse::<u8>()
+                .ok()
+                .filter(|q| *q <= 100)
+                .ok_or_else(|| tr!(Msg::QualityInvalid, q))?,
+        ),
+    };
+    if row.output.contains(['/', '\\']) {
+        return Err(tr!(Msg::JobOutputName, row.output));
+    }
+
+    let sizes = list(&row.sizes);
+    let sizes = if sizes.is_empty() {
+        default_sizes.iter().map(String::as_str).collect()
+    } else {
+        sizes
+    };
+    let row_formats: Vec<String> = list(&row.formats).into_iter().map(String::from).collect();
+    let formats = if row_formats.is_empty() {
+        formats
+    } else {
+        &row_formats
+    };
+
+    Ok(Job {
+        input,
+        script: Script::variants(crop, &sizes, formats, quality)?,
+        output: Some(row.output).filter(|name| !name.is_empty()),
+        content: None,
+    })
+}
+
+/// Splits a `sizes` or `formats` cell
+fn list(cell: &str) -> Vec<&str> {
+    cell.split([';', ',', ' '])
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn parse_csv(text: &str) -> Result<Vec<(String, Row)>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    if let Some(unknown) = headers.iter().find(|h| !COLUMNS.contains(h)) {
+        return Err(tr!(Msg::JobsUnknownColumn, unknown, COLUMNS.join(", ")));
+    }
+    if !headers.iter().any(|h| h == "input") {
+        return Err(tr!(Msg::JobsInputColumn));
+    }
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let line = record.position().map_or(0, csv::Position::line);
+        let mut row = Row::default();
+        for (header, value) in headers.iter().zip(record.iter()) {
+            *field(&mut row, header) = value.to_string();
+        }
+        rows.push((tr!(Msg::JobLine, line), row));
+    }
+    Ok(rows)
+}
+
+fn parse_json(text: &str) -> Result<Vec<(String, Row)>, String> {
+    let value: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let Value::Array(entries) = value else {
+        return Err(tr!(Msg::JobsArray));
+    };
+
+    let mut rows = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let label = tr!(Msg::JobEntry, i + 1);
+        let Value::Object(entry) = entry else {
+            return Err(tr!(Msg::JobObject, label));
+        };
+        let mut row = Row::default();
+        for (key, value) in entry {
+            if !COLUMNS.contains(&key.as_str()) {
+                return Err(tr!(Msg::JobUnknownKey, label, key, COLUMNS.join(", ")));
+            }
+            *field(&mut row, key) = match value {
+                Value::Null => String::new(),
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                Value::Array(items) => items
+                    .iter()
+                    .map(|item| match item {
+                        Value::String(s) => Ok(s.clone()),
+                        _ => Err(tr!(Msg::JobStrings, label, key)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(";"),
+                _ => return Err(tr!(Msg::JobValue, label, key)),
+            };
+        }
+        rows.push((label, row));
+    }
+    Ok(rows)
+}
+
+/// The row cell for a known column name
+fn field<'a>(row: &'a mut Row, column: &str) -> &'a mut String {
+    match column {
+        "input" => &mut row.input,
+        "crop" => &mut row.crop,
+        "sizes" => &mut row.sizes,
+        "formats" => &mut row.formats,
+        "quality" => &mut row.quality,
+        _ => &mut row.output,
+    }
+}
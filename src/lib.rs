@@ -0,0 +1,55 @@
+// src/lib.rs
+//
+// The rsimg library: decoding, transforms and encoders, plus the in-memory
+// `pipeline` built from them. Batch processing over files and thread pools
+// (`processor`, with its progress reporting and duplicate detection) needs
+// the default `native` feature; without it (and without `webp`, which
+// builds libwebp from C) the crate compiles for wasm32 and works on byte
+// slices only. `ffi` exposes the pipeline to C.
+
+#[cfg(feature = "native")]
+pub mod budget;
+#[cfg(feature = "native")]
+pub mod burst;
+#[cfg(feature = "native")]
+pub mod collision;
+pub mod decode;
+#[cfg(feature = "native")]
+pub mod dedupe;
+pub mod encode;
+pub mod exit;
+pub mod ffi;
+#[cfg(feature = "native")]
+pub mod hook;
+pub mod i18n;
+#[cfg(feature = "native")]
+pub mod jobs;
+#[cfg(feature = "native")]
+pub mod lock;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod paths;
+pub mod pipeline;
+#[cfg(feature = "native")]
+pub mod plugin;
+#[cfg(feature = "native")]
+pub mod preset;
+#[cfg(feature = "native")]
+pub mod processor;
+#[cfg(feature = "native")]
+pub mod profile;
+#[cfg(feature = "native")]
+pub mod progress;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "native")]
+pub mod results;
+#[cfg(feature = "native")]
+pub mod scratch;
+#[cfg(feature = "native")]
+pub mod screen;
+#[cfg(feature = "native")]
+pub mod select;
+#[cfg(feature = "native")]
+pub mod space;
+pub mod transform;
@@ -0,0 +1,157 @@
+// src/lock.rs
+//
+// Advisory locks keeping rsimg processes that write into the same output
+// directory (CI matrix jobs, overlapping cron runs) from interleaving their
+// outputs. A batch holds an exclusive lock on a `.rsimg.lock` file in every
+// directory it writes to; another batch touching any of them waits until
+// the first finishes.
+//
+// A batch removes its lock files when it finishes. A process may already
+// have opened one to wait on it, so the releasing batch unlinks the file
+// and then writes a byte into it while still holding the lock: a waiter
+// that gets the lock on a non-empty file knows it is gone and starts over
+// on a fresh one. A lock file that can't be removed is left empty and is
+// simply reused.
+//
+// The locks are advisory and only bind rsimg itself. Filesystems without
+// locking support (some network mounts) are written to unlocked.
+
+use crate::i18n::{Msg, tr};
+use crate::paths::long_path;
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the lock file in each output directory
+pub const LOCK_FILE: &str = ".rsimg.lock";
+
+/// Locks held until dropped
+pub struct OutputLock {
+    _files: Vec<DirLock>,
+}
+
+/// The lock on one directory, removing its lock file when released
+struct DirLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        // The lock itself is released when `file` closes, after this
+        if fs::remove_file(long_path(&self.path)).is_ok() {
+            let _ = self.file.write_all(b"x");
+        }
+    }
+}
+
+impl OutputLock {
+    /// Locks every directory in `dirs`, waiting for other rsimg processes
+    /// holding any of them
+    pub fn acquire<'a>(dirs: impl IntoIterator<Item = &'a Path>) -> Result<Self> {
+        // A fixed order keeps two batches from each waiting on the other
+        let dirs: BTreeSet<&Path> = dirs.into_iter().collect();
+        let mut files = Vec::with_capacity(dirs.len());
+        for dir in dirs {
+            files.extend(lock_dir(dir)?);
+        }
+        Ok(Self { _files: files })
+    }
+}
+
+/// Locks one directory, or returns `None` when its filesystem can't lock
+fn lock_dir(dir: &Path) -> Result<Option<DirLock>> {
+    let path = dir.join(LOCK_FILE);
+    let mut waited = false;
+    loop {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(long_path(&path))
+            .with_context(|| format!("Failed to open lock file: {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                if !waited {
+                    eprintln!("  {}", tr!(Msg::LockWaiting, dir.display()));
+                    waited = true;
+                }
+                file.lock()
+                    .with_context(|| format!("Failed to lock {}", path.display()))?;
+            }
+            Err(TryLockError::Error(err)) if err.kind() == ErrorKind::Unsupported => {
+                return Ok(None);
+            }
+            Err(TryLockError::Error(err)) => {
+                return Err(err).with_context(|| format!("Failed to lock {}", path.display()));
+            }
+        }
+
+        // A non-empty file was removed by the batch that held it
+        let retired = file
+            .metadata()
+            .with_context(|| format!("Failed to lock {}", path.display()))?
+            .len()
+            > 0;
+        if !retired {
+            return Ok(Some(DirLock { file, path }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp dir
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rsimg-lock-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn lock_file_is_removed_on_release() {
+        let dir = scratch("release");
+        let lock = OutputLock::acquire([dir.as_path()]).unwrap();
+        assert!(dir.join(LOCK_FILE).exists());
+        drop(lock);
+        assert!(!dir.join(LOCK_FILE).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn waiters_on_a_removed_file_start_over() {
+        let dir = scratch("waiter");
+        let lock = OutputLock::acquire([dir.as_path()]).unwrap();
+        let stale = File::open(dir.join(LOCK_FILE)).unwrap();
+
+        let waiter = {
+            let dir = dir.clone();
+            std::thread::spawn(move || {
+                let lock = OutputLock::acquire([dir.as_path()]).unwrap();
+                let held = dir.join(LOCK_FILE);
+                (
+                    held.exists(),
+                    fs::metadata(held).map(|m| m.len()).ok(),
+                    lock,
+                )
+            })
+        };
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        drop(lock);
+
+        // The released file was marked, and the waiter locked a fresh one
+        assert_eq!(stale.metadata().unwrap().len(), 1);
+        let (exists, len, lock) = waiter.join().unwrap();
+        assert!(exists);
+        assert_eq!(len, Some(0));
+        drop(lock);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
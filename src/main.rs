@@ -3,14 +3,73 @@
 // Main entry point for RSIMG — a Rust-powered parallel image optimizer.
 // Handles argument parsing, validation, and orchestrates image processing.
 
-mod processor;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+mod completions;
+mod detect;
+mod diff;
+mod font;
+mod formats;
+mod gallery;
+#[cfg(feature = "history")]
+mod history;
+#[cfg(feature = "http")]
+mod http;
+mod icons;
+mod meta;
+mod package;
+mod pyramid;
+mod serve;
+#[cfg(feature = "otlp")]
+mod telemetry;
+mod tune;
+#[cfg(feature = "self-update")]
+mod update;
 
 use anyhow::{Context, Result};
-use clap::Parser;
-use indicatif::MultiProgress;
+use clap::builder::StringValueParser;
+use clap::{CommandFactory, Parser, Subcommand};
+use completions::Suggest;
 use owo_colors::OwoColorize;
+use rsimg::budget;
+use rsimg::burst;
+use rsimg::collision::OnCollision;
+use rsimg::decode::{self, Frame};
+use rsimg::encode::{
+    AlphaMode, Channels, Classifier, Dither, EncodeOptions, Intent, OutputProfile, PngBitDepth,
+    PngColorType, Quality, QualityPreset, parse_alpha_arg,
+};
+#[cfg(feature = "c2pa")]
+use rsimg::encode::{Provenance, Signer};
+use rsimg::exit::{Exit, InvalidConfig, NoInputs};
+use rsimg::hook::{ExecAfter, ExecDecode};
+use rsimg::i18n::{self, Lang, Msg, tr};
+use rsimg::jobs::{self, Rule};
+use rsimg::paths;
+use rsimg::plugin;
+use rsimg::preset::{self, Preset};
+use rsimg::processor::{self, Inputs, Limits, Order, Passthrough, ProcessOptions, ScanOptions};
+use rsimg::profile::{Profile, ProfileLayer};
+use rsimg::progress::{BarStyle, Progress, Theme};
+use rsimg::results::ResultsPipe;
+use rsimg::screen::Screening;
+use rsimg::select::Selection;
+use rsimg::space;
+use rsimg::transform::Transforms;
+use rsimg::transform::background::BackgroundRemoval;
+use rsimg::transform::color::Color;
+use rsimg::transform::face::FaceDetector;
+use rsimg::transform::geometry::{self, AspectRatio, Gravity, Rect, ResizeMode, RotateEdges};
+use rsimg::transform::lut::Lut3d;
+use rsimg::transform::script::Script;
+use rsimg::transform::simulate::Deficiency;
+use rsimg::transform::tonemap::Tonemap;
+use rsimg::transform::watermark::Watermark;
 use std::path::PathBuf;
-use walkdir::WalkDir;
+use std::process::ExitCode;
+use std::sync::Arc;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 
 // CLI arguments structure using clap
 #[derive(Parser)]
@@ -24,67 +83,1160 @@ use walkdir::WalkDir;
                   rsimg photo.jpg\n    \
                   rsimg ./photos --output ./optimized --recursive\n    \
                   rsimg ./images --formats webp,jpg --scales 100,75,50 --quality 85\n    \
-                  rsimg ./gallery --threads 4 -r\n\n\
-                  For more information, visit: https://github.com/yourusername/rsimg"
+                  rsimg ./gallery --threads 4 -r\n    \
+                  rsimg tune photo.jpg --qualities 60,70,80,90\n    \
+                  rsimg serve --socket /tmp/rsimg.sock\n\n\
+                  QUALITY LEVELS (--quality, as jpg / webp):\n    \
+                  low 60 / 50, medium 75 / 70, high 88 / 85, lossless 100 / lossless WebP\n\n\
+                  EXIT CODES:\n    \
+                  0 success, 1 error, 2 some images failed, 3 no inputs found, 4 invalid options\n\n\
+                  For more information, visit: https://github.com/yourusername/rsimg",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// File or folder to process
     #[arg(value_name = "INPUT", help = "Input file or directory")]
-    input: PathBuf,
+    #[cfg_attr(
+        feature = "clipboard",
+        arg(required_unless_present_any = ["jobs", "from_clipboard"])
+    )]
+    #[cfg_attr(not(feature = "clipboard"), arg(required_unless_present = "jobs"))]
+    input: Option<PathBuf>,
+
+    /// Process the image on the clipboard instead of INPUT; outputs are
+    /// named `clipboard_*` and written to --output, the current directory,
+    /// or nowhere with --to-clipboard
+    #[cfg(feature = "clipboard")]
+    #[arg(
+        long,
+        conflicts_with_all = ["input", "jobs", "recursive"],
+        help = "Optimize the image on the clipboard"
+    )]
+    from_clipboard: bool,
+
+    /// Copy the largest output back to the clipboard, as pixels
+    #[cfg(feature = "clipboard")]
+    #[arg(
+        long,
+        conflicts_with = "total_budget",
+        help = "Copy the largest output to the clipboard"
+    )]
+    to_clipboard: bool,
+
+    /// Output formats (comma-separated: jpg,webp,png,apng,gif,dds,ktx2,ppm,pgm,ff,tga,tiff)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = vec!["jpg".to_string(), "webp".to_string()],
+        value_parser = formats::output_formats(),
+        ignore_case = true,
+        hide_possible_values = true,
+        value_name = "FORMATS",
+        help = "Output image formats"
+    )]
+    formats: Vec<String>,
+
+    /// Scale percentages (comma-separated: 100,75,50,25)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = vec![75, 50, 25],
+        value_name = "SCALES",
+        help = "Image scale percentages (10-100)"
+    )]
+    scales: Vec<u32>,
+
+    /// Convert to linear light before scaling and back to sRGB after, which
+    /// keeps fine bright detail on dark backgrounds from dimming
+    #[arg(long, help = "Resize in linear light instead of on sRGB values")]
+    linear_resize: bool,
+
+    /// Compression quality (0-100, higher is better) or a named level with
+    /// its own setting per format, listed at the end of --help
+    #[arg(
+        long,
+        default_value = "80",
+        value_parser = Suggest::new(
+            rsimg::encode::parse_quality_arg,
+            &["low", "medium", "high", "lossless"],
+        ),
+        hide_possible_values = true,
+        value_name = "QUALITY",
+        help = "JPEG/WebP quality: 0-100 or low, medium, high, lossless"
+    )]
+    quality: Quality,
+
+    /// TOML file overriding the per-format settings of named quality levels
+    #[arg(
+        long,
+        env = "RSIMG_QUALITY_LEVELS",
+        value_name = "FILE",
+        help = "Override the settings of --quality levels"
+    )]
+    quality_levels: Option<PathBuf>,
+
+    /// Named setup: `screenshot` sends text-heavy and flat-color images to
+    /// lossless WebP, and to palette PNG instead of JPEG, keeping photos as
+    /// asked; `social` writes every input at the sizes of Open Graph and
+    /// Twitter/X link previews, Instagram square and portrait posts and
+    /// YouTube thumbnails instead of --scales, named `{stem}_{label}` and
+    /// brought to each shape as --resize-mode says
+    #[arg(
+        long,
+        value_enum,
+        value_name = "PRESET",
+        help = "Settings for a kind of image or destination: screenshot, social"
+    )]
+    preset: Option<Preset>,
+
+    /// Label every input photo, graphic or text, from its colors and the
+    /// steps between neighbouring pixels (or with --classify-model); the
+    /// labels are counted after the batch and sent as `classified` events
+    /// with --progress-json
+    #[arg(long, help = "Report whether inputs are photos, graphics or text")]
+    classify: bool,
+
+    /// Cap on the size of all outputs together: the batch is redone at
+    /// lower qualities, then without its largest scales, until it fits
+    #[arg(
+        long,
+        value_parser = budget::parse_size_arg,
+        value_name = "SIZE",
+        conflicts_with_all = ["pipeline", "jobs", "breakpoints"],
+        help = "Keep the total size of all outputs under SIZE (e.g. 50MB)"
+    )]
+    total_budget: Option<u64>,
+
+    /// Palette size for indexed PNG/GIF output (2-256)
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u16).range(2..=256),
+        value_name = "N",
+        help = "Reduce PNG/GIF outputs to an N-color palette"
+    )]
+    colors: Option<u16>,
+
+    /// Dithering used when reducing to a palette
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Dither::FloydSteinberg,
+        value_name = "MODE",
+        help = "Palette dithering (none, ordered, floyd-steinberg)"
+    )]
+    dither: Dither,
+
+    /// Generate mipmap chains for DDS/KTX2 outputs
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Include mipmaps in DDS/KTX2 texture outputs"
+    )]
+    mipmaps: bool,
+
+    /// Channel layout for JPEG/PNG outputs; `gray` keeps scans single-channel
+    #[arg(
+        long,
+        value_enum,
+        value_name = "LAYOUT",
+        help = "Encode JPEG/PNG outputs as gray or rgb"
+    )]
+    channels: Option<Channels>,
+
+    /// What transparent sources get in JPEG, PPM and PGM outputs:
+    /// `flatten:COLOR` composites onto a color (white by default), `matte`
+    /// onto the image's average visible color, `skip` leaves those outputs out
+    #[arg(
+        long,
+        value_parser = Suggest::new(parse_alpha_arg, &["flatten", "skip", "matte"]),
+        default_value = "flatten",
+        value_name = "MODE",
+        hide_possible_values = true,
+        help = "Transparency in JPEG outputs: flatten[:COLOR], skip or matte"
+    )]
+    alpha: AlphaMode,
+
+    /// PNG color type; `auto` drops unused alpha, detects gray and few-color
+    /// images, and never loses information
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = PngColorType::Auto,
+        value_name = "TYPE",
+        help = "PNG color type: auto, rgb, rgba, gray, palette"
+    )]
+    png_color_type: PngColorType,
+
+    /// PNG sample depth (default: 16 only when the pixels need it)
+    #[arg(long, value_enum, value_name = "BITS", help = "PNG bit depth: 8 or 16")]
+    png_bit_depth: Option<PngBitDepth>,
+
+    /// Process subdirectories recursively
+    #[arg(
+        short,
+        long,
+        default_value_t = false,
+        help = "Scan directories recursively"
+    )]
+    recursive: bool,
+
+    /// Scan everything, ignoring `.gitignore` and `.rsimgignore` files
+    #[arg(long, help = "Don't skip files excluded by .gitignore or .rsimgignore")]
+    no_ignore: bool,
+
+    /// Identify images by their magic bytes instead of their extension:
+    /// extension-less files are taken too, and misnamed ones are reported
+    #[arg(long, help = "Detect image files by content instead of extension")]
+    sniff: bool,
+
+    /// Resize from the JPEG preview cameras embed in JPEG, TIFF and RAW
+    /// files when it's at least the size of the largest output, instead of
+    /// decoding the full image; camera RAW files (DNG, CR2, NEF, ARW, PEF)
+    /// are taken too and always read this way. Plain --scales batches
+    /// without transforms only
+    #[arg(
+        long,
+        conflicts_with_all = ["pipeline", "jobs", "breakpoints", "resize", "if_portrait", "if_landscape", "if_photo", "if_graphic", "if_text"],
+        help = "Resize from embedded previews when they're large enough"
+    )]
+    use_embedded_preview: bool,
+
+    /// Start on the first images while the directory scan goes on, for
+    /// inputs with many files on slow storage. Identical files aren't
+    /// decoded once, `{seq}` isn't available and colliding output names
+    /// fail the later file instead of renaming it
+    #[arg(
+        long,
+        requires = "input",
+        conflicts_with_all = ["jobs", "if_portrait", "if_landscape", "if_photo", "if_graphic", "if_text", "total_budget", "on_collision", "order"],
+        help = "Process images while the input directory is still being scanned"
+    )]
+    stream: bool,
+
+    /// Number of parallel threads (default: auto-detect CPU cores)
+    #[arg(short, long, value_name = "N", help = "Number of threads to use")]
+    threads: Option<usize>,
+
+    /// Output directory for optimized images (default: same as input)
+    #[arg(short, long, value_name = "DIR", help = "Output directory path")]
+    output: Option<PathBuf>,
+
+    /// Reuse the source file for variants it already satisfies (100% scale, same format)
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Passthrough::Off,
+        value_name = "POLICY",
+        help = "Link or copy sources instead of re-encoding them at 100%"
+    )]
+    passthrough: Passthrough,
+
+    /// Treat an input without any valid images as an error (exit code 3)
+    #[arg(long, help = "Fail when no valid images are found")]
+    strict: bool,
+
+    /// Rotate (per EXIF), crop and strip JPEG sources written as 100% JPEGs
+    /// by rearranging DCT coefficients instead of re-encoding
+    #[arg(
+        long,
+        help = "Transform JPEGs losslessly at 100% instead of re-encoding"
+    )]
+    lossless_jpeg: bool,
+
+    /// Write a fresh EXIF thumbnail into JPEG outputs (otherwise stale or
+    /// oversized thumbnails carried over by --lossless-jpeg are dropped)
+    #[arg(long, help = "Embed a regenerated EXIF thumbnail in JPEG outputs")]
+    embed_thumbnail: bool,
+
+    /// Build Huffman tables from each JPEG's own statistics, typically a few
+    /// percent smaller at no cost in quality
+    #[arg(long, help = "Optimize Huffman tables of JPEG outputs")]
+    jpeg_optimize: bool,
+
+    /// Restart markers every N MCUs (8x8 or 16x16 pixel blocks) let decoders
+    /// resynchronize after transmission errors; implies --jpeg-optimize
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u16).range(1..),
+        value_name = "N",
+        help = "Insert JPEG restart markers every N MCUs"
+    )]
+    jpeg_restart: Option<u16>,
+
+    /// Drop EXIF, XMP and comments from losslessly transformed JPEGs
+    #[arg(
+        long,
+        requires = "lossless_jpeg",
+        help = "Strip metadata from lossless JPEG outputs"
+    )]
+    strip: bool,
+
+    /// Copy EXIF (upright, without the stale thumbnail) and XMP from each
+    /// source into re-encoded JPEG, PNG and WebP outputs
+    #[arg(
+        long,
+        conflicts_with = "strip",
+        help = "Keep source EXIF/XMP in JPEG, PNG and WebP outputs"
+    )]
+    keep_metadata: bool,
+
+    /// Separate JPEG and TIFF outputs into the inks of a CMYK press profile
+    /// (e.g. FOGRA39, GRACoL), embedding it, for print and EPUB workflows
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Convert JPEG/TIFF outputs to CMYK with this ICC profile"
+    )]
+    output_profile: Option<PathBuf>,
+
+    /// How colors the press can't print are mapped: perceptual compresses
+    /// the whole gamut, relative keeps printable colors exact
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Intent::Perceptual,
+        requires = "output_profile",
+        value_name = "INTENT",
+        help = "Rendering intent of --output-profile: perceptual or relative"
+    )]
+    rendering_intent: Intent,
+
+    /// Embed a C2PA manifest in every output, signed with this key, that
+    /// records the source and the transforms, resize and encoding that
+    /// made the output, for publishing with provenance (JPEG and PNG only)
+    #[cfg(feature = "c2pa")]
+    #[arg(
+        long,
+        value_name = "KEY",
+        conflicts_with_all = ["passthrough", "lossless_jpeg", "tile"],
+        help = "Sign outputs with C2PA manifests using this PKCS#8 PEM key"
+    )]
+    c2pa_sign: Option<PathBuf>,
+
+    /// Certificate chain of the --c2pa-sign key, signer first (default:
+    /// the certificates in the key file)
+    #[cfg(feature = "c2pa")]
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "c2pa_sign",
+        help = "PEM certificate chain for --c2pa-sign"
+    )]
+    c2pa_cert: Option<PathBuf>,
+
+    /// Split outputs larger than WxH into a directory of tiles with an
+    /// index.json manifest (for deep-zoom viewers)
+    #[arg(
+        long,
+        value_parser = geometry::parse_size_arg,
+        value_name = "WxH",
+        conflicts_with_all = ["passthrough", "lossless_jpeg"],
+        help = "Split large outputs into WxH tiles with an index manifest"
+    )]
+    tile: Option<(u32, u32)>,
+
+    /// What to do when inputs such as `photo.jpg` and `photo.png` would
+    /// write the same outputs: stop before processing, or number (`suffix`)
+    /// or hash (`hash`) the later names
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = OnCollision::Error,
+        value_name = "MODE",
+        help = "Inputs that would write the same outputs: error, suffix or hash"
+    )]
+    on_collision: OnCollision,
+
+    /// Skip the `.rsimg.lock` lock that keeps concurrent rsimg runs from
+    /// writing into the same output directory at once; the lock file is
+    /// removed when the batch finishes
+    #[arg(long, help = "Don't lock output directories against other rsimg runs")]
+    no_lock: bool,
+
+    /// Read every output back right after writing it, checking that it is
+    /// the format its extension names, has the expected size and decodes;
+    /// an output that doesn't fails its input like an encoding error
+    #[arg(long, help = "Check that every output decodes after writing it")]
+    verify: bool,
+
+    /// Leave out inputs whose JPEG comment, PNG text or WebP XMP says an
+    /// earlier rsimg run wrote them, whatever they're named, so outputs
+    /// aren't re-encoded into further generation loss
+    #[arg(long, help = "Skip inputs rsimg already optimized")]
+    skip_marked: bool,
+
+    /// Treat near-identical frames taken at most SECONDS apart (default 2)
+    /// as a burst and process only its sharpest frame
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "2",
+        conflicts_with_all = ["stream", "jobs"],
+        value_name = "SECONDS",
+        help = "Keep only the sharpest frame of each burst"
+    )]
+    dedupe_bursts: Option<f64>,
+
+    /// Leave out inputs whose sharpness (variance of the Laplacian of a
+    /// copy reduced to 512 pixels) is below THRESHOLD (default 100), or
+    /// that are nearly black or white, listing them at the end
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "100",
+        value_name = "THRESHOLD",
+        help = "Skip blurry, underexposed or overexposed photos"
+    )]
+    skip_blurry: Option<f64>,
+
+    /// Take only inputs rated at least this many stars in their XMP sidecar,
+    /// XMP or EXIF (unrated counts as 0, rejected as -1)
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(i32).range(0..=5),
+        value_name = "STARS",
+        help = "Only process images rated at least STARS (0-5)"
+    )]
+    min_rating: Option<i32>,
+
+    /// Take only inputs tagged with this keyword (dc:subject) in their XMP
+    /// sidecar or XMP, ignoring case; repeat for inputs having them all
+    #[arg(
+        long = "keyword",
+        value_name = "KEYWORD",
+        help = "Only process images tagged with KEYWORD (repeatable)"
+    )]
+    keywords: Vec<String>,
+
+    /// Write JPEG, PNG and WebP outputs without the note naming the rsimg
+    /// version and settings that wrote them
+    #[arg(long, help = "Don't mark outputs with the rsimg version and settings")]
+    no_marker: bool,
+
+    /// Start the largest (or smallest) input files first instead of going
+    /// in path order; largest-first keeps one huge file from running alone
+    /// after everything else has finished. Output names and `{seq}` still
+    /// follow path order
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Order::Path,
+        value_name = "ORDER",
+        help = "Order inputs start in: path, largest-first or smallest-first"
+    )]
+    order: Order,
+
+    /// Process only the first N inputs, in path order (or as a --stream
+    /// scan finds them); the rest are left for a later run
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        value_name = "N",
+        help = "Stop after N input files"
+    )]
+    limit_files: Option<u64>,
+
+    /// Stop taking inputs once their sizes add up to SIZE, the one crossing
+    /// it included; the rest are left for a later run
+    #[arg(
+        long,
+        value_parser = budget::parse_size_arg,
+        value_name = "SIZE",
+        help = "Stop after SIZE of input files (e.g. 2GB)"
+    )]
+    limit_bytes: Option<u64>,
+
+    /// Start even when the outputs are estimated not to fit on their volume
+    #[arg(long, help = "Skip the free disk space check before a batch")]
+    no_space_check: bool,
+
+    /// Time the decode, resize, encode and write stages of every file and
+    /// print percentiles per stage at the end
+    #[arg(long, help = "Print per-stage timing percentiles at the end")]
+    profile: bool,
+
+    /// Append this run's inputs, outputs, sizes, timings and command line
+    /// to a SQLite database, created if missing; `rsimg stats` totals it
+    #[cfg(feature = "history")]
+    #[arg(
+        long,
+        value_name = "DB",
+        help = "Record the run in a SQLite history database"
+    )]
+    history: Option<PathBuf>,
+
+    /// Collect the outputs into a ZIP to hand over: a folder per format, an
+    /// index.html gallery and a report.html listing every file; nothing is
+    /// packaged when some inputs failed
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "total_budget",
+        help = "Package the outputs as a ZIP with a gallery and report"
+    )]
+    package: Option<PathBuf>,
+
+    /// Make every app icon of a square INPUT instead of a batch: the iOS
+    /// asset catalog, Android legacy, round and adaptive icons, favicons and
+    /// the PWA's plain and maskable icons, with their Contents.json, XML
+    /// resources and web manifest entries. Written to --output (default
+    /// `{stem}_icons` next to INPUT); --background fills the icons that
+    /// can't be transparent
+    #[arg(
+        long,
+        requires = "input",
+        conflicts_with_all = ["jobs", "recursive", "stream", "pipeline", "breakpoints", "resize", "total_budget", "package"],
+        help = "Generate iOS, Android and PWA icon sets from a square image"
+    )]
+    app_icons: bool,
+
+    /// Write a JSON line per input to this FIFO (or file) as soon as it
+    /// finishes, with its outputs and their sizes, for a downstream process
+    /// to act on while the batch goes on; a FIFO's reader must start first
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with = "total_budget",
+        help = "Stream per-file results as JSON lines to a FIFO"
+    )]
+    results_fifo: Option<PathBuf>,
+
+    /// Run a command for every output once its input has finished, e.g.
+    /// `--exec-after 'aws s3 cp {output} s3://bucket/{name}'`; `{output}`,
+    /// `{name}`, `{dir}` and `{input}` are filled in per argument, without
+    /// a shell. A command exiting non-zero fails the input
+    #[arg(
+        long,
+        value_name = "CMD",
+        conflicts_with = "total_budget",
+        help = "Run CMD for every output ({output}, {name}, {dir}, {input})"
+    )]
+    exec_after: Option<String>,
+
+    /// How many --exec-after commands run at once (default: CPU count)
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        value_name = "N",
+        requires = "exec_after",
+        help = "Concurrent --exec-after commands"
+    )]
+    exec_jobs: Option<u64>,
+
+    /// Convert inputs rsimg can't decode with an external tool, which reads
+    /// `{input}` and writes a PNG to `{tmp_png}` (a path in --tmpdir), e.g.
+    /// `--exec-decode 'darktable-cli {input} {tmp_png}'`
+    #[arg(
+        long,
+        value_name = "CMD",
+        help = "External decoder for unsupported inputs ({input}, {tmp_png})"
+    )]
+    exec_decode: Option<String>,
+
+    /// Extensions of the inputs --exec-decode converts, taken from input
+    /// directories besides the ones rsimg reads (comma-separated: cr3,jxl)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "EXTS",
+        requires = "exec_decode",
+        help = "Also take files with these extensions for --exec-decode"
+    )]
+    exec_decode_ext: Vec<String>,
+
+    /// Say when a newer release is out, asking GitHub at most once a day
+    #[cfg(feature = "self-update")]
+    #[arg(
+        long,
+        env = "RSIMG_CHECK_UPDATES",
+        help = "Print a notice when a newer rsimg is released"
+    )]
+    check_updates: bool,
+
+    /// Threads for decoding and resizing (default: --threads or CPU cores)
+    #[arg(long, value_name = "N", help = "Number of decode/resize threads")]
+    decode_threads: Option<usize>,
+
+    /// Threads for encoding and writing (default: --threads or CPU cores)
+    #[arg(long, value_name = "N", help = "Number of encode/write threads")]
+    encode_threads: Option<usize>,
+
+    /// Exposure adjustment for HDR (EXR/Radiance) inputs, in stops
+    #[arg(
+        long,
+        default_value_t = 0.0,
+        allow_negative_numbers = true,
+        value_name = "STOPS",
+        help = "Exposure for HDR inputs in stops"
+    )]
+    exposure: f32,
+
+    /// Tone curve for HDR inputs
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Tonemap::Aces,
+        value_name = "CURVE",
+        help = "Tone mapping for HDR inputs (aces, reinhard, clamp)"
+    )]
+    tonemap: Tonemap,
+
+    /// Straighten scanned pages and whiten their paper
+    #[arg(
+        long,
+        help = "Clean up scanned pages: deskew and remove the paper tint"
+    )]
+    document: bool,
+
+    /// Reduce --document pages to black and white with an adaptive threshold
+    #[arg(
+        long,
+        requires = "document",
+        help = "Binarize --document pages (black and white)"
+    )]
+    binarize: bool,
+
+    /// Rotate sources clockwise by any angle in degrees (negative for
+    /// counterclockwise), e.g. 1.4 to level a handheld shot
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        allow_negative_numbers = true,
+        conflicts_with = "auto_straighten",
+        help = "Rotate by an arbitrary angle before scaling"
+    )]
+    rotate_deg: Option<f32>,
+
+    /// Level the dominant horizon or straight edges (up to 10°)
+    #[arg(long, help = "Detect and correct a tilted horizon")]
+    auto_straighten: bool,
+
+    /// What --rotate-deg and --auto-straighten do with the uncovered corners
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = RotateEdges::Crop,
+        value_name = "MODE",
+        help = "Corners uncovered by rotation: crop, fill (with --background) or expand"
+    )]
+    rotate_edges: RotateEdges,
+
+    /// Apply the crop, straightening and orientation saved in each source's
+    /// Lightroom or darktable XMP sidecar (photo.xmp, photo.cr2.xmp) first
+    #[arg(long, help = "Apply crop and rotation from XMP sidecars")]
+    xmp_sidecars: bool,
+
+    /// Trim uniform borders, with an optional color tolerance in percent
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "0",
+        value_name = "FUZZ",
+        help = "Remove uniform borders before scaling (optional fuzz %)"
+    )]
+    trim: Option<f32>,
+
+    /// Crop rectangle applied before scaling (X,Y,WxH)
+    #[arg(
+        long,
+        value_name = "X,Y,WxH",
+        help = "Crop to a rectangle before scaling"
+    )]
+    crop: Option<Rect>,
+
+    /// Aspect ratio to crop to before scaling (e.g. 16:9)
+    #[arg(
+        long,
+        value_name = "W:H",
+        help = "Crop to an aspect ratio before scaling"
+    )]
+    aspect: Option<AspectRatio>,
+
+    /// How --aspect and --preset social reach a ratio: crop the edges by
+    /// --gravity, carve away the least detailed seams (experimental; keeps
+    /// content near both edges), or pad with --background
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ResizeMode::Crop,
+        value_name = "MODE",
+        help = "Aspect ratio changes by cropping, seam carving or padding (crop, seam, pad)"
+    )]
+    resize_mode: ResizeMode,
+
+    /// Noise reduction strength (1-100)
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "50",
+        value_name = "STRENGTH",
+        help = "Reduce noise before encoding (optional strength 1-100)"
+    )]
+    denoise: Option<f32>,
+
+    /// Frame of animated GIF/WebP/APNG inputs used for the static outputs:
+    /// `first`, `middle` or a zero-based frame number (past the end means the
+    /// last frame)
+    #[arg(
+        long,
+        value_parser = decode::parse_frame_arg,
+        default_value = "first",
+        value_name = "FRAME",
+        help = "Frame of animated inputs to use: first, middle or N"
+    )]
+    frame: Frame,
+
+    /// Remove red eyes from flash photos (within faces if --face-model is given)
+    #[arg(long, help = "Remove red eyes from flash photos")]
+    fix_redeye: bool,
+
+    /// Stretch levels, correct white balance and boost saturation a little
+    #[arg(
+        long,
+        help = "Auto-enhance: levels, white balance and a mild saturation boost"
+    )]
+    auto_enhance: bool,
+
+    /// Canvas size to pad to without scaling (e.g. 2000x2000)
+    #[arg(
+        long,
+        value_parser = geometry::parse_size_arg,
+        value_name = "WxH",
+        help = "Pad onto a fixed-size canvas before scaling"
+    )]
+    extent: Option<(u32, u32)>,
+
+    /// Shrink sources wider than this before the scales apply, so 100% is
+    /// at most this wide
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        value_name = "PIXELS",
+        help = "Cap the width of sources before scaling"
+    )]
+    max_width: Option<u32>,
+
+    /// Canvas color used by --extent and --rotate-edges fill/expand
+    #[arg(
+        long,
+        default_value = "#ffffff",
+        value_name = "COLOR",
+        help = "Background color (#rrggbb or #rrggbbaa)"
+    )]
+    background: Color,
+
+    /// Make the background transparent (flood fill from the edges)
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "10",
+        value_name = "TOLERANCE",
+        help = "Remove the background, matching border colors within TOLERANCE %"
+    )]
+    remove_background: Option<f32>,
+
+    /// ONNX matting model used by --remove-background instead of flood fill
+    #[cfg(feature = "onnx")]
+    #[arg(long, value_name = "MODEL", help = "ONNX model for background matting")]
+    matting_model: Option<PathBuf>,
+
+    /// Enlarge sources by this factor (2x, 4x) with --upscale-model before scaling
+    #[cfg(feature = "onnx")]
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        value_parser = rsimg::transform::upscale::parse_factor_arg,
+        requires = "upscale_model",
+        help = "Upscale sources with a super-resolution model (e.g. 2x)"
+    )]
+    upscale: Option<u32>,
+
+    /// ONNX super-resolution model (ESRGAN-style) matching the --upscale factor
+    #[cfg(feature = "onnx")]
+    #[arg(
+        long,
+        visible_alias = "model",
+        value_name = "MODEL",
+        requires = "upscale",
+        help = "ONNX model for --upscale"
+    )]
+    upscale_model: Option<PathBuf>,
+
+    /// ONNX face detector (UltraFace 320x240) used by --gravity face and --fix-redeye
+    #[cfg(feature = "onnx")]
+    #[arg(
+        long,
+        value_name = "MODEL",
+        help = "ONNX face detection model for --gravity face and --fix-redeye"
+    )]
+    face_model: Option<PathBuf>,
+
+    /// ONNX classifier taking 224x224 RGB and returning scores for photo,
+    /// graphic and text, used instead of the built-in heuristic
+    #[cfg(feature = "onnx")]
+    #[arg(
+        long,
+        value_name = "MODEL",
+        help = "ONNX model for --classify, --if-photo/graphic/text and --preset"
+    )]
+    classify_model: Option<PathBuf>,
+
+    /// 3D LUT (.cube) applied to every output as a color grade
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Apply a 3D color lookup table (.cube)"
+    )]
+    lut: Option<PathBuf>,
+
+    /// Corner darkening strength in percent
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "40",
+        value_name = "STRENGTH",
+        help = "Darken the corners (optional strength 0-100)"
+    )]
+    vignette: Option<f32>,
+
+    /// Levels per color channel for a posterized look (2-255)
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(2..),
+        value_name = "N",
+        help = "Posterize outputs to N levels per channel"
+    )]
+    posterize: Option<u8>,
+
+    /// Image whose colors form the output palette
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Map outputs onto the colors of a palette image"
+    )]
+    palette_file: Option<PathBuf>,
+
+    /// Hide a mark of ID in every output, in DCT coefficients of its luma,
+    /// that `rsimg detect-watermark` reads back to trace leaked copies
+    #[arg(
+        long,
+        value_name = "ID",
+        help = "Embed an invisible watermark carrying ID"
+    )]
+    invisible_watermark: Option<String>,
+
+    /// Anchor used when cropping to an aspect ratio or extending the canvas
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Gravity::Center,
+        value_name = "GRAVITY",
+        help = "Crop/extent anchor (center, north, southeast, face, ...)"
+    )]
+    gravity: Gravity,
+
+    /// Ordered chain of operations, e.g. "resize(50%) | sharpen(0.5) | encode(webp, 75)";
+    /// `@FILE` reads the chain from a file
+    #[arg(
+        long,
+        value_name = "CHAIN",
+        conflicts_with_all = ["formats", "scales", "passthrough", "lossless_jpeg"],
+        help = "Run a pipeline of operations instead of --formats/--scales"
+    )]
+    pipeline: Option<String>,
 
-    /// Output formats (comma-separated: jpg,webp,png)
+    /// Also write every output as seen with these color vision deficiencies
+    /// (comma-separated: deuteranopia,protanopia,tritanopia), named with
+    /// the deficiency added, e.g. chart_50pct_deuteranopia.png
     #[arg(
         long,
         value_delimiter = ',',
-        default_values_t = vec!["jpg".to_string(), "webp".to_string()],
-        value_name = "FORMATS",
-        help = "Output image formats"
+        value_name = "DEFICIENCIES",
+        help = "Add outputs simulating color blindness"
     )]
-    formats: Vec<String>,
+    simulate: Vec<Deficiency>,
 
-    /// Scale percentages (comma-separated: 100,75,50,25)
+    /// CSV or JSON file giving each image its own crop, sizes, formats and output names
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["input", "recursive", "pipeline", "passthrough", "lossless_jpeg"],
+        help = "Process the images listed in a job file, each with its own settings"
+    )]
+    jobs: Option<PathBuf>,
+
+    /// Settings for portrait inputs, as `KEY=VALUE` with the keys of a job
+    /// file row (crop, sizes, formats, quality, output) or `scales`; repeat
+    /// the flag or separate settings with spaces
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        conflicts_with_all = ["jobs", "pipeline", "breakpoints", "resize", "name", "total_budget", "passthrough", "lossless_jpeg"],
+        help = "Settings for portrait images, e.g. scales=60,30"
+    )]
+    if_portrait: Vec<String>,
+
+    /// Settings for landscape inputs, as for --if-portrait
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        conflicts_with_all = ["jobs", "pipeline", "breakpoints", "resize", "name", "total_budget", "passthrough", "lossless_jpeg"],
+        help = "Settings for landscape images, e.g. sizes=1920x;960x"
+    )]
+    if_landscape: Vec<String>,
+
+    /// Settings for inputs classified as photos, as for --if-portrait
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        conflicts_with_all = ["jobs", "pipeline", "breakpoints", "resize", "name", "total_budget", "passthrough", "lossless_jpeg", "if_portrait", "if_landscape"],
+        help = "Settings for photos, e.g. formats=jpg"
+    )]
+    if_photo: Vec<String>,
+
+    /// Settings for inputs classified as UI or flat-color graphics, as for
+    /// --if-portrait
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        conflicts_with_all = ["jobs", "pipeline", "breakpoints", "resize", "name", "total_budget", "passthrough", "lossless_jpeg", "if_portrait", "if_landscape"],
+        help = "Settings for graphics, e.g. formats=png"
+    )]
+    if_graphic: Vec<String>,
+
+    /// Settings for inputs classified as text on a plain background, as for
+    /// --if-portrait
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        conflicts_with_all = ["jobs", "pipeline", "breakpoints", "resize", "name", "total_budget", "passthrough", "lossless_jpeg", "if_portrait", "if_landscape"],
+        help = "Settings for text images, e.g. formats=png quality=90"
+    )]
+    if_text: Vec<String>,
+
+    /// Widths of responsive breakpoints instead of --scales: `tailwind` (640,
+    /// 768, 1024, 1280, 1536), `bootstrap` (576, 768, 992, 1200, 1400) or a
+    /// JSON file with a list of widths. Images are never enlarged; outputs
+    /// are named `{stem}_{width}w`
+    #[arg(
+        long,
+        value_name = "SET",
+        value_parser = Suggest::new(StringValueParser::new(), &["tailwind", "bootstrap"]),
+        hide_possible_values = true,
+        conflicts_with_all = ["scales", "pipeline", "jobs", "passthrough", "lossless_jpeg"],
+        help = "Resize to breakpoint widths: tailwind, bootstrap or a JSON file"
+    )]
+    breakpoints: Option<String>,
+
+    /// ImageMagick geometries instead of --scales: `50%`, `800x600` (fit
+    /// within), `800x600>` (only shrink larger images), `800x600<` (only
+    /// enlarge smaller ones), `800x600^` (cover), `800x600!` (exact), `x400`
+    /// or `800`; outputs are named `{stem}_{width}x{height}`
     #[arg(
         long,
         value_delimiter = ',',
-        default_values_t = vec![75, 50, 25],
-        value_name = "SCALES",
-        help = "Image scale percentages (10-100)"
+        value_name = "GEOMETRY",
+        conflicts_with_all = ["scales", "pipeline", "jobs", "breakpoints", "total_budget", "passthrough", "lossless_jpeg"],
+        help = "Resize to ImageMagick geometries, e.g. 800x600> or 50%"
     )]
-    scales: Vec<u32>,
+    resize: Vec<String>,
 
-    /// Compression quality (0-100, higher is better)
+    /// Output name template, without extension: `{stem}`, `{seq}` (or
+    /// `{seq:04}`, zero-padded), `{scale}`, `{width}`, `{height}`,
+    /// `{label}` (a --preset social output's platform) and, from the
+    /// source's EXIF, `{exif_date}` (YYYY-MM-DD), `{camera}`, `{iso}` and
+    /// `{focal}` (mm)
     #[arg(
         long,
-        default_value_t = 80,
-        value_name = "QUALITY",
-        help = "JPEG/WebP quality level"
+        value_name = "TEMPLATE",
+        conflicts_with_all = ["jobs", "passthrough", "lossless_jpeg"],
+        help = "Name outputs after a template, e.g. {exif_date}_{camera}_{stem}_w{width}"
     )]
-    quality: u8,
+    name: Option<String>,
 
-    /// Process subdirectories recursively
+    /// Number `{seq}` starts counting from; inputs are numbered in path order
     #[arg(
-        short,
         long,
-        default_value_t = false,
-        help = "Scan directories recursively"
+        value_name = "N",
+        default_value_t = 1,
+        help = "First number of {seq} in output names"
     )]
-    recursive: bool,
+    start_number: u32,
 
-    /// Number of parallel threads (default: auto-detect CPU cores)
-    #[arg(short, long, value_name = "N", help = "Number of threads to use")]
-    threads: Option<usize>,
+    /// TOML file listing transform plugins (shared libraries or .wasm) and their stages
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Load custom transform plugins listed in a config file"
+    )]
+    plugins: Option<PathBuf>,
 
-    /// Output directory for optimized images (default: same as input)
-    #[arg(short, long, value_name = "DIR", help = "Output directory path")]
-    output: Option<PathBuf>,
+    /// Report progress as newline-delimited JSON events on stdout instead of bars
+    #[arg(
+        long,
+        help = "Emit NDJSON progress events (for GUIs and wrappers) instead of bars"
+    )]
+    progress_json: bool,
+
+    /// Look of the per-file progress bars: `detailed` (emoji and colors),
+    /// `compact` (plain ASCII) or `none`
+    #[arg(
+        long,
+        env = "RSIMG_PROGRESS_STYLE",
+        value_enum,
+        default_value_t = BarStyle::Detailed,
+        value_name = "STYLE",
+        help = "Progress bar look: detailed, compact or none"
+    )]
+    progress_style: BarStyle,
+
+    /// indicatif template for the bars, e.g. `{msg:30} {bar:20.green/white}
+    /// {pos}/{len}` (colors go after the bar width)
+    #[arg(
+        long,
+        env = "RSIMG_PROGRESS_TEMPLATE",
+        value_name = "TEMPLATE",
+        help = "Custom progress bar template (indicatif syntax)"
+    )]
+    progress_template: Option<String>,
+
+    /// Characters drawing the bars: filled, any in-progress ones, then empty
+    #[arg(
+        long,
+        env = "RSIMG_PROGRESS_CHARS",
+        value_name = "CHARS",
+        help = "Progress bar characters, e.g. \"#>-\""
+    )]
+    progress_chars: Option<String>,
+
+    /// Language of console output and error reports (default: from the locale)
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "LANG",
+        help = "Output language (en, it, de)"
+    )]
+    lang: Option<Lang>,
+
+    /// Directory for intermediate files, such as the staged clipboard image,
+    /// `tune` candidates and --exec-decode PNGs (default: the system's
+    /// temporary directory).
+    /// Outputs are still written beside their final path and renamed in
+    /// place, so a rename never crosses volumes
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "Directory for intermediate files"
+    )]
+    tmpdir: Option<PathBuf>,
+
+    /// Export tracing spans to an OpenTelemetry collector at this base URL
+    /// (default: OTEL_EXPORTER_OTLP_ENDPOINT, or localhost:4318)
+    #[cfg(feature = "otlp")]
+    #[arg(
+        long,
+        global = true,
+        num_args = 0..=1,
+        default_missing_value = "",
+        value_name = "URL",
+        help = "Export traces over OTLP/HTTP"
+    )]
+    otlp: Option<String>,
+}
+
+fn main() -> ExitCode {
+    // Parse CLI arguments; usage errors exit with the invalid-config code
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(err) => {
+            let _ = err.print();
+            return if err.use_stderr() {
+                Exit::InvalidConfig.into()
+            } else {
+                Exit::Success.into()
+            };
+        }
+    };
+
+    i18n::init(args.lang);
+
+    match run(args) {
+        Ok(()) => Exit::Success.into(),
+        Err(err) => {
+            eprintln!("{}: {err:?}", i18n::text(Msg::Error));
+            Exit::for_error(&err).into()
+        }
+    }
 }
 
-fn main() -> Result<()> {
-    // Parse CLI arguments
-    let args = Args::parse();
+fn run(args: Args) -> Result<()> {
+    // Tracing subscribers: --profile timings and --otlp export
+    let profile = args.profile.then(|| Arc::new(Profile::default()));
+    // Held until the run ends, when pending spans are flushed
+    #[cfg(feature = "otlp")]
+    let (_telemetry, otlp) = args
+        .otlp
+        .as_deref()
+        .map(telemetry::init)
+        .transpose()?
+        .unzip();
+    #[cfg(not(feature = "otlp"))]
+    let otlp: Option<tracing_subscriber::layer::Identity> = None;
+    if profile.is_some() || otlp.is_some() {
+        tracing_subscriber::registry()
+            .with(otlp)
+            .with(profile.clone().map(ProfileLayer::new))
+            .try_init()
+            .context("Failed to install the tracing subscriber")?;
+    }
+
+    let tmpdir = match &args.tmpdir {
+        Some(dir) if !dir.is_dir() => {
+            return Err(InvalidConfig(tr!(Msg::TmpdirMissing, dir.display())).into());
+        }
+        Some(dir) => dir.clone(),
+        None => std::env::temp_dir(),
+    };
+
+    // Subcommands replace the batch run entirely
+    if let Some(command) = args.command {
+        return match command {
+            Command::Tune(tune) => tune::run(tune, &tmpdir),
+            Command::Serve(serve) => serve::run(serve),
+            Command::Pyramid(pyramid) => pyramid::run(pyramid),
+            Command::ExportMeta(export) => meta::run(export),
+            Command::Gallery(gallery) => gallery::run(gallery),
+            Command::Formats(formats) => formats::run(formats),
+            Command::Diff(diff) => diff::run(diff),
+            Command::DetectWatermark(detect) => detect::run(detect),
+            #[cfg(feature = "history")]
+            Command::Stats(stats) => history::run(stats),
+            Command::Completions(shell) => completions::completions(shell, Args::command()),
+            Command::Man(man) => completions::man(man, Args::command()),
+            #[cfg(feature = "self-update")]
+            Command::SelfUpdate(update) => update::run(update),
+        };
+    }
+
+    // A fixed set of outputs from one image: none of the batch options apply
+    if args.app_icons {
+        let input = args.input.as_deref().context("No input path given")?;
+        return icons::run(input, args.output.as_deref(), args.background);
+    }
+
+    // Stdout carries only JSON events in --progress-json mode
+    let human = !args.progress_json;
 
     // Clear terminal screen
-    print!("\x1B[2J\x1B[1;1H");
+    if human {
+        print!("\x1B[2J\x1B[1;1H");
+    }
 
     // Configure Rayon thread pool if user specified a thread count
     if let Some(threads) = args.threads {
@@ -95,31 +1247,214 @@ fn main() -> Result<()> {
     }
 
     // Print header with styling
-    println!("{}", "\n=== RSIMG — Image Optimizer ===\n".bold().cyan());
+    if human {
+        println!(
+            "{}",
+            format!("\n=== RSIMG — {} ===\n", i18n::text(Msg::Title))
+                .bold()
+                .cyan()
+        );
+    }
 
     // Validate quality parameter
-    if args.quality > 100 {
-        anyhow::bail!("Quality must be between 0 and 100");
+    let (quality, preset) = match args.quality {
+        Quality::Value(quality) if quality > 100 => {
+            return Err(InvalidConfig(tr!(Msg::QualityRange)).into());
+        }
+        Quality::Value(quality) => (quality, None),
+        Quality::Level(level) => {
+            let preset = match &args.quality_levels {
+                Some(path) => {
+                    QualityPreset::load(level, path).map_err(|e| InvalidConfig(format!("{e:#}")))?
+                }
+                None => QualityPreset::builtin(level),
+            };
+            (preset.jpg, Some(preset))
+        }
+    };
+
+    // Validate trim tolerance
+    if let Some(fuzz) = args.trim
+        && !(0.0..=100.0).contains(&fuzz)
+    {
+        return Err(InvalidConfig(tr!(Msg::TrimRange)).into());
+    }
+
+    // Validate denoise strength
+    if let Some(strength) = args.denoise
+        && !(1.0..=100.0).contains(&strength)
+    {
+        return Err(InvalidConfig(tr!(Msg::DenoiseRange)).into());
+    }
+
+    // Validate vignette strength
+    if let Some(strength) = args.vignette
+        && !(0.0..=100.0).contains(&strength)
+    {
+        return Err(InvalidConfig(tr!(Msg::VignetteRange)).into());
     }
 
     // Validate scale percentages
     for scale in &args.scales {
         if *scale < 10 || *scale > 100 {
-            anyhow::bail!("Scales must be between 10 and 100 ({}% is invalid)", scale);
+            return Err(InvalidConfig(tr!(Msg::ScaleInvalid, scale)).into());
+        }
+    }
+
+    // --preset social sets the sizes itself, and --preset email the total
+    // size and how JPEGs are written
+    let sizing = [
+        ("--pipeline", args.pipeline.is_some()),
+        ("--breakpoints", args.breakpoints.is_some()),
+        ("--resize", !args.resize.is_empty()),
+        ("--jobs", args.jobs.is_some()),
+    ];
+    let conflicts: Vec<(&str, bool)> = match args.preset {
+        Some(Preset::Social) => sizing.to_vec(),
+        Some(Preset::Email) => {
+            let mut conflicts = sizing.to_vec();
+            conflicts.extend([
+                ("--stream", args.stream),
+                ("--package", args.package.is_some()),
+                ("--results-fifo", args.results_fifo.is_some()),
+                ("--exec-after", args.exec_after.is_some()),
+                ("--if-portrait", !args.if_portrait.is_empty()),
+                ("--if-landscape", !args.if_landscape.is_empty()),
+                ("--if-photo", !args.if_photo.is_empty()),
+                ("--if-graphic", !args.if_graphic.is_empty()),
+                ("--if-text", !args.if_text.is_empty()),
+                ("--passthrough", args.passthrough != Passthrough::Off),
+                ("--lossless-jpeg", args.lossless_jpeg),
+                ("--keep-metadata", args.keep_metadata),
+                ("--embed-thumbnail", args.embed_thumbnail),
+            ]);
+            #[cfg(feature = "clipboard")]
+            conflicts.push(("--to-clipboard", args.to_clipboard));
+            conflicts
+        }
+        _ => Vec::new(),
+    };
+    if let (Some(preset), Some((flag, _))) =
+        (args.preset, conflicts.iter().find(|(_, given)| *given))
+    {
+        return Err(InvalidConfig(tr!(Msg::PresetConflict, preset.name(), flag)).into());
+    }
+    let email = args.preset == Some(Preset::Email);
+    let args = match email {
+        true => Args {
+            formats: email_formats(&args.formats),
+            max_width: args.max_width.or(Some(preset::EMAIL_MAX_WIDTH)),
+            total_budget: args.total_budget.or(Some(preset::EMAIL_BUDGET)),
+            ..args
+        },
+        false => args,
+    };
+    let output_profile = args
+        .output_profile
+        .as_deref()
+        .map(|path| OutputProfile::load(path, args.rendering_intent).map(Arc::new))
+        .transpose()?;
+    #[cfg(feature = "c2pa")]
+    let signer = args
+        .c2pa_sign
+        .as_deref()
+        .map(|key| Signer::load(key, args.c2pa_cert.as_deref()))
+        .transpose()?;
+
+    // Collect all valid image files based on input path, or the job file's rows
+    let mut jobs = args
+        .jobs
+        .as_deref()
+        .map(|path| jobs::load(path, &args.formats, &args.scales))
+        .transpose()?;
+    #[cfg(feature = "clipboard")]
+    let clipboard_input = args
+        .from_clipboard
+        .then(|| clipboard::ClipboardInput::read(&tmpdir))
+        .transpose()?;
+    #[cfg(feature = "clipboard")]
+    let args = Args {
+        output: match &clipboard_input {
+            Some(input) if args.output.is_none() => Some(input.output_dir(args.to_clipboard)),
+            _ => args.output,
+        },
+        ..args
+    };
+    #[cfg(feature = "clipboard")]
+    let clipboard_file = clipboard_input
+        .as_ref()
+        .map(|input| input.path().to_path_buf());
+    #[cfg(not(feature = "clipboard"))]
+    let clipboard_file: Option<PathBuf> = None;
+
+    let scan = ScanOptions {
+        recursive: args.recursive,
+        respect_ignore: !args.no_ignore,
+        sniff: args.sniff,
+        raw: args.use_embedded_preview,
+        extensions: args
+            .exec_decode_ext
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect(),
+    };
+    let files = match (&jobs, clipboard_file) {
+        (Some(jobs), _) => jobs.iter().map(|job| job.input.clone()).collect(),
+        (None, Some(file)) => vec![file],
+        // Found while processing
+        (None, None) if args.stream => Vec::new(),
+        (None, None) => {
+            let input = args.input.as_deref().context("No input path given")?;
+            processor::collect_image_files(input, &scan)?
         }
+    };
+    let (files, bursts) = match args.dedupe_bursts {
+        Some(gap) => {
+            let culled = burst::keep_sharpest(files, gap);
+            (culled.kept, Some((culled.bursts, culled.dropped)))
+        }
+        None => (files, None),
+    };
+
+    let rule = |settings: &[String], flag: &str| {
+        (!settings.is_empty())
+            .then(|| Rule::parse(settings))
+            .transpose()
+            .map_err(|e| InvalidConfig(format!("--{flag}: {e}")))
+    };
+    if !args.if_portrait.is_empty() || !args.if_landscape.is_empty() {
+        let portrait = rule(&args.if_portrait, "if-portrait")?;
+        let landscape = rule(&args.if_landscape, "if-landscape")?;
+        jobs = Some(jobs::by_orientation(
+            files.clone(),
+            portrait.as_ref(),
+            landscape.as_ref(),
+            &args.formats,
+            &args.scales,
+        )?);
     }
 
-    // Collect all valid image files based on input path
-    let files = collect_image_files(&args)?;
+    let classifier = classifier(&args)?;
+    if !args.if_photo.is_empty() || !args.if_graphic.is_empty() || !args.if_text.is_empty() {
+        let photo = rule(&args.if_photo, "if-photo")?;
+        let graphic = rule(&args.if_graphic, "if-graphic")?;
+        let text = rule(&args.if_text, "if-text")?;
+        jobs = Some(jobs::by_content(
+            files.clone(),
+            [photo.as_ref(), graphic.as_ref(), text.as_ref()],
+            &classifier,
+            &args.formats,
+            &args.scales,
+        )?);
+    }
 
-    if files.is_empty() {
-        println!("{}", "No valid images found.".red());
-        return Ok(());
+    if files.is_empty() && !args.stream {
+        return no_images(args.strict, human);
     }
 
     // Create output directory if user specified one
     if let Some(ref output_dir) = args.output {
-        std::fs::create_dir_all(output_dir).with_context(|| {
+        std::fs::create_dir_all(paths::long_path(output_dir)).with_context(|| {
             format!(
                 "Failed to create output directory: {}",
                 output_dir.display()
@@ -127,139 +1462,539 @@ fn main() -> Result<()> {
         })?;
     }
 
-    let total_files = files.len(); // Save total number of files for later display
+    // Save total number of files for later display
+    let mut total_files = files.len();
 
-    // Print summary of files found
-    println!(
-        "  {} {} {}",
-        "📁".bright_blue(),
-        "Found".bright_white(),
-        format!("{} images", total_files).bright_cyan().bold()
+    // Uploads often carry the wrong extension; they're decoded by content
+    if args.sniff && human {
+        for (path, format) in processor::misnamed_files(&files) {
+            println!(
+                "  {} {}",
+                "⚠️ ".yellow(),
+                tr!(Msg::Misnamed, path.display(), format.to_uppercase())
+            );
+        }
+    }
+
+    if human {
+        print_batch_info(&args, (!args.stream).then_some(total_files));
+        if let Some((bursts, dropped)) = bursts {
+            println!(
+                "  {} {}",
+                "📸".bright_white(),
+                tr!(Msg::Bursts, bursts, dropped)
+            );
+        }
+    }
+
+    // Terminal bars, or JSON events for a wrapping GUI
+    let progress = Progress::new(
+        args.progress_json,
+        Theme::new(
+            args.progress_style,
+            args.progress_template.as_deref(),
+            args.progress_chars.as_deref(),
+        )?,
     );
 
-    // Display output directory info if specified
-    if let Some(ref output_dir) = args.output {
+    // Process all images through processor module
+    let remove_background = background_removal(&args)?;
+    let lut = args.lut.as_deref().map(Lut3d::load).transpose()?;
+    let palette = args
+        .palette_file
+        .as_deref()
+        .map(rsimg::transform::posterize::load_palette)
+        .transpose()?;
+    let face_detector = face_detector(&args)?;
+    let script = match (&args.pipeline, &args.breakpoints) {
+        (Some(pipeline), _) => Some(Script::parse(pipeline)?),
+        (None, Some(set)) => Some(Script::breakpoints(&breakpoint_widths(set)?, &args.formats)),
+        (None, None) if !args.resize.is_empty() => Some(
+            Script::geometries(&args.resize, &args.formats)
+                .map_err(|err| InvalidConfig(tr!(Msg::ResizeInvalid, err)))?,
+        ),
+        (None, None) if args.preset == Some(Preset::Social) => Some(Script::targets(
+            &preset::SOCIAL,
+            &args.formats,
+            args.resize_mode,
+            args.gravity,
+            args.background,
+        )),
+        (None, None) => None,
+    };
+    let script = match (script, args.name) {
+        (Some(script), Some(name)) => Some(script.with_output_name(name)),
+        (None, Some(name)) => {
+            let sizes: Vec<String> = args.scales.iter().map(|s| format!("{s}%")).collect();
+            let sizes: Vec<&str> = sizes.iter().map(String::as_str).collect();
+            let script = Script::variants(None, &sizes, &args.formats, None)
+                .map_err(|err| InvalidConfig(tr!(Msg::ScalesInvalid, err)))?;
+            Some(script.with_output_name(name))
+        }
+        (script, None) => script,
+    };
+    let encoded = encoded_formats(&args.formats, script.as_ref(), jobs.as_deref());
+    if output_profile.is_some()
+        && let Some(format) = encoded.iter().find(|format| {
+            !matches!(
+                format.to_lowercase().as_str(),
+                "jpg" | "jpeg" | "tiff" | "tif"
+            )
+        })
+    {
+        return Err(InvalidConfig(tr!(Msg::ProfileFormat, format)).into());
+    }
+    #[cfg(feature = "c2pa")]
+    if signer.is_some()
+        && let Some(format) = encoded
+            .iter()
+            .find(|format| !matches!(format.to_lowercase().as_str(), "jpg" | "jpeg" | "png"))
+    {
+        return Err(InvalidConfig(tr!(Msg::C2paFormat, format)).into());
+    }
+    let mut custom = Vec::new();
+    // Upscale first so plugins see the final resolution
+    #[cfg(feature = "onnx")]
+    if let (Some(factor), Some(model)) = (args.upscale, &args.upscale_model) {
+        let upscale = rsimg::transform::upscale::Upscale::load(model, factor)?;
+        custom.push((
+            rsimg::transform::Stage::BeforeScale,
+            std::sync::Arc::new(upscale) as _,
+        ));
+    }
+    if let Some(config) = &args.plugins {
+        custom.extend(plugin::load_config(config)?);
+    }
+    let exec_after = args
+        .exec_after
+        .as_deref()
+        .map(|command| {
+            let jobs = args
+                .exec_jobs
+                .map_or_else(rayon::current_num_threads, |n| n as usize);
+            ExecAfter::new(command, jobs).map(Arc::new)
+        })
+        .transpose()?;
+    let exec_decode = args
+        .exec_decode
+        .as_deref()
+        .map(|command| ExecDecode::new(command, &tmpdir).map(Arc::new))
+        .transpose()?;
+    #[cfg(feature = "history")]
+    let history = args
+        .history
+        .as_deref()
+        .map(history::History::open)
+        .transpose()?;
+    let mut opts = ProcessOptions {
+        formats: args.formats,
+        scales: args.scales,
+        encode: EncodeOptions {
+            quality,
+            preset,
+            colors: args.colors,
+            dither: args.dither,
+            mipmaps: args.mipmaps,
+            embed_thumbnail: args.embed_thumbnail,
+            jpeg_optimize: args.jpeg_optimize,
+            jpeg_restart: args.jpeg_restart,
+            channels: args.channels,
+            png_color_type: args.png_color_type,
+            png_bit_depth: args.png_bit_depth,
+            metadata: None,
+            alpha: args.alpha,
+            // Nothing but the pixels in emails
+            mark: !args.no_marker && !email,
+            output_profile,
+            // Set below, once the transforms it records are known
+            #[cfg(feature = "c2pa")]
+            provenance: None,
+        },
+        output_dir: args.output,
+        passthrough: args.passthrough,
+        lossless_jpeg: args.lossless_jpeg,
+        strip: args.strip,
+        keep_metadata: args.keep_metadata,
+        on_collision: args.on_collision,
+        lock: !args.no_lock,
+        sniff: args.sniff,
+        frame: args.frame,
+        tile: args.tile,
+        decode_threads: args.decode_threads,
+        encode_threads: args.encode_threads,
+        transforms: Transforms {
+            exposure: args.exposure,
+            tonemap: args.tonemap,
+            sidecars: args.xmp_sidecars,
+            document: args.document,
+            binarize: args.binarize,
+            rotate: args.rotate_deg,
+            auto_straighten: args.auto_straighten,
+            rotate_edges: args.rotate_edges,
+            trim: args.trim,
+            crop: args.crop,
+            aspect: args.aspect,
+            resize_mode: args.resize_mode,
+            denoise: args.denoise,
+            fix_redeye: args.fix_redeye,
+            auto_enhance: args.auto_enhance,
+            remove_background,
+            extent: args.extent,
+            max_width: args.max_width,
+            linear_resize: args.linear_resize,
+            lut,
+            vignette: args.vignette,
+            posterize: args.posterize,
+            palette,
+            watermark: args.invisible_watermark.as_deref().map(Watermark::new),
+            background: args.background,
+            gravity: args.gravity,
+            face_detector,
+            custom,
+        },
+        script,
+        simulate: args.simulate,
+        start_number: args.start_number,
+        route_graphics: args.preset == Some(Preset::Screenshot),
+        classifier,
+        classify: args.classify,
+        embedded_preview: args.use_embedded_preview,
+        #[cfg(feature = "history")]
+        log: history.as_ref().map(history::History::log),
+        #[cfg(not(feature = "history"))]
+        log: None,
+        results: None,
+        exec_after,
+        exec_decode,
+        verify: args.verify,
+        skip_marked: args.skip_marked,
+        select: Selection {
+            min_rating: args.min_rating,
+            keywords: args.keywords,
+        },
+        screen: args
+            .skip_blurry
+            .map(|min_sharpness| Screening { min_sharpness }),
+        limits: Limits {
+            files: args.limit_files.map(|n| n as usize),
+            bytes: args.limit_bytes,
+        },
+        order: args.order,
+    };
+    #[cfg(feature = "c2pa")]
+    if let Some(signer) = signer {
+        opts.encode.provenance = Some(Arc::new(Provenance::new(signer, &opts.transforms)));
+    }
+    // Streams, job files and scripts decide their outputs as they go
+    if !args.stream && jobs.is_none() && opts.script.is_none() {
+        let taken = opts.limits.files.unwrap_or(usize::MAX).min(files.len());
+        space::preflight(&files[..taken], &opts, args.no_space_check)?;
+    }
+    // Waits for a FIFO's reader, so only once the batch is ready to start
+    if let Some(path) = &args.results_fifo {
+        opts.results = Some(Arc::new(ResultsPipe::open(path)?));
+    }
+    let batch = || match (&jobs, args.total_budget) {
+        (Some(jobs), _) => processor::process_jobs(jobs, &opts, &progress).map(Some),
+        (None, Some(budget)) => {
+            budget::process_within_budget(&files, &mut opts, &progress, budget).map(|()| None)
+        }
+        (None, None) if args.stream => {
+            let input = args.input.as_deref().context("No input path given")?;
+            processor::process_all(Inputs::Scan(input, &scan), &opts, &progress).map(Some)
+        }
+        (None, None) => processor::process_all(Inputs::Files(files), &opts, &progress).map(Some),
+    };
+    #[cfg(feature = "history")]
+    let processed = match history {
+        Some(history) => history.record(batch)?,
+        None => batch()?,
+    };
+    #[cfg(not(feature = "history"))]
+    let processed = batch()?;
+    // Streamed inputs are only counted by the batch, and marked, unselected
+    // or screened ones left out
+    if let Some(processed) = &processed {
+        if args.stream
+            && processed.files == 0
+            && !args.skip_marked
+            && opts.select.is_all()
+            && opts.screen.is_none()
+        {
+            return no_images(args.strict, human);
+        }
+        total_files = processed.files;
+    }
+    let outputs = processed
+        .map(|processed| processed.outputs)
+        .unwrap_or_default();
+    if let Some(path) = &args.package {
+        let packaged = package::write(path, &outputs)?;
+        if human {
+            println!(
+                "  {} {}: {} ({})",
+                "📦".bright_white(),
+                i18n::text(Msg::Package),
+                path.display().to_string().bright_yellow(),
+                tr!(Msg::Files, packaged).bright_cyan()
+            );
+        }
+    }
+    #[cfg(feature = "clipboard")]
+    if args.to_clipboard {
+        let copied = clipboard::copy_largest(&outputs)?;
+        if human {
+            println!(
+                "  {} {}",
+                "📋".bright_white(),
+                tr!(Msg::CopiedToClipboard, copied.display())
+            );
+        }
+    }
+
+    // Print success message
+    if human {
         println!(
-            "  {} Output: {}/",
-            "💾".bright_white(),
-            output_dir.display().to_string().bright_yellow()
+            "\n  {} {}",
+            "✓".green().bold(),
+            i18n::text(Msg::Done).green().bold()
+        );
+
+        println!(
+            "  {} {}\n",
+            "  ".dimmed(),
+            tr!(Msg::Optimized, total_files.bright_cyan())
         );
     }
 
-    // Display formats, scales, and quality settings
-    println!(
-        "  {} Formats: {} | Scales: {} | Quality: {}",
-        "⚙️ ".bright_white(),
-        args.formats.join(", ").bright_yellow(),
-        args.scales
-            .iter()
-            .map(|s| format!("{}%", s))
-            .collect::<Vec<_>>()
-            .join(", ")
-            .bright_yellow(),
-        format!("{}%", args.quality).bright_yellow()
-    );
+    #[cfg(feature = "self-update")]
+    if args.check_updates && human {
+        update::notify_if_outdated();
+    }
 
-    // Display number of threads in use
-    let num_threads = rayon::current_num_threads();
-    println!(
-        "  {} Using {} {}",
-        "🚀".bright_white(),
-        num_threads.to_string().bright_green().bold(),
-        if num_threads == 1 {
-            "thread"
+    if let Some(report) = profile.and_then(|profile| profile.report()) {
+        // Stdout carries only JSON events in --progress-json mode
+        if human {
+            println!("{report}");
         } else {
-            "threads"
+            eprintln!("{report}");
         }
-        .dimmed()
-    );
-
-    println!(); // Empty line for spacing
+    }
 
-    // Create multi-progress bar for concurrent image processing
-    let mp = create_multi_progress();
+    Ok(())
+}
 
-    // Process all images through processor module
-    processor::process_all(
-        files,
-        &args.formats,
-        &args.scales,
-        args.quality,
-        args.output.as_ref(),
-        &mp,
-    )?;
+/// Subcommands; without one, rsimg optimizes INPUT
+#[derive(Subcommand)]
+enum Command {
+    /// Compare one image encoded at several qualities/formats in a labeled grid
+    Tune(tune::TuneArgs),
+    /// Accept jobs as JSON-RPC requests on a unix socket or stdin
+    Serve(serve::ServeArgs),
+    /// Cut a large image into a Deep Zoom (DZI) or IIIF level 0 tile pyramid
+    Pyramid(pyramid::PyramidArgs),
+    /// Write the size, format and EXIF basics of every image to CSV
+    ExportMeta(meta::ExportMetaArgs),
+    /// Publish a directory of images as a static gallery site
+    Gallery(gallery::GalleryArgs),
+    /// List the input and output formats compiled into this binary
+    Formats(formats::FormatsArgs),
+    /// Compare two directories of images, writing highlighted diff images
+    Diff(diff::DiffArgs),
+    /// Read the invisible watermark of images, to trace leaked copies
+    DetectWatermark(detect::DetectWatermarkArgs),
+    /// Total the runs recorded with --history by day, week, month or run
+    #[cfg(feature = "history")]
+    Stats(history::StatsArgs),
+    /// Print a shell completion script
+    Completions(completions::CompletionsArgs),
+    /// Print the man page, or write every page into a directory
+    Man(completions::ManArgs),
+    /// Replace this binary with the latest GitHub release
+    #[cfg(feature = "self-update")]
+    SelfUpdate(update::SelfUpdateArgs),
+}
 
-    // Print success message
-    println!(
-        "\n  {} {}",
-        "✓".green().bold(),
-        "Processing completed successfully!".green().bold()
-    );
+// Select the background removal method requested on the command line
+fn background_removal(args: &Args) -> Result<Option<BackgroundRemoval>> {
+    let Some(tolerance) = args.remove_background else {
+        return Ok(None);
+    };
 
-    println!(
-        "  {} {} images optimized\n",
-        "  ".dimmed(),
-        total_files.to_string().bright_cyan()
-    );
+    #[cfg(feature = "onnx")]
+    if let Some(ref model) = args.matting_model {
+        // Common matting models (U²-Net, MODNet) work at 320×320
+        let model = rsimg::onnx::OnnxModel::load(model, (320, 320))?;
+        return Ok(Some(BackgroundRemoval::Model(std::sync::Arc::new(model))));
+    }
 
-    Ok(())
+    Ok(Some(BackgroundRemoval::FloodFill { tolerance }))
 }
 
-// Collect all image files from input path
-fn collect_image_files(args: &Args) -> Result<Vec<PathBuf>> {
-    const VALID_EXTENSIONS: &[&str] = &[
-        "jpg", "jpeg", "png", "gif", "webp", "bmp", "tiff", "tif", "ico",
-    ];
-    let mut files = Vec::new();
+// Load the face detector for --gravity face and --fix-redeye
+fn face_detector(args: &Args) -> Result<Option<std::sync::Arc<FaceDetector>>> {
+    #[cfg(feature = "onnx")]
+    if let Some(ref model) = args.face_model {
+        return Ok(Some(std::sync::Arc::new(FaceDetector::load(model)?)));
+    }
 
-    if !args.input.exists() {
-        anyhow::bail!("Path '{}' does not exist", args.input.display());
+    if args.gravity == Gravity::Face {
+        return Err(InvalidConfig(tr!(Msg::FaceModelRequired)).into());
     }
+    Ok(None)
+}
 
-    if args.input.is_file() {
-        // Single file input
-        validate_image_file(&args.input, VALID_EXTENSIONS)?;
-        files.push(args.input.clone());
-    } else if args.input.is_dir() {
-        // Directory input (recursively if specified)
-        let walker = if args.recursive {
-            WalkDir::new(&args.input)
-        } else {
-            WalkDir::new(&args.input).max_depth(1)
-        };
+// Load the content classifier for --classify, the --if-photo/graphic/text
+// rules and --preset screenshot
+#[cfg_attr(not(feature = "onnx"), allow(unused_variables))]
+fn classifier(args: &Args) -> Result<Classifier> {
+    #[cfg(feature = "onnx")]
+    if let Some(ref model) = args.classify_model {
+        return Classifier::with_model(model);
+    }
+    Ok(Classifier::default())
+}
 
-        for entry in walker.into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
+// The email-safe formats among those asked for, or JPEG if there are none
+fn email_formats(formats: &[String]) -> Vec<String> {
+    let safe: Vec<String> = formats
+        .iter()
+        .filter(|format| preset::EMAIL_FORMATS.contains(&format.to_lowercase().as_str()))
+        .cloned()
+        .collect();
+    match safe.is_empty() {
+        true => vec!["jpg".to_string()],
+        false => safe,
+    }
+}
 
-            if path.is_file() {
-                if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                    if VALID_EXTENSIONS.contains(&ext.to_lowercase().as_str()) {
-                        files.push(path.to_path_buf());
-                    }
-                }
-            }
-        }
-    } else {
-        anyhow::bail!(
-            "Path '{}' is not a valid file or directory",
-            args.input.display()
-        );
+/// Formats the batch actually encodes: those of the job scripts, or of the
+/// pipeline, when there are any, else `--formats`
+fn encoded_formats<'a>(
+    formats: &'a [String],
+    script: Option<&'a Script>,
+    jobs: Option<&'a [processor::Job]>,
+) -> Vec<&'a str> {
+    match (jobs, script) {
+        (Some(jobs), _) => jobs.iter().flat_map(|job| job.script.formats()).collect(),
+        (None, Some(script)) => script.formats().collect(),
+        (None, None) => formats.iter().map(String::as_str).collect(),
+    }
+}
+
+// Widths of a --breakpoints set: a CSS framework's, or a JSON list in a file
+fn breakpoint_widths(set: &str) -> Result<Vec<u32>> {
+    match set.to_ascii_lowercase().as_str() {
+        "tailwind" => return Ok(vec![640, 768, 1024, 1280, 1536]),
+        "bootstrap" => return Ok(vec![576, 768, 992, 1200, 1400]),
+        _ => {}
     }
 
-    Ok(files)
+    let text = std::fs::read_to_string(set)
+        .map_err(|err| InvalidConfig(tr!(Msg::BreakpointsRead, set, err)))?;
+    let widths: Vec<u32> = serde_json::from_str(&text)
+        .map_err(|err| InvalidConfig(tr!(Msg::BreakpointsInvalid, set, err)))?;
+    if widths.is_empty() || widths.contains(&0) {
+        return Err(InvalidConfig(tr!(Msg::BreakpointsEmpty, set)).into());
+    }
+    Ok(widths)
 }
 
-// Validate that a file has a supported image extension
-fn validate_image_file(path: &PathBuf, valid_ext: &[&str]) -> Result<()> {
-    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-        if valid_ext.contains(&ext.to_lowercase().as_str()) {
-            return Ok(());
-        }
+// Report a batch without images, an error with --strict
+fn no_images(strict: bool, human: bool) -> Result<()> {
+    if strict {
+        return Err(NoInputs(tr!(Msg::NoImages)).into());
     }
-    anyhow::bail!("File '{}' is not a supported image format", path.display());
+    if human {
+        println!("{}", format!("{}.", i18n::text(Msg::NoImages)).red());
+    }
+    Ok(())
 }
 
-// Create a MultiProgress object for concurrent progress bars
-fn create_multi_progress() -> MultiProgress {
-    MultiProgress::new()
+// Print the files found and the settings they'll be processed with
+fn print_batch_info(args: &Args, total_files: Option<usize>) {
+    // Print summary of files found, or that they're processed as found
+    match total_files {
+        Some(total_files) => println!(
+            "  {} {} {}",
+            "📁".bright_blue(),
+            i18n::text(Msg::Found).bright_white(),
+            tr!(Msg::Images, total_files).bright_cyan().bold()
+        ),
+        None => println!(
+            "  {} {}",
+            "📁".bright_blue(),
+            i18n::text(Msg::Streaming).bright_white()
+        ),
+    }
+
+    // Display output directory info if specified
+    if let Some(ref output_dir) = args.output {
+        println!(
+            "  {} {}: {}/",
+            "💾".bright_white(),
+            i18n::text(Msg::Output),
+            output_dir.display().to_string().bright_yellow()
+        );
+    }
+
+    // Display the pipeline, or formats, scales, and quality settings
+    if let Some(ref pipeline) = args.pipeline {
+        println!(
+            "  {} {}: {}",
+            "⚙️ ".bright_white(),
+            i18n::text(Msg::Pipeline),
+            pipeline.bright_yellow()
+        );
+    } else if let Some(ref jobs) = args.jobs {
+        println!(
+            "  {} {}: {}",
+            "⚙️ ".bright_white(),
+            i18n::text(Msg::Jobs),
+            jobs.display().to_string().bright_yellow()
+        );
+    } else {
+        let (sizes, list) = match &args.breakpoints {
+            Some(set) => (Msg::Breakpoints, set.clone()),
+            None if !args.resize.is_empty() => (Msg::Geometries, args.resize.join(", ")),
+            None => (
+                Msg::Scales,
+                args.scales
+                    .iter()
+                    .map(|s| format!("{}%", s))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        };
+        println!(
+            "  {} {}: {} | {}: {} | {}: {}",
+            "⚙️ ".bright_white(),
+            i18n::text(Msg::Formats),
+            args.formats.join(", ").bright_yellow(),
+            i18n::text(sizes),
+            list.bright_yellow(),
+            i18n::text(Msg::Quality),
+            args.quality.to_string().bright_yellow()
+        );
+    }
+
+    // Display number of threads in use per stage
+    let num_threads = rayon::current_num_threads();
+    println!(
+        "  {} {}",
+        "🚀".bright_white(),
+        tr!(
+            Msg::Threads,
+            args.decode_threads
+                .unwrap_or(num_threads)
+                .bright_green()
+                .bold(),
+            args.encode_threads
+                .unwrap_or(num_threads)
+                .bright_green()
+                .bold()
+        )
+    );
+
+    println!(); // Empty line for spacing
 }
@@ -0,0 +1,123 @@
+// src/meta.rs
+//
+// `rsimg export-meta`: one CSV row per image an input holds, for audits and
+// spreadsheets. Files are found by the same parallel walk a batch uses
+// (`.rsimgignore`, `--recursive`, `--sniff`) and read in parallel, but only
+// as far as their headers and EXIF: nothing is decoded unless the format
+// keeps its size elsewhere (PSD, XCF). Rows come in path order; a file that
+// can't be read gets a row with its error instead of stopping the export.
+//
+//   path,format,width,height,bytes,date,camera,iso,focal,error
+
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use rayon::prelude::*;
+use rsimg::decode;
+use rsimg::exit::NoInputs;
+use rsimg::i18n::{self, Msg, tr};
+use rsimg::processor::{self, ScanOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const HEADER: [&str; 10] = [
+    "path", "format", "width", "height", "bytes", "date", "camera", "iso", "focal", "error",
+];
+
+#[derive(clap::Args)]
+pub struct ExportMetaArgs {
+    /// Image file or directory to list
+    #[arg(value_name = "INPUT", help = "Input file or directory")]
+    pub input: PathBuf,
+
+    /// CSV file to write; stdout without it
+    #[arg(long, value_name = "FILE", help = "Write the CSV to FILE")]
+    pub csv: Option<PathBuf>,
+
+    /// Descend into subdirectories
+    #[arg(short, long, help = "Scan directories recursively")]
+    pub recursive: bool,
+
+    /// Also list what `.gitignore` and `.rsimgignore` files exclude
+    #[arg(long, help = "Don't skip files excluded by .gitignore or .rsimgignore")]
+    pub no_ignore: bool,
+
+    /// Identify images by their magic bytes instead of their extension
+    #[arg(long, help = "Detect image files by content instead of extension")]
+    pub sniff: bool,
+}
+
+/// Runs the `export-meta` subcommand
+pub fn run(args: ExportMetaArgs) -> Result<()> {
+    let scan = ScanOptions {
+        recursive: args.recursive,
+        respect_ignore: !args.no_ignore,
+        sniff: args.sniff,
+        raw: false,
+        extensions: Vec::new(),
+    };
+    let files = processor::collect_image_files(&args.input, &scan)?;
+    if files.is_empty() {
+        return Err(NoInputs(tr!(Msg::NoImages)).into());
+    }
+    let rows: Vec<[String; 10]> = files.par_iter().map(|path| row(path)).collect();
+
+    let Some(path) = &args.csv else {
+        return write_csv(std::io::stdout().lock(), &rows).context("Failed to write the CSV");
+    };
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    write_csv(std::io::BufWriter::new(file), &rows)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    println!(
+        "  {} {}: {} ({})",
+        "💾".bright_white(),
+        i18n::text(Msg::Metadata),
+        path.display().to_string().bright_yellow(),
+        tr!(Msg::Images, rows.len()).bright_cyan()
+    );
+    Ok(())
+}
+
+/// The CSV fields of one file
+fn row(path: &Path) -> [String; 10] {
+    let format = decode::sniff_file(path)
+        .map(str::to_string)
+        .or_else(|| {
+            let ext = path.extension()?.to_string_lossy().to_lowercase();
+            Some(ext)
+        })
+        .unwrap_or_default();
+    let bytes = std::fs::metadata(path).map(|m| m.len().to_string());
+    let capture = decode::load_metadata(path)
+        .map(|metadata| metadata.capture_info())
+        .unwrap_or_default();
+    let (size, error) = match decode::load_dimensions(path) {
+        Ok((width, height)) => ((width.to_string(), height.to_string()), String::new()),
+        Err(err) => (Default::default(), format!("{err:#}")),
+    };
+    [
+        path.display().to_string(),
+        format,
+        size.0,
+        size.1,
+        bytes.unwrap_or_default(),
+        capture.date.unwrap_or_default(),
+        capture.camera.unwrap_or_default(),
+        capture.iso.map(|iso| iso.to_string()).unwrap_or_default(),
+        capture
+            .focal
+            .map(|focal| focal.to_string())
+            .unwrap_or_default(),
+        error,
+    ]
+}
+
+fn write_csv(out: impl Write, rows: &[[String; 10]]) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(HEADER)?;
+    for row in rows {
+        writer.write_record(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,108 @@
+// src/onnx.rs
+//
+// Shared ONNX model runner (feature `onnx`), backed by the pure-Rust tract
+// runtime so no native onnxruntime install is required. Models are expected
+// to take a single NCHW float tensor of RGB values, in [0, 1] unless the
+// caller normalizes them itself.
+
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, Luma, RgbImage};
+use std::path::Path;
+use std::sync::Arc;
+use tract_onnx::prelude::*;
+
+/// A loaded, optimized model with a fixed input size
+pub struct OnnxModel {
+    plan: Arc<TypedRunnableModel>,
+    width: u32,
+    height: u32,
+}
+
+impl OnnxModel {
+    /// Loads a model, pinning its input to `width`×`height`
+    pub fn load(path: &Path, (width, height): (u32, u32)) -> Result<Self> {
+        let input_shape = [1, 3, height as usize, width as usize];
+        let plan = tract_onnx::onnx()
+            .model_for_path(path)
+            .and_then(|model| model.with_input_fact(0, f32::fact(input_shape).into()))
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .with_context(|| format!("Failed to load ONNX model: {}", path.display()))?;
+
+        Ok(Self {
+            plan,
+            width,
+            height,
+        })
+    }
+
+    /// Runs the model on the image stretched to the model's input size
+    pub fn run(&self, img: &DynamicImage) -> Result<tract_ndarray::ArrayD<f32>> {
+        let rgb = img
+            .resize_exact(self.width, self.height, FilterType::Triangle)
+            .to_rgb8();
+        self.infer(&rgb)
+    }
+
+    /// Runs the model on an image already at the model's input size
+    pub fn infer(&self, rgb: &RgbImage) -> Result<tract_ndarray::ArrayD<f32>> {
+        self.infer_outputs(rgb, |v| v as f32 / 255.0)?
+            .into_iter()
+            .next()
+            .context("ONNX model has no outputs")
+    }
+
+    /// Runs the model on an image at the model's input size, mapping each
+    /// channel value through `normalize`, and returns every output
+    pub fn infer_outputs(
+        &self,
+        rgb: &RgbImage,
+        normalize: impl Fn(u8) -> f32,
+    ) -> Result<Vec<tract_ndarray::ArrayD<f32>>> {
+        if rgb.dimensions() != (self.width, self.height) {
+            anyhow::bail!(
+                "ONNX model input must be {}x{}, got {}x{}",
+                self.width,
+                self.height,
+                rgb.width(),
+                rgb.height()
+            );
+        }
+        let shape = (1, 3, self.height as usize, self.width as usize);
+        let input: Tensor = tract_ndarray::Array4::from_shape_fn(shape, |(_, c, y, x)| {
+            normalize(rgb.get_pixel(x as u32, y as u32)[c])
+        })
+        .into();
+
+        let outputs = self
+            .plan
+            .run(tvec!(input.into()))
+            .context("ONNX inference failed")?;
+        outputs
+            .iter()
+            .map(|output| {
+                let view = output
+                    .to_plain_array_view::<f32>()
+                    .context("ONNX model output is not a float tensor")?;
+                Ok(view.to_owned())
+            })
+            .collect()
+    }
+
+    /// Runs the model and reads its first output plane as a [0, 1] map
+    pub fn predict_map(&self, img: &DynamicImage) -> Result<GrayImage> {
+        let output = self.run(img)?;
+        let shape = output.shape();
+        if shape.len() < 2 {
+            anyhow::bail!("ONNX model output has unexpected shape {shape:?}");
+        }
+        let (height, width) = (shape[shape.len() - 2], shape[shape.len() - 1]);
+
+        let values: Vec<f32> = output.iter().take(width * height).copied().collect();
+        Ok(GrayImage::from_fn(width as u32, height as u32, |x, y| {
+            let value = values[y as usize * width + x as usize];
+            Luma([(value.clamp(0.0, 1.0) * 255.0).round() as u8])
+        }))
+    }
+}
@@ -0,0 +1,217 @@
+// src/package.rs
+//
+// `--package client.zip`: a batch's outputs in one archive to hand over,
+// sorted into a folder per format, with an `index.html` gallery showing
+// each image once (in the first of its formats a browser shows, linking to
+// all of them) and a `report.html` listing every file with its dimensions
+// and size. Images are stored as they are, being compressed already; the
+// pages are deflated. A batch with failures isn't packaged.
+//
+//   client.zip
+//   ├── index.html
+//   ├── report.html
+//   ├── jpg/photo_75pct.jpg
+//   └── webp/photo_75pct.webp
+
+use anyhow::{Context, Result};
+use rsimg::budget::format_size;
+use rsimg::decode;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Formats browsers show, in the order the gallery picks them
+const WEB_FORMATS: [&str; 5] = ["jpg", "png", "webp", "gif", "apng"];
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem;color:#222}\
+    main{display:grid;grid-template-columns:repeat(auto-fill,minmax(240px,1fr));gap:1rem}\
+    figure{margin:0}img{width:100%;height:auto;border-radius:4px}\
+    figcaption{font-size:.85rem;margin-top:.25rem}figcaption a{margin-left:.4rem}\
+    table{border-collapse:collapse}td,th{padding:.3rem .8rem;text-align:left}\
+    tr:nth-child(even){background:#f4f4f4}td.n{text-align:right}";
+
+/// One packaged output
+struct Entry {
+    source: PathBuf,
+    /// Path inside the archive
+    name: String,
+    stem: String,
+    format: String,
+    bytes: u64,
+    size: Option<(u32, u32)>,
+}
+
+/// Writes the outputs of a batch to the archive at `path`, returning how
+/// many were packaged; tiled outputs, being directories, are left out
+pub fn write(path: &Path, outputs: &[PathBuf]) -> Result<usize> {
+    let mut taken = HashSet::new();
+    let mut entries: Vec<Entry> = outputs
+        .iter()
+        .filter(|output| output.is_file())
+        .filter_map(|output| {
+            let stem = output.file_stem()?.to_string_lossy().into_owned();
+            let format = output.extension()?.to_string_lossy().to_lowercase();
+            // Outputs of inputs from different directories may share a name
+            let mut name = format!("{format}/{stem}.{format}");
+            let mut n = 2;
+            while !taken.insert(name.clone()) {
+                name = format!("{format}/{stem}-{n}.{format}");
+                n += 1;
+            }
+            Some(Entry {
+                source: output.clone(),
+                bytes: std::fs::metadata(output).map_or(0, |m| m.len()),
+                size: decode::load_dimensions(output).ok(),
+                name,
+                stem,
+                format,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    write_zip(path, &entries, &title)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(entries.len())
+}
+
+fn write_zip(path: &Path, entries: &[Entry], title: &str) -> Result<()> {
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(path)?));
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for entry in entries {
+        zip.start_file(entry.name.as_str(), stored)?;
+        let mut source = File::open(&entry.source)
+            .with_context(|| format!("Failed to read {}", entry.source.display()))?;
+        std::io::copy(&mut source, &mut zip)?;
+    }
+    zip.start_file("index.html", deflated)?;
+    zip.write_all(gallery(entries, title).as_bytes())?;
+    zip.start_file("report.html", deflated)?;
+    zip.write_all(report(entries, title).as_bytes())?;
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+/// One figure per output stem, showing its first web format
+fn gallery(entries: &[Entry], title: &str) -> String {
+    let mut stems: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+    for entry in entries {
+        stems.entry(&entry.stem).or_default().push(entry);
+    }
+
+    let mut body = String::new();
+    for (stem, formats) in stems {
+        let shown = WEB_FORMATS
+            .iter()
+            .find_map(|web| formats.iter().find(|entry| entry.format == *web));
+        let Some(shown) = shown else {
+            continue;
+        };
+        let size = match shown.size {
+            Some((width, height)) => format!(" width=\"{width}\" height=\"{height}\""),
+            None => String::new(),
+        };
+        let _ = write!(
+            body,
+            "<figure><a href=\"{href}\"><img src=\"{href}\" alt=\"{alt}\" loading=\"lazy\"{size}></a>\
+             <figcaption>{alt}",
+            href = href(&shown.name),
+            alt = escape(stem),
+        );
+        for entry in formats {
+            let _ = write!(
+                body,
+                "<a href=\"{}\">{}</a>",
+                href(&entry.name),
+                entry.format
+            );
+        }
+        body.push_str("</figcaption></figure>\n");
+    }
+    page(
+        title,
+        &format!("<p><a href=\"report.html\">Report</a></p>\n<main>\n{body}</main>"),
+    )
+}
+
+/// Every file with its format, dimensions and size, then totals per format
+fn report(entries: &[Entry], title: &str) -> String {
+    let mut rows = String::new();
+    let mut totals: BTreeMap<&str, (usize, u64)> = BTreeMap::new();
+    for entry in entries {
+        let size = entry
+            .size
+            .map(|(width, height)| format!("{width}×{height}"))
+            .unwrap_or_default();
+        let _ = writeln!(
+            rows,
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td class=\"n\">{size}</td><td class=\"n\">{}</td></tr>",
+            href(&entry.name),
+            escape(&entry.name),
+            entry.format,
+            format_size(entry.bytes)
+        );
+        let total = totals.entry(&entry.format).or_default();
+        total.0 += 1;
+        total.1 += entry.bytes;
+    }
+    let mut summary = String::new();
+    for (format, (files, bytes)) in totals {
+        let _ = writeln!(
+            summary,
+            "<tr><td>{format}</td><td class=\"n\">{files}</td><td class=\"n\">{}</td></tr>",
+            format_size(bytes)
+        );
+    }
+    page(
+        title,
+        &format!(
+            "<p><a href=\"index.html\">Gallery</a></p>\n<table>\n<tr><th>Format</th><th>Files</th><th>Size</th></tr>\n{summary}</table>\n\
+             <h2>Files</h2>\n<table>\n<tr><th>File</th><th>Format</th><th>Dimensions</th><th>Size</th></tr>\n\
+             {rows}</table>"
+        ),
+    )
+}
+
+fn page(title: &str, body: &str) -> String {
+    let title = escape(title);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n\
+         {body}\n</body>\n</html>\n"
+    )
+}
+
+/// Text escaped for HTML content and attributes
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A relative URL for a path inside the archive
+pub fn href(name: &str) -> String {
+    let mut url = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                url.push(byte as char)
+            }
+            byte => {
+                let _ = write!(url, "%{byte:02X}");
+            }
+        }
+    }
+    url
+}
@@ -0,0 +1,136 @@
+// src/paths.rs
+//
+// Windows path quirks. Deeply nested photo archives easily exceed the
+// 260-character MAX_PATH that the Win32 file APIs enforce unless a path is
+// written in the `\\?\` verbatim form, and stems such as `CON` or `nul.raw`
+// name devices instead of files there. Paths are kept in their ordinary form
+// everywhere (they show up in messages) and only converted right before a
+// filesystem call; on other platforms both helpers are no-ops.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// Paths at least this long get the verbatim prefix; directories are
+/// limited to MAX_PATH minus room for an 8.3 filename
+const LONG_PATH: usize = 248;
+
+/// Device names Windows reserves regardless of extension
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "COM¹", "COM²", "COM³", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8",
+    "LPT9", "LPT¹", "LPT²", "LPT³",
+];
+
+/// `path` in the `\\?\` form when it's too long for the Win32 APIs
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    if !cfg!(windows) || path.as_os_str().len() < LONG_PATH {
+        return Cow::Borrowed(path);
+    }
+    // Verbatim paths skip normalization: make the path absolute with
+    // backslashes and no `.` or `..` first
+    let absolute = std::path::absolute(path)
+        .ok()
+        .and_then(|absolute| absolute.to_str().map(str::to_owned));
+    let Some(absolute) = absolute.filter(|absolute| !absolute.starts_with(r"\\?\")) else {
+        return Cow::Borrowed(path);
+    };
+    Cow::Owned(PathBuf::from(match absolute.strip_prefix(r"\\") {
+        Some(share) => format!(r"\\?\UNC\{share}"),
+        None => format!(r"\\?\{absolute}"),
+    }))
+}
+
+/// `stem` with an underscore after a reserved device name (`con.old` →
+/// `con_.old`), so outputs named after it can be created
+pub fn safe_stem(stem: &str) -> Cow<'_, str> {
+    match cfg!(windows) {
+        true => escape_device_name(stem),
+        false => Cow::Borrowed(stem),
+    }
+}
+
+/// `safe_stem` whatever the platform
+fn escape_device_name(stem: &str) -> Cow<'_, str> {
+    let (base, rest) = stem.split_at(stem.find('.').unwrap_or(stem.len()));
+    // Windows ignores trailing spaces when matching device names
+    let name = base.trim_end_matches(' ');
+    if !RESERVED.iter().any(|r| r.eq_ignore_ascii_case(name)) {
+        return Cow::Borrowed(stem);
+    }
+    Cow::Owned(format!("{name}_{rest}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_names_are_escaped() {
+        assert_eq!(escape_device_name("CON"), "CON_");
+        assert_eq!(escape_device_name("nul.raw"), "nul_.raw");
+        assert_eq!(escape_device_name("con .old"), "con_.old");
+        assert_eq!(escape_device_name("COM¹"), "COM¹_");
+        assert_eq!(escape_device_name("lpt9.tar.gz"), "lpt9_.tar.gz");
+    }
+
+    #[test]
+    fn other_names_are_kept() {
+        for stem in [
+            "CONSOLE",
+            "nullable",
+            "COM10",
+            "LPT0",
+            "photo.con",
+            "xcon",
+            "",
+        ] {
+            assert!(matches!(escape_device_name(stem), Cow::Borrowed(s) if s == stem));
+        }
+    }
+
+    #[test]
+    fn safe_stem_only_escapes_on_windows() {
+        assert_eq!(safe_stem("aux") == "aux", !cfg!(windows));
+        assert_eq!(safe_stem("photo"), "photo");
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn long_paths_are_kept_elsewhere() {
+        let path = PathBuf::from(format!("/{}", "a".repeat(400)));
+        assert!(matches!(long_path(&path), Cow::Borrowed(p) if p == path));
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn short_paths_are_kept() {
+        let path = PathBuf::from(format!(r"C:\{}", "a".repeat(LONG_PATH - 4)));
+        assert!(path.as_os_str().len() < LONG_PATH);
+        assert!(matches!(long_path(&path), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn long_paths_get_the_verbatim_prefix() {
+        let path = PathBuf::from(format!(r"C:\photos\{}\..\b.jpg", "a".repeat(LONG_PATH)));
+        let long = long_path(&path);
+        let long = long.to_str().unwrap();
+        assert!(long.starts_with(r"\\?\C:\photos\"), "{long}");
+        assert!(long.ends_with(r"\b.jpg") && !long.contains(".."), "{long}");
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn long_share_paths_get_the_unc_prefix() {
+        let path = PathBuf::from(format!(r"\\server\share\{}.jpg", "a".repeat(LONG_PATH)));
+        let long = long_path(&path);
+        assert!(long.to_str().unwrap().starts_with(r"\\?\UNC\server\share\"));
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn verbatim_paths_are_kept() {
+        let path = PathBuf::from(format!(r"\\?\C:\{}", "a".repeat(LONG_PATH)));
+        assert!(matches!(long_path(&path), Cow::Borrowed(_)));
+    }
+}
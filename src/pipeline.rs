@@ -0,0 +1,168 @@
+// src/pipeline.rs
+//
+// The per-image pipeline on in-memory data: decode, transform, resize and
+// encode without touching the filesystem or spawning threads. Batch runs
+// (`processor`) build on the same steps; embedders and wasm32 builds call
+// these functions directly.
+
+use crate::decode;
+use crate::encode::{EncodeOptions, encode_image};
+use crate::transform::{Transforms, resample};
+use anyhow::Result;
+use image::DynamicImage;
+use image::imageops::FilterType;
+use std::path::Path;
+
+/// Source name reported for in-memory inputs
+const IN_MEMORY: &str = "<memory>";
+
+/// Settings for optimizing one in-memory image
+pub struct Options {
+    /// Output formats, each written at every scale
+    pub formats: Vec<String>,
+    /// Scale percentages (10-100)
+    pub scales: Vec<u32>,
+    pub encode: EncodeOptions,
+    pub transforms: Transforms,
+}
+
+impl Default for Options {
+    /// The command-line defaults
+    fn default() -> Self {
+        Self {
+            formats: vec!["jpg".to_string(), "webp".to_string()],
+            scales: vec![75, 50, 25],
+            encode: EncodeOptions::default(),
+            transforms: Transforms::default(),
+        }
+    }
+}
+
+/// One encoded variant of the input
+pub struct Output {
+    pub scale: u32,
+    pub format: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Decodes an encoded image and applies the pre-scale transforms
+///
+/// The format is detected from the content, so layered design files
+/// (PSD/XCF), which are only recognized by extension, aren't accepted.
+pub fn decode(bytes: &[u8], transforms: &Transforms) -> Result<DynamicImage> {
+    let source = Path::new(IN_MEMORY);
+    transforms.apply(decode::decode_bytes(bytes, source)?, source)
+}
+
+/// Encodes every scale and format of an encoded image, scale-major
+pub fn optimize(bytes: &[u8], opts: &Options) -> Result<Vec<Output>> {
+    variants(
+        &decode(bytes, &opts.transforms)?,
+        Path::new(IN_MEMORY),
+        opts,
+    )
+}
+
+/// Encodes every scale and format of an image that has already been decoded
+/// and transformed (see `decode`), scale-major
+pub fn variants(img: &DynamicImage, source: &Path, opts: &Options) -> Result<Vec<Output>> {
+    let mut outputs = Vec::with_capacity(opts.scales.len() * opts.formats.len());
+    for &scale in &opts.scales {
+        let resized = opts.transforms.apply_scaled(
+            resize_image(img, scale, opts.transforms.linear_resize)?,
+            source,
+            scale,
+        )?;
+        for format in &opts.formats {
+            outputs.push(Output {
+                scale,
+                format: format.clone(),
+                bytes: encode_image(&resized, format, &opts.encode)?,
+            });
+        }
+    }
+    Ok(outputs)
+}
+
+/// Encodes an image in one format, shrunk to fit within `width` x `height`
+/// (either may be left open; never enlarged)
+pub fn optimize_to_fit(
+    bytes: &[u8],
+    width: Option<u32>,
+    height: Option<u32>,
+    format: &str,
+    encode: &EncodeOptions,
+    transforms: &Transforms,
+) -> Result<Vec<u8>> {
+    let img = decode(bytes, transforms)?;
+    let original_width = img.width();
+    let img = fit_within(img, width, height, transforms.linear_resize);
+    let scale = (img.width() as u64 * 100 / original_width.max(1) as u64) as u32;
+    let img = transforms.apply_scaled(img, Path::new(IN_MEMORY), scale)?;
+    encode_image(&img, format, encode)
+}
+
+/// Shrinks an image to fit within the given bounds, keeping its aspect ratio
+pub fn fit_within(
+    img: DynamicImage,
+    width: Option<u32>,
+    height: Option<u32>,
+    linear: bool,
+) -> DynamicImage {
+    let fx = width.map_or(f64::MAX, |w| w as f64 / img.width() as f64);
+    let fy = height.map_or(f64::MAX, |h| h as f64 / img.height() as f64);
+    let factor = fx.min(fy);
+    if factor >= 1.0 {
+        return img;
+    }
+
+    let new_width = ((img.width() as f64 * factor).round() as u32).max(1);
+    let new_height = ((img.height() as f64 * factor).round() as u32).max(1);
+    resize_to(&img, new_width, new_height, linear)
+}
+
+/// Resizes an image to exactly the given dimensions, optionally in linear
+/// light
+pub fn resize_to(img: &DynamicImage, width: u32, height: u32, linear: bool) -> DynamicImage {
+    if linear {
+        return resample::resize_linear(img, width, height);
+    }
+    img.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+/// Size of a `width`x`height` image at `scale` percent
+pub fn scaled_size((width, height): (u32, u32), scale: u32) -> (u32, u32) {
+    let factor = scale as f32 / 100.0;
+    (
+        (width as f32 * factor).round() as u32,
+        (height as f32 * factor).round() as u32,
+    )
+}
+
+/// Resizes an image according to the given scale percentage, optionally in
+/// linear light
+#[tracing::instrument(name = "resize", skip(img))]
+pub fn resize_image(img: &DynamicImage, scale: u32, linear: bool) -> Result<DynamicImage> {
+    if scale == 100 {
+        // Return original image if scale is 100%
+        return Ok(img.clone());
+    }
+
+    let (new_width, new_height) = scaled_size((img.width(), img.height()), scale);
+
+    // Prevent creating images with zero dimensions
+    if new_width == 0 || new_height == 0 {
+        anyhow::bail!(
+            "Resulting dimensions too small: {}x{} (scale: {}%)",
+            new_width,
+            new_height,
+            scale
+        );
+    }
+
+    // Resize using high-quality Lanczos3 filter
+    if linear {
+        return Ok(resample::resize_linear(img, new_width, new_height));
+    }
+    Ok(img.resize(new_width, new_height, FilterType::Lanczos3))
+}
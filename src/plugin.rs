@@ -0,0 +1,249 @@
+// src/plugin.rs
+//
+// Custom transforms loaded at runtime (`--plugins FILE`), for logic that
+// can't live in rsimg itself (proprietary watermarks, ...). The config file
+// lists plugins in the order they run:
+//
+//   [[plugin]]
+//   path = "libwatermark.so"   # relative to the config file
+//   stage = "after-scale"      # or "before-scale" (default)
+//   options = "text=ACME"      # passed to the plugin as-is
+//
+// Native plugins are shared libraries exporting (see include/rsimg_plugin.h)
+//
+//   int rsimg_transform(uint8_t *rgba, uint32_t width, uint32_t height,
+//                       const RsimgPluginContext *ctx);
+//
+// which edits the RGBA pixels in place and returns 0 on success. `.wasm`
+// plugins (`wasm-plugins` feature) export their `memory`, plus
+//
+//   alloc(len: i32) -> i32
+//   transform(rgba: i32, width: i32, height: i32, scale: i32,
+//             options: i32, options_len: i32) -> i32
+//
+// `alloc` is called before every image and may hand out the same buffer each
+// time. Pixels are passed as RGBA8, so 16-bit and HDR sources are reduced to
+// 8 bits by a plugin.
+
+use crate::i18n::{Msg, tr};
+use crate::transform::{Stage, Transform, TransformContext};
+use anyhow::{Context, Result};
+use image::DynamicImage;
+use std::ffi::{CString, c_char, c_int};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Loads every plugin listed in a config file
+pub fn load_config(path: &Path) -> Result<Vec<(Stage, Arc<dyn Transform>)>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| tr!(Msg::PluginConfigRead, path.display()))?;
+    let config: toml::Table = text
+        .parse()
+        .with_context(|| tr!(Msg::PluginConfigInvalid, path.display()))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+
+    let entries = match config.get("plugin") {
+        None => return Ok(Vec::new()),
+        Some(toml::Value::Array(entries)) => entries,
+        Some(_) => anyhow::bail!(tr!(Msg::PluginList)),
+    };
+
+    let mut plugins = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let field = |key: &str| entry.get(key).and_then(|v| v.as_str());
+        let Some(file) = field("path") else {
+            anyhow::bail!(tr!(Msg::PluginPath, i + 1, path.display()));
+        };
+        let stage = match field("stage") {
+            None | Some("before-scale") => Stage::BeforeScale,
+            Some("after-scale") => Stage::AfterScale,
+            Some(other) => anyhow::bail!(tr!(Msg::PluginStage, other)),
+        };
+        let options = field("options").unwrap_or_default();
+
+        let file = base.join(file);
+        let plugin =
+            load(&file, options).with_context(|| tr!(Msg::PluginLoadFailed, file.display()))?;
+        plugins.push((stage, plugin));
+    }
+    Ok(plugins)
+}
+
+fn load(path: &Path, options: &str) -> Result<Arc<dyn Transform>> {
+    let options = CString::new(options).with_context(|| tr!(Msg::PluginOptionsNul))?;
+    let is_wasm = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wasm"));
+
+    if is_wasm {
+        #[cfg(feature = "wasm-plugins")]
+        return Ok(Arc::new(wasm::WasmPlugin::load(path, options)?));
+        #[cfg(not(feature = "wasm-plugins"))]
+        anyhow::bail!(tr!(Msg::PluginWasm));
+    }
+    Ok(Arc::new(NativePlugin::load(path, options)?))
+}
+
+/// Mirrors `RsimgPluginContext` in `include/rsimg_plugin.h`
+#[repr(C)]
+struct PluginContext {
+    source: *const c_char,
+    /// Scale percentage, or 0 before scaling
+    scale: u32,
+    options: *const c_char,
+}
+
+type TransformFn = unsafe extern "C" fn(*mut u8, u32, u32, *const PluginContext) -> c_int;
+
+/// A shared library exporting `rsimg_transform`
+struct NativePlugin {
+    name: PathBuf,
+    transform: TransformFn,
+    options: CString,
+    // Keeps `transform` loaded
+    _library: libloading::Library,
+}
+
+impl NativePlugin {
+    fn load(path: &Path, options: CString) -> Result<Self> {
+        // SAFETY: loading runs the library's initializers; plugins are trusted
+        // code named by the user
+        let library = unsafe { libloading::Library::new(path) }?;
+        // SAFETY: the symbol has the documented `rsimg_transform` signature
+        let transform = unsafe { library.get::<TransformFn>(b"rsimg_transform\0") }
+            .with_context(|| tr!(Msg::PluginExport, "rsimg_transform"))?;
+        Ok(Self {
+            name: path.to_path_buf(),
+            transform: *transform,
+            options,
+            _library: library,
+        })
+    }
+}
+
+impl Transform for NativePlugin {
+    fn apply(&self, img: DynamicImage, ctx: &TransformContext) -> Result<DynamicImage> {
+        let mut pixels = img.into_rgba8();
+        let (width, height) = pixels.dimensions();
+        let source = CString::new(ctx.source.to_string_lossy().as_bytes()).unwrap_or_default();
+        let context = PluginContext {
+            source: source.as_ptr(),
+            scale: ctx.scale.unwrap_or(0),
+            options: self.options.as_ptr(),
+        };
+
+        // SAFETY: `pixels` holds width * height * 4 bytes and every pointer
+        // in `context` outlives the call
+        let code = unsafe { (self.transform)(pixels.as_mut_ptr(), width, height, &context) };
+        if code != 0 {
+            anyhow::bail!(tr!(Msg::PluginCode, self.name.display(), code));
+        }
+        Ok(DynamicImage::ImageRgba8(pixels))
+    }
+}
+
+#[cfg(feature = "wasm-plugins")]
+mod wasm {
+    use super::*;
+    use std::sync::Mutex;
+    use wasmi::{Engine, Instance, Linker, Memory, Module, Store, TypedFunc};
+
+    type TransformParams = (i32, i32, i32, i32, i32, i32);
+
+    /// A WebAssembly module run in the wasmi interpreter; calls are
+    /// serialized since an instance has a single memory
+    pub struct WasmPlugin {
+        name: PathBuf,
+        options: Vec<u8>,
+        instance: Mutex<WasmInstance>,
+    }
+
+    struct WasmInstance {
+        store: Store<()>,
+        memory: Memory,
+        alloc: TypedFunc<i32, i32>,
+        transform: TypedFunc<TransformParams, i32>,
+    }
+
+    impl WasmPlugin {
+        pub fn load(path: &Path, options: CString) -> Result<Self> {
+            let wasm = std::fs::read(path)?;
+            let engine = Engine::default();
+            let module = Module::new(&engine, &wasm)?;
+            let mut store = Store::new(&engine, ());
+            let instance: Instance = Linker::<()>::new(&engine)
+                .instantiate(&mut store, &module)?
+                .start(&mut store)?;
+
+            let memory = instance
+                .get_memory(&store, "memory")
+                .with_context(|| tr!(Msg::PluginExport, "memory"))?;
+            let alloc = instance
+                .get_typed_func(&store, "alloc")
+                .with_context(|| tr!(Msg::PluginExport, "alloc"))?;
+            let transform = instance
+                .get_typed_func(&store, "transform")
+                .with_context(|| tr!(Msg::PluginExport, "transform"))?;
+
+            Ok(Self {
+                name: path.to_path_buf(),
+                options: options.into_bytes(),
+                instance: Mutex::new(WasmInstance {
+                    store,
+                    memory,
+                    alloc,
+                    transform,
+                }),
+            })
+        }
+    }
+
+    impl Transform for WasmPlugin {
+        fn apply(&self, img: DynamicImage, ctx: &TransformContext) -> Result<DynamicImage> {
+            let mut pixels = img.into_rgba8();
+            let (width, height) = pixels.dimensions();
+            let failed = |e: &dyn std::fmt::Display| {
+                anyhow::anyhow!(tr!(Msg::PluginFailed, self.name.display(), e))
+            };
+
+            let mut guard = self.instance.lock().unwrap_or_else(|e| e.into_inner());
+            let WasmInstance {
+                store,
+                memory,
+                alloc,
+                transform,
+            } = &mut *guard;
+
+            let len = i32::try_from(pixels.len() + self.options.len())
+                .map_err(|_| failed(&"image too large for a 32-bit plugin"))?;
+            let ptr = alloc.call(&mut *store, len).map_err(|e| failed(&e))?;
+            let options_ptr = ptr + pixels.len() as i32;
+            memory
+                .write(&mut *store, ptr as usize, &pixels)
+                .map_err(|e| failed(&e))?;
+            memory
+                .write(&mut *store, options_ptr as usize, &self.options)
+                .map_err(|e| failed(&e))?;
+
+            let params = (
+                ptr,
+                width as i32,
+                height as i32,
+                ctx.scale.unwrap_or(0) as i32,
+                options_ptr,
+                self.options.len() as i32,
+            );
+            let code = transform
+                .call(&mut *store, params)
+                .map_err(|e| failed(&e))?;
+            if code != 0 {
+                anyhow::bail!(tr!(Msg::PluginCode, self.name.display(), code));
+            }
+
+            memory
+                .read(&*store, ptr as usize, &mut pixels)
+                .map_err(|e| failed(&e))?;
+            Ok(DynamicImage::ImageRgba8(pixels))
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// src/preset.rs
+//
+// `--preset`: named setups for a kind of image or destination, for users who
+// know what the images are for rather than which settings suit that.
+
+use clap::ValueEnum;
+
+/// A named setup
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// Classify each image; screenshots, text and flat-color graphics are
+    /// written as lossless WebP, and as palette PNG instead of JPEG
+    Screenshot,
+    /// Every input at the sizes social networks show shared images at
+    Social,
+    /// Outputs every mail client shows, small enough to send: at most
+    /// `EMAIL_MAX_WIDTH` wide, baseline JPEG, PNG or GIF without metadata,
+    /// and `EMAIL_BUDGET` in all unless `--total-budget` says otherwise
+    Email,
+}
+
+impl Preset {
+    /// Name on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Screenshot => "screenshot",
+            Preset::Social => "social",
+            Preset::Email => "email",
+        }
+    }
+}
+
+/// Outputs of `--preset social` as (label, width, height): link previews
+/// for Open Graph and Twitter/X cards, Instagram's square and portrait
+/// posts, and a YouTube thumbnail
+pub const SOCIAL: [(&str, u32, u32); 5] = [
+    ("og", 1200, 630),
+    ("twitter", 1200, 600),
+    ("instagram", 1080, 1080),
+    ("instagram-portrait", 1080, 1350),
+    ("youtube", 1280, 720),
+];
+
+/// Widest output of `--preset email`: twice the usual 600px email column,
+/// for high-density screens
+pub const EMAIL_MAX_WIDTH: u32 = 1200;
+
+/// Default size of all `--preset email` outputs together
+pub const EMAIL_BUDGET: u64 = 1 << 20;
+
+/// Formats mail clients show; Outlook and older webmail show no WebP
+pub const EMAIL_FORMATS: [&str; 3] = ["jpg", "png", "gif"];
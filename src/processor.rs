@@ -1,105 +1,833 @@
 // src/processor.rs
 //
+use crate::collision::{self, OnCollision};
+use crate::decode::{self, Frame};
+use crate::dedupe::{self, DuplicateGroup};
+use crate::encode::{
+    AlphaMode, AnimationFrame, CaptureInfo, Channels, Classifier, Content, Dither, EncodeOptions,
+    LosslessJpeg, Metadata, QualityLevel, QualityPreset, Reduction, has_transparency, reduce,
+    route, save_apng, save_image, save_lossless_jpeg, save_tiled, supports_alpha, tiles_dir,
+};
+use crate::exit::{InvalidConfig, NoInputs, PartialFailure};
+use crate::hook::{ExecAfter, ExecDecode};
+use crate::i18n::{self, Msg, tr};
+use crate::lock::OutputLock;
+use crate::paths::{self, long_path};
+use crate::pipeline::{self, scaled_size};
+use crate::progress::{FileProgress, Progress};
+use crate::results::ResultsPipe;
+use crate::screen::{Fault, Screening};
+use crate::select::Selection;
+use crate::transform::Transforms;
+use crate::transform::script::{Script, Variant};
+use crate::transform::simulate::{self, Deficiency};
 use anyhow::{Context, Result};
-use image::{DynamicImage, ImageFormat};
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use clap::ValueEnum;
+use ignore::{WalkBuilder, WalkState};
+use image::{DynamicImage, GenericImageView};
 use owo_colors::OwoColorize;
+use rayon::iter::Either;
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Settings shared by every image in a batch
+pub struct ProcessOptions {
+    pub formats: Vec<String>,
+    pub scales: Vec<u32>,
+    pub encode: EncodeOptions,
+    pub output_dir: Option<PathBuf>,
+    pub passthrough: Passthrough,
+    /// Orient/crop 100% JPEG-to-JPEG variants in the DCT domain
+    pub lossless_jpeg: bool,
+    /// Drop metadata from losslessly transformed JPEGs
+    pub strip: bool,
+    /// Copy source EXIF/XMP into re-encoded JPEG, PNG and WebP outputs
+    pub keep_metadata: bool,
+    /// Whether inputs that would write the same outputs stop the batch or
+    /// get distinct names
+    pub on_collision: OnCollision,
+    /// Lock output directories so concurrent rsimg runs wait for each other
+    pub lock: bool,
+    /// Tell source formats by content rather than extension
+    pub sniff: bool,
+    /// Frame of animated inputs that static outputs are made from
+    pub frame: Frame,
+    /// Tile size outputs larger than one tile are split into
+    pub tile: Option<(u32, u32)>,
+    /// Threads decoding and resizing (default: global pool size)
+    pub decode_threads: Option<usize>,
+    /// Threads encoding and writing outputs (default: global pool size)
+    pub encode_threads: Option<usize>,
+    /// Transforms applied to each source before scaling
+    pub transforms: Transforms,
+    /// `--pipeline` chain, replacing `formats` and `scales`
+    pub script: Option<Script>,
+    /// Color vision deficiencies every output is also written as seen
+    /// with (`--simulate`)
+    pub simulate: Vec<Deficiency>,
+    /// `{seq}` of the first input in path order
+    pub start_number: u32,
+    /// Write text and flat-color images losslessly: WebP as lossless WebP
+    /// and JPEG as a palette PNG (`--preset screenshot`)
+    pub route_graphics: bool,
+    /// Decides what inputs show, for `route_graphics` and `classify`
+    pub classifier: Classifier,
+    /// Report the class of every input (`--classify`)
+    pub classify: bool,
+    /// Resize from an embedded JPEG preview when it's large enough
+    /// (`--use-embedded-preview`); plain `scales` batches only
+    pub embedded_preview: bool,
+    /// Where every input is recorded as it finishes (`--history`)
+    pub log: Option<Arc<FileLog>>,
+    /// Where every input is reported as it finishes (`--results-fifo`)
+    pub results: Option<Arc<ResultsPipe>>,
+    /// Command run for every output (`--exec-after`)
+    pub exec_after: Option<Arc<ExecAfter>>,
+    /// Tool converting inputs that don't decode (`--exec-decode`)
+    pub exec_decode: Option<Arc<ExecDecode>>,
+    /// Read every output back after writing it; one that doesn't decode
+    /// fails its input (`--verify`)
+    pub verify: bool,
+    /// Leave out inputs carrying the marker of an earlier rsimg run
+    /// (`--skip-marked`)
+    pub skip_marked: bool,
+    /// Ratings and keywords inputs must have (`--min-rating`, `--keyword`)
+    pub select: Selection,
+    /// Sharpness and exposure inputs must pass (`--skip-blurry`)
+    pub screen: Option<Screening>,
+    /// How much input the batch takes at most
+    pub limits: Limits,
+    /// Order inputs are decoded in; names and `{seq}` keep path order
+    pub order: Order,
+}
+
+impl Default for ProcessOptions {
+    /// The command-line defaults
+    fn default() -> Self {
+        Self {
+            formats: vec!["jpg".to_string(), "webp".to_string()],
+            scales: vec![75, 50, 25],
+            encode: EncodeOptions::default(),
+            output_dir: None,
+            passthrough: Passthrough::Off,
+            lossless_jpeg: false,
+            strip: false,
+            keep_metadata: false,
+            on_collision: OnCollision::Error,
+            lock: true,
+            sniff: false,
+            frame: Frame::First,
+            tile: None,
+            decode_threads: None,
+            encode_threads: None,
+            transforms: Transforms::default(),
+            script: None,
+            simulate: Vec::new(),
+            start_number: 1,
+            route_graphics: false,
+            classifier: Classifier::default(),
+            classify: false,
+            embedded_preview: false,
+            log: None,
+            results: None,
+            exec_after: None,
+            exec_decode: None,
+            verify: false,
+            skip_marked: false,
+            select: Selection::default(),
+            screen: None,
+            limits: Limits::default(),
+            order: Order::Path,
+        }
+    }
+}
+
+/// Caps on how much input a batch takes (`--limit-files`, `--limit-bytes`),
+/// for working through a large collection over several runs; inputs past
+/// them are left out
+#[derive(Clone, Copy, Default)]
+pub struct Limits {
+    pub files: Option<usize>,
+    /// Total size of the input files
+    pub bytes: Option<u64>,
+}
+
+/// Input taken so far against `Limits`
+struct Quota {
+    limits: Limits,
+    /// Files and bytes
+    taken: Mutex<(usize, u64)>,
+}
+
+impl Quota {
+    fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            taken: Mutex::new((0, 0)),
+        }
+    }
+
+    /// Takes `group` while neither limit has been reached, so the input
+    /// crossing one is the last processed; duplicates come with their primary
+    fn admit(&self, group: &DuplicateGroup) -> bool {
+        let Limits { files, bytes } = self.limits;
+        if files.is_none() && bytes.is_none() {
+            return true;
+        }
+        let count = 1 + group.duplicates.len();
+        let size = match bytes {
+            Some(_) => std::fs::metadata(&group.primary).map_or(0, |m| m.len()),
+            None => 0,
+        };
+        let mut taken = self.taken.lock().unwrap_or_else(|e| e.into_inner());
+        if files.is_some_and(|max| taken.0 >= max) || bytes.is_some_and(|max| taken.1 >= max) {
+            return false;
+        }
+        taken.0 += count;
+        taken.1 += size * count as u64;
+        true
+    }
+}
+
+/// Inputs a batch left out, for the notes at its end
+#[derive(Default)]
+struct LeftOut {
+    /// Already optimized by rsimg (`skip_marked`)
+    marked: AtomicUsize,
+    /// Without the rating or keywords of `select`
+    unselected: AtomicUsize,
+    /// Failing `screen`, with why
+    screened: Mutex<Vec<(PathBuf, Fault)>>,
+    /// Past the batch's `limits`
+    limited: AtomicUsize,
+}
+
+/// How one input of a batch went
+pub struct FileRecord {
+    pub input: PathBuf,
+    pub input_bytes: u64,
+    /// Outputs written for it, with their sizes; tiled outputs count as 0
+    pub outputs: Vec<(PathBuf, u64)>,
+    /// From the start of decoding to the last output; 0 for duplicates,
+    /// whose outputs are links to the primary's
+    pub elapsed: Duration,
+    pub error: Option<String>,
+}
+
+impl FileRecord {
+    fn new(
+        input: &Path,
+        outputs: &[PathBuf],
+        elapsed: Duration,
+        error: Option<&anyhow::Error>,
+    ) -> Self {
+        let size = |path: &Path| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        Self {
+            input: input.to_path_buf(),
+            input_bytes: size(input),
+            outputs: outputs
+                .iter()
+                .map(|path| (path.clone(), size(path)))
+                .collect(),
+            elapsed,
+            error: error.map(|err| format!("{err:#}")),
+        }
+    }
+}
+
+/// Hands a finished input to the batch's `log`, `results` and `exec_after`
+fn report(
+    opts: &ProcessOptions,
+    input: &Path,
+    outputs: &[PathBuf],
+    elapsed: Duration,
+    error: Option<&anyhow::Error>,
+) {
+    if let Some(hook) = &opts.exec_after
+        && error.is_none()
+    {
+        hook.queue(input, outputs);
+    }
+    if opts.log.is_none() && opts.results.is_none() {
+        return;
+    }
+    let record = FileRecord::new(input, outputs, elapsed, error);
+    if let Some(results) = &opts.results {
+        results.write(&record);
+    }
+    if let Some(log) = &opts.log {
+        log.push(record);
+    }
+}
+
+/// Inputs finished so far, shared with whoever started the batch
+#[derive(Default)]
+pub struct FileLog(Mutex<Vec<FileRecord>>);
+
+impl FileLog {
+    /// Removes and returns the records collected so far
+    pub fn take(&self) -> Vec<FileRecord> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn push(&self, record: FileRecord) {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(record);
+    }
+}
+
+/// One `--jobs` row: a source with its own crop, sizes, formats and names
+pub struct Job {
+    pub input: PathBuf,
+    /// Crop, resizes and encodes for this source
+    pub script: Script,
+    /// Output name template (`{stem}`, `{seq}`, `{scale}`, `{width}`,
+    /// `{height}`, EXIF tokens), without the extension
+    pub output: Option<String>,
+    /// What the source shows, when the rule that picked the job already
+    /// classified it
+    pub content: Option<Content>,
+}
+
+/// What to do with variants the source already satisfies (100% scale, same format)
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Passthrough {
+    /// Always decode and re-encode
+    Off,
+    /// Hard-link the source, falling back to a copy across filesystems
+    Link,
+    /// Copy the source byte-for-byte
+    Copy,
+}
+
+/// Order a batch's inputs are started in
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Order {
+    /// Path order
+    #[default]
+    Path,
+    /// Largest files first, so a huge one doesn't run alone at the end
+    LargestFirst,
+    /// Smallest files first, for many outputs early
+    SmallestFirst,
+}
+
+/// Where a batch's images come from
+pub enum Inputs<'a> {
+    /// Files known up front: identical ones are decoded once, colliding
+    /// output names follow `on_collision` and `{seq}` numbers them in path
+    /// order
+    Files(Vec<PathBuf>),
+    /// The images of an input, processed as a parallel directory scan finds
+    /// them (`--stream`)
+    ///
+    /// Every file is an entry of its own: identical files aren't decoded
+    /// once, `{seq}` has no path order to number by, and a file whose outputs
+    /// would overwrite those of one found earlier fails instead of being
+    /// renamed. Only the output directory, or the input itself, is locked.
+    Scan(&'a Path, &'a ScanOptions),
+}
+
+/// What a batch did
+pub struct Processed {
+    /// Inputs, found by the scan for `Inputs::Scan`
+    pub files: usize,
+    /// Path of every output, named as if untiled
+    pub outputs: Vec<PathBuf>,
+}
+
+/// One input of a batch, with its `--jobs` row
+type BatchEntry<'a> = (DuplicateGroup, Option<&'a Job>);
 
 /// Processes all images in parallel, handling errors and progress display
+///
+/// Work runs in two stages on separate Rayon pools: decode (load + resize)
+/// and encode (format encoding + write). Inputs reach the decoders through
+/// a bounded queue and resized variants the encoders through a bounded
+/// channel, so a batch holds a few inputs at a time however many it has,
+/// slow encoders can't starve decoding, and decoding can't run arbitrarily
+/// far ahead of the encoders.
 pub fn process_all(
-    files: Vec<PathBuf>,
-    formats: &[String],
-    scales: &[u32],
-    quality: u8,
-    output_dir: Option<&PathBuf>,
-    mp: &MultiProgress,
-) -> Result<()> {
-    // Total operations per image (scales * formats)
-    let operations_per_image = (formats.len() * scales.len()) as u64;
-
-    // Parallel processing using Rayon
-    let results: Vec<Result<()>> = files
-        .par_iter()
-        .map(|path| {
-            // Create a progress bar for each file
-            let pb = if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                let pb = mp.add(ProgressBar::new(operations_per_image));
-                pb.set_style(
-                    ProgressStyle::with_template(
-                        "  {msg:40} [{bar:40.cyan/blue}] {pos:>2}/{len:2}",
-                    )
-                    .unwrap()
-                    .progress_chars("━━╾─"),
-                );
-
-                // Truncate filename if too long for display
-                let display_name = if name.len() > 35 {
-                    format!("{}...{}", &name[..20], &name[name.len() - 12..])
-                } else {
-                    name.to_string()
-                };
+    inputs: Inputs,
+    opts: &ProcessOptions,
+    progress: &Progress,
+) -> Result<Processed> {
+    match inputs {
+        Inputs::Files(files) => {
+            let total = files.len();
+            // Identical sources are decoded once and their outputs shared
+            let entries = dedupe::group_duplicates(files)
+                .into_iter()
+                .map(|group| (group, None))
+                .collect();
+            run_batch(entries, total, opts, progress)
+        }
+        Inputs::Scan(input, scan) => run_scan(input, scan, opts, progress),
+    }
+}
 
-                pb.set_message(format!("📄 {}", display_name.bright_white()));
-                Some(pb)
-            } else {
-                None
+/// Processes `--jobs` rows in one batch, each with its own settings
+///
+/// Rows aren't deduplicated: the same source may appear with different crops.
+pub fn process_jobs(jobs: &[Job], opts: &ProcessOptions, progress: &Progress) -> Result<Processed> {
+    let entries = jobs
+        .iter()
+        .map(|job| {
+            let group = DuplicateGroup {
+                primary: job.input.clone(),
+                duplicates: Vec::new(),
             };
+            (group, Some(job))
+        })
+        .collect();
+    run_batch(entries, jobs.len(), opts, progress)
+}
 
-            // Process the image with progress tracking
-            let result = process_single_with_progress(
-                path,
-                formats,
-                scales,
-                quality,
-                output_dir,
-                pb.as_ref(),
-            );
-
-            // Finish progress bar with success/failure
-            if let Some(pb) = &pb {
-                if result.is_ok() {
-                    let name = path
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .map(|n| {
-                            if n.len() > 35 {
-                                format!("{}...{}", &n[..20], &n[n.len() - 12..])
-                            } else {
-                                n.to_string()
-                            }
-                        })
-                        .unwrap_or("unknown".to_string());
-
-                    pb.finish_with_message(format!("  ✓ {}", name.green()));
-                } else {
-                    let name = path
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .map(|n| {
-                            if n.len() > 35 {
-                                format!("{}...{}", &n[..20], &n[n.len() - 12..])
-                            } else {
-                                n.to_string()
-                            }
-                        })
-                        .unwrap_or("unknown".to_string());
-
-                    pb.finish_with_message(format!("  ✗ {}", name.red()));
-                }
-            }
+/// `entries` without those whose primary `keep` refuses, and how many
+/// inputs, duplicates included, that leaves out
+fn retain(
+    entries: Vec<BatchEntry>,
+    keep: impl Fn(&Path) -> bool + Sync,
+) -> (Vec<BatchEntry>, usize) {
+    let (kept, dropped): (Vec<BatchEntry>, Vec<BatchEntry>) = entries
+        .into_par_iter()
+        .partition(|(group, _)| keep(&group.primary));
+    let left: usize = dropped
+        .iter()
+        .map(|(group, _)| 1 + group.duplicates.len())
+        .sum();
+    (kept, left)
+}
 
-            result
+/// `entries` with only the inputs `selection` admits, and how many it
+/// leaves out; duplicates are judged on their own, having their own
+/// sidecars
+fn select<'a>(entries: Vec<BatchEntry<'a>>, selection: &Selection) -> (Vec<BatchEntry<'a>>, usize) {
+    let left = AtomicUsize::new(0);
+    let entries = entries
+        .into_par_iter()
+        .filter_map(|(group, job)| {
+            let count = 1 + group.duplicates.len();
+            let mut admitted = std::iter::once(group.primary)
+                .chain(group.duplicates)
+                .filter(|path| selection.admits(path));
+            let primary = admitted.next();
+            let duplicates: Vec<PathBuf> = admitted.collect();
+            let taken = primary.is_some() as usize + duplicates.len();
+            left.fetch_add(count - taken, Ordering::Relaxed);
+            Some((
+                DuplicateGroup {
+                    primary: primary?,
+                    duplicates,
+                },
+                job,
+            ))
         })
         .collect();
+    (entries, left.into_inner())
+}
+
+/// `entries` without those whose primary fails `screening`, and every
+/// input, duplicates included, that leaves out with its fault
+fn screen(
+    entries: Vec<BatchEntry>,
+    screening: Screening,
+) -> (Vec<BatchEntry>, Vec<(PathBuf, Fault)>) {
+    let (kept, failed): (Vec<BatchEntry>, Vec<Vec<(PathBuf, Fault)>>) = entries
+        .into_par_iter()
+        .partition_map(|(group, job)| match screening.fault(&group.primary) {
+            None => Either::Left((group, job)),
+            Some(fault) => Either::Right(
+                std::iter::once(group.primary)
+                    .chain(group.duplicates)
+                    .map(|path| (path, fault))
+                    .collect(),
+            ),
+        });
+    (kept, failed.into_iter().flatten().collect())
+}
+
+fn run_batch(
+    entries: Vec<BatchEntry>,
+    total: usize,
+    opts: &ProcessOptions,
+    progress: &Progress,
+) -> Result<Processed> {
+    // Duplicates share the primary's contents, and so its marker
+    let (entries, marked) = match opts.skip_marked {
+        true => retain(entries, |path| !decode::is_marked(path)),
+        false => (entries, 0),
+    };
+    let (entries, unselected) = match opts.select.is_all() {
+        true => (entries, 0),
+        false => select(entries, &opts.select),
+    };
+    let (entries, screened) = match opts.screen {
+        Some(screening) => screen(entries, screening),
+        None => (entries, Vec::new()),
+    };
+    // Taken in path order, so the next run picks up where this one stopped
+    let quota = Quota::new(opts.limits);
+    let (entries, limited): (Vec<BatchEntry>, Vec<BatchEntry>) = entries
+        .into_iter()
+        .partition(|(group, _)| quota.admit(group));
+    let limited: usize = limited
+        .iter()
+        .map(|(group, _)| 1 + group.duplicates.len())
+        .sum();
+    let total = total - marked - unselected - screened.len() - limited;
+    // Inputs sharing a stem would overwrite each other's outputs
+    let inputs: Vec<&Path> = entries
+        .iter()
+        .flat_map(|(group, _)| std::iter::once(&group.primary).chain(&group.duplicates))
+        .map(PathBuf::as_path)
+        .collect();
+    let names = InputNames {
+        stems: collision::output_stems(&inputs, opts.output_dir.as_deref(), opts.on_collision)?,
+        seqs: sequence_numbers(&inputs, opts.start_number),
+    };
+
+    // Held until every output of the batch is written
+    let _lock = if opts.lock {
+        let dirs = inputs
+            .iter()
+            .map(|path| output_parent(path, opts.output_dir.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        Some(OutputLock::acquire(dirs.iter().map(PathBuf::as_path))?)
+    } else {
+        None
+    };
 
+    let batch = tracing::info_span!("batch", files = total);
+    let _batch = batch.enter();
+
+    let steps = entries
+        .iter()
+        .map(|(group, job)| (1 + group.duplicates.len() as u64) * operations(opts, *job))
+        .sum();
+    progress.batch(total, steps);
+
+    // Decoders take entries off the queue in the order they're sent
+    let entries = match opts.order {
+        Order::Path => entries,
+        order => {
+            let size = |path: &Path| std::fs::metadata(path).map_or(0, |m| m.len());
+            let mut sized: Vec<(u64, BatchEntry)> = entries
+                .into_par_iter()
+                .map(|entry| (size(&entry.0.primary), entry))
+                .collect();
+            match order {
+                Order::SmallestFirst => sized.sort_by_key(|(size, _)| *size),
+                _ => sized.sort_by_key(|(size, _)| std::cmp::Reverse(*size)),
+            }
+            sized.into_iter().map(|(_, entry)| entry).collect()
+        }
+    };
+
+    let feed = |queue: SyncSender<_>| {
+        for entry in entries {
+            // The decode stage only stops reading on a panic
+            if queue.send(entry).is_err() {
+                break;
+            }
+        }
+        Ok(total)
+    };
+    let left_out = LeftOut {
+        marked: AtomicUsize::new(marked),
+        unselected: AtomicUsize::new(unselected),
+        screened: Mutex::new(screened),
+        limited: AtomicUsize::new(limited),
+    };
+    run_stages(feed, &names, |_| Ok(()), opts, progress, &batch, &left_out)
+}
+
+/// Processes the images of a directory scan as they're found
+/// (`Inputs::Scan`)
+fn run_scan(
+    input: &Path,
+    scan: &ScanOptions,
+    opts: &ProcessOptions,
+    progress: &Progress,
+) -> Result<Processed> {
+    let output_name = opts.script.as_ref().and_then(Script::output_name);
+    if output_name.is_some_and(|name| name.contains("{seq}")) {
+        return Err(InvalidConfig(tr!(Msg::StreamSeq)).into());
+    }
+
+    let _lock = match opts.lock {
+        true => Some(OutputLock::acquire([opts
+            .output_dir
+            .as_deref()
+            .unwrap_or(input)])?),
+        false => None,
+    };
+
+    let batch = tracing::info_span!("batch", streamed = true);
+    let _batch = batch.enter();
+
+    // Walker threads block on a full queue, so the scan keeps only a little
+    // ahead of the decoders
+    let left_out = LeftOut::default();
+    let quota = Quota::new(opts.limits);
+    let feed = |queue: SyncSender<_>| {
+        let count = AtomicUsize::new(0);
+        walk_image_files(input, scan, |path| {
+            if opts.skip_marked && decode::is_marked(&path) {
+                left_out.marked.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            if !opts.select.admits(&path) {
+                left_out.unselected.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            if let Some(fault) = opts.screen.and_then(|screening| screening.fault(&path)) {
+                let mut screened = left_out.screened.lock().unwrap_or_else(|e| e.into_inner());
+                screened.push((path, fault));
+                return;
+            }
+            let group = DuplicateGroup {
+                primary: path,
+                duplicates: Vec::new(),
+            };
+            // Inputs are taken in the order the scan finds them
+            if !quota.admit(&group) {
+                left_out.limited.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            count.fetch_add(1, Ordering::Relaxed);
+            let _ = queue.send((group, None));
+        })?;
+        let count = count.into_inner();
+        progress.batch(count, count as u64 * operations(opts, None));
+        Ok(count)
+    };
+
+    // First input of each output name, for telling collisions apart
+    let claimed: Mutex<HashMap<_, PathBuf>> = Mutex::new(HashMap::new());
+    let check = |path: &Path| {
+        let Some(key) = collision::output_key(path, opts.output_dir.as_deref()) else {
+            return Ok(());
+        };
+        let mut claimed = claimed.lock().unwrap_or_else(|e| e.into_inner());
+        match claimed.entry(key) {
+            Entry::Occupied(first) => Err(InvalidConfig(tr!(
+                Msg::StreamCollision,
+                path.display(),
+                Path::display(first.get())
+            ))
+            .into()),
+            Entry::Vacant(slot) => {
+                slot.insert(path.to_path_buf());
+                Ok(())
+            }
+        }
+    };
+    run_stages(
+        feed,
+        &InputNames::default(),
+        check,
+        opts,
+        progress,
+        &batch,
+        &left_out,
+    )
+}
+
+/// Runs the decode and encode stages over the entries `feed` sends, which
+/// returns how many there were; `check` can fail an entry before it's
+/// decoded, and `left_out` counts the inputs that weren't sent
+///
+/// The queue holds twice as many entries as there are decoders: `feed`
+/// blocks while it's full, and the first entry is decoded as soon as it's
+/// sent.
+fn run_stages<'a>(
+    feed: impl FnOnce(SyncSender<BatchEntry<'a>>) -> Result<usize> + Send,
+    names: &InputNames,
+    check: impl Fn(&Path) -> Result<()> + Sync,
+    opts: &ProcessOptions,
+    progress: &Progress,
+    batch: &tracing::Span,
+    left_out: &LeftOut,
+) -> Result<Processed> {
+    let decode_pool = build_pool(opts.decode_threads, "decode")?;
+    let encode_pool = build_pool(opts.encode_threads, "encode")?;
+
+    // Inputs waiting for a decoder
+    let (queue, entries) = mpsc::sync_channel::<BatchEntry>(decode_pool.current_num_threads() * 2);
+    // Resized variants waiting for an encoder
+    let (tx, rx) = mpsc::sync_channel::<EncodeJob>(encode_pool.current_num_threads() * 2);
+    let errors = Mutex::new(Vec::new());
+    let outputs = Outputs::default();
+
+    let total = std::thread::scope(|scope| {
+        let errors = &errors;
+        let outputs = &outputs;
+        let encode_pool = &encode_pool;
+
+        // Feed: ends the decode stage's input when done
+        let feeder = scope.spawn(move || feed(queue));
+
+        // Encode stage: drains the channel until every decoder is done
+        scope.spawn(move || {
+            encode_pool.install(|| {
+                rx.into_iter()
+                    .par_bridge()
+                    .for_each(|job| job.run(opts, progress, errors))
+            })
+        });
+
+        // Decode stage: feeds the channel, one task per distinct input
+        decode_pool.install(|| {
+            entries
+                .into_iter()
+                .par_bridge()
+                .for_each_with(tx, |tx, (group, job)| {
+                    // Files not started before a cancel are left alone
+                    if progress.cancelled() {
+                        return;
+                    }
+                    let check = check(&group.primary);
+                    let span =
+                        tracing::info_span!(parent: batch, "file", path = %group.primary.display());
+                    let task = FileTask::new(group, job, names, outputs, opts, progress, span);
+                    run_task(task, check, opts, progress, errors, tx);
+                })
+        });
+        feeder
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })?;
+
+    // `--exec-after` commands may still be running for the last outputs
+    let mut errors = errors;
+    if let Some(hook) = &opts.exec_after {
+        let failures = hook.wait();
+        errors
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(failures);
+    }
+    Ok(Processed {
+        files: total,
+        outputs: summarize(outputs, errors, total, left_out, opts, progress)?,
+    })
+}
+
+/// Submits a file's variants for encoding, unless `check` already failed
+/// it, and lets go of the decode stage's hold on it
+fn run_task<'a>(
+    task: FileTask<'a>,
+    check: Result<()>,
+    opts: &ProcessOptions,
+    progress: &Progress,
+    errors: &Mutex<Vec<anyhow::Error>>,
+    tx: &SyncSender<EncodeJob<'a>>,
+) {
+    let task = Arc::new(task);
+    let submitted = check.and_then(|()| task.span.in_scope(|| submit_variants(&task, opts, tx)));
+    if let Err(err) = submitted {
+        task.fail(err);
+    }
+    task.release(opts, progress, errors);
+}
+
+/// Ends a batch of `total` inputs, `left_out` more having been left out:
+/// notes on what was written differently than asked, then the errors;
+/// returns every output path
+fn summarize(
+    outputs: Outputs,
+    errors: Mutex<Vec<anyhow::Error>>,
+    total: usize,
+    left_out: &LeftOut,
+    opts: &ProcessOptions,
+    progress: &Progress,
+) -> Result<Vec<PathBuf>> {
     // Collect all errors
-    let errors: Vec<_> = results.into_iter().filter_map(|r| r.err()).collect();
+    let errors = errors.into_inner().unwrap_or_else(|e| e.into_inner());
+    progress.finish(total, errors.len());
+
+    // Notes on what was written differently than asked
+    let skipped = outputs.skipped.lock().unwrap_or_else(|e| e.into_inner());
+    for (path, format) in skipped.iter() {
+        eprintln!(
+            "  {} {}",
+            "ⓘ".cyan(),
+            tr!(Msg::AlphaSkipped, format, path.display())
+        );
+    }
+    let gray = outputs.gray.load(Ordering::Relaxed);
+    let opaque = outputs.opaque.load(Ordering::Relaxed);
+    if gray + opaque > 0 {
+        eprintln!(
+            "  {} {}",
+            "ⓘ".cyan(),
+            tr!(Msg::ChannelsReduced, gray, opaque)
+        );
+    }
+    let [photos, graphics, text] = outputs
+        .classes
+        .each_ref()
+        .map(|n| n.load(Ordering::Relaxed));
+    if opts.classify {
+        eprintln!(
+            "  {} {}",
+            "ⓘ".cyan(),
+            tr!(Msg::Classified, photos, graphics, text)
+        );
+    }
+    if opts.route_graphics && graphics + text > 0 {
+        eprintln!(
+            "  {} {}",
+            "ⓘ".cyan(),
+            tr!(Msg::GraphicsRouted, graphics + text)
+        );
+    }
+    let previews = outputs.previews.load(Ordering::Relaxed);
+    if previews > 0 {
+        eprintln!("  {} {}", "ⓘ".cyan(), tr!(Msg::PreviewsUsed, previews));
+    }
+    let marked = left_out.marked.load(Ordering::Relaxed);
+    if marked > 0 {
+        eprintln!("  {} {}", "ⓘ".cyan(), tr!(Msg::MarkedSkipped, marked));
+    }
+    let unselected = left_out.unselected.load(Ordering::Relaxed);
+    if unselected > 0 {
+        eprintln!("  {} {}", "ⓘ".cyan(), tr!(Msg::Unselected, unselected));
+    }
+    let mut screened = left_out.screened.lock().unwrap_or_else(|e| e.into_inner());
+    if !screened.is_empty() {
+        screened.sort_by(|a, b| a.0.cmp(&b.0));
+        eprintln!("  {} {}", "ⓘ".cyan(), tr!(Msg::Screened, screened.len()));
+        for (path, fault) in screened.iter() {
+            eprintln!("      {}: {fault}", path.display());
+        }
+    }
+    let limited = left_out.limited.load(Ordering::Relaxed);
+    if limited > 0 {
+        eprintln!("  {} {}", "ⓘ".cyan(), tr!(Msg::LimitReached, limited));
+    }
+    let elided = outputs.elided.load(Ordering::Relaxed);
+    if elided > 0 {
+        eprintln!("  {} {}", "ⓘ".cyan(), tr!(Msg::RepeatsElided, elided));
+    }
 
     // Report any errors encountered during processing
     if !errors.is_empty() {
-        eprintln!("\n{} Errors during processing:", "⚠️ ".yellow().bold());
+        eprintln!(
+            "\n{} {}",
+            "⚠️ ".yellow().bold(),
+            i18n::text(Msg::ErrorsHeader)
+        );
         for (i, err) in errors.iter().enumerate() {
             eprintln!(
                 "  {}. {}",
@@ -108,131 +836,1146 @@ pub fn process_all(
             );
         }
         eprintln!();
-        anyhow::bail!("{} images were not processed correctly", errors.len());
+        if errors.len() >= total {
+            anyhow::bail!(tr!(Msg::NoneProcessed, total));
+        }
+        return Err(PartialFailure(errors.len()).into());
     }
 
-    Ok(())
+    Ok(outputs
+        .paths
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner()))
 }
 
-/// Processes a single image, resizing and saving to all specified formats,
-/// and updating the progress bar incrementally
-fn process_single_with_progress(
-    path: &Path,
-    formats: &[String],
-    scales: &[u32],
-    quality: u8,
-    output_dir: Option<&PathBuf>,
-    pb: Option<&ProgressBar>,
-) -> Result<()> {
-    // Load the image from disk
-    let img =
-        image::open(path).with_context(|| format!("Failed to open image: {}", path.display()))?;
+/// Builds a dedicated thread pool, sized like the global pool unless overridden
+fn build_pool(threads: Option<usize>, stage: &'static str) -> Result<ThreadPool> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads.unwrap_or_else(rayon::current_num_threads))
+        .thread_name(move |i| format!("rsimg-{stage}-{i}"))
+        .build()
+        .with_context(|| format!("Failed to configure {stage} thread pool"))
+}
 
-    // Extract filename without extension
-    let stem = path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .ok_or_else(|| anyhow::anyhow!("Invalid filename: {}", path.display()))?;
+/// What a batch wrote, and what it left out
+#[derive(Default)]
+struct Outputs {
+    /// Every output path of the batch
+    paths: Mutex<Vec<PathBuf>>,
+    /// Inputs and the formats `--alpha skip` left out for them
+    skipped: Mutex<Vec<(PathBuf, String)>>,
+    /// Outputs written single-channel because their content is gray
+    gray: AtomicUsize,
+    /// Outputs written without their fully opaque alpha channel
+    opaque: AtomicUsize,
+    /// Outputs left out for repeating another variant's size and format
+    elided: AtomicUsize,
+    /// Inputs classified as photos, graphics and text
+    classes: [AtomicUsize; 3],
+    /// Inputs resized from an embedded preview
+    previews: AtomicUsize,
+}
 
-    // Determine output directory (user-specified or same as input)
-    let output_parent = if let Some(out_dir) = output_dir {
-        out_dir.clone()
-    } else {
-        path.parent()
-            .ok_or_else(|| anyhow::anyhow!("Cannot determine parent directory"))?
-            .to_path_buf()
+impl Outputs {
+    fn classified(&self, content: Content) {
+        let class = Content::ALL.iter().position(|&c| c == content);
+        if let Some(count) = class.and_then(|class| self.classes.get(class)) {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn reduced(&self, reduction: Reduction) {
+        if reduction.gray {
+            self.gray.fetch_add(1, Ordering::Relaxed);
+        }
+        if reduction.alpha {
+            self.opaque.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// What the outputs of each input of a batch are named after
+#[derive(Default)]
+struct InputNames {
+    /// Output stems of inputs renamed to avoid collisions
+    stems: HashMap<PathBuf, String>,
+    /// `{seq}` of every input
+    seqs: HashMap<PathBuf, u32>,
+}
+
+/// Per-input bookkeeping shared between the decode and encode stages
+struct FileTask<'a> {
+    group: DuplicateGroup,
+    /// Per-file settings overriding the batch's
+    job: Option<&'a Job>,
+    names: &'a InputNames,
+    outputs: &'a Outputs,
+    progress: FileProgress,
+    /// Outstanding encode jobs, plus one hold owned by the decode stage
+    pending: AtomicUsize,
+    /// First error hit by any stage; later jobs for this file are skipped
+    error: Mutex<Option<anyhow::Error>>,
+    /// Output paths of the primary, for linking duplicates, with the name
+    /// template they were filled from less the per-input tokens
+    written: Mutex<Vec<(PathBuf, Option<String>)>>,
+    /// Source EXIF/XMP for the outputs (`keep_metadata`)
+    metadata: Option<Arc<Metadata>>,
+    /// Capture details for output names, read on first use
+    capture: OnceLock<CaptureInfo>,
+    /// What the source shows, classified on first use
+    content: OnceLock<Content>,
+    /// Parent of the spans of every stage working on this file
+    span: tracing::Span,
+    started: Instant,
+}
+
+impl<'a> FileTask<'a> {
+    fn new(
+        group: DuplicateGroup,
+        job: Option<&'a Job>,
+        names: &'a InputNames,
+        outputs: &'a Outputs,
+        opts: &ProcessOptions,
+        progress: &Progress,
+        span: tracing::Span,
+    ) -> Self {
+        Self {
+            job,
+            names,
+            outputs,
+            progress: progress.start(&group.primary, operations(opts, job)),
+            pending: AtomicUsize::new(1),
+            error: Mutex::new(None),
+            written: Mutex::new(Vec::new()),
+            metadata: opts
+                .keep_metadata
+                .then(|| decode::load_metadata(&group.primary))
+                .flatten()
+                .map(Arc::new),
+            capture: OnceLock::new(),
+            content: OnceLock::new(),
+            group,
+            span,
+            started: Instant::now(),
+        }
+    }
+
+    /// Capture details of the primary, from the metadata already read for
+    /// `keep_metadata` or the file itself
+    fn capture(&self) -> &CaptureInfo {
+        self.capture.get_or_init(|| match &self.metadata {
+            Some(metadata) => metadata.capture_info(),
+            None => decode::load_metadata(&self.group.primary)
+                .map(|metadata| metadata.capture_info())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// What the decoded source `img` shows, unless the rule picking the
+    /// file's job already classified it; counted for the summary
+    fn content(&self, img: &DynamicImage, opts: &ProcessOptions) -> Result<Content> {
+        if let Some(content) = self.content.get() {
+            return Ok(*content);
+        }
+        let content = match self.job.and_then(|job| job.content) {
+            Some(content) => content,
+            None => opts.classifier.classify(img)?,
+        };
+        if self.content.set(content).is_ok() {
+            self.outputs.classified(content);
+            if opts.classify {
+                self.progress.classified(content.name());
+            }
+        }
+        Ok(content)
+    }
+
+    /// Stem the outputs of `path` (the primary or a duplicate) are named after
+    fn stem<'p>(&'p self, path: &'p Path) -> Result<&'p str> {
+        match self.names.stems.get(path) {
+            Some(stem) => Ok(stem),
+            None => file_stem(path),
+        }
+    }
+
+    /// `{seq}` of `path` (the primary or a duplicate)
+    fn seq(&self, path: &Path) -> u32 {
+        self.names.seqs.get(path).copied().unwrap_or_default()
+    }
+
+    fn failed(&self) -> bool {
+        self.error.lock().map(|e| e.is_some()).unwrap_or(true)
+    }
+
+    fn fail(&self, err: anyhow::Error) {
+        if let Ok(mut slot) = self.error.lock() {
+            slot.get_or_insert(err);
+        }
+    }
+
+    fn inc(&self) {
+        self.progress.inc();
+    }
+
+    /// Notes an output of the primary, named after `name` if templated
+    fn output(&self, path: &Path, name: Option<String>) {
+        if let Ok(mut written) = self.written.lock() {
+            written.push((path.to_path_buf(), name));
+        }
+        if let Ok(mut outputs) = self.outputs.paths.lock() {
+            outputs.push(path.to_path_buf());
+        }
+    }
+
+    /// Leaves out the primary's outputs in `format` (`--alpha skip`)
+    fn skip(&self, format: &str) {
+        if let Ok(mut skipped) = self.outputs.skipped.lock() {
+            skipped.push((self.group.primary.clone(), format.to_lowercase()));
+        }
+        self.inc();
+    }
+
+    /// Leaves out `output`, which would repeat `same_as` byte for byte;
+    /// reported only when it would have had a name of its own
+    fn elide(&self, output: &Path, same_as: &Path) {
+        if output != same_as {
+            self.progress.elided(output, same_as);
+            self.outputs.elided.fetch_add(1, Ordering::Relaxed);
+        }
+        self.inc();
+    }
+
+    /// Drops one pending hold; whoever releases the last one finishes the file
+    fn release(
+        &self,
+        opts: &ProcessOptions,
+        progress: &Progress,
+        errors: &Mutex<Vec<anyhow::Error>>,
+    ) {
+        if self.pending.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+
+        let error = self.error.lock().ok().and_then(|mut e| e.take());
+        let primary = &self.group.primary;
+        self.progress.finish(primary, error.as_ref());
+
+        // Duplicates reuse the primary's outputs, or share its failure
+        let written = std::mem::take(&mut *self.written.lock().unwrap_or_else(|e| e.into_inner()));
+        let paths: Vec<PathBuf> = written.iter().map(|(path, _)| path.clone()).collect();
+        report(
+            opts,
+            primary,
+            &paths,
+            self.started.elapsed(),
+            error.as_ref(),
+        );
+        let mut failures = Vec::new();
+        for duplicate in &self.group.duplicates {
+            let dup_progress = progress.start(duplicate, operations(opts, None));
+            let dup_result = match &error {
+                None => link_duplicate_outputs(self, duplicate, &written, opts, &dup_progress),
+                Some(_) => Err(anyhow::anyhow!(tr!(
+                    Msg::DuplicateSkipped,
+                    duplicate.display()
+                ))),
+            };
+            dup_progress.finish(duplicate, dup_result.as_ref().err());
+            let (paths, error) = match &dup_result {
+                Ok(paths) => (paths.as_slice(), None),
+                Err(err) => (&[][..], Some(err)),
+            };
+            report(opts, duplicate, paths, Duration::ZERO, error);
+            failures.extend(dup_result.err());
+        }
+
+        if let Ok(mut errors) = errors.lock() {
+            errors.extend(error);
+            errors.extend(failures);
+        }
+    }
+}
+
+/// One resized variant queued for encoding in a single format
+struct EncodeJob<'a> {
+    task: Arc<FileTask<'a>>,
+    image: Arc<DynamicImage>,
+    format: String,
+    /// Overrides the batch quality (`encode(fmt, q)` pipeline steps)
+    quality: Option<u8>,
+    /// Graphic content moved off a lossy encoding (`route_graphics`): WebP
+    /// is written lossless, and PNG in place of JPEG with a palette
+    routed: bool,
+    output_path: PathBuf,
+    /// Scale percentage and simulated deficiency, for the C2PA manifest
+    #[cfg(feature = "c2pa")]
+    origin: (u32, Option<Deficiency>),
+}
+
+impl EncodeJob<'_> {
+    fn run(self, opts: &ProcessOptions, progress: &Progress, errors: &Mutex<Vec<anyhow::Error>>) {
+        let _span = self.task.span.enter();
+        // Skip encoding once another variant of this file has failed
+        if progress.cancelled() {
+            self.task.fail(anyhow::anyhow!(tr!(Msg::Cancelled)));
+        } else if !self.task.failed() {
+            let encode = match (self.quality, &self.task.metadata, self.routed) {
+                (None, None, false) => &opts.encode,
+                (quality, metadata, false) => &EncodeOptions {
+                    quality: quality.unwrap_or(opts.encode.quality),
+                    // A variant's own quality beats a named level
+                    preset: quality.map_or(opts.encode.preset, |_| None),
+                    metadata: metadata.clone(),
+                    ..opts.encode.clone()
+                },
+                (_, metadata, true) => &EncodeOptions {
+                    preset: Some(QualityPreset::builtin(QualityLevel::Lossless)),
+                    colors: opts.encode.colors.or(Some(256)),
+                    // Dithering would speckle the flat areas
+                    dither: Dither::None,
+                    metadata: metadata.clone(),
+                    ..opts.encode.clone()
+                },
+            };
+            #[cfg(feature = "c2pa")]
+            let signed = encode.provenance.as_ref().map(|provenance| {
+                let (scale, simulated) = self.origin;
+                EncodeOptions {
+                    provenance: Some(Arc::new(provenance.for_output(
+                        &self.task.group.primary,
+                        scale,
+                        simulated.map(Deficiency::name),
+                    ))),
+                    ..encode.clone()
+                }
+            });
+            #[cfg(feature = "c2pa")]
+            let encode = signed.as_ref().unwrap_or(encode);
+            // Dropped channels are counted here; the encoder would drop them too
+            let (image, reduction) = reduce(&self.image, &self.format, encode);
+            // Save image to disk, split into tiles if it's larger than one
+            let saved = match opts.tile {
+                Some(tile) => save_tiled(&image, &self.output_path, &self.format, tile, encode),
+                None => save_image(&image, &self.output_path, &self.format, encode),
+            };
+            let saved = saved
+                .with_context(|| tr!(Msg::SaveFailed, self.output_path.display()))
+                .and_then(|()| verify(&self.output_path, Some(image.dimensions()), opts));
+            match saved {
+                Ok(()) => {
+                    self.task.outputs.reduced(reduction);
+                    self.task.inc();
+                }
+                Err(err) => self.task.fail(err),
+            }
+        }
+        self.task.release(opts, progress, errors);
+    }
+}
+
+/// Reads `output` back when `opts.verify` asks for it; tiled outputs, which
+/// are directories of tiles, are left alone
+fn verify(output: &Path, size: Option<(u32, u32)>, opts: &ProcessOptions) -> Result<()> {
+    if !opts.verify || (opts.tile.is_some() && !output.is_file()) {
+        return Ok(());
+    }
+    decode::verify(output, size).with_context(|| tr!(Msg::VerifyFailed, output.display()))
+}
+
+/// Total operations per image (scales * formats, or the outputs of its job
+/// or the pipeline)
+fn operations(opts: &ProcessOptions, job: Option<&Job>) -> u64 {
+    let outputs = match job.map(|job| &job.script).or(opts.script.as_ref()) {
+        Some(script) => script.outputs(),
+        None => opts.formats.len() * opts.scales.len(),
+    };
+    (outputs * (1 + opts.simulate.len())) as u64
+}
+
+/// Decode stage for a single image: resizes to every scale and queues the
+/// encodes, handling passthrough variants directly
+fn submit_variants<'a>(
+    task: &Arc<FileTask<'a>>,
+    opts: &ProcessOptions,
+    tx: &SyncSender<EncodeJob<'a>>,
+) -> Result<()> {
+    let path = &task.group.primary;
+    let stem = task.stem(path)?;
+    if let Some(job) = task.job {
+        return submit_script(task, &job.script, job.output.as_deref(), opts, tx);
+    }
+    if let Some(script) = &opts.script {
+        return submit_script(task, script, script.output_name(), opts, tx);
+    }
+
+    // Decoded lazily: a fully passed-through image is never loaded
+    let mut img = None;
+    // Every frame, for APNG outputs of animated inputs (`None` for stills)
+    let mut animation = None;
+    // Whether the source has transparency, for `--alpha skip`
+    let mut transparent = None;
+    // First output of each size and format, for scales that round alike
+    let mut encoded: HashMap<_, PathBuf> = HashMap::new();
+    // Upright size of the source an embedded preview stands in for; the
+    // scales still apply to it
+    let mut source_size = None;
+    if opts.embedded_preview && opts.transforms.is_identity() {
+        let largest = opts.scales.iter().copied().max().unwrap_or(100);
+        if let Some(preview) = decode::load_preview(path, largest)? {
+            source_size = preview.source;
+            img = Some(preview.image);
+            task.outputs.previews.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    // What the source shows, for --classify and --preset screenshot
+    let content = match opts.classify || opts.route_graphics {
+        true => task.content(decoded(&mut img, path, opts)?, opts)?,
+        false => Content::Photo,
     };
 
     // Iterate over all scales and formats
-    for &scale in scales {
-        let resized = resize_image(&img, scale)?;
+    for &scale in &opts.scales {
+        let mut resized = None;
+
+        for fmt in &opts.formats {
+            let (fmt, routed) = match opts.route_graphics {
+                true => route(fmt, content),
+                false => (fmt.as_str(), false),
+            };
+            if opts.encode.alpha == AlphaMode::Skip && !supports_alpha(fmt) {
+                if transparent.is_none() {
+                    transparent = Some(has_transparency(decoded(&mut img, path, opts)?));
+                }
+                if transparent == Some(true) {
+                    task.skip(fmt);
+                    opts.simulate.iter().for_each(|_| task.inc());
+                    continue;
+                }
+            }
+            let output_path = output_path(path, stem, opts.output_dir.as_ref(), scale, fmt)?;
 
-        for fmt in formats {
-            let output_name = format!("{stem}_{scale}pct.{fmt}");
-            let output_path = output_parent.join(output_name);
+            // Written whichever way the variant itself is
+            if !opts.simulate.is_empty() {
+                let image = scaled(&mut resized, &mut img, source_size, path, scale, opts)?;
+                let first = match opts.transforms.is_scale_independent() {
+                    true => encoded.get(&(image.dimensions(), fmt.to_lowercase())),
+                    false => None,
+                };
+                match first {
+                    Some(first) => {
+                        for &deficiency in &opts.simulate {
+                            task.elide(
+                                &simulated_path(&output_path, deficiency),
+                                &simulated_path(first, deficiency),
+                            );
+                        }
+                    }
+                    None => {
+                        let variant = EncodeJob {
+                            task: Arc::clone(task),
+                            image,
+                            format: fmt.to_string(),
+                            quality: None,
+                            routed,
+                            output_path: output_path.clone(),
+                            #[cfg(feature = "c2pa")]
+                            origin: (scale, None),
+                        };
+                        queue_simulations(&variant, None, opts, tx)?;
+                    }
+                }
+            }
+
+            if opts.passthrough != Passthrough::Off
+                && scale == 100
+                && opts.transforms.is_identity()
+                && opts.encode.is_default_for(fmt)
+                && same_format(path, fmt, opts.sniff)
+            {
+                // The source already is this variant: link/copy instead of re-encoding
+                dedupe::link_or_copy(path, &output_path, opts.passthrough == Passthrough::Link)?;
+                task.output(&output_path, None);
+                task.inc();
+                continue;
+            }
+
+            if opts.lossless_jpeg
+                && scale == 100
+                && opts.transforms.is_crop_only()
+                && opts.encode.channels != Some(Channels::Rgb)
+                && opts.encode.output_profile.is_none()
+                && !opts.encode.is_signed()
+                && same_format(path, "jpg", opts.sniff)
+                && same_format(path, fmt, opts.sniff)
+            {
+                let op = LosslessJpeg {
+                    crop: opts.transforms.crop,
+                    strip: opts.strip,
+                    thumbnail: opts.encode.embed_thumbnail,
+                    grayscale: opts.encode.channels == Some(Channels::Gray),
+                    restart_interval: opts.encode.jpeg_restart.unwrap_or(0),
+                    mark: opts.encode.mark,
+                };
+                // Progressive and other unsupported files take the re-encode path
+                if save_lossless_jpeg(path, &output_path, &op)? {
+                    verify(&output_path, None, opts)?;
+                    task.output(&output_path, None);
+                    task.inc();
+                    continue;
+                }
+            }
+
+            if fmt.eq_ignore_ascii_case("apng") {
+                if animation.is_none() {
+                    animation = Some(decode::load_animation(path)?);
+                }
+                if let Some(Some(frames)) = &animation {
+                    save_apng(
+                        &scaled_frames(frames, path, scale, opts)?,
+                        &output_path,
+                        &opts.encode,
+                    )?;
+                    verify(&output_path, None, opts)?;
+                    task.output(&output_path, None);
+                    task.inc();
+                    continue;
+                }
+            }
 
-            // Save image to disk
-            save_image(&resized, &output_path, fmt, quality)
-                .with_context(|| format!("Error saving: {}", output_path.display()))?;
+            let image = scaled(&mut resized, &mut img, source_size, path, scale, opts)?;
 
-            // Increment progress bar
-            if let Some(pb) = pb {
-                pb.inc(1);
+            if opts.transforms.is_scale_independent() {
+                let key = (image.dimensions(), fmt.to_lowercase());
+                if let Some(first) = encoded.get(&key) {
+                    task.elide(&output_path, first);
+                    continue;
+                }
+                encoded.insert(key, output_path.clone());
             }
+            task.output(&output_path, None);
+
+            let job = EncodeJob {
+                task: Arc::clone(task),
+                image,
+                format: fmt.to_string(),
+                quality: None,
+                routed,
+                output_path,
+                #[cfg(feature = "c2pa")]
+                origin: (scale, None),
+            };
+            queue(task, job, tx)?;
         }
     }
 
     Ok(())
 }
 
-/// Resizes an image according to the given scale percentage
-fn resize_image(img: &DynamicImage, scale: u32) -> Result<DynamicImage> {
-    if scale == 100 {
-        // Return original image if scale is 100%
-        return Ok(img.clone());
+/// The source at `scale` with the scaled transforms applied, resizing it
+/// into `resized` on first use
+fn scaled(
+    resized: &mut Option<Arc<DynamicImage>>,
+    img: &mut Option<DynamicImage>,
+    source_size: Option<(u32, u32)>,
+    path: &Path,
+    scale: u32,
+    opts: &ProcessOptions,
+) -> Result<Arc<DynamicImage>> {
+    if resized.is_none() {
+        let source = decoded(img, path, opts)?;
+        let linear = opts.transforms.linear_resize;
+        let scaled = match source_size.map(|size| scaled_size(size, scale)) {
+            Some(size) if size == source.dimensions() => source.clone(),
+            Some((width, height)) => pipeline::resize_to(source, width, height, linear),
+            None => pipeline::resize_image(source, scale, linear)?,
+        };
+        let transformed = opts
+            .transforms
+            .apply_scaled(scaled, path, scale)
+            .with_context(|| tr!(Msg::TransformFailed, path.display()))?;
+        *resized = Some(Arc::new(transformed));
+    }
+    Ok(Arc::clone(resized.as_ref().expect("resized above")))
+}
+
+/// Queues `variant` as seen with each deficiency of `--simulate`, named as
+/// it is (or as the templated `name`) with the deficiency added
+fn queue_simulations<'a>(
+    variant: &EncodeJob<'a>,
+    name: Option<&str>,
+    opts: &ProcessOptions,
+    tx: &SyncSender<EncodeJob<'a>>,
+) -> Result<()> {
+    let task = &variant.task;
+    for &deficiency in &opts.simulate {
+        let output_path = simulated_path(&variant.output_path, deficiency);
+        task.output(
+            &output_path,
+            name.map(|name| format!("{name}_{}", deficiency.name())),
+        );
+        let job = EncodeJob {
+            task: Arc::clone(task),
+            image: Arc::new(simulate::simulate(&variant.image, deficiency)),
+            format: variant.format.clone(),
+            quality: variant.quality,
+            routed: variant.routed,
+            output_path,
+            #[cfg(feature = "c2pa")]
+            origin: (variant.origin.0, Some(deficiency)),
+        };
+        queue(task, job, tx)?;
     }
+    Ok(())
+}
 
-    let factor = scale as f32 / 100.0;
-    let new_width = (img.width() as f32 * factor).round() as u32;
-    let new_height = (img.height() as f32 * factor).round() as u32;
+/// `output_path` with the name of `deficiency` before its extension:
+/// `photo_50pct_deuteranopia.png`
+fn simulated_path(output_path: &Path, deficiency: Deficiency) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let name = match output_path.extension() {
+        Some(extension) => format!(
+            "{stem}_{}.{}",
+            deficiency.name(),
+            extension.to_string_lossy()
+        ),
+        None => format!("{stem}_{}", deficiency.name()),
+    };
+    output_path.with_file_name(name)
+}
 
-    // Prevent creating images with zero dimensions
-    if new_width == 0 || new_height == 0 {
+/// Runs every frame of an animation through the transforms and the resize
+/// of one scale
+fn scaled_frames(
+    frames: &[AnimationFrame],
+    path: &Path,
+    scale: u32,
+    opts: &ProcessOptions,
+) -> Result<Vec<AnimationFrame>> {
+    // Rayon workers don't inherit the file's span
+    let span = tracing::Span::current();
+    let scaled = frames
+        .par_iter()
+        .map(|frame| {
+            let _span = span.enter();
+            let img = opts
+                .transforms
+                .apply(DynamicImage::ImageRgba8(frame.image.clone()), path)
+                .with_context(|| tr!(Msg::TransformFailed, path.display()))?;
+            let img = pipeline::resize_image(&img, scale, opts.transforms.linear_resize)?;
+            let img = opts
+                .transforms
+                .apply_scaled(img, path, scale)
+                .with_context(|| tr!(Msg::TransformFailed, path.display()))?;
+            Ok(AnimationFrame {
+                image: img.to_rgba8(),
+                delay_ms: frame.delay_ms,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Content-dependent transforms (trim, face crops) can size frames apart
+    if scaled
+        .windows(2)
+        .any(|pair| pair[0].image.dimensions() != pair[1].image.dimensions())
+    {
         anyhow::bail!(
-            "Resulting dimensions too small: {}x{} (scale: {}%)",
-            new_width,
-            new_height,
-            scale
+            "Transforms gave the frames of {} different sizes",
+            path.display()
         );
     }
+    Ok(scaled)
+}
+
+/// Decode stage for a `--pipeline` run or `--jobs` row: queues an encode
+/// for every `encode(...)` step of the chain
+fn submit_script<'a>(
+    task: &Arc<FileTask<'a>>,
+    script: &Script,
+    name: Option<&str>,
+    opts: &ProcessOptions,
+    tx: &SyncSender<EncodeJob<'a>>,
+) -> Result<()> {
+    let path = &task.group.primary;
+    let stem = task.stem(path)?;
+    let mut img = None;
+    decoded(&mut img, path, opts)?;
+    let source = img.take().expect("decoded above");
+    let mut written = HashSet::new();
+    // First output of each size, format and quality, for encodes that repeat
+    let mut encoded: HashMap<_, PathBuf> = HashMap::new();
+    let transparent = opts.encode.alpha == AlphaMode::Skip && has_transparency(&source);
+
+    let content = match opts.classify || opts.route_graphics {
+        true => task.content(&source, opts)?,
+        false => Content::Photo,
+    };
+
+    script.run(source, opts.transforms.linear_resize, |mut variant| {
+        let (format, routed) = match opts.route_graphics {
+            true => route(&variant.format, content),
+            false => (variant.format.as_str(), false),
+        };
+        variant.format = format.to_string();
+        if transparent && !supports_alpha(&variant.format) {
+            task.skip(&variant.format);
+            opts.simulate.iter().for_each(|_| task.inc());
+            return Ok(());
+        }
+        let name = name.map(|template| output_name(template, &variant, task));
+        let output_path = match &name {
+            Some(name) => output_parent(path, opts.output_dir.as_ref())?.join(format!(
+                "{}.{}",
+                input_name(name, stem, task.seq(path)),
+                variant.format
+            )),
+            None => output_path(
+                path,
+                stem,
+                opts.output_dir.as_ref(),
+                variant.scale,
+                &variant.format,
+            )?,
+        };
+        if script.skips_repeats() && opts.transforms.is_scale_independent() {
+            let key = (
+                variant.image.dimensions(),
+                variant.format.to_lowercase(),
+                variant.quality,
+            );
+            if let Some(first) = encoded.get(&key) {
+                task.elide(&output_path, first);
+                for &deficiency in &opts.simulate {
+                    task.elide(
+                        &simulated_path(&output_path, deficiency),
+                        &simulated_path(first, deficiency),
+                    );
+                }
+                return Ok(());
+            }
+            encoded.insert(key, output_path.clone());
+        }
+        if !written.insert(output_path.clone()) {
+            anyhow::bail!(
+                "{} would be written more than once; resize between encodes of the same format",
+                output_path.display()
+            );
+        }
+        task.output(&output_path, name.clone());
+
+        let image = opts
+            .transforms
+            .apply_scaled(variant.image, path, variant.scale)
+            .with_context(|| tr!(Msg::TransformFailed, path.display()))?;
+        let job = EncodeJob {
+            task: Arc::clone(task),
+            image: Arc::new(image),
+            format: variant.format,
+            quality: variant.quality,
+            routed,
+            output_path,
+            #[cfg(feature = "c2pa")]
+            origin: (variant.scale, None),
+        };
+        queue_simulations(&job, name.as_deref(), opts, tx)?;
+        queue(task, job, tx)
+    })
+}
+
+/// Hands a variant to the encode stage
+fn queue<'a>(
+    task: &FileTask<'a>,
+    job: EncodeJob<'a>,
+    tx: &SyncSender<EncodeJob<'a>>,
+) -> Result<()> {
+    task.pending.fetch_add(1, Ordering::AcqRel);
+    if tx.send(job).is_err() {
+        task.pending.fetch_sub(1, Ordering::AcqRel);
+        anyhow::bail!("Encoder stage stopped unexpectedly");
+    }
+    Ok(())
+}
+
+/// Returns the decoded and transformed source, loading it from disk on first use
+fn decoded<'a>(
+    img: &'a mut Option<DynamicImage>,
+    path: &Path,
+    opts: &ProcessOptions,
+) -> Result<&'a DynamicImage> {
+    if img.is_none() {
+        // Load the image from disk (memory-mapped when possible), or have
+        // the external decoder convert what rsimg can't read
+        let loaded = match (decode::load_frame(path, opts.frame), &opts.exec_decode) {
+            (Err(_), Some(decoder)) => decoder.decode(path, opts.frame)?,
+            (loaded, _) => loaded?,
+        };
+        let transformed = opts
+            .transforms
+            .apply(loaded, path)
+            .with_context(|| tr!(Msg::TransformFailed, path.display()))?;
+        *img = Some(transformed);
+    }
+    Ok(img.as_ref().expect("decoded above"))
+}
 
-    // Resize using high-quality Lanczos3 filter
-    Ok(img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3))
+/// Checks whether the source file is already encoded in the given output
+/// format, going by its content when `sniff` is set and by its extension
+/// otherwise
+fn same_format(path: &Path, fmt: &str, sniff: bool) -> bool {
+    if sniff {
+        return decode::sniff_file(path).is_some_and(|format| format == normalize_format(fmt));
+    }
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| normalize_format(ext) == normalize_format(fmt))
 }
 
-/// Saves an image to disk in the specified format and quality
-fn save_image(img: &DynamicImage, path: &Path, format: &str, quality: u8) -> Result<()> {
-    match format.to_lowercase().as_str() {
-        "jpg" | "jpeg" => save_jpeg(img, path, quality),
-        "webp" => save_webp(img, path, quality),
-        "png" => save_png(img, path),
-        _ => Err(anyhow::anyhow!("Unsupported format: {}", format)),
+/// Lowercase format name, with the alternative spellings folded
+fn normalize_format(ext: &str) -> String {
+    match ext.to_lowercase().as_str() {
+        "jpeg" => "jpg".to_string(),
+        "tif" => "tiff".to_string(),
+        other => other.to_string(),
     }
 }
 
-/// Saves image as JPEG with the given quality
-fn save_jpeg(img: &DynamicImage, path: &Path, quality: u8) -> Result<()> {
-    let file = std::fs::File::create(path)
-        .with_context(|| format!("Failed to create file: {}", path.display()))?;
+/// Inputs whose content is in another format than their extension says,
+/// with the format found
+pub fn misnamed_files(files: &[PathBuf]) -> Vec<(&Path, &'static str)> {
+    files
+        .iter()
+        .filter_map(|path| {
+            let ext = path.extension()?.to_str()?;
+            let format = decode::sniff_file(path)?;
+            (normalize_format(ext) != format).then_some((path.as_path(), format))
+        })
+        .collect()
+}
+
+/// Produces a duplicate's outputs by linking (or copying) the primary's,
+/// returning the ones with names of their own
+fn link_duplicate_outputs(
+    task: &FileTask,
+    duplicate: &Path,
+    written: &[(PathBuf, Option<String>)],
+    opts: &ProcessOptions,
+    progress: &FileProgress,
+) -> Result<Vec<PathBuf>> {
+    let primary_stem = paths::safe_stem(task.stem(&task.group.primary)?);
+    let duplicate_stem = task.stem(duplicate)?;
+    let target_dir = output_parent(duplicate, opts.output_dir.as_ref())?;
+
+    let mut linked = Vec::new();
+    for (source, template) in written {
+        let target = match template {
+            Some(template) => {
+                let extension = source.extension().unwrap_or_default().to_string_lossy();
+                let name = input_name(template, duplicate_stem, task.seq(duplicate));
+                target_dir.join(format!("{name}.{extension}"))
+            }
+            None => {
+                // Output names start with the stem; the rest tells the variant apart
+                let name = source.file_name().unwrap_or_default().to_string_lossy();
+                let variant = name.strip_prefix(&*primary_stem).unwrap_or(&name);
+                target_dir.join(format!("{}{variant}", paths::safe_stem(duplicate_stem)))
+            }
+        };
+        // A template without {stem} or {seq} names the duplicate's outputs the same
+        if target == *source {
+            progress.inc();
+            continue;
+        }
+
+        let tiles = tiles_dir(source);
+        if opts.tile.is_some() && tiles.is_dir() {
+            link_tiles(&tiles, &tiles_dir(&target))?;
+        } else {
+            dedupe::link_or_copy(source, &target, true)?;
+        }
+
+        if let Ok(mut outputs) = task.outputs.paths.lock() {
+            outputs.push(target.clone());
+        }
+        linked.push(target);
+        progress.inc();
+    }
 
-    let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(file, quality);
-    encoder
-        .encode_image(img)
-        .with_context(|| "Error during JPEG encoding")?;
+    Ok(linked)
+}
 
+/// Links every file of a tiled output into the duplicate's tile directory
+fn link_tiles(source: &Path, target: &Path) -> Result<()> {
+    std::fs::create_dir_all(long_path(target))
+        .with_context(|| format!("Failed to create tile directory: {}", target.display()))?;
+    let entries = std::fs::read_dir(long_path(source))
+        .with_context(|| format!("Failed to read tile directory: {}", source.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        dedupe::link_or_copy(
+            &source.join(entry.file_name()),
+            &target.join(entry.file_name()),
+            true,
+        )?;
+    }
     Ok(())
 }
 
-/// Saves image as WebP with the given quality
-fn save_webp(img: &DynamicImage, path: &Path, quality: u8) -> Result<()> {
-    use webp::Encoder;
+/// Builds the output path for one scale/format variant of an input file,
+/// named after `stem`
+fn output_path(
+    path: &Path,
+    stem: &str,
+    output_dir: Option<&PathBuf>,
+    scale: u32,
+    fmt: &str,
+) -> Result<PathBuf> {
+    let stem = paths::safe_stem(stem);
+    Ok(output_parent(path, output_dir)?.join(format!("{stem}_{scale}pct.{fmt}")))
+}
+
+/// Fills the tokens of an output name template (`--name`, `--jobs`) that
+/// depend on the variant and the image, leaving `{stem}` and `{seq}`, which
+/// differ between duplicates, to `input_name`
+///
+/// The EXIF tokens read the source's metadata only when the template uses
+/// one; a missing value fills in as `unknown`.
+fn output_name(template: &str, variant: &Variant, task: &FileTask) -> String {
+    let mut name = template
+        .replace("{scale}", &variant.scale.to_string())
+        .replace("{width}", &variant.image.width().to_string())
+        .replace("{height}", &variant.image.height().to_string())
+        .replace("{label}", variant.label.as_deref().unwrap_or_default());
+    if !EXIF_TOKENS.iter().any(|token| name.contains(token)) {
+        return name;
+    }
+
+    let capture = task.capture();
+    let values = [
+        capture.date.clone(),
+        capture
+            .camera
+            .as_deref()
+            .map(camera_slug)
+            .filter(|slug| !slug.is_empty()),
+        capture.iso.map(|iso| iso.to_string()),
+        // 50 mm rather than 50.0, but 4.2 mm as is
+        capture
+            .focal
+            .map(|focal| format!("{}", (focal * 10.0).round() / 10.0)),
+    ];
+    for (token, value) in EXIF_TOKENS.iter().zip(values) {
+        name = name.replace(token, value.as_deref().unwrap_or("unknown"));
+    }
+    name
+}
+
+/// Fills `{stem}` and `{seq}` (`{seq:04}` zero-pads to 4 digits) into a
+/// name from `output_name`
+fn input_name(name: &str, stem: &str, seq: u32) -> String {
+    let name = name.replace("{stem}", &paths::safe_stem(stem));
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name.as_str();
+    while let Some(start) = rest.find("{seq") {
+        out.push_str(&rest[..start]);
+        let token = &rest[start..];
+        let end = token.find('}').map_or(token.len(), |end| end + 1);
+        match seq_width(&token[..end]) {
+            Some(width) => out.push_str(&format!("{seq:0width$}")),
+            None => out.push_str(&token[..end]),
+        }
+        rest = &token[end..];
+    }
+    out.push_str(rest);
+    out
+}
 
-    // Convert to RGB8 for WebP encoder
-    let rgb = img.to_rgb8();
-    let encoder = Encoder::from_rgb(&rgb, rgb.width(), rgb.height());
-    let webp_data = encoder.encode(quality as f32);
+/// Zero-padded width of a `{seq}` or `{seq:0N}` token, `None` for anything
+/// else starting with `{seq`
+fn seq_width(token: &str) -> Option<usize> {
+    match token.strip_prefix("{seq")?.strip_suffix('}')? {
+        "" => Some(0),
+        spec => spec.strip_prefix(":0")?.parse().ok(),
+    }
+}
 
-    // Write encoded WebP bytes to disk
-    std::fs::write(path, &*webp_data)
-        .with_context(|| format!("Failed to write WebP file: {}", path.display()))?;
+/// Numbers the distinct inputs in path order from `start`, for `{seq}`;
+/// walk order depends on the filesystem
+fn sequence_numbers(inputs: &[&Path], start: u32) -> HashMap<PathBuf, u32> {
+    let mut sorted = inputs.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    sorted
+        .into_iter()
+        .zip(start..)
+        .map(|(path, seq)| (path.to_path_buf(), seq))
+        .collect()
+}
 
-    Ok(())
+/// Name template tokens filled from the source's EXIF
+const EXIF_TOKENS: [&str; 4] = ["{exif_date}", "{camera}", "{iso}", "{focal}"];
+
+/// Camera model as a filename part: `Canon EOS 5D Mark IV` → `canon-eos-5d-mark-iv`
+fn camera_slug(model: &str) -> String {
+    model
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Filename without extension
+fn file_stem(path: &Path) -> Result<&str> {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Invalid filename: {}", path.display()))
+}
+
+/// Output directory: user-specified or the input's own
+fn output_parent(path: &Path, output_dir: Option<&PathBuf>) -> Result<PathBuf> {
+    match output_dir {
+        Some(out_dir) => Ok(out_dir.clone()),
+        None => Ok(path
+            .parent()
+            .ok_or_else(|| anyhow::anyhow!("Cannot determine parent directory"))?
+            .to_path_buf()),
+    }
+}
+
+/// File naming paths a directory scan skips, in `.gitignore` syntax
+pub const IGNORE_FILE: &str = ".rsimgignore";
+
+const VALID_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "apng", "gif", "webp", "bmp", "tiff", "tif", "ico", "exr", "hdr", "psd",
+    "xcf",
+];
+
+/// Which files an input directory contributes
+pub struct ScanOptions {
+    /// Descend into subdirectories
+    pub recursive: bool,
+    /// Skip what `.gitignore` (inside a git repository) and `.rsimgignore`
+    /// files exclude
+    pub respect_ignore: bool,
+    /// Recognize images by their magic bytes, so extension-less and
+    /// misnamed files are taken too
+    pub sniff: bool,
+    /// Take camera RAW files, read through their embedded previews
+    pub raw: bool,
+    /// More extensions to take, lowercase, for `--exec-decode`
+    pub extensions: Vec<String>,
 }
 
-/// Saves image as PNG (lossless)
-fn save_png(img: &DynamicImage, path: &Path) -> Result<()> {
-    img.save_with_format(path, ImageFormat::Png)
-        .with_context(|| format!("Failed to save PNG: {}", path.display()))?;
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            respect_ignore: true,
+            sniff: false,
+            raw: false,
+            extensions: Vec::new(),
+        }
+    }
+}
+
+// Collect all image files from input path, in path order
+#[tracing::instrument(name = "collect", skip_all, fields(input = %input.display()))]
+pub fn collect_image_files(input: &Path, scan: &ScanOptions) -> Result<Vec<PathBuf>> {
+    let files = Mutex::new(Vec::new());
+    walk_image_files(input, scan, |path| {
+        files.lock().unwrap_or_else(|e| e.into_inner()).push(path);
+    })?;
+    // The walk's threads find files in no particular order
+    let mut files = files.into_inner().unwrap_or_else(|e| e.into_inner());
+    files.sort_unstable();
+    Ok(files)
+}
+
+// Call `found` with every image file of an input, from the threads of a
+// parallel directory walk; an input file named directly is taken whatever
+// the ignore files say
+pub fn walk_image_files(
+    input: &Path,
+    scan: &ScanOptions,
+    found: impl Fn(PathBuf) + Sync,
+) -> Result<()> {
+    let mut extensions = VALID_EXTENSIONS.to_vec();
+    if scan.raw {
+        extensions.extend(decode::RAW_EXTENSIONS);
+    }
+    extensions.extend(scan.extensions.iter().map(String::as_str));
+
+    if !input.exists() {
+        return Err(NoInputs(tr!(Msg::PathMissing, input.display())).into());
+    }
+
+    if input.is_file() {
+        // Single file input
+        if !(scan.sniff && decode::sniff_file(input).is_some()) {
+            validate_image_file(input, &extensions)?;
+        }
+        found(input.to_path_buf());
+    } else if input.is_dir() {
+        // Directory input (recursively if specified). A verbatim root lets
+        // the walk descend past MAX_PATH on Windows; found paths are given
+        // back relative to the input as typed.
+        let root = long_path(input);
+        let mut walker = WalkBuilder::new(&root);
+        walker
+            .standard_filters(false)
+            .max_depth((!scan.recursive).then_some(1))
+            .threads(rayon::current_num_threads());
+        if scan.respect_ignore {
+            walker
+                .git_ignore(true)
+                .git_exclude(true)
+                .git_global(true)
+                .parents(true)
+                .add_custom_ignore_filename(IGNORE_FILE);
+        }
+
+        walker.build_parallel().run(|| {
+            let (found, extensions, root) = (&found, &extensions, &root);
+            Box::new(move |entry| {
+                let Ok(entry) = entry else {
+                    return WalkState::Continue;
+                };
+                let path = entry.path();
+
+                // The directory listing tells files apart without a stat
+                // per entry, which is slow on network storage; symlinks
+                // still need one
+                let is_file = entry
+                    .file_type()
+                    .is_some_and(|kind| kind.is_file() || kind.is_symlink() && path.is_file());
+                let valid_ext = path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|ext| extensions.contains(&ext.to_lowercase().as_str()));
+                if is_file && (valid_ext || scan.sniff && decode::sniff_file(path).is_some()) {
+                    found(match path.strip_prefix(root) {
+                        Ok(relative) => input.join(relative),
+                        Err(_) => path.to_path_buf(),
+                    });
+                }
+                WalkState::Continue
+            })
+        });
+    } else {
+        return Err(NoInputs(tr!(Msg::PathInvalid, input.display())).into());
+    }
 
     Ok(())
 }
+
+// Validate that a file has a supported image extension
+fn validate_image_file(path: &Path, valid_ext: &[&str]) -> Result<()> {
+    if let Some(ext) = path.extension().and_then(|e| e.to_str())
+        && valid_ext.contains(&ext.to_lowercase().as_str())
+    {
+        return Ok(());
+    }
+    Err(NoInputs(tr!(Msg::Unsupported, path.display())).into())
+}
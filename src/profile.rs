@@ -0,0 +1,152 @@
+// src/profile.rs
+//
+// `--profile`: how long each file spends in each pipeline stage, taken from
+// the tracing spans the library opens around them. A stage's spans are
+// summed per file (a file decoded once but encoded in six variants gets the
+// six encodes), and the report gives percentiles of those per-file sums plus
+// each stage's share of the total: a batch dominated by `write` wants faster
+// disks, one dominated by `resize` and `encode` more cores. `decode` includes
+// reading the source.
+
+use crate::i18n::{Msg, tr};
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::span::{Attributes, Id};
+use tracing::{Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Stages timed, in pipeline order, named after their spans
+pub const STAGES: [&str; 4] = ["decode", "resize", "encode", "write"];
+
+/// Span grouping the stages of one input
+const FILE_SPAN: &str = "file";
+
+/// Stage durations of every finished file
+#[derive(Default)]
+pub struct Profile {
+    files: Mutex<Vec<[Duration; STAGES.len()]>>,
+}
+
+impl Profile {
+    /// Percentile table of the files finished so far, or `None` without any
+    pub fn report(&self) -> Option<String> {
+        let files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        if files.is_empty() {
+            return None;
+        }
+
+        let totals: Vec<Duration> = (0..STAGES.len())
+            .map(|stage| files.iter().map(|file| file[stage]).sum())
+            .collect();
+        let overall: Duration = totals.iter().sum();
+
+        let mut out = format!("  {}\n", tr!(Msg::ProfileHeader, files.len()));
+        let _ = writeln!(
+            out,
+            "    {:<8}{:>10}{:>10}{:>10}{:>10}{:>10}{:>8}",
+            "", "p50", "p90", "p99", "max", "total", "share"
+        );
+        for (stage, name) in STAGES.iter().enumerate() {
+            let mut times: Vec<Duration> = files.iter().map(|file| file[stage]).collect();
+            times.sort_unstable();
+            let share = if overall.is_zero() {
+                0.0
+            } else {
+                totals[stage].as_secs_f64() / overall.as_secs_f64() * 100.0
+            };
+            let _ = writeln!(
+                out,
+                "    {name:<8}{:>10}{:>10}{:>10}{:>10}{:>10}{:>7.1}%",
+                format_duration(percentile(&times, 50)),
+                format_duration(percentile(&times, 90)),
+                format_duration(percentile(&times, 99)),
+                format_duration(times[times.len() - 1]),
+                format_duration(totals[stage]),
+                share
+            );
+        }
+        Some(out)
+    }
+}
+
+/// Tracing layer feeding a `Profile`
+pub struct ProfileLayer {
+    profile: Arc<Profile>,
+}
+
+impl ProfileLayer {
+    pub fn new(profile: Arc<Profile>) -> Self {
+        Self { profile }
+    }
+}
+
+/// When a stage span was opened
+struct Started(Instant);
+
+/// Stage durations accumulated on a file span
+#[derive(Default)]
+struct FileTimes([Duration; STAGES.len()]);
+
+impl<S> Layer<S> for ProfileLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id).filter(|span| is_ours(span.metadata())) else {
+            return;
+        };
+        if span.name() == FILE_SPAN {
+            span.extensions_mut().insert(FileTimes::default());
+        } else if STAGES.contains(&span.name()) {
+            span.extensions_mut().insert(Started(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id).filter(|span| is_ours(span.metadata())) else {
+            return;
+        };
+        if span.name() == FILE_SPAN {
+            if let Some(times) = span.extensions_mut().remove::<FileTimes>()
+                && let Ok(mut files) = self.profile.files.lock()
+            {
+                files.push(times.0);
+            }
+            return;
+        }
+
+        let Some(stage) = STAGES.iter().position(|&name| name == span.name()) else {
+            return;
+        };
+        let Some(elapsed) = span.extensions().get::<Started>().map(|s| s.0.elapsed()) else {
+            return;
+        };
+        // Stages outside a batch (`tune`, `serve --http`) aren't attributed
+        if let Some(file) = span.scope().skip(1).find(|s| s.name() == FILE_SPAN)
+            && let Some(times) = file.extensions_mut().get_mut::<FileTimes>()
+        {
+            times.0[stage] += elapsed;
+        }
+    }
+}
+
+/// Whether a span was opened by this crate rather than a dependency
+fn is_ours(metadata: &Metadata<'_>) -> bool {
+    metadata.target().split("::").next() == Some(env!("CARGO_CRATE_NAME"))
+}
+
+/// Nearest-rank percentile of sorted, non-empty durations
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Milliseconds below a second, seconds above
+fn format_duration(duration: Duration) -> String {
+    match duration.as_secs_f64() {
+        secs if secs < 1.0 => format!("{:.1} ms", secs * 1000.0),
+        secs => format!("{secs:.2} s"),
+    }
+}
@@ -0,0 +1,441 @@
+// src/progress.rs
+//
+// Progress reporting for a batch. The processor only talks to `Progress`
+// and `FileProgress`; whether that ends up as terminal bars, as
+// newline-delimited JSON events for a wrapping GUI, or as counters polled by
+// `rsimg serve` is decided here.
+//
+// JSON events, one object per line on stdout:
+//   {"event":"batch","files":N}
+//   {"event":"started","file":"...","steps":N}
+//   {"event":"classified","file":"...","class":"photo"}  (with --classify)
+//   {"event":"progress","file":"...","step":I,"steps":N,"percent":P}
+//   {"event":"elided","file":"...","output":"...","same_as":"..."}
+//   {"event":"done","file":"...","ok":true}  (or "ok":false,"error":"...")
+//   {"event":"finished","files":N,"failed":M}
+//
+// With `--stream` the `batch` event comes once the directory scan ends, after
+// the first files have started.
+//
+// Terminal bars come in a `detailed` look (emoji, colors, wide bar) and a
+// plain `compact` one for terminals that render those poorly; either can be
+// restyled with an indicatif template and bar characters.
+
+use crate::exit::InvalidConfig;
+use crate::i18n::{Msg, tr};
+use clap::ValueEnum;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use owo_colors::OwoColorize;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Where progress for a batch is reported
+pub enum Progress {
+    /// One indicatif bar per file
+    Bars(MultiProgress, Theme),
+    /// NDJSON events on stdout
+    Json,
+    /// Nothing is reported
+    Hidden,
+    /// Counters read (and cancelled) from another thread
+    Tracked(Arc<Tracker>),
+}
+
+/// Live counters of a batch, shared with whoever started it
+#[derive(Default)]
+pub struct Tracker {
+    pub files: AtomicU64,
+    pub files_done: AtomicU64,
+    pub failed: AtomicU64,
+    pub steps: AtomicU64,
+    pub steps_done: AtomicU64,
+    /// Set to stop the batch; files not started yet are skipped
+    pub cancelled: AtomicBool,
+}
+
+impl Tracker {
+    /// Completed operations as a percentage of the whole batch
+    pub fn percent(&self) -> u64 {
+        match self.steps.load(Ordering::Acquire) {
+            0 => 0,
+            steps => self.steps_done.load(Ordering::Acquire) * 100 / steps,
+        }
+    }
+}
+
+/// Look of the per-file terminal bars
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum BarStyle {
+    /// Plain names, a short ASCII bar, no emoji or colors
+    Compact,
+    /// Emoji, colored names and a wide bar
+    #[default]
+    Detailed,
+    /// No bars
+    None,
+}
+
+/// A bar style with its template and characters resolved
+pub struct Theme {
+    kind: BarStyle,
+    style: ProgressStyle,
+}
+
+impl Theme {
+    /// The look of `style`, with an indicatif `template` (e.g.
+    /// `{msg:30} {bar:20.green/white} {pos}/{len}`) and bar `chars` (filled,
+    /// in-progress..., empty) replacing its own
+    pub fn new(
+        style: BarStyle,
+        template: Option<&str>,
+        chars: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let (default_template, default_chars) = if style != BarStyle::Compact {
+            ("  {msg:40} [{bar:40.cyan/blue}] {pos:>2}/{len:2}", "━━╾─")
+        } else {
+            ("  {msg:40} [{bar:20}] {pos:>2}/{len:2}", "=> ")
+        };
+        let bar_style = ProgressStyle::with_template(template.unwrap_or(default_template))
+            .map_err(|err| InvalidConfig(tr!(Msg::ProgressTemplate, err)))?;
+        let chars = chars.unwrap_or(default_chars);
+        if chars.chars().count() < 2 {
+            return Err(InvalidConfig(tr!(Msg::ProgressChars)).into());
+        }
+        Ok(Self {
+            kind: style,
+            style: bar_style.progress_chars(chars),
+        })
+    }
+}
+
+impl Progress {
+    /// Terminal bars in `theme`, or JSON events when `json` is set
+    pub fn new(json: bool, theme: Theme) -> Self {
+        match (json, theme.kind) {
+            (true, _) => Progress::Json,
+            (false, BarStyle::None) => Progress::Hidden,
+            (false, _) => Progress::Bars(MultiProgress::new(), theme),
+        }
+    }
+
+    /// Announces the number of files in the batch and their total operations
+    pub fn batch(&self, files: usize, steps: u64) {
+        match self {
+            Progress::Bars(..) | Progress::Hidden => {}
+            Progress::Json => emit(&format!(r#"{{"event":"batch","files":{files}}}"#)),
+            Progress::Tracked(tracker) => {
+                tracker.files.store(files as u64, Ordering::Release);
+                tracker.steps.store(steps, Ordering::Release);
+            }
+        }
+    }
+
+    /// True once the batch has been asked to stop
+    pub fn cancelled(&self) -> bool {
+        match self {
+            Progress::Tracked(tracker) => tracker.cancelled.load(Ordering::Acquire),
+            _ => false,
+        }
+    }
+
+    /// Starts reporting a file that takes `steps` operations
+    pub fn start(&self, path: &Path, steps: u64) -> FileProgress {
+        match self {
+            Progress::Bars(mp, theme) => {
+                let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                    return FileProgress::Hidden;
+                };
+                let pb = mp.add(ProgressBar::new(steps));
+                pb.set_style(theme.style.clone());
+                let fancy = theme.kind == BarStyle::Detailed;
+                if fancy {
+                    pb.set_message(format!("📄 {}", display_name(name).bright_white()));
+                } else {
+                    pb.set_message(display_name(name));
+                }
+                FileProgress::Bar { bar: pb, fancy }
+            }
+            Progress::Json => {
+                let file = json_string(&path.to_string_lossy());
+                emit(&format!(
+                    r#"{{"event":"started","file":{file},"steps":{steps}}}"#
+                ));
+                FileProgress::Json {
+                    file,
+                    steps,
+                    step: AtomicU64::new(0),
+                }
+            }
+            Progress::Tracked(tracker) => FileProgress::Tracked {
+                tracker: Arc::clone(tracker),
+                steps,
+                step: AtomicU64::new(0),
+            },
+            Progress::Hidden => FileProgress::Hidden,
+        }
+    }
+
+    /// Reports the end of the batch
+    pub fn finish(&self, files: usize, failed: usize) {
+        if let Progress::Json = self {
+            emit(&format!(
+                r#"{{"event":"finished","files":{files},"failed":{failed}}}"#
+            ));
+        }
+    }
+}
+
+/// Progress of a single input file
+pub enum FileProgress {
+    Bar {
+        bar: ProgressBar,
+        /// Emoji and colors, as in the bar's theme
+        fancy: bool,
+    },
+    Json {
+        /// Path, already encoded as a JSON string
+        file: String,
+        steps: u64,
+        step: AtomicU64,
+    },
+    Tracked {
+        tracker: Arc<Tracker>,
+        steps: u64,
+        step: AtomicU64,
+    },
+    /// Nothing is shown (e.g. a filename that can't be displayed)
+    Hidden,
+}
+
+impl FileProgress {
+    /// Marks one operation as done
+    pub fn inc(&self) {
+        match self {
+            FileProgress::Bar { bar, .. } => bar.inc(1),
+            FileProgress::Json { file, steps, step } => {
+                let step = step.fetch_add(1, Ordering::AcqRel) + 1;
+                let percent = if *steps == 0 { 100 } else { step * 100 / steps };
+                emit(&format!(
+                    r#"{{"event":"progress","file":{file},"step":{step},"steps":{steps},"percent":{percent}}}"#
+                ));
+            }
+            FileProgress::Tracked { tracker, step, .. } => {
+                step.fetch_add(1, Ordering::AcqRel);
+                tracker.steps_done.fetch_add(1, Ordering::AcqRel);
+            }
+            FileProgress::Hidden => {}
+        }
+    }
+
+    /// Notes what the file shows: `photo`, `graphic` or `text`
+    pub fn classified(&self, class: &str) {
+        if let FileProgress::Json { file, .. } = self {
+            emit(&format!(
+                r#"{{"event":"classified","file":{file},"class":{}}}"#,
+                json_string(class)
+            ));
+        }
+    }
+
+    /// Notes an output left out for repeating `same_as`; counted by `inc`
+    pub fn elided(&self, output: &Path, same_as: &Path) {
+        if let FileProgress::Json { file, .. } = self {
+            emit(&format!(
+                r#"{{"event":"elided","file":{file},"output":{},"same_as":{}}}"#,
+                json_string(&output.to_string_lossy()),
+                json_string(&same_as.to_string_lossy())
+            ));
+        }
+    }
+
+    /// Finishes the file as succeeded, or failed with `error`
+    pub fn finish(&self, path: &Path, error: Option<&anyhow::Error>) {
+        match self {
+            FileProgress::Bar { bar, fancy } => {
+                let name = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .map(display_name)
+                    .unwrap_or("unknown".to_string());
+
+                bar.finish_with_message(match (error.is_none(), fancy) {
+                    (true, true) => format!("  ✓ {}", name.green()),
+                    (false, true) => format!("  ✗ {}", name.red()),
+                    (true, false) => format!("ok {name}"),
+                    (false, false) => format!("!! {name}"),
+                });
+            }
+            FileProgress::Json { file, .. } => match error {
+                None => emit(&format!(r#"{{"event":"done","file":{file},"ok":true}}"#)),
+                Some(err) => emit(&format!(
+                    r#"{{"event":"done","file":{file},"ok":false,"error":{}}}"#,
+                    json_string(&err.to_string())
+                )),
+            },
+            FileProgress::Tracked {
+                tracker,
+                steps,
+                step,
+            } => {
+                // Steps a failed file never ran still count as handled
+                let skipped = steps.saturating_sub(step.load(Ordering::Acquire));
+                tracker.steps_done.fetch_add(skipped, Ordering::AcqRel);
+                tracker.files_done.fetch_add(1, Ordering::AcqRel);
+                if error.is_some() {
+                    tracker.failed.fetch_add(1, Ordering::AcqRel);
+                }
+            }
+            FileProgress::Hidden => {}
+        }
+    }
+}
+
+/// Writes one event line; the stdout lock keeps lines from interleaving
+fn emit(line: &str) {
+    let mut out = std::io::stdout().lock();
+    let _ = writeln!(out, "{line}");
+    let _ = out.flush();
+}
+
+/// Encodes a string as a quoted JSON string
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Terminal columns a filename may take in a bar's message
+const NAME_WIDTH: usize = 35;
+/// Columns kept from the start and the end of a truncated filename
+const NAME_HEAD: usize = 20;
+const NAME_TAIL: usize = 12;
+
+/// Truncates a filename if too wide for display, cutting between whole
+/// graphemes so CJK names and emoji stay intact and within the width
+fn display_name(name: &str) -> String {
+    if name.width() <= NAME_WIDTH {
+        return name.to_string();
+    }
+
+    let graphemes: Vec<&str> = name.graphemes(true).collect();
+    let head = fit_width(graphemes.iter().copied(), NAME_HEAD);
+    let tail = fit_width(graphemes[head.len()..].iter().rev().copied(), NAME_TAIL);
+    format!(
+        "{}...{}",
+        head.concat(),
+        tail.into_iter().rev().collect::<String>()
+    )
+}
+
+/// Leading graphemes that fit in `width` columns
+fn fit_width<'a>(graphemes: impl Iterator<Item = &'a str>, width: usize) -> Vec<&'a str> {
+    let mut used = 0;
+    graphemes
+        .take_while(|g| {
+            used += g.width();
+            used <= width
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Byte offsets where the graphemes of `name` start, plus its end
+    fn boundaries(name: &str) -> Vec<usize> {
+        let mut offsets: Vec<usize> = name.grapheme_indices(true).map(|(i, _)| i).collect();
+        offsets.push(name.len());
+        offsets
+    }
+
+    /// Displays `name` and checks the result fits and, when truncated,
+    /// is a whole-grapheme head and tail of it
+    fn check(name: &str) -> String {
+        let shown = display_name(name);
+        assert!(
+            shown.width() <= NAME_WIDTH,
+            "{shown:?} is {} columns",
+            shown.width()
+        );
+        if shown != name {
+            let (head, tail) = shown.split_once("...").expect("truncation marker");
+            assert!(name.starts_with(head) && name.ends_with(tail), "{shown:?}");
+            let cuts = boundaries(name);
+            assert!(
+                cuts.contains(&head.len()),
+                "head of {shown:?} splits a grapheme"
+            );
+            assert!(
+                cuts.contains(&(name.len() - tail.len())),
+                "tail of {shown:?} splits a grapheme"
+            );
+        }
+        shown
+    }
+
+    #[test]
+    fn ascii_names_at_the_width_are_kept() {
+        let name = "a".repeat(NAME_WIDTH);
+        assert_eq!(check(&name), name);
+        let longer = "b".repeat(NAME_WIDTH + 1);
+        assert_ne!(check(&longer), longer);
+    }
+
+    #[test]
+    fn cjk_names_are_cut_between_wide_characters() {
+        // 17 double-width characters and one narrow: exactly NAME_WIDTH
+        let exact = format!("{}.", "写真".repeat(8) + "旅");
+        assert_eq!(exact.width(), NAME_WIDTH);
+        assert_eq!(check(&exact), exact);
+
+        let long = format!("{}.jpg", "東京の夜景".repeat(6));
+        let shown = check(&long);
+        assert!(shown.ends_with(".jpg"));
+        assert!(shown.width() >= NAME_WIDTH - 2);
+    }
+
+    #[test]
+    fn emoji_sequences_stay_whole() {
+        let family = "👨\u{200d}👩\u{200d}👧\u{200d}👦";
+        let flag = "🇮🇹";
+        let name = format!(
+            "{}{}_{}.png",
+            family.repeat(10),
+            flag.repeat(5),
+            "x".repeat(8)
+        );
+        let shown = check(&name);
+        assert_ne!(shown, name);
+        // A cut family would leave a dangling joiner
+        assert!(!shown.contains("\u{200d}..."));
+        assert!(!shown.contains("...\u{200d}"));
+    }
+
+    #[test]
+    fn combining_marks_stay_with_their_letter() {
+        let name = "e\u{301}".repeat(NAME_WIDTH + 5);
+        let shown = check(&name);
+        assert!(!shown.starts_with('\u{301}'));
+        assert!(!shown.contains("...\u{301}"));
+    }
+}
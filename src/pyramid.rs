@@ -0,0 +1,338 @@
+// src/pyramid.rs
+//
+// `rsimg pyramid`: cuts a large image into a zoomable tile pyramid for
+// OpenSeadragon, Leaflet-IIIF, Mirador and similar viewers, without a
+// separate vips install.
+//
+//   dzi   photo.dzi + photo_files/{level}/{column}_{row}.jpg (Deep Zoom; level
+//         N is full size, each level below half the one above, down to 1x1)
+//   iiif  photo/info.json + photo/{x},{y},{w},{h}/{w},{h}/0/default.jpg
+//         (IIIF Image API 3.0, level 0: static tiles at power-of-two scales)
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use image::DynamicImage;
+use image::imageops::FilterType;
+use owo_colors::OwoColorize;
+use rayon::prelude::*;
+use rsimg::decode;
+use rsimg::encode::{EncodeOptions, save_image};
+use rsimg::exit::InvalidConfig;
+use rsimg::i18n::{self, Msg, tr};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Layout {
+    /// Deep Zoom (.dzi descriptor and a _files directory)
+    Dzi,
+    /// IIIF Image API 3.0 level 0 (info.json and static tiles)
+    Iiif,
+}
+
+#[derive(clap::Args)]
+pub struct PyramidArgs {
+    /// Image to cut into tiles
+    #[arg(value_name = "INPUT", help = "Input image")]
+    pub input: PathBuf,
+
+    /// Pyramid layout
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Layout::Dzi,
+        value_name = "LAYOUT",
+        help = "Pyramid layout (dzi, iiif)"
+    )]
+    pub layout: Layout,
+
+    /// Tile edge in pixels, not counting the DZI overlap
+    #[arg(
+        long,
+        default_value_t = 254,
+        value_parser = clap::value_parser!(u32).range(16..=4096),
+        value_name = "PIXELS",
+        help = "Tile size"
+    )]
+    pub tile_size: u32,
+
+    /// Pixels each DZI tile shares with its neighbors
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_name = "PIXELS",
+        help = "Tile overlap (DZI only)"
+    )]
+    pub overlap: u32,
+
+    /// Tile format
+    #[arg(
+        long,
+        default_value = "jpg",
+        value_parser = ["jpg", "png", "webp"],
+        value_name = "FORMAT",
+        help = "Tile format (jpg, png, webp)"
+    )]
+    pub format: String,
+
+    /// JPEG/WebP tile quality (0-100)
+    #[arg(
+        short,
+        long,
+        default_value_t = 80,
+        value_name = "QUALITY",
+        help = "Tile quality (0-100)"
+    )]
+    pub quality: u8,
+
+    /// URL the IIIF pyramid will be served from, used as its `id`
+    /// (default: the directory name)
+    #[arg(long, value_name = "URL", help = "Public URL of the IIIF pyramid")]
+    pub base_url: Option<String>,
+
+    /// Output name without extension (default: the input's stem next to it)
+    #[arg(short, long, value_name = "PATH", help = "Output path")]
+    pub output: Option<PathBuf>,
+}
+
+/// One tile to cut from a level
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    path: PathBuf,
+}
+
+/// Runs the `pyramid` subcommand
+pub fn run(args: PyramidArgs) -> Result<()> {
+    if args.quality > 100 {
+        return Err(InvalidConfig(tr!(Msg::QualityRange)).into());
+    }
+    if args.overlap >= args.tile_size / 2 {
+        return Err(InvalidConfig(tr!(Msg::OverlapRange, args.tile_size)).into());
+    }
+
+    let img = decode::load_image(&args.input)?;
+    let base = match &args.output {
+        Some(path) => path.clone(),
+        None => default_base(&args.input)?,
+    };
+    let encode = EncodeOptions {
+        quality: args.quality,
+        ..Default::default()
+    };
+
+    println!(
+        "{}",
+        format!("\n=== RSIMG — {} ===\n", i18n::text(Msg::PyramidTitle))
+            .bold()
+            .cyan()
+    );
+    let (descriptor, tiles) = match args.layout {
+        Layout::Dzi => dzi(&img, &base, &args, &encode)?,
+        Layout::Iiif => iiif(&img, &base, &args, &encode)?,
+    };
+    println!(
+        "  {} {}: {} ({})\n",
+        "💾".bright_white(),
+        i18n::text(Msg::Pyramid),
+        descriptor.display().to_string().bright_yellow(),
+        tr!(Msg::Tiles, tiles).bright_cyan()
+    );
+    Ok(())
+}
+
+/// Writes a Deep Zoom pyramid; returns the descriptor path and tile count
+fn dzi(
+    img: &DynamicImage,
+    base: &Path,
+    args: &PyramidArgs,
+    encode: &EncodeOptions,
+) -> Result<(PathBuf, usize)> {
+    let (size, overlap) = (args.tile_size, args.overlap);
+    let files = with_suffix(base, "_files");
+    let levels = levels(img, 1);
+    let max_level = levels.len() - 1;
+
+    let mut count = 0;
+    for (depth, level) in levels.iter().enumerate() {
+        let dir = files.join((max_level - depth).to_string());
+        create_dir(&dir)?;
+        let (width, height) = (level.width(), level.height());
+        let mut tiles = Vec::new();
+        for row in 0..height.div_ceil(size) {
+            for column in 0..width.div_ceil(size) {
+                // Tiles reach `overlap` pixels into each neighbor
+                let x = (column * size).saturating_sub(overlap);
+                let y = (row * size).saturating_sub(overlap);
+                let right = ((column + 1) * size + overlap).min(width);
+                let bottom = ((row + 1) * size + overlap).min(height);
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: right - x,
+                    height: bottom - y,
+                    path: dir.join(format!("{column}_{row}.{}", args.format)),
+                });
+            }
+        }
+        count += save_tiles(level, &tiles, &args.format, encode)?;
+    }
+
+    let descriptor = with_suffix(base, ".dzi");
+    let xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<Image xmlns="http://schemas.microsoft.com/deepzoom/2008" Format="{}" Overlap="{overlap}" TileSize="{size}">
+  <Size Width="{}" Height="{}"/>
+</Image>
+"#,
+        args.format,
+        img.width(),
+        img.height()
+    );
+    write(&descriptor, &xml)?;
+    Ok((descriptor, count))
+}
+
+/// Writes an IIIF level 0 pyramid; returns the info.json path and tile count
+fn iiif(
+    img: &DynamicImage,
+    base: &Path,
+    args: &PyramidArgs,
+    encode: &EncodeOptions,
+) -> Result<(PathBuf, usize)> {
+    let size = args.tile_size;
+    let levels = levels(img, size);
+
+    let mut count = 0;
+    let mut scale_factors = Vec::with_capacity(levels.len());
+    for (depth, level) in levels.iter().enumerate() {
+        let scale = 1u32 << depth;
+        scale_factors.push(scale.to_string());
+        let (width, height) = (level.width(), level.height());
+        let mut tiles = Vec::new();
+        for row in 0..height.div_ceil(size) {
+            for column in 0..width.div_ceil(size) {
+                let (x, y) = (column * size, row * size);
+                let (w, h) = (size.min(width - x), size.min(height - y));
+                // Regions are addressed in full-resolution pixels
+                let region_w = (w * scale).min(img.width() - x * scale);
+                let region_h = (h * scale).min(img.height() - y * scale);
+                let path = base
+                    .join(format!("{},{},{region_w},{region_h}", x * scale, y * scale))
+                    .join(format!("{w},{h}"))
+                    .join("0");
+                create_dir(&path)?;
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: w,
+                    height: h,
+                    path: path.join(format!("default.{}", args.format)),
+                });
+            }
+        }
+        count += save_tiles(level, &tiles, &args.format, encode)?;
+    }
+
+    // The smallest level doubles as a full-image thumbnail
+    let smallest = levels.last().expect("at least the full-size level");
+    let (thumb_w, thumb_h) = (smallest.width(), smallest.height());
+    let full = base
+        .join("full")
+        .join(format!("{thumb_w},{thumb_h}"))
+        .join("0");
+    create_dir(&full)?;
+    save_image(
+        smallest,
+        &full.join(format!("default.{}", args.format)),
+        &args.format,
+        encode,
+    )?;
+
+    let id = match &args.base_url {
+        Some(url) => url.trim_end_matches('/').to_string(),
+        None => base
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    let info = format!(
+        r#"{{
+  "@context": "http://iiif.io/api/image/3/context.json",
+  "id": "{}",
+  "type": "ImageService3",
+  "protocol": "http://iiif.io/api/image",
+  "profile": "level0",
+  "width": {},
+  "height": {},
+  "sizes": [{{"width": {thumb_w}, "height": {thumb_h}}}],
+  "tiles": [{{"width": {size}, "height": {size}, "scaleFactors": [{}]}}],
+  "preferredFormats": ["{}"]
+}}
+"#,
+        id.replace('\\', "\\\\").replace('"', "\\\""),
+        img.width(),
+        img.height(),
+        scale_factors.join(", "),
+        args.format
+    );
+    let descriptor = base.join("info.json");
+    write(&descriptor, &info)?;
+    Ok((descriptor, count + 1))
+}
+
+/// The image halved repeatedly (rounding up) until both sides are at most
+/// `smallest`, full size first
+fn levels(img: &DynamicImage, smallest: u32) -> Vec<DynamicImage> {
+    let mut levels = vec![img.clone()];
+    loop {
+        let last = levels.last().expect("at least the full-size level");
+        let (width, height) = (last.width(), last.height());
+        if width <= smallest && height <= smallest {
+            return levels;
+        }
+        let half = last.resize_exact(width.div_ceil(2), height.div_ceil(2), FilterType::Triangle);
+        levels.push(half);
+    }
+}
+
+/// Encodes the tiles of one level in parallel; returns how many were written
+fn save_tiles(
+    level: &DynamicImage,
+    tiles: &[Tile],
+    format: &str,
+    encode: &EncodeOptions,
+) -> Result<usize> {
+    tiles.par_iter().try_for_each(|tile| {
+        let region = level.crop_imm(tile.x, tile.y, tile.width, tile.height);
+        save_image(&region, &tile.path, format, encode)
+            .with_context(|| format!("Failed to save tile: {}", tile.path.display()))
+    })?;
+    Ok(tiles.len())
+}
+
+/// `photo.tif` → `photo` in the same directory
+fn default_base(input: &Path) -> Result<PathBuf> {
+    let stem = input
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Invalid filename: {}", input.display()))?;
+    Ok(input.with_file_name(stem))
+}
+
+fn with_suffix(base: &Path, suffix: &str) -> PathBuf {
+    let mut name = base.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn create_dir(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create directory: {}", dir.display()))
+}
+
+fn write(path: &Path, contents: &str) -> Result<()> {
+    std::fs::write(path, contents)
+        .with_context(|| format!("Failed to write file: {}", path.display()))
+}
@@ -0,0 +1,224 @@
+// src/python.rs
+//
+// Python extension module (`python` feature, built with maturin):
+//
+//   import rsimg
+//   for scale, fmt, data in rsimg.optimize("photo.jpg", formats=["webp"], scales=[50]):
+//       ...
+//   files, failed = rsimg.optimize_batch("./photos", output="./out",
+//                                        progress=lambda done, total, pct: ...)
+//
+// The GIL is released while images are processed; batch progress callbacks
+// run on the calling thread.
+
+use crate::decode;
+use crate::encode::EncodeOptions;
+use crate::exit::{InvalidConfig, NoInputs, PartialFailure};
+use crate::i18n::{Msg, tr};
+use crate::pipeline::{self, Options};
+use crate::processor::{self, Inputs, ProcessOptions, ScanOptions};
+use crate::progress::{Progress, Tracker};
+use crate::transform::Transforms;
+use anyhow::{Context, Result};
+use pyo3::exceptions::{PyFileNotFoundError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// How often a running batch reports progress
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[pymodule]
+fn rsimg(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(optimize, m)?)?;
+    m.add_function(wrap_pyfunction!(optimize_batch, m)?)?;
+    Ok(())
+}
+
+/// An image given as encoded bytes or as a path
+#[derive(FromPyObject)]
+enum Source {
+    Bytes(Vec<u8>),
+    Path(PathBuf),
+}
+
+/// Optimizes one image in memory.
+///
+/// `source` is a path or the encoded image as bytes. Returns a list of
+/// `(scale, format, data)` tuples, scale-major.
+#[pyfunction]
+#[pyo3(signature = (source, formats=None, scales=None, quality=None))]
+fn optimize<'py>(
+    py: Python<'py>,
+    source: Source,
+    formats: Option<Vec<String>>,
+    scales: Option<Vec<u32>>,
+    quality: Option<u8>,
+) -> PyResult<Vec<(u32, String, Bound<'py, PyBytes>)>> {
+    let opts = Options {
+        formats: formats.unwrap_or_else(|| Options::default().formats),
+        scales: checked_scales(scales).map_err(to_py)?,
+        encode: encode_options(quality).map_err(to_py)?,
+        transforms: Transforms::default(),
+    };
+
+    let outputs = py
+        .detach(|| match &source {
+            Source::Bytes(bytes) => pipeline::optimize(bytes, &opts),
+            Source::Path(path) => {
+                let img = opts
+                    .transforms
+                    .apply(decode::load_image(path)?, path)
+                    .with_context(|| tr!(Msg::TransformFailed, path.display()))?;
+                pipeline::variants(&img, path, &opts)
+            }
+        })
+        .map_err(to_py)?;
+
+    Ok(outputs
+        .into_iter()
+        .map(|output| (output.scale, output.format, PyBytes::new(py, &output.bytes)))
+        .collect())
+}
+
+/// Optimizes a file or folder, writing outputs like the command line does.
+///
+/// `progress`, if given, is called as `progress(done, total, percent)` while
+/// the batch runs; raising from it cancels the rest of the batch. Returns
+/// `(files, failed)`.
+#[pyfunction]
+#[pyo3(signature = (
+    input,
+    output=None,
+    formats=None,
+    scales=None,
+    quality=None,
+    recursive=false,
+    progress=None
+))]
+#[allow(clippy::too_many_arguments)]
+fn optimize_batch(
+    py: Python<'_>,
+    input: PathBuf,
+    output: Option<PathBuf>,
+    formats: Option<Vec<String>>,
+    scales: Option<Vec<u32>>,
+    quality: Option<u8>,
+    recursive: bool,
+    progress: Option<Py<PyAny>>,
+) -> PyResult<(usize, usize)> {
+    let opts = ProcessOptions {
+        formats: formats.unwrap_or_else(|| Options::default().formats),
+        scales: checked_scales(scales).map_err(to_py)?,
+        encode: encode_options(quality).map_err(to_py)?,
+        output_dir: output,
+        ..Default::default()
+    };
+    let files = processor::collect_image_files(
+        &input,
+        &ScanOptions {
+            recursive,
+            ..ScanOptions::default()
+        },
+    )
+    .map_err(to_py)?;
+    if files.is_empty() {
+        return Ok((0, 0));
+    }
+    if let Some(dir) = &opts.output_dir {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create output directory: {}", dir.display()))
+            .map_err(to_py)?;
+    }
+
+    let total = files.len();
+    let tracker = Arc::new(Tracker::default());
+    let mut callback_error = None;
+
+    let result = py.detach(|| {
+        std::thread::scope(|scope| {
+            let batch = scope.spawn(|| {
+                processor::process_all(
+                    Inputs::Files(files),
+                    &opts,
+                    &Progress::Tracked(Arc::clone(&tracker)),
+                )
+            });
+
+            // Report from this thread so the callback runs where it was given
+            let mut last = None;
+            loop {
+                let finished = batch.is_finished();
+                let state = (
+                    tracker.files_done.load(Ordering::Acquire),
+                    tracker.percent(),
+                );
+                if callback_error.is_none() && last != Some(state) {
+                    last = Some(state);
+                    let reported = Python::attach(|py| {
+                        py.check_signals()?;
+                        match &progress {
+                            Some(callback) => callback.call1(py, (state.0, total, state.1)),
+                            None => Ok(py.None()),
+                        }
+                    });
+                    if let Err(err) = reported {
+                        tracker.cancelled.store(true, Ordering::Release);
+                        callback_error = Some(err);
+                    }
+                }
+                if finished {
+                    break;
+                }
+                std::thread::sleep(PROGRESS_INTERVAL);
+            }
+
+            batch.join().expect("batch thread panicked")
+        })
+    });
+
+    if let Some(err) = callback_error {
+        return Err(err);
+    }
+    match result {
+        Ok(_) => Ok((total, 0)),
+        Err(err) => match err.downcast_ref::<PartialFailure>() {
+            Some(PartialFailure(failed)) => Ok((total, *failed)),
+            None => Err(to_py(err)),
+        },
+    }
+}
+
+/// Scale percentages, defaulting to the command-line ones
+fn checked_scales(scales: Option<Vec<u32>>) -> Result<Vec<u32>> {
+    let scales = scales.unwrap_or_else(|| Options::default().scales);
+    if let Some(scale) = scales.iter().find(|&&s| !(10..=100).contains(&s)) {
+        return Err(InvalidConfig(tr!(Msg::ScaleInvalid, scale)).into());
+    }
+    Ok(scales)
+}
+
+fn encode_options(quality: Option<u8>) -> Result<EncodeOptions> {
+    let mut encode = EncodeOptions::default();
+    match quality {
+        None => {}
+        Some(q @ 0..=100) => encode.quality = q,
+        Some(q) => return Err(InvalidConfig(tr!(Msg::QualityInvalid, q)).into()),
+    }
+    Ok(encode)
+}
+
+/// Raises an error as the closest Python exception
+fn to_py(err: anyhow::Error) -> PyErr {
+    let message = format!("{err:#}");
+    if err.downcast_ref::<InvalidConfig>().is_some() {
+        PyValueError::new_err(message)
+    } else if err.downcast_ref::<NoInputs>().is_some() {
+        PyFileNotFoundError::new_err(message)
+    } else {
+        PyRuntimeError::new_err(message)
+    }
+}
@@ -0,0 +1,79 @@
+// src/results.rs
+//
+// `--results-fifo`: one JSON line per input as soon as it finishes, so a
+// downstream process (an uploader pushing outputs to a CDN, say) can work
+// on finished files while the batch goes on with the rest:
+//
+//   {"input":"a.jpg","ms":84,"ok":true,"outputs":[{"bytes":9120,"path":"out/a_75pct.webp"}]}
+//   {"error":"...","input":"b.png","ms":3,"ok":false,"outputs":[]}
+//
+// The path is usually a named pipe (`mkfifo`); opening one for writing
+// waits until a reader has it open, so the reader starts first. Any other
+// path is written as a plain file, created or truncated. Lines are flushed
+// one by one. A reader that goes away ends the records, not the batch.
+
+use crate::i18n::{Msg, tr};
+use crate::processor::FileRecord;
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use serde_json::json;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where a batch's per-file results are written as they come in
+pub struct ResultsPipe {
+    path: PathBuf,
+    /// None once writing has failed
+    out: Mutex<Option<File>>,
+}
+
+impl ResultsPipe {
+    /// Opens `path` for writing, waiting for a reader if it's a FIFO
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            out: Mutex::new(Some(file)),
+        })
+    }
+
+    /// Writes the line for `record`; the first failure, usually the reader
+    /// closing its end, is reported once and stops further lines
+    pub(crate) fn write(&self, record: &FileRecord) {
+        let outputs: Vec<_> = record
+            .outputs
+            .iter()
+            .map(|(path, bytes)| json!({ "path": path.to_string_lossy(), "bytes": bytes }))
+            .collect();
+        let mut line = json!({
+            "input": record.input.to_string_lossy(),
+            "ok": record.error.is_none(),
+            "outputs": outputs,
+            "ms": record.elapsed.as_millis() as u64,
+        });
+        if let Some(error) = &record.error {
+            line["error"] = json!(error);
+        }
+
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(file) = out.as_mut() else {
+            return;
+        };
+        // One write per line, so a pipe's reader never sees half of one
+        if file.write_all(format!("{line}\n").as_bytes()).is_err() {
+            *out = None;
+            eprintln!(
+                "  {} {}",
+                "⚠".yellow(),
+                tr!(Msg::ResultsClosed, self.path.display())
+            );
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// src/scratch.rs
+//
+// Private working directories under `--tmpdir` (the staged clipboard image,
+// `tune` candidates, `--exec-decode` PNGs). The temp dir is usually shared
+// between users, so a predictable name would let another user create the
+// directory, or a symlink in its place, before rsimg does. Each directory
+// gets a random name, is created only if nothing is there yet and is
+// readable by its owner alone; it's removed with its contents when dropped.
+
+use anyhow::{Context, Result};
+use std::fs::DirBuilder;
+use std::hash::{BuildHasher, RandomState};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Names tried before giving up on finding a free one
+const ATTEMPTS: usize = 16;
+
+/// A directory of our own, removed when dropped
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    /// Creates a new `{prefix}-{random}` directory in `tmpdir`
+    pub fn new(tmpdir: &Path, prefix: &str) -> Result<Self> {
+        let mut builder = DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+        let mut attempt = 0;
+        loop {
+            let dir = tmpdir.join(format!("{prefix}-{:016x}", random()));
+            match builder.create(&dir) {
+                Ok(()) => return Ok(Self(dir)),
+                Err(err) if err.kind() == ErrorKind::AlreadyExists && attempt < ATTEMPTS => {
+                    attempt += 1;
+                }
+                Err(err) => {
+                    return Err(err)
+                        .with_context(|| format!("Failed to create directory: {}", dir.display()));
+                }
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A hard-to-guess number: the standard library seeds `RandomState` from
+/// the OS, and the time and a counter tell apart calls within the process
+fn random() -> u64 {
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    RandomState::new().hash_one((
+        std::process::id(),
+        nanos,
+        CALLS.fetch_add(1, Ordering::Relaxed),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directories_are_fresh_and_removed() {
+        let tmp = std::env::temp_dir();
+        let first = ScratchDir::new(&tmp, "rsimg-scratch-test").unwrap();
+        let second = ScratchDir::new(&tmp, "rsimg-scratch-test").unwrap();
+        assert_ne!(first.path(), second.path());
+        assert!(first.path().is_dir());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(first.path())
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        let path = first.path().to_path_buf();
+        std::fs::write(path.join("file"), b"x").unwrap();
+        drop(first);
+        assert!(!path.exists());
+    }
+}
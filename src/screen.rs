@@ -0,0 +1,107 @@
+// src/screen.rs
+//
+// `--skip-blurry`: photo dumps hold missed shots, out of focus, shaken,
+// or with the lens cap still on, that nobody wants published. Each input
+// is decoded and reduced once before the batch, and fails when its
+// sharpness, the variance of the Laplacian of the reduced copy, is below
+// the threshold, or when its mean brightness leaves it nearly black or
+// nearly white. Failures are left out and listed at the end of the batch,
+// sparing their encodes. Flat images (a logo on white, a gradient) score
+// as blurry too, so the screening is for photos.
+
+use crate::decode;
+use crate::i18n::{self, Msg};
+use crate::tr;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage};
+use std::fmt;
+use std::path::Path;
+
+/// Size images are reduced to before they're scored, so scores don't
+/// depend on resolution
+const WORKING_SIZE: u32 = 512;
+/// Mean brightness (0-255) under which an image is underexposed
+const DARKEST: f64 = 24.0;
+/// Mean brightness over which an image is overexposed
+const BRIGHTEST: f64 = 235.0;
+
+/// How inputs are screened
+#[derive(Clone, Copy, Debug)]
+pub struct Screening {
+    /// Sharpness an input must reach
+    pub min_sharpness: f64,
+}
+
+/// Why an input failed screening
+#[derive(Clone, Copy, Debug)]
+pub enum Fault {
+    /// With the sharpness it scored
+    Blurry(f64),
+    Underexposed,
+    Overexposed,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::Blurry(sharpness) => {
+                write!(f, "{}", tr!(Msg::Blurry, format!("{sharpness:.0}")))
+            }
+            Fault::Underexposed => f.write_str(i18n::text(Msg::Underexposed)),
+            Fault::Overexposed => f.write_str(i18n::text(Msg::Overexposed)),
+        }
+    }
+}
+
+impl Screening {
+    /// Why the input at `path` fails screening, if it does; inputs that
+    /// don't decode pass, to fail later
+    pub fn fault(&self, path: &Path) -> Option<Fault> {
+        let img = decode::load_image(path).ok()?;
+        let gray = reduce(&img);
+        let pixels = gray.as_raw();
+        let brightness =
+            pixels.iter().map(|&v| f64::from(v)).sum::<f64>() / pixels.len().max(1) as f64;
+        if brightness < DARKEST {
+            return Some(Fault::Underexposed);
+        }
+        if brightness > BRIGHTEST {
+            return Some(Fault::Overexposed);
+        }
+        let sharpness = laplacian_variance(&gray);
+        (sharpness < self.min_sharpness).then_some(Fault::Blurry(sharpness))
+    }
+}
+
+/// `img` as luma, fit within `WORKING_SIZE`
+pub(crate) fn reduce(img: &DynamicImage) -> GrayImage {
+    let gray = img.to_luma8();
+    let (width, height) = gray.dimensions();
+    if width <= WORKING_SIZE && height <= WORKING_SIZE {
+        return gray;
+    }
+    let scale = f64::from(WORKING_SIZE) / f64::from(width.max(height));
+    let size = |side: u32| ((f64::from(side) * scale).round() as u32).max(1);
+    image::imageops::resize(&gray, size(width), size(height), FilterType::Triangle)
+}
+
+/// Variance of the 4-neighbour Laplacian: high where edges are crisp
+pub(crate) fn laplacian_variance(gray: &GrayImage) -> f64 {
+    let (width, height) = gray.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+    let at = |x: u32, y: u32| f64::from(gray.get_pixel(x, y)[0]);
+    let (mut sum, mut squares, mut n) = (0.0, 0.0, 0.0);
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let laplacian =
+                4.0 * at(x, y) - at(x - 1, y) - at(x + 1, y) - at(x, y - 1) - at(x, y + 1);
+            sum += laplacian;
+            squares += laplacian * laplacian;
+            n += 1.0;
+        }
+    }
+    let mean = sum / n;
+    squares / n - mean * mean
+}
@@ -0,0 +1,93 @@
+// src/select.rs
+//
+// `--min-rating` and `--keyword`: a batch takes only the inputs picked in a
+// photo catalog, by the star rating and keywords Lightroom, darktable,
+// digiKam or Bridge saved. They're read from the XMP sidecar beside the
+// input when there is one (as `--xmp-sidecars` finds it), then from the
+// input's own XMP (`xmp:Rating`, `dc:subject`), and the rating last from
+// the EXIF Rating tag Windows writes. Unrated inputs count as 0 stars and
+// rejected ones (-1) as below any rating.
+
+use crate::decode;
+use crate::transform::sidecar;
+use std::path::Path;
+
+/// Which inputs a batch takes by their metadata
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    /// Fewest stars (0-5) an input must have
+    pub min_rating: Option<i32>,
+    /// Keywords an input must all have; case is ignored
+    pub keywords: Vec<String>,
+}
+
+impl Selection {
+    /// True when every input is taken
+    pub fn is_all(&self) -> bool {
+        self.min_rating.is_none() && self.keywords.is_empty()
+    }
+
+    /// Whether the input at `path` has the rating and keywords asked for
+    pub fn admits(&self, path: &Path) -> bool {
+        if self.is_all() {
+            return true;
+        }
+        let sidecar = sidecar::read_xmp(path);
+        let meta = decode::load_metadata(path);
+        let embedded = meta
+            .as_ref()
+            .and_then(|meta| meta.xmp())
+            .map(String::from_utf8_lossy);
+        let packets: Vec<&str> = sidecar
+            .as_deref()
+            .into_iter()
+            .chain(embedded.as_deref())
+            .collect();
+
+        if let Some(min) = self.min_rating {
+            let rating = packets
+                .iter()
+                .find_map(|xmp| sidecar::property(xmp, "xmp:Rating")?.parse::<f32>().ok())
+                .map(|rating| rating.round() as i32)
+                .or_else(|| Some(i32::from(meta.as_ref()?.rating()?)))
+                .unwrap_or(0);
+            if rating < min {
+                return false;
+            }
+        }
+        let keywords: Vec<String> = packets.iter().flat_map(|xmp| keywords(xmp)).collect();
+        self.keywords.iter().all(|wanted| {
+            keywords
+                .iter()
+                .any(|keyword| keyword.to_lowercase() == wanted.to_lowercase())
+        })
+    }
+}
+
+/// The `dc:subject` keywords of an XMP packet
+fn keywords(xmp: &str) -> Vec<String> {
+    let Some(start) = xmp.find("<dc:subject") else {
+        return Vec::new();
+    };
+    let subject = &xmp[start..];
+    let subject = &subject[..subject.find("</dc:subject>").unwrap_or(subject.len())];
+    subject
+        .split("<rdf:li")
+        .skip(1)
+        .filter_map(|item| {
+            let text = &item[item.find('>')? + 1..];
+            let text = &text[..text.find("</rdf:li>")?];
+            Some(unescape(text.trim()))
+        })
+        .filter(|keyword| !keyword.is_empty())
+        .collect()
+}
+
+/// XML text with its entities replaced
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,368 @@
+// src/serve.rs
+//
+// `rsimg serve`: a long-running process that takes batches as JSON-RPC 2.0
+// requests, one per line, so desktop frontends and editors can drive rsimg
+// without spawning a process per batch. Requests are read from a unix socket
+// (one thread per connection) or from stdin, and answered on the same stream.
+//
+// Methods:
+//   submit {input, output?, formats?, scales?, quality?, recursive?} -> {job}
+//   status {job} -> {job, state, files, done, failed, percent, error?}
+//   jobs          -> [status, ...]
+//   cancel {job} -> {job, cancelled}
+
+use anyhow::{Context, Result};
+use rsimg::encode::EncodeOptions;
+use rsimg::exit::PartialFailure;
+use rsimg::i18n::{Msg, tr};
+use rsimg::processor::{self, Inputs, ProcessOptions, ScanOptions};
+use rsimg::progress::{Progress, Tracker};
+use serde_json::{Value, json};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(clap::Args)]
+pub struct ServeArgs {
+    /// Unix socket to listen on (default: requests on stdin, responses on stdout)
+    #[arg(long, value_name = "PATH", help = "Unix socket to listen on")]
+    pub socket: Option<PathBuf>,
+
+    /// Address for the HTTP /optimize endpoint instead of JSON-RPC (e.g. 0.0.0.0:8080)
+    #[cfg(feature = "http")]
+    #[arg(
+        long,
+        value_name = "ADDR",
+        conflicts_with = "socket",
+        help = "Serve /optimize over HTTP"
+    )]
+    pub http: Option<String>,
+
+    /// Hosts `/optimize?url=` may fetch from: a name or address, `*.domain`
+    /// or `*`; fetching by URL is off without any
+    #[cfg(feature = "http")]
+    #[arg(
+        long,
+        value_name = "HOST",
+        requires = "http",
+        help = "Allow fetching /optimize?url= sources from HOST (repeatable; *.domain, *)"
+    )]
+    pub http_allow_host: Vec<String>,
+}
+
+/// Runs the `serve` subcommand until the socket closes or stdin ends
+pub fn run(args: ServeArgs) -> Result<()> {
+    #[cfg(feature = "http")]
+    if let Some(addr) = &args.http {
+        return crate::http::serve(addr, &args.http_allow_host);
+    }
+
+    let server = Arc::new(Server::default());
+
+    match args.socket {
+        Some(path) => listen(&path, &server),
+        None => {
+            serve_stream(std::io::stdin().lock(), std::io::stdout(), &server)?;
+            // Batches submitted before EOF still run to completion
+            server.wait();
+            Ok(())
+        }
+    }
+}
+
+#[cfg(unix)]
+fn listen(path: &Path, server: &Arc<Server>) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    // A socket left behind by an earlier run would make bind fail
+    if let Ok(meta) = std::fs::symlink_metadata(path)
+        && meta.file_type().is_socket()
+    {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let listener =
+        UnixListener::bind(path).with_context(|| tr!(Msg::ListenFailed, path.display()))?;
+    eprintln!("{}", tr!(Msg::Listening, path.display()));
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let server = Arc::clone(server);
+        std::thread::spawn(move || {
+            if let Ok(reader) = stream.try_clone() {
+                let _ = serve_stream(std::io::BufReader::new(reader), stream, &server);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen(_path: &Path, _server: &Arc<Server>) -> Result<()> {
+    Err(rsimg::exit::InvalidConfig(tr!(Msg::SocketUnix)).into())
+}
+
+/// Answers each request line on `reader` with a response line on `writer`
+fn serve_stream(reader: impl BufRead, mut writer: impl Write, server: &Arc<Server>) -> Result<()> {
+    for line in reader.lines() {
+        let line = line.context("Failed to read request")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = server.handle(&line) {
+            writeln!(writer, "{response}").context("Failed to write response")?;
+            writer.flush().context("Failed to write response")?;
+        }
+    }
+    Ok(())
+}
+
+/// A JSON-RPC error object
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Jobs submitted over every connection; a job's id is its index plus one
+#[derive(Default)]
+struct Server {
+    jobs: Mutex<Vec<Arc<Job>>>,
+}
+
+impl Server {
+    /// Handles one request line; notifications (no `id`) get no response
+    fn handle(&self, line: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(err) => {
+                return Some(response(
+                    Value::Null,
+                    Err(RpcError::new(PARSE_ERROR, format!("Parse error: {err}"))),
+                ));
+            }
+        };
+
+        let method = request["method"].as_str().unwrap_or_default();
+        let params = &request["params"];
+        let result = match method {
+            "submit" => self.submit(params),
+            "status" => self.job(params).map(|job| job.status()),
+            "jobs" => Ok(self.all()),
+            "cancel" => self.job(params).map(|job| job.cancel()),
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method: {method}"),
+            )),
+        };
+
+        let id = request.get("id")?.clone();
+        Some(response(id, result))
+    }
+
+    /// Starts a batch in the background and returns its job id
+    fn submit(&self, params: &Value) -> Result<Value, RpcError> {
+        let opts = process_options(params)?;
+        let input = params["input"]
+            .as_str()
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing 'input' path"))?;
+        let recursive = params["recursive"].as_bool().unwrap_or(false);
+
+        let files = processor::collect_image_files(
+            Path::new(input),
+            &ScanOptions {
+                recursive,
+                ..ScanOptions::default()
+            },
+        )
+        .map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))?;
+        if files.is_empty() {
+            return Err(RpcError::new(INVALID_PARAMS, tr!(Msg::NoImages)));
+        }
+        if let Some(dir) = &opts.output_dir {
+            std::fs::create_dir_all(dir).map_err(|err| {
+                RpcError::new(
+                    SERVER_ERROR,
+                    format!(
+                        "Failed to create output directory: {}: {err}",
+                        dir.display()
+                    ),
+                )
+            })?;
+        }
+
+        let job = {
+            let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+            let job = Arc::new(Job {
+                id: jobs.len() + 1,
+                tracker: Arc::new(Tracker::default()),
+                state: Mutex::new(State::Running),
+                handle: Mutex::new(None),
+            });
+            jobs.push(Arc::clone(&job));
+            job
+        };
+
+        let worker = Arc::clone(&job);
+        let handle = std::thread::spawn(move || {
+            let progress = Progress::Tracked(Arc::clone(&worker.tracker));
+            let result = processor::process_all(Inputs::Files(files), &opts, &progress);
+            let state = match result {
+                _ if worker.tracker.cancelled.load(Ordering::Acquire) => State::Cancelled,
+                Ok(_) => State::Done,
+                // Per-file failures show up in the job's `failed` count
+                Err(err) if err.downcast_ref::<PartialFailure>().is_some() => State::Done,
+                Err(err) => State::Failed(err.to_string()),
+            };
+            *worker.state.lock().unwrap_or_else(|e| e.into_inner()) = state;
+        });
+        *job.handle.lock().unwrap_or_else(|e| e.into_inner()) = Some(handle);
+
+        Ok(json!({ "job": job.id }))
+    }
+
+    /// The job named by the `job` parameter
+    fn job(&self, params: &Value) -> Result<Arc<Job>, RpcError> {
+        let id = params["job"]
+            .as_u64()
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing 'job' id"))?;
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        id.checked_sub(1)
+            .and_then(|index| jobs.get(index as usize))
+            .cloned()
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Unknown job: {id}")))
+    }
+
+    /// Status of every job, oldest first
+    fn all(&self) -> Value {
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        Value::Array(jobs.iter().map(|job| job.status()).collect())
+    }
+
+    /// Blocks until every submitted job has finished
+    fn wait(&self) {
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        for job in jobs {
+            let handle = job.handle.lock().unwrap_or_else(|e| e.into_inner()).take();
+            if let Some(handle) = handle {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+/// Lifecycle of a submitted batch
+enum State {
+    Running,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+struct Job {
+    id: usize,
+    tracker: Arc<Tracker>,
+    state: Mutex<State>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Job {
+    fn status(&self) -> Value {
+        let t = &self.tracker;
+        let mut status = json!({
+            "job": self.id,
+            "files": t.files.load(Ordering::Acquire),
+            "done": t.files_done.load(Ordering::Acquire),
+            "failed": t.failed.load(Ordering::Acquire),
+            "percent": t.percent(),
+        });
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        status["state"] = json!(match &*state {
+            State::Running => "running",
+            State::Done => "done",
+            State::Failed(_) => "failed",
+            State::Cancelled => "cancelled",
+        });
+        if let State::Failed(error) = &*state {
+            status["error"] = json!(error);
+        }
+        status
+    }
+
+    /// Stops the job; files already being encoded are finished as failed
+    fn cancel(&self) -> Value {
+        let running = matches!(
+            *self.state.lock().unwrap_or_else(|e| e.into_inner()),
+            State::Running
+        );
+        if running {
+            self.tracker.cancelled.store(true, Ordering::Release);
+        }
+        json!({ "job": self.id, "cancelled": running })
+    }
+}
+
+/// Batch settings from `submit` parameters, with the CLI defaults
+fn process_options(params: &Value) -> Result<ProcessOptions, RpcError> {
+    let invalid = |message: String| RpcError::new(INVALID_PARAMS, message);
+
+    let formats = match &params["formats"] {
+        Value::Null => vec!["jpg".to_string(), "webp".to_string()],
+        value => serde_json::from_value(value.clone())
+            .map_err(|_| invalid("'formats' must be a list of strings".into()))?,
+    };
+    let scales: Vec<u32> = match &params["scales"] {
+        Value::Null => vec![75, 50, 25],
+        value => serde_json::from_value(value.clone())
+            .map_err(|_| invalid("'scales' must be a list of percentages".into()))?,
+    };
+    if let Some(scale) = scales.iter().find(|&&s| !(10..=100).contains(&s)) {
+        return Err(invalid(tr!(Msg::ScaleInvalid, scale)));
+    }
+    let quality = match params["quality"].as_u64() {
+        None => EncodeOptions::default().quality,
+        Some(q @ 0..=100) => q as u8,
+        Some(q) => return Err(invalid(tr!(Msg::QualityInvalid, q))),
+    };
+
+    Ok(ProcessOptions {
+        formats,
+        scales,
+        encode: EncodeOptions {
+            quality,
+            ..Default::default()
+        },
+        output_dir: params["output"].as_str().map(PathBuf::from),
+        ..Default::default()
+    })
+}
+
+/// A JSON-RPC 2.0 response carrying either a result or an error
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": err.code, "message": err.message },
+        }),
+    }
+}
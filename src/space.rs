@@ -0,0 +1,128 @@
+// src/space.rs
+//
+// Disk-space preflight: a batch that runs out of space hours in leaves half
+// its outputs written and the rest failed, so before starting, the outputs
+// are estimated and set against the free space of the volumes they go to.
+// The estimate is rough on purpose: each input's header gives its size,
+// each `--scales` entry scales it, and each format is charged a typical
+// number of bytes per pixel, on the generous side for photos. Batches that
+// would fill a volume don't start; ones that would nearly fill it start
+// with a warning. Only Unix reports free space here; elsewhere the check is
+// skipped.
+
+use crate::budget::format_size;
+use crate::decode;
+use crate::i18n::{Msg, tr};
+use crate::pipeline::scaled_size;
+use crate::processor::ProcessOptions;
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Share of a volume's free space from which a batch is warned about
+const NEARLY_FULL: f64 = 0.9;
+
+/// Typical output bytes per pixel of a format
+fn bytes_per_pixel(format: &str) -> f64 {
+    match format.to_lowercase().as_str() {
+        "jpg" | "jpeg" => 0.5,
+        "webp" => 0.4,
+        "gif" | "pgm" => 1.0,
+        // BC7 blocks, plus a third for the mipmaps
+        "dds" | "ktx2" => 1.4,
+        "png" | "apng" | "ppm" => 3.0,
+        "tga" => 4.0,
+        // Deflated, or four inks separated for print
+        "tiff" | "tif" => 3.0,
+        "ff" | "farbfeld" => 8.0,
+        _ => 4.0,
+    }
+}
+
+/// Estimated bytes the outputs of `files` take, per output directory
+///
+/// Files whose header can't be read are left out; they'll fail anyway.
+pub fn estimate(files: &[PathBuf], opts: &ProcessOptions) -> BTreeMap<PathBuf, u64> {
+    let per_pixel: f64 = opts.formats.iter().map(|f| bytes_per_pixel(f)).sum();
+    let sizes: Vec<(PathBuf, u64)> = files
+        .par_iter()
+        .filter_map(|path| {
+            let size = decode::load_dimensions(path).ok()?;
+            let bytes: f64 = opts
+                .scales
+                .iter()
+                .map(|&scale| {
+                    let (width, height) = scaled_size(size, scale);
+                    f64::from(width) * f64::from(height) * per_pixel
+                })
+                .sum();
+            let dir = match &opts.output_dir {
+                Some(dir) => dir.clone(),
+                None => path.parent()?.to_path_buf(),
+            };
+            Some((dir, bytes as u64))
+        })
+        .collect();
+
+    let mut dirs = BTreeMap::new();
+    for (dir, bytes) in sizes {
+        *dirs.entry(dir).or_default() += bytes;
+    }
+    dirs
+}
+
+/// Checks that the outputs of `files` fit on their volumes: fails when one
+/// would run out of space, unless `force`, and warns when one would be
+/// nearly full
+pub fn preflight(files: &[PathBuf], opts: &ProcessOptions, force: bool) -> Result<()> {
+    // Directories sharing a volume add up
+    let mut volumes: BTreeMap<u64, (PathBuf, u64, u64)> = BTreeMap::new();
+    for (dir, bytes) in estimate(files, opts) {
+        let Some((id, free)) = volume(&dir) else {
+            continue;
+        };
+        let entry = volumes.entry(id).or_insert((dir, free, 0));
+        entry.2 += bytes;
+    }
+
+    for (dir, free, needed) in volumes.into_values() {
+        let (needed_text, free_text) = (format_size(needed), format_size(free));
+        if needed > free {
+            let message = tr!(Msg::SpaceShort, needed_text, dir.display(), free_text);
+            if !force {
+                anyhow::bail!(message);
+            }
+            eprintln!("  {} {}", "⚠".yellow(), message);
+        } else if needed as f64 > free as f64 * NEARLY_FULL {
+            eprintln!(
+                "  {} {}",
+                "⚠".yellow(),
+                tr!(Msg::SpaceLow, needed_text, free_text, dir.display())
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Device and free bytes of the volume `dir` is on, or would be created on
+#[cfg(unix)]
+fn volume(dir: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    // Outputs next to a relative input go to the current directory
+    let dir = match dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => dir,
+    };
+    let existing = dir.ancestors().find(|dir| dir.is_dir())?;
+    let device = std::fs::metadata(existing).ok()?.dev();
+    let stats = rustix::fs::statvfs(existing).ok()?;
+    Some((device, stats.f_bavail.saturating_mul(stats.f_frsize)))
+}
+
+#[cfg(not(unix))]
+fn volume(_dir: &Path) -> Option<(u64, u64)> {
+    None
+}
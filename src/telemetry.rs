@@ -0,0 +1,51 @@
+// src/telemetry.rs
+//
+// `--otlp`: exports the spans the library opens around each stage (collect,
+// decode, resize, encode, write, grouped per file under one batch span) to
+// an OpenTelemetry collector over OTLP/HTTP, to see where time goes when
+// rsimg runs inside build infrastructure. Without a URL the standard
+// OTEL_EXPORTER_OTLP_* variables apply, falling back to localhost:4318.
+// Spans are batched on a background thread and flushed on exit.
+
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::Registry;
+
+/// Exporter installed for the process; flushes pending spans when dropped
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+}
+
+/// Sets up the OTLP exporter, returning it with the tracing layer feeding it
+///
+/// `endpoint` is the collector's base URL (`/v1/traces` is appended), or
+/// empty to go by the environment.
+pub fn init(endpoint: &str) -> Result<(Telemetry, OpenTelemetryLayer<Registry, SdkTracer>)> {
+    let mut exporter = SpanExporter::builder().with_http();
+    if !endpoint.is_empty() {
+        let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+        exporter = exporter.with_endpoint(url);
+    }
+    let exporter = exporter
+        .build()
+        .context("Failed to set up the OTLP exporter")?;
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name("rsimg").build())
+        .build();
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("rsimg"));
+    Ok((Telemetry { provider }, layer))
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(err) = self.provider.shutdown() {
+            eprintln!("Failed to export traces: {err}");
+        }
+    }
+}
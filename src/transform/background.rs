@@ -0,0 +1,153 @@
+// src/transform/background.rs
+//
+// Background removal for product shots: a flood fill from the image edges
+// over pixels close to the dominant border color, or (with the `onnx`
+// feature) an alpha matte predicted by a segmentation model.
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::collections::VecDeque;
+
+#[cfg(feature = "onnx")]
+use crate::onnx::OnnxModel;
+#[cfg(feature = "onnx")]
+use std::sync::Arc;
+
+/// How the background is separated from the subject
+pub enum BackgroundRemoval {
+    /// Flood fill from the edges, tolerance in percent of the channel range
+    FloodFill { tolerance: f32 },
+    /// Alpha matte predicted by an ONNX model
+    #[cfg(feature = "onnx")]
+    Model(Arc<OnnxModel>),
+}
+
+impl BackgroundRemoval {
+    pub fn apply(&self, img: &DynamicImage) -> anyhow::Result<DynamicImage> {
+        match self {
+            BackgroundRemoval::FloodFill { tolerance } => Ok(flood_fill(img, *tolerance)),
+            #[cfg(feature = "onnx")]
+            BackgroundRemoval::Model(model) => model_matte(img, model),
+        }
+    }
+}
+
+/// Makes the edge-connected region matching the border color transparent
+fn flood_fill(img: &DynamicImage, tolerance: f32) -> DynamicImage {
+    let mut rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    if width == 0 || height == 0 {
+        return img.clone();
+    }
+
+    let reference = border_median(&rgba);
+    let limit = tolerance.clamp(0.0, 100.0) / 100.0 * 255.0;
+    let is_background = |p: &Rgba<u8>| distance(p, &reference) <= limit;
+
+    // Breadth-first fill seeded from every matching edge pixel
+    let index = |x: u32, y: u32| (y * width + x) as usize;
+    let mut removed = vec![false; (width * height) as usize];
+    let mut queue = VecDeque::new();
+    for x in 0..width {
+        queue.push_back((x, 0));
+        queue.push_back((x, height - 1));
+    }
+    for y in 0..height {
+        queue.push_back((0, y));
+        queue.push_back((width - 1, y));
+    }
+
+    while let Some((x, y)) = queue.pop_front() {
+        if removed[index(x, y)] || !is_background(rgba.get_pixel(x, y)) {
+            continue;
+        }
+        removed[index(x, y)] = true;
+
+        if x > 0 {
+            queue.push_back((x - 1, y));
+        }
+        if x + 1 < width {
+            queue.push_back((x + 1, y));
+        }
+        if y > 0 {
+            queue.push_back((x, y - 1));
+        }
+        if y + 1 < height {
+            queue.push_back((x, y + 1));
+        }
+    }
+
+    // Clear the background, and soften subject pixels touching it in
+    // proportion to how close they are to the background color
+    for y in 0..height {
+        for x in 0..width {
+            if removed[index(x, y)] {
+                rgba.get_pixel_mut(x, y)[3] = 0;
+                continue;
+            }
+
+            let touches = (x > 0 && removed[index(x - 1, y)])
+                || (x + 1 < width && removed[index(x + 1, y)])
+                || (y > 0 && removed[index(x, y - 1)])
+                || (y + 1 < height && removed[index(x, y + 1)]);
+            if touches {
+                let pixel = rgba.get_pixel_mut(x, y);
+                let weight = (distance(pixel, &reference) / (2.0 * limit.max(1.0))).min(1.0);
+                pixel[3] = (pixel[3] as f32 * weight).round() as u8;
+            }
+        }
+    }
+
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Per-channel median of the outermost pixels
+fn border_median(img: &RgbaImage) -> Rgba<u8> {
+    let (width, height) = img.dimensions();
+    let mut channels: [Vec<u8>; 4] = Default::default();
+
+    let mut push = |x: u32, y: u32| {
+        for (c, values) in channels.iter_mut().enumerate() {
+            values.push(img.get_pixel(x, y)[c]);
+        }
+    };
+    for x in 0..width {
+        push(x, 0);
+        push(x, height - 1);
+    }
+    for y in 0..height {
+        push(0, y);
+        push(width - 1, y);
+    }
+
+    Rgba(channels.map(|mut values| {
+        values.sort_unstable();
+        values[values.len() / 2]
+    }))
+}
+
+/// Largest per-channel difference between two colors
+fn distance(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    a.0.iter()
+        .zip(b.0)
+        .map(|(&x, y)| x.abs_diff(y))
+        .max()
+        .unwrap_or(0) as f32
+}
+
+/// Uses a matting model's single-channel output as the alpha channel
+#[cfg(feature = "onnx")]
+fn model_matte(img: &DynamicImage, model: &OnnxModel) -> anyhow::Result<DynamicImage> {
+    let matte = model.predict_map(img)?;
+    let matte = image::imageops::resize(
+        &matte,
+        img.width(),
+        img.height(),
+        image::imageops::FilterType::Triangle,
+    );
+
+    let mut rgba = img.to_rgba8();
+    for (pixel, alpha) in rgba.pixels_mut().zip(matte.pixels()) {
+        pixel[3] = ((pixel[3] as u16 * alpha[0] as u16) / 255) as u8;
+    }
+    Ok(DynamicImage::ImageRgba8(rgba))
+}
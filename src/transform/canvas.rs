@@ -0,0 +1,53 @@
+// src/transform/canvas.rs
+//
+// Canvas extension: placing an image on a fixed-size background.
+
+use super::color::Color;
+use super::geometry::{AspectRatio, Gravity};
+use image::{DynamicImage, RgbaImage, imageops};
+
+/// Places the image on a `width`×`height` canvas without scaling it.
+/// Dimensions larger than the canvas are cropped around the gravity anchor.
+pub fn extend(
+    img: &DynamicImage,
+    (width, height): (u32, u32),
+    gravity: Gravity,
+    background: Color,
+) -> DynamicImage {
+    // Crop first if the source overflows the canvas in either dimension
+    let fit = (img.width().min(width), img.height().min(height));
+    let (crop_x, crop_y) = gravity.offset((img.width(), img.height()), fit);
+    let source = img.crop_imm(crop_x, crop_y, fit.0, fit.1).to_rgba8();
+
+    let mut canvas = RgbaImage::from_pixel(width, height, background.0);
+    let (x, y) = gravity.offset((width, height), fit);
+    imageops::overlay(&mut canvas, &source, x as i64, y as i64);
+
+    // Keep the alpha channel only when something can actually be transparent
+    if background.is_opaque() && !img.color().has_alpha() {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+    } else {
+        DynamicImage::ImageRgba8(canvas)
+    }
+}
+
+/// Extends the canvas to the aspect ratio with `background`, keeping the
+/// whole image, placed by gravity
+pub fn pad_aspect(
+    img: &DynamicImage,
+    aspect: AspectRatio,
+    gravity: Gravity,
+    background: Color,
+) -> DynamicImage {
+    let (width, height) = (img.width() as u64, img.height() as u64);
+    let (aw, ah) = (aspect.width as u64, aspect.height as u64);
+
+    // Keep the full width if the image is wider than the target ratio,
+    // otherwise keep the full height
+    let (canvas_w, canvas_h) = if width * ah >= height * aw {
+        (width, (width * ah).div_ceil(aw))
+    } else {
+        ((height * aw).div_ceil(ah), height)
+    };
+    extend(img, (canvas_w as u32, canvas_h as u32), gravity, background)
+}
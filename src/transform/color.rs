@@ -0,0 +1,58 @@
+// src/transform/color.rs
+//
+// Color arguments (`#rrggbb`, `#rrggbbaa`, short forms and a few names).
+
+use image::Rgba;
+use std::str::FromStr;
+
+/// An sRGB color with alpha, as given on the command line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Color(pub Rgba<u8>);
+
+impl Color {
+    pub const WHITE: Color = Color(Rgba([255, 255, 255, 255]));
+
+    /// True when the color has no transparency
+    pub fn is_opaque(self) -> bool {
+        self.0[3] == 255
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid color '{s}' (expected #rgb, #rrggbb or #rrggbbaa)");
+
+        match s.trim().to_lowercase().as_str() {
+            "white" => return Ok(Color::WHITE),
+            "black" => return Ok(Color(Rgba([0, 0, 0, 255]))),
+            "transparent" | "none" => return Ok(Color(Rgba([0, 0, 0, 0]))),
+            _ => {}
+        }
+
+        let hex = s.trim().trim_start_matches('#');
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        // Expand short forms (#rgb, #rgba) to one byte per channel
+        let expanded: String = match hex.len() {
+            3 | 4 => hex.chars().flat_map(|c| [c, c]).collect(),
+            6 | 8 => hex.to_string(),
+            _ => return Err(invalid()),
+        };
+
+        let channel = |i: usize| u8::from_str_radix(&expanded[i * 2..i * 2 + 2], 16);
+        let alpha = if expanded.len() == 8 {
+            channel(3)
+        } else {
+            Ok(255)
+        };
+
+        match (channel(0), channel(1), channel(2), alpha) {
+            (Ok(r), Ok(g), Ok(b), Ok(a)) => Ok(Color(Rgba([r, g, b, a]))),
+            _ => Err(invalid()),
+        }
+    }
+}
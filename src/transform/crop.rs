@@ -0,0 +1,64 @@
+// src/transform/crop.rs
+//
+// Cropping to an explicit rectangle or to a target aspect ratio.
+
+use super::geometry::{AspectRatio, Gravity, Rect};
+use anyhow::Result;
+use image::DynamicImage;
+
+/// Crops to an explicit rectangle, clipped to the image bounds
+pub fn crop_rect(img: &DynamicImage, rect: Rect) -> Result<DynamicImage> {
+    let right = rect.x.saturating_add(rect.width).min(img.width());
+    let bottom = rect.y.saturating_add(rect.height).min(img.height());
+
+    if rect.x >= right || rect.y >= bottom {
+        anyhow::bail!(
+            "Crop rectangle {},{},{}x{} lies outside the {}x{} image",
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+            img.width(),
+            img.height()
+        );
+    }
+
+    Ok(img.crop_imm(rect.x, rect.y, right - rect.x, bottom - rect.y))
+}
+
+/// Crops the largest region with the given aspect ratio, placed by gravity
+pub fn crop_aspect(img: &DynamicImage, aspect: AspectRatio, gravity: Gravity) -> DynamicImage {
+    let (crop_w, crop_h) = aspect_size(img, aspect);
+    let (x, y) = gravity.offset((img.width(), img.height()), (crop_w, crop_h));
+    img.crop_imm(x, y, crop_w, crop_h)
+}
+
+/// Crops the largest region with the given aspect ratio, centered on
+/// `focus` as far as the image bounds allow
+pub fn crop_aspect_around(
+    img: &DynamicImage,
+    aspect: AspectRatio,
+    (focus_x, focus_y): (u32, u32),
+) -> DynamicImage {
+    let (crop_w, crop_h) = aspect_size(img, aspect);
+    let x = focus_x.saturating_sub(crop_w / 2).min(img.width() - crop_w);
+    let y = focus_y
+        .saturating_sub(crop_h / 2)
+        .min(img.height() - crop_h);
+    img.crop_imm(x, y, crop_w, crop_h)
+}
+
+/// Size of the largest region of `img` with the given aspect ratio
+fn aspect_size(img: &DynamicImage, aspect: AspectRatio) -> (u32, u32) {
+    let (width, height) = (img.width() as u64, img.height() as u64);
+    let (aw, ah) = (aspect.width as u64, aspect.height as u64);
+
+    // Keep the full width if the image is taller than the target ratio,
+    // otherwise keep the full height
+    let (crop_w, crop_h) = if width * ah <= height * aw {
+        (width, (width * ah / aw).max(1))
+    } else {
+        ((height * aw / ah).max(1), height)
+    };
+    (crop_w as u32, crop_h as u32)
+}
@@ -0,0 +1,82 @@
+// src/transform/denoise.rs
+//
+// Edge-preserving noise reduction (bilateral filter). Rows are filtered in
+// parallel on the calling Rayon pool.
+
+use image::{DynamicImage, RgbaImage};
+#[cfg(feature = "native")]
+use rayon::prelude::*;
+
+/// Neighborhood radius in pixels
+const RADIUS: i64 = 3;
+/// Spatial falloff of the filter, in pixels
+const SIGMA_SPACE: f32 = 1.5;
+
+/// Smooths noise while keeping edges, `strength` ranging over 1–100
+pub fn denoise(img: &DynamicImage, strength: f32) -> DynamicImage {
+    let src = img.to_rgba8();
+    let (width, height) = src.dimensions();
+
+    // Larger strength lets more distant colors be averaged together
+    let sigma_range = 5.0 + strength.clamp(1.0, 100.0) * 0.5;
+
+    let spatial: Vec<f32> = (-RADIUS..=RADIUS)
+        .flat_map(|dy| (-RADIUS..=RADIUS).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| (-((dx * dx + dy * dy) as f32) / (2.0 * SIGMA_SPACE * SIGMA_SPACE)).exp())
+        .collect();
+    // Range weights indexed by the rounded RGB distance (at most ~442)
+    let range: Vec<f32> = (0..=442)
+        .map(|d| (-((d * d) as f32) / (2.0 * sigma_range * sigma_range)).exp())
+        .collect();
+
+    let mut out = RgbaImage::new(width, height);
+    let filter_row = |(y, row): (usize, &mut [u8])| {
+        let y = y as i64;
+        for x in 0..width as i64 {
+            let center = src.get_pixel(x as u32, y as u32);
+            let mut sum = [0.0f32; 3];
+            let mut total = 0.0f32;
+
+            for dy in -RADIUS..=RADIUS {
+                let ny = (y + dy).clamp(0, height as i64 - 1) as u32;
+                for dx in -RADIUS..=RADIUS {
+                    let nx = (x + dx).clamp(0, width as i64 - 1) as u32;
+                    let pixel = src.get_pixel(nx, ny);
+
+                    let dist = (0..3)
+                        .map(|c| (pixel[c] as f32 - center[c] as f32).powi(2))
+                        .sum::<f32>()
+                        .sqrt();
+                    let k = ((dy + RADIUS) * (2 * RADIUS + 1) + dx + RADIUS) as usize;
+                    let weight = spatial[k] * range[(dist.round() as usize).min(442)];
+
+                    for c in 0..3 {
+                        sum[c] += pixel[c] as f32 * weight;
+                    }
+                    total += weight;
+                }
+            }
+
+            let offset = x as usize * 4;
+            for c in 0..3 {
+                row[offset + c] = (sum[c] / total).round().clamp(0.0, 255.0) as u8;
+            }
+            row[offset + 3] = center[3];
+        }
+    };
+    // Rows are independent: filter them in parallel where threads exist
+    #[cfg(feature = "native")]
+    out.par_chunks_mut(width as usize * 4)
+        .enumerate()
+        .for_each(filter_row);
+    #[cfg(not(feature = "native"))]
+    out.chunks_mut(width as usize * 4)
+        .enumerate()
+        .for_each(filter_row);
+
+    if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(out)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(out).to_rgb8())
+    }
+}
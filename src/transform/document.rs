@@ -0,0 +1,167 @@
+// src/transform/document.rs
+//
+// Scan cleanup (`--document`): straightens pages scanned slightly askew,
+// divides out the paper color and uneven lighting so the page turns white,
+// and optionally (`--binarize`) reduces it to black and white with an
+// adaptive threshold. Clean pages compress far better than raw scans.
+
+use super::color::Color;
+use super::geometry::RotateEdges;
+use super::rotate;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, Luma, RgbaImage};
+
+/// Largest skew corrected, in degrees either way
+const MAX_SKEW: f32 = 5.0;
+/// Longest side of the copy the skew is measured on
+const SKEW_SIZE: u32 = 800;
+/// Background cells along the longer side
+const BACKGROUND_CELLS: u32 = 48;
+/// Sauvola sensitivity: higher keeps less faint ink
+const SAUVOLA_K: f32 = 0.2;
+/// Whitened pixels at least this bright in every channel become pure white
+const PAPER_WHITE: u8 = 240;
+
+/// Deskews and whitens a scanned page, binarizing it if asked
+pub fn clean_page(img: &DynamicImage, binarize: bool) -> DynamicImage {
+    // Whiten first, so the corners rotation fills in match the paper
+    let mut page = DynamicImage::ImageRgba8(flatten_background(img));
+    let skew = detect_skew(&page);
+    if skew.abs() >= 0.05 {
+        page = rotate::rotate(&page, -skew, RotateEdges::Fill, Color::WHITE);
+    }
+
+    let page = page.to_rgba8();
+    if binarize {
+        DynamicImage::ImageLuma8(sauvola(&DynamicImage::ImageRgba8(page).to_luma8()))
+    } else if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(page)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(page).to_rgb8())
+    }
+}
+
+/// Clockwise angle of the text lines in degrees, found as the shear that
+/// lines the dark pixels up into the sharpest row profile
+fn detect_skew(img: &DynamicImage) -> f32 {
+    let small = if img.width().max(img.height()) > SKEW_SIZE {
+        img.resize(SKEW_SIZE, SKEW_SIZE, FilterType::Triangle)
+    } else {
+        img.clone()
+    }
+    .to_luma8();
+
+    let mean = small.pixels().map(|p| p[0] as f32).sum::<f32>() / small.len().max(1) as f32;
+    let ink: Vec<(f32, f32)> = small
+        .enumerate_pixels()
+        .filter(|(_, _, p)| (p[0] as f32) < mean * 0.75)
+        .map(|(x, y, _)| (x as f32, y as f32))
+        .collect();
+    if ink.len() < 100 {
+        return 0.0;
+    }
+
+    let points: Vec<(f32, f32, f32)> = ink.into_iter().map(|(x, y)| (x, y, 1.0)).collect();
+    rotate::line_angle(&points, small.dimensions(), MAX_SKEW).map_or(0.0, |(angle, _)| angle)
+}
+
+/// Divides every channel by a smooth estimate of the paper color, so the
+/// paper becomes white whatever its tint or lighting
+fn flatten_background(img: &DynamicImage) -> RgbaImage {
+    let mut rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let cell = (width.max(height) / BACKGROUND_CELLS).max(1);
+    let (cols, rows) = (width.div_ceil(cell), height.div_ceil(cell));
+
+    // Brightest value per cell, smoothed first so lone specks don't count
+    let smooth = image::imageops::blur(&rgba, 1.5);
+    let mut cells = vec![[0u8; 3]; (cols * rows) as usize];
+    for (x, y, p) in smooth.enumerate_pixels() {
+        let cell = &mut cells[((y / cell) * cols + x / cell) as usize];
+        for c in 0..3 {
+            cell[c] = cell[c].max(p[c]);
+        }
+    }
+    // Cells covered by a picture or heavy text take their neighbors' paper
+    let mut paper = cells.clone();
+    for row in 0..rows {
+        for col in 0..cols {
+            for dy in -1i64..=1 {
+                for dx in -1i64..=1 {
+                    let (nx, ny) = (col as i64 + dx, row as i64 + dy);
+                    if nx < 0 || ny < 0 || nx >= cols as i64 || ny >= rows as i64 {
+                        continue;
+                    }
+                    let neighbor = cells[(ny as u32 * cols + nx as u32) as usize];
+                    let own = &mut paper[(row * cols + col) as usize];
+                    for c in 0..3 {
+                        own[c] = own[c].max(neighbor[c]);
+                    }
+                }
+            }
+        }
+    }
+
+    // Interpolate between cell centers
+    let paper_at = |x: u32, y: u32, c: usize| {
+        let gx = ((x as f32 + 0.5) / cell as f32 - 0.5).clamp(0.0, (cols - 1) as f32);
+        let gy = ((y as f32 + 0.5) / cell as f32 - 0.5).clamp(0.0, (rows - 1) as f32);
+        let (x0, y0) = (gx as u32, gy as u32);
+        let (x1, y1) = ((x0 + 1).min(cols - 1), (y0 + 1).min(rows - 1));
+        let (fx, fy) = (gx - x0 as f32, gy - y0 as f32);
+        let at = |cx: u32, cy: u32| paper[(cy * cols + cx) as usize][c] as f32;
+        let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+        let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    };
+
+    for (x, y, p) in rgba.enumerate_pixels_mut() {
+        for c in 0..3 {
+            let paper = paper_at(x, y, c).max(32.0);
+            p[c] = (p[c] as f32 * 255.0 / paper).min(255.0).round() as u8;
+        }
+        // Leftover grain on the paper costs bytes and shows nothing
+        if p.0[..3].iter().all(|&v| v >= PAPER_WHITE) {
+            p.0[..3].fill(255);
+        }
+    }
+    rgba
+}
+
+/// Sauvola adaptive threshold: black where a pixel is darker than its
+/// neighborhood's mean, lowered where the neighborhood is flat
+fn sauvola(gray: &GrayImage) -> GrayImage {
+    let (width, height) = gray.dimensions();
+    let radius = (width.min(height) / 60).max(7) as i64;
+
+    // Integral images of values and squares, one row and column of padding
+    let stride = width as usize + 1;
+    let mut sum = vec![0f64; stride * (height as usize + 1)];
+    let mut squares = sum.clone();
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let v = gray.get_pixel(x as u32, y as u32)[0] as f64;
+            let i = (y + 1) * stride + x + 1;
+            sum[i] = v + sum[i - 1] + sum[i - stride] - sum[i - stride - 1];
+            squares[i] = v * v + squares[i - 1] + squares[i - stride] - squares[i - stride - 1];
+        }
+    }
+    let area = |table: &[f64], x0: usize, y0: usize, x1: usize, y1: usize| {
+        table[y1 * stride + x1] - table[y0 * stride + x1] - table[y1 * stride + x0]
+            + table[y0 * stride + x0]
+    };
+
+    GrayImage::from_fn(width, height, |x, y| {
+        let x0 = (x as i64 - radius).max(0) as usize;
+        let y0 = (y as i64 - radius).max(0) as usize;
+        let x1 = (x as i64 + radius + 1).min(width as i64) as usize;
+        let y1 = (y as i64 + radius + 1).min(height as i64) as usize;
+        let n = ((x1 - x0) * (y1 - y0)) as f64;
+
+        let mean = area(&sum, x0, y0, x1, y1) / n;
+        let variance = (area(&squares, x0, y0, x1, y1) / n - mean * mean).max(0.0);
+        let threshold = mean * (1.0 + SAUVOLA_K as f64 * (variance.sqrt() / 128.0 - 1.0));
+        let ink = (gray.get_pixel(x, y)[0] as f64) < threshold;
+        Luma([if ink { 0 } else { 255 }])
+    })
+}
@@ -0,0 +1,93 @@
+// src/transform/enhance.rs
+//
+// One-click cleanup for phone snapshots (`--auto-enhance`): a levels stretch
+// of the brightness range, gray-world white balance and a mild saturation
+// boost. Every correction is limited so already well-exposed, neutral photos
+// pass through almost unchanged.
+
+use image::DynamicImage;
+
+/// Share of the darkest and brightest pixels clipped by the levels stretch
+const CLIP: f64 = 0.005;
+/// Largest levels gain, so near-flat images (fog, documents) aren't blown up
+const MAX_STRETCH: f32 = 2.0;
+/// Range of the white balance gain per channel
+const MAX_CAST: (f32, f32) = (0.85, 1.2);
+const SATURATION: f32 = 1.15;
+
+/// Stretches levels, neutralizes color casts and adds a little saturation
+pub fn auto_enhance(img: &DynamicImage) -> DynamicImage {
+    let has_alpha = img.color().has_alpha();
+    let mut rgba = img.to_rgba8();
+
+    // Luma histogram and channel sums over the visible pixels
+    let mut histogram = [0u64; 256];
+    let mut sums = [0f64; 3];
+    let mut count = 0u64;
+    for pixel in rgba.pixels().filter(|p| p[3] > 0) {
+        histogram[luma(pixel[0] as f32, pixel[1] as f32, pixel[2] as f32) as usize] += 1;
+        for c in 0..3 {
+            sums[c] += pixel[c] as f64;
+        }
+        count += 1;
+    }
+    if count == 0 {
+        return img.clone();
+    }
+
+    // Levels: map the clipped luma range onto 0-255, one curve for all
+    // channels so hues don't shift
+    let low = percentile(&histogram, count, CLIP) as f32;
+    let high = percentile(&histogram, count, 1.0 - CLIP) as f32;
+    let gain = (255.0 / (high - low).max(1.0)).min(MAX_STRETCH);
+    // A capped stretch keeps the range centered
+    let offset = (low + high) / 2.0 - 127.5 / gain;
+
+    // White balance: scale each channel toward the average gray
+    let means = sums.map(|sum| (sum / count as f64) as f32);
+    let gray = (means[0] + means[1] + means[2]) / 3.0;
+    let balance = means.map(|mean| (gray / mean.max(1.0)).clamp(MAX_CAST.0, MAX_CAST.1));
+
+    let tables: Vec<[u8; 256]> = balance
+        .iter()
+        .map(|b| {
+            std::array::from_fn(|v| {
+                ((v as f32 * b - offset) * gain).clamp(0.0, 255.0).round() as u8
+            })
+        })
+        .collect();
+
+    for pixel in rgba.pixels_mut() {
+        let [r, g, b] = [0, 1, 2].map(|c| tables[c][pixel[c] as usize] as f32);
+        let y = luma(r, g, b);
+        for (c, value) in [r, g, b].into_iter().enumerate() {
+            pixel[c] = (y + (value - y) * SATURATION).clamp(0.0, 255.0).round() as u8;
+        }
+    }
+
+    if has_alpha {
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba).to_rgb8())
+    }
+}
+
+/// Rec. 601 luma, rounded into 0-255
+fn luma(r: f32, g: f32, b: f32) -> f32 {
+    (0.299 * r + 0.587 * g + 0.114 * b)
+        .round()
+        .clamp(0.0, 255.0)
+}
+
+/// Smallest value with at least `share` of the pixels at or below it
+fn percentile(histogram: &[u64; 256], count: u64, share: f64) -> usize {
+    let target = (count as f64 * share).ceil() as u64;
+    let mut seen = 0;
+    for (value, &n) in histogram.iter().enumerate() {
+        seen += n;
+        if seen >= target.max(1) {
+            return value;
+        }
+    }
+    255
+}
@@ -0,0 +1,104 @@
+// src/transform/face.rs
+//
+// Face detection for `--gravity face`: aspect crops are centered on the
+// faces in the picture instead of its middle, so portrait thumbnails keep
+// their heads. Detection runs an UltraFace-style ONNX model (feature `onnx`,
+// `--face-model`); when no face is found the crop falls back to the center.
+// `--fix-redeye` also limits its search to the detected faces.
+//
+// UltraFace models take a 1x3x240x320 tensor of (v - 127) / 128 values and
+// return per-anchor scores [1, N, 2] (background, face) and boxes [1, N, 4]
+// as corners normalized to the input.
+
+use super::geometry::Rect;
+use anyhow::Result;
+use image::DynamicImage;
+
+#[cfg(feature = "onnx")]
+use crate::onnx::OnnxModel;
+#[cfg(feature = "onnx")]
+use image::imageops::FilterType;
+#[cfg(feature = "onnx")]
+use std::path::Path;
+
+/// Input size of the RFB-320 / slim-320 models
+#[cfg(feature = "onnx")]
+const INPUT: (u32, u32) = (320, 240);
+/// Face probability an anchor needs to count
+#[cfg(feature = "onnx")]
+const THRESHOLD: f32 = 0.7;
+
+/// A loaded face detection model
+pub struct FaceDetector {
+    #[cfg(feature = "onnx")]
+    model: OnnxModel,
+}
+
+impl FaceDetector {
+    #[cfg(feature = "onnx")]
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self {
+            model: OnnxModel::load(path, INPUT)?,
+        })
+    }
+
+    /// Center of the region covering every detected face, in image pixels
+    pub fn focus(&self, img: &DynamicImage) -> Result<Option<(u32, u32)>> {
+        let faces = self.detect(img)?;
+        let Some(left) = faces.iter().map(|f| f.x).min() else {
+            return Ok(None);
+        };
+        let top = faces.iter().map(|f| f.y).min().unwrap_or(0);
+        let right = faces.iter().map(|f| f.x + f.width).max().unwrap_or(left);
+        let bottom = faces.iter().map(|f| f.y + f.height).max().unwrap_or(top);
+        Ok(Some(((left + right) / 2, (top + bottom) / 2)))
+    }
+
+    /// Boxes of the detected faces, in image pixels
+    #[cfg(feature = "onnx")]
+    pub fn detect(&self, img: &DynamicImage) -> Result<Vec<Rect>> {
+        let rgb = img
+            .resize_exact(INPUT.0, INPUT.1, FilterType::Triangle)
+            .to_rgb8();
+        let outputs = self
+            .model
+            .infer_outputs(&rgb, |v| (v as f32 - 127.0) / 128.0)?;
+        let output = |columns: usize| {
+            outputs
+                .iter()
+                .find(|output| output.ndim() == 3 && output.shape()[2] == columns)
+        };
+        let (Some(scores), Some(boxes)) = (output(2), output(4)) else {
+            anyhow::bail!(
+                "Face model outputs have shapes {:?}, expected scores [1, N, 2] and boxes [1, N, 4]",
+                outputs.iter().map(|o| o.shape()).collect::<Vec<_>>()
+            );
+        };
+
+        // Neighboring anchors report the same face several times; neither
+        // the crop focus nor red-eye search minds the duplicates
+        let (width, height) = (img.width() as f32, img.height() as f32);
+        let anchors = scores.shape()[1].min(boxes.shape()[1]);
+        let faces = (0..anchors)
+            .filter(|&i| scores[[0, i, 1]] >= THRESHOLD)
+            .filter_map(|i| {
+                let corner = |c: usize| boxes[[0, i, c]].clamp(0.0, 1.0);
+                let (left, top) = ((corner(0) * width) as u32, (corner(1) * height) as u32);
+                let (right, bottom) = ((corner(2) * width) as u32, (corner(3) * height) as u32);
+                (right > left && bottom > top).then(|| Rect {
+                    x: left,
+                    y: top,
+                    width: right - left,
+                    height: bottom - top,
+                })
+            })
+            .collect();
+        Ok(faces)
+    }
+
+    /// Never constructed without the `onnx` feature
+    #[cfg(not(feature = "onnx"))]
+    pub fn detect(&self, _img: &DynamicImage) -> Result<Vec<Rect>> {
+        Ok(Vec::new())
+    }
+}
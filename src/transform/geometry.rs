@@ -0,0 +1,146 @@
+// src/transform/geometry.rs
+//
+// Geometry primitives shared by the transforms: gravity anchoring and the
+// parsers for rectangle / aspect-ratio arguments.
+
+use clap::ValueEnum;
+use std::str::FromStr;
+
+/// Anchor point used to place a smaller region inside a larger one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Gravity {
+    #[value(alias = "nw")]
+    Northwest,
+    #[value(alias = "n")]
+    North,
+    #[value(alias = "ne")]
+    Northeast,
+    #[value(alias = "w")]
+    West,
+    #[default]
+    Center,
+    #[value(alias = "e")]
+    East,
+    #[value(alias = "sw")]
+    Southwest,
+    #[value(alias = "s")]
+    South,
+    #[value(alias = "se")]
+    Southeast,
+    // Centered on detected faces (`--face-model`), otherwise like center
+    Face,
+}
+
+/// How `--aspect` and `--preset social` reach a ratio
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ResizeMode {
+    /// Cut away the edges, placed by gravity
+    #[default]
+    Crop,
+    /// Remove the least detailed seams of pixels (experimental)
+    Seam,
+    /// Extend the canvas with --background, keeping the whole image
+    Pad,
+}
+
+/// What becomes of the corners an arbitrary-angle rotation uncovers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RotateEdges {
+    /// Crop to the largest rectangle of the same shape inside the image
+    #[default]
+    Crop,
+    /// Keep the size and fill the corners with --background
+    Fill,
+    /// Enlarge the canvas to hold the whole image, filled with --background
+    Expand,
+}
+
+impl Gravity {
+    /// Offset of an `inner`-sized region within `outer` along both axes
+    pub fn offset(self, outer: (u32, u32), inner: (u32, u32)) -> (u32, u32) {
+        let free_x = outer.0.saturating_sub(inner.0);
+        let free_y = outer.1.saturating_sub(inner.1);
+
+        let x = match self {
+            Gravity::Northwest | Gravity::West | Gravity::Southwest => 0,
+            Gravity::North | Gravity::Center | Gravity::South | Gravity::Face => free_x / 2,
+            Gravity::Northeast | Gravity::East | Gravity::Southeast => free_x,
+        };
+        let y = match self {
+            Gravity::Northwest | Gravity::North | Gravity::Northeast => 0,
+            Gravity::West | Gravity::Center | Gravity::East | Gravity::Face => free_y / 2,
+            Gravity::Southwest | Gravity::South | Gravity::Southeast => free_y,
+        };
+
+        (x, y)
+    }
+}
+
+/// Explicit crop rectangle, written as `X,Y,WxH`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Rect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid rectangle '{s}' (expected X,Y,WxH)");
+
+        let mut parts = s.split(',').map(str::trim);
+        let (Some(x), Some(y), Some(size), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let (width, height) = parse_size(size).ok_or_else(invalid)?;
+
+        Ok(Rect {
+            x: x.parse().map_err(|_| invalid())?,
+            y: y.parse().map_err(|_| invalid())?,
+            width,
+            height,
+        })
+    }
+}
+
+/// Width-to-height ratio, written as `W:H`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for AspectRatio {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid aspect ratio '{s}' (expected W:H, e.g. 16:9)");
+
+        let (w, h) = s.split_once(':').ok_or_else(invalid)?;
+        let width: u32 = w.trim().parse().map_err(|_| invalid())?;
+        let height: u32 = h.trim().parse().map_err(|_| invalid())?;
+        if width == 0 || height == 0 {
+            return Err(invalid());
+        }
+
+        Ok(AspectRatio { width, height })
+    }
+}
+
+/// Clap value parser for `WxH` sizes
+pub fn parse_size_arg(s: &str) -> Result<(u32, u32), String> {
+    parse_size(s).ok_or_else(|| format!("invalid size '{s}' (expected WxH, e.g. 2000x2000)"))
+}
+
+/// Parses a `WxH` size with both dimensions non-zero
+pub fn parse_size(s: &str) -> Option<(u32, u32)> {
+    let (w, h) = s.trim().split_once(['x', 'X'])?;
+    let width: u32 = w.trim().parse().ok()?;
+    let height: u32 = h.trim().parse().ok()?;
+    (width > 0 && height > 0).then_some((width, height))
+}
@@ -0,0 +1,127 @@
+// src/transform/lut.rs
+//
+// 3D color lookup tables in the Adobe/Resolve `.cube` format, applied with
+// trilinear interpolation.
+
+use anyhow::{Context, Result};
+use image::DynamicImage;
+use std::path::Path;
+
+/// A parsed 3D LUT
+pub struct Lut3d {
+    size: usize,
+    /// Output colors, red varying fastest, then green, then blue
+    table: Vec<[f32; 3]>,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+}
+
+impl Lut3d {
+    /// Loads a `.cube` file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read LUT: {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid LUT file: {}", path.display()))
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = Vec::new();
+
+        let triple = |rest: &[&str]| -> Result<[f32; 3]> {
+            match rest {
+                [r, g, b] => Ok([r.parse()?, g.parse()?, b.parse()?]),
+                _ => anyhow::bail!("expected three values, got {}", rest.len()),
+            }
+        };
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let parsed = match fields[0] {
+                "TITLE" => Ok(()),
+                "LUT_3D_SIZE" => fields
+                    .get(1)
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&n: &usize| (2..=256).contains(&n))
+                    .map(|n| size = Some(n))
+                    .ok_or_else(|| anyhow::anyhow!("invalid LUT_3D_SIZE")),
+                "LUT_1D_SIZE" => Err(anyhow::anyhow!("1D LUTs are not supported")),
+                "DOMAIN_MIN" => triple(&fields[1..]).map(|v| domain_min = v),
+                "DOMAIN_MAX" => triple(&fields[1..]).map(|v| domain_max = v),
+                _ => triple(&fields).map(|v| table.push(v)),
+            };
+            parsed.with_context(|| format!("line {}", number + 1))?;
+        }
+
+        let size = size.ok_or_else(|| anyhow::anyhow!("missing LUT_3D_SIZE"))?;
+        if table.len() != size * size * size {
+            anyhow::bail!(
+                "expected {} table entries for size {size}, found {}",
+                size * size * size,
+                table.len()
+            );
+        }
+
+        Ok(Self {
+            size,
+            table,
+            domain_min,
+            domain_max,
+        })
+    }
+
+    /// Applies the LUT to every pixel, keeping alpha untouched
+    pub fn apply(&self, img: &DynamicImage) -> DynamicImage {
+        let has_alpha = img.color().has_alpha();
+        let mut rgba = img.to_rgba8();
+        for pixel in rgba.pixels_mut() {
+            let out = self.lookup([pixel[0], pixel[1], pixel[2]]);
+            pixel.0 = [out[0], out[1], out[2], pixel[3]];
+        }
+
+        if has_alpha {
+            DynamicImage::ImageRgba8(rgba)
+        } else {
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba).to_rgb8())
+        }
+    }
+
+    /// Trilinear interpolation between the eight surrounding lattice points
+    fn lookup(&self, rgb: [u8; 3]) -> [u8; 3] {
+        let max = (self.size - 1) as f32;
+        let pos: [f32; 3] = std::array::from_fn(|c| {
+            let range = (self.domain_max[c] - self.domain_min[c]).max(f32::EPSILON);
+            let v = (rgb[c] as f32 / 255.0 - self.domain_min[c]) / range;
+            v.clamp(0.0, 1.0) * max
+        });
+        let base = pos.map(|p| (p.floor() as usize).min(self.size - 2));
+        let frac: [f32; 3] = std::array::from_fn(|c| pos[c] - base[c] as f32);
+
+        let entry = |r: usize, g: usize, b: usize| {
+            self.table
+                [(base[2] + b) * self.size * self.size + (base[1] + g) * self.size + base[0] + r]
+        };
+
+        let mut out = [0.0f32; 3];
+        for (b, wb) in [(0, 1.0 - frac[2]), (1, frac[2])] {
+            for (g, wg) in [(0, 1.0 - frac[1]), (1, frac[1])] {
+                for (r, wr) in [(0, 1.0 - frac[0]), (1, frac[0])] {
+                    let weight = wr * wg * wb;
+                    let value = entry(r, g, b);
+                    for c in 0..3 {
+                        out[c] += value[c] * weight;
+                    }
+                }
+            }
+        }
+
+        out.map(|v| (v * 255.0).round().clamp(0.0, 255.0) as u8)
+    }
+}
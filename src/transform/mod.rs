@@ -0,0 +1,317 @@
+// src/transform/mod.rs
+//
+// Transforms applied to each decoded image: geometry and cleanup before it
+// is scaled, color effects on every scaled variant (so resampling doesn't
+// reintroduce the colors an effect removed). Custom transforms (embedders'
+// own, or `--plugins`) run after the built-in ones of their stage.
+
+pub mod background;
+mod canvas;
+pub mod color;
+pub mod crop;
+mod denoise;
+mod document;
+mod enhance;
+pub mod face;
+pub mod geometry;
+pub mod lut;
+pub mod posterize;
+mod redeye;
+pub mod resample;
+mod rotate;
+pub mod script;
+mod seam;
+#[cfg(feature = "native")]
+pub(crate) mod sidecar;
+pub mod simulate;
+pub mod tonemap;
+mod trim;
+#[cfg(feature = "onnx")]
+pub mod upscale;
+mod vignette;
+pub mod watermark;
+
+use anyhow::Result;
+use background::BackgroundRemoval;
+use color::Color;
+use face::FaceDetector;
+use geometry::{AspectRatio, Gravity, Rect, ResizeMode, RotateEdges};
+use image::DynamicImage;
+use lut::Lut3d;
+use std::path::Path;
+use std::sync::Arc;
+use tonemap::Tonemap;
+use watermark::Watermark;
+
+/// A transform supplied from outside rsimg
+pub trait Transform: Send + Sync {
+    fn apply(&self, img: DynamicImage, ctx: &TransformContext) -> Result<DynamicImage>;
+}
+
+/// The image a custom transform is applied to
+pub struct TransformContext<'a> {
+    /// Source file (`<memory>` for in-memory inputs)
+    pub source: &'a Path,
+    /// Scale percentage of the variant, or `None` before scaling
+    pub scale: Option<u32>,
+}
+
+/// Where a custom transform runs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Once per source, after cropping/cleanup and before scaling
+    BeforeScale,
+    /// On every scaled variant, after the color effects
+    AfterScale,
+}
+
+/// Transforms requested on the command line, applied in field order
+pub struct Transforms {
+    // Applied to floating-point (HDR) sources only
+    /// Exposure adjustment in stops
+    pub exposure: f32,
+    pub tonemap: Tonemap,
+
+    /// Crop and rotation from Lightroom/darktable XMP sidecars
+    pub sidecars: bool,
+    /// Deskew and whiten scanned pages
+    pub document: bool,
+    /// Reduce `document` pages to black and white
+    pub binarize: bool,
+    /// Clockwise rotation in degrees
+    pub rotate: Option<f32>,
+    /// Level the dominant horizon or edges
+    pub auto_straighten: bool,
+    /// What `rotate` and `auto_straighten` do with the corners they uncover
+    pub rotate_edges: RotateEdges,
+    /// Border color tolerance in percent for uniform-border trimming
+    pub trim: Option<f32>,
+    pub crop: Option<Rect>,
+    pub aspect: Option<AspectRatio>,
+    /// Whether `aspect` crops or carves seams
+    pub resize_mode: ResizeMode,
+    /// Bilateral noise reduction strength (1–100)
+    pub denoise: Option<f32>,
+    /// Red-eye correction, within faces when `face_detector` is set
+    pub fix_redeye: bool,
+    /// Levels stretch, white balance and saturation boost
+    pub auto_enhance: bool,
+    /// Makes the background transparent
+    pub remove_background: Option<BackgroundRemoval>,
+    /// Canvas size to pad (or crop) to without scaling
+    pub extent: Option<(u32, u32)>,
+    /// Width wider sources are shrunk to, before the scales apply
+    pub max_width: Option<u32>,
+
+    /// Resize in linear light rather than on the gamma-encoded values
+    pub linear_resize: bool,
+
+    // Applied after scaling
+    /// 3D color lookup table (color grade)
+    pub lut: Option<Lut3d>,
+    /// Corner darkening strength in percent
+    pub vignette: Option<f32>,
+    /// Levels per color channel
+    pub posterize: Option<u8>,
+    /// Fixed palette every pixel is mapped onto
+    pub palette: Option<Vec<[u8; 3]>>,
+    /// Invisible mark of an ID, embedded last
+    pub watermark: Option<Watermark>,
+
+    /// Canvas color for `extent` and rotated corners
+    pub background: Color,
+    /// Anchor for `aspect` and `extent`
+    pub gravity: Gravity,
+    /// Finds the faces `Gravity::Face` centers aspect crops on and
+    /// `fix_redeye` searches
+    pub face_detector: Option<Arc<FaceDetector>>,
+
+    /// Custom transforms, in order within each stage
+    pub custom: Vec<(Stage, Arc<dyn Transform>)>,
+}
+
+impl Default for Transforms {
+    /// No transforms, with the command-line defaults for the settings
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tonemap: Tonemap::Aces,
+            sidecars: false,
+            document: false,
+            binarize: false,
+            rotate: None,
+            auto_straighten: false,
+            rotate_edges: RotateEdges::Crop,
+            trim: None,
+            crop: None,
+            aspect: None,
+            resize_mode: ResizeMode::Crop,
+            denoise: None,
+            fix_redeye: false,
+            auto_enhance: false,
+            remove_background: None,
+            extent: None,
+            max_width: None,
+            linear_resize: false,
+            lut: None,
+            vignette: None,
+            posterize: None,
+            palette: None,
+            watermark: None,
+            background: Color::WHITE,
+            gravity: Gravity::Center,
+            face_detector: None,
+            custom: Vec::new(),
+        }
+    }
+}
+
+impl Transforms {
+    /// True when no transform would modify the image
+    pub fn is_identity(&self) -> bool {
+        self.crop.is_none() && self.is_crop_only()
+    }
+
+    /// True when at most a rectangle crop would modify the image
+    pub fn is_crop_only(&self) -> bool {
+        !self.sidecars
+            && !self.document
+            && self.rotate.is_none()
+            && !self.auto_straighten
+            && self.trim.is_none()
+            && self.aspect.is_none()
+            && self.denoise.is_none()
+            && !self.fix_redeye
+            && !self.auto_enhance
+            && self.remove_background.is_none()
+            && self.extent.is_none()
+            && self.max_width.is_none()
+            && self.lut.is_none()
+            && self.vignette.is_none()
+            && self.posterize.is_none()
+            && self.palette.is_none()
+            && self.watermark.is_none()
+            && self.custom.is_empty()
+    }
+
+    /// True when the post-scale transforms don't look at the scale, so two
+    /// scales resizing to the same dimensions give the same pixels
+    pub fn is_scale_independent(&self) -> bool {
+        !self
+            .custom
+            .iter()
+            .any(|(stage, _)| *stage == Stage::AfterScale)
+    }
+
+    /// Applies the pre-scale transforms to a freshly decoded image
+    pub fn apply(&self, mut img: DynamicImage, source: &Path) -> Result<DynamicImage> {
+        if tonemap::is_hdr(&img) {
+            img = tonemap::tonemap(&img, self.exposure, self.tonemap);
+        }
+        #[cfg(feature = "native")]
+        if self.sidecars {
+            img = sidecar::apply(img, source, self.background);
+        }
+        if self.document {
+            img = document::clean_page(&img, self.binarize);
+        }
+        if let Some(degrees) = self.rotate {
+            img = rotate::rotate(&img, degrees, self.rotate_edges, self.background);
+        }
+        if self.auto_straighten {
+            let tilt = rotate::detect_tilt(&img);
+            if tilt != 0.0 {
+                img = rotate::rotate(&img, -tilt, self.rotate_edges, self.background);
+            }
+        }
+        if let Some(fuzz) = self.trim {
+            img = trim::trim(&img, fuzz);
+        }
+        if let Some(rect) = self.crop {
+            img = crop::crop_rect(&img, rect)?;
+        }
+        if let Some(aspect) = self.aspect {
+            img = match self.resize_mode {
+                ResizeMode::Crop => match self.face_focus(&img)? {
+                    Some(focus) => crop::crop_aspect_around(&img, aspect, focus),
+                    None => crop::crop_aspect(&img, aspect, self.gravity),
+                },
+                ResizeMode::Seam => seam::carve_aspect(&img, aspect),
+                ResizeMode::Pad => canvas::pad_aspect(&img, aspect, self.gravity, self.background),
+            };
+        }
+        if let Some(strength) = self.denoise {
+            img = denoise::denoise(&img, strength);
+        }
+        if self.fix_redeye {
+            let faces = match &self.face_detector {
+                Some(detector) => Some(detector.detect(&img)?),
+                None => None,
+            };
+            img = redeye::fix_redeye(&img, faces.as_deref());
+        }
+        if self.auto_enhance {
+            img = enhance::auto_enhance(&img);
+        }
+        if let Some(removal) = &self.remove_background {
+            img = removal.apply(&img)?;
+        }
+        if let Some(size) = self.extent {
+            img = canvas::extend(&img, size, self.gravity, self.background);
+        }
+        if let Some(width) = self.max_width
+            && img.width() > width
+        {
+            let height = (img.height() as u64 * width as u64 / img.width() as u64).max(1);
+            img = crate::pipeline::resize_to(&img, width, height as u32, self.linear_resize);
+        }
+        self.apply_custom(img, Stage::BeforeScale, source, None)
+    }
+
+    /// Where face gravity centers a crop, if it's requested and finds a face
+    fn face_focus(&self, img: &DynamicImage) -> Result<Option<(u32, u32)>> {
+        match &self.face_detector {
+            Some(detector) if self.gravity == Gravity::Face => detector.focus(img),
+            _ => Ok(None),
+        }
+    }
+
+    /// Applies the color effects to one scaled variant
+    pub fn apply_scaled(
+        &self,
+        mut img: DynamicImage,
+        source: &Path,
+        scale: u32,
+    ) -> Result<DynamicImage> {
+        if let Some(lut) = &self.lut {
+            img = lut.apply(&img);
+        }
+        if let Some(strength) = self.vignette {
+            img = vignette::vignette(&img, strength);
+        }
+        if let Some(levels) = self.posterize {
+            img = posterize::posterize(&img, levels);
+        }
+        if let Some(palette) = &self.palette {
+            img = posterize::map_to_palette(&img, palette);
+        }
+        if let Some(watermark) = &self.watermark {
+            img = watermark.embed(&img);
+        }
+        self.apply_custom(img, Stage::AfterScale, source, Some(scale))
+    }
+
+    fn apply_custom(
+        &self,
+        mut img: DynamicImage,
+        stage: Stage,
+        source: &Path,
+        scale: Option<u32>,
+    ) -> Result<DynamicImage> {
+        let ctx = TransformContext { source, scale };
+        for (_, transform) in self.custom.iter().filter(|(s, _)| *s == stage) {
+            img = transform.apply(img, &ctx)?;
+        }
+        Ok(img)
+    }
+}
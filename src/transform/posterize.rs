@@ -0,0 +1,84 @@
+// src/transform/posterize.rs
+//
+// Color-count reduction effects: per-channel posterization and mapping onto
+// a fixed palette taken from an image file.
+
+use anyhow::{Context, Result};
+use image::DynamicImage;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Largest palette accepted from a palette file
+const MAX_PALETTE: usize = 4096;
+
+/// Reduces every color channel to `levels` evenly spaced values
+pub fn posterize(img: &DynamicImage, levels: u8) -> DynamicImage {
+    let steps = (levels.max(2) - 1) as f32;
+    let table: Vec<u8> = (0..=255u32)
+        .map(|v| ((v as f32 * steps / 255.0).round() * 255.0 / steps).round() as u8)
+        .collect();
+
+    map_rgb(img, |rgb| rgb.map(|c| table[c as usize]))
+}
+
+/// Maps every pixel to the nearest color of a fixed palette (no dithering)
+pub fn map_to_palette(img: &DynamicImage, palette: &[[u8; 3]]) -> DynamicImage {
+    let mut cache: HashMap<[u8; 3], [u8; 3]> = HashMap::new();
+
+    map_rgb(img, |rgb| {
+        *cache.entry(rgb).or_insert_with(|| {
+            palette
+                .iter()
+                .copied()
+                .min_by_key(|entry| {
+                    entry
+                        .iter()
+                        .zip(rgb)
+                        .map(|(&a, b)| (a as i32 - b as i32).pow(2))
+                        .sum::<i32>()
+                })
+                .unwrap_or(rgb)
+        })
+    })
+}
+
+/// Collects the distinct colors of a palette image (e.g. a row of swatches)
+pub fn load_palette(path: &Path) -> Result<Vec<[u8; 3]>> {
+    let img = image::open(path)
+        .with_context(|| format!("Failed to open palette file: {}", path.display()))?
+        .to_rgb8();
+
+    let mut seen = HashSet::new();
+    let palette: Vec<[u8; 3]> = img
+        .pixels()
+        .map(|p| p.0)
+        .filter(|c| seen.insert(*c))
+        .collect();
+
+    if palette.len() > MAX_PALETTE {
+        anyhow::bail!(
+            "Palette file {} has {} colors (maximum {MAX_PALETTE})",
+            path.display(),
+            palette.len()
+        );
+    }
+    Ok(palette)
+}
+
+/// Applies a color mapping to the RGB channels, keeping alpha untouched
+fn map_rgb(img: &DynamicImage, mut f: impl FnMut([u8; 3]) -> [u8; 3]) -> DynamicImage {
+    if img.color().has_alpha() {
+        let mut rgba = img.to_rgba8();
+        for pixel in rgba.pixels_mut() {
+            let [r, g, b] = f([pixel[0], pixel[1], pixel[2]]);
+            pixel.0 = [r, g, b, pixel[3]];
+        }
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        let mut rgb = img.to_rgb8();
+        for pixel in rgb.pixels_mut() {
+            pixel.0 = f(pixel.0);
+        }
+        DynamicImage::ImageRgb8(rgb)
+    }
+}
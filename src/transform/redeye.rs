@@ -0,0 +1,177 @@
+// src/transform/redeye.rs
+//
+// Red-eye removal for flash photos (`--fix-redeye`). Strongly red pixels are
+// grouped into connected blobs; small, roughly round, compact blobs are taken
+// for pupils and their red channel replaced by the green/blue average, which
+// leaves the dark pupil and its catchlight.
+//
+// With `--face-model` the search is limited to the upper half of each
+// detected face, otherwise the whole image is searched, where small round red
+// things (berries, LEDs) can be mistaken for eyes.
+
+use super::geometry::Rect;
+use image::{DynamicImage, RgbaImage};
+
+/// Red excess over green/blue, relative to red, that marks a red-eye pixel
+const REDNESS: f32 = 0.45;
+/// Weaker redness of the blob's fringe, corrected along with it
+const FRINGE: f32 = 0.25;
+/// Largest pupil as a share of the searched area, for faces and full images
+const MAX_AREA_FACE: f32 = 0.03;
+const MAX_AREA_IMAGE: f32 = 0.002;
+/// Fewest pixels a pupil blob must have
+const MIN_AREA: usize = 4;
+
+/// Removes red eyes within the upper halves of `faces`, or anywhere when no
+/// faces are given
+pub fn fix_redeye(img: &DynamicImage, faces: Option<&[Rect]>) -> DynamicImage {
+    let has_alpha = img.color().has_alpha();
+    let mut rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+
+    let (regions, max_share) = match faces {
+        Some(faces) => (
+            faces
+                .iter()
+                .map(|face| Rect {
+                    height: face.height.div_ceil(2),
+                    ..*face
+                })
+                .collect(),
+            MAX_AREA_FACE,
+        ),
+        None => (
+            vec![Rect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            }],
+            MAX_AREA_IMAGE,
+        ),
+    };
+
+    for region in regions {
+        let right = region.x.saturating_add(region.width).min(width);
+        let bottom = region.y.saturating_add(region.height).min(height);
+        if region.x >= right || region.y >= bottom {
+            continue;
+        }
+        let region = Rect {
+            width: right - region.x,
+            height: bottom - region.y,
+            ..region
+        };
+        let max_area =
+            (region.width as f32 * region.height as f32 * max_share).max(MIN_AREA as f32);
+        for blob in blobs(&rgba, region) {
+            if is_pupil(&blob, max_area as usize) {
+                correct(&mut rgba, &blob);
+            }
+        }
+    }
+
+    if has_alpha {
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba).to_rgb8())
+    }
+}
+
+/// How much red dominates a pixel: 0 for neutral or other hues, up to 1
+fn redness(px: &image::Rgba<u8>) -> f32 {
+    let [r, g, b] = [px[0], px[1], px[2]].map(f32::from);
+    if r < 50.0 || px[3] == 0 {
+        return 0.0;
+    }
+    (r - g.max(b)) / r
+}
+
+/// A connected group of red pixels
+struct Blob {
+    pixels: Vec<(u32, u32)>,
+    bounds: Rect,
+}
+
+/// 4-connected blobs of red-eye pixels within `region`
+fn blobs(img: &RgbaImage, region: Rect) -> Vec<Blob> {
+    let (rw, rh) = (region.width, region.height);
+    let index = |x: u32, y: u32| ((y - region.y) * rw + (x - region.x)) as usize;
+    let mut seen = vec![false; (rw * rh) as usize];
+    let mut found = Vec::new();
+
+    for y in region.y..region.y + rh {
+        for x in region.x..region.x + rw {
+            if seen[index(x, y)] || redness(img.get_pixel(x, y)) < REDNESS {
+                continue;
+            }
+
+            seen[index(x, y)] = true;
+            let mut pixels = Vec::new();
+            let mut stack = vec![(x, y)];
+            while let Some((px, py)) = stack.pop() {
+                pixels.push((px, py));
+                let neighbors = [
+                    (px.wrapping_sub(1), py),
+                    (px + 1, py),
+                    (px, py.wrapping_sub(1)),
+                    (px, py + 1),
+                ];
+                for (nx, ny) in neighbors {
+                    let inside = (region.x..region.x + rw).contains(&nx)
+                        && (region.y..region.y + rh).contains(&ny);
+                    if inside && !seen[index(nx, ny)] && redness(img.get_pixel(nx, ny)) >= REDNESS {
+                        seen[index(nx, ny)] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+
+            let left = pixels.iter().map(|p| p.0).min().unwrap_or(x);
+            let top = pixels.iter().map(|p| p.1).min().unwrap_or(y);
+            let right = pixels.iter().map(|p| p.0).max().unwrap_or(x);
+            let bottom = pixels.iter().map(|p| p.1).max().unwrap_or(y);
+            found.push(Blob {
+                pixels,
+                bounds: Rect {
+                    x: left,
+                    y: top,
+                    width: right - left + 1,
+                    height: bottom - top + 1,
+                },
+            });
+        }
+    }
+    found
+}
+
+/// Whether a blob is small, round and solid enough to be a pupil
+fn is_pupil(blob: &Blob, max_area: usize) -> bool {
+    let area = blob.pixels.len();
+    let Rect { width, height, .. } = blob.bounds;
+    let aspect = width.max(height) as f32 / width.min(height) as f32;
+    // A filled circle covers about 79% of its bounding box
+    let fill = area as f32 / (width * height) as f32;
+    (MIN_AREA..=max_area).contains(&area) && aspect <= 2.0 && fill >= 0.5
+}
+
+/// Desaturates the red of a blob and of the reddish fringe around it
+fn correct(img: &mut RgbaImage, blob: &Blob) {
+    let (width, height) = img.dimensions();
+    let margin = blob.bounds.width.max(blob.bounds.height) / 4 + 1;
+    let left = blob.bounds.x.saturating_sub(margin);
+    let top = blob.bounds.y.saturating_sub(margin);
+    let right = (blob.bounds.x + blob.bounds.width + margin).min(width);
+    let bottom = (blob.bounds.y + blob.bounds.height + margin).min(height);
+
+    for y in top..bottom {
+        for x in left..right {
+            let px = img.get_pixel_mut(x, y);
+            let amount = ((redness(px) - FRINGE) / (REDNESS - FRINGE)).clamp(0.0, 1.0);
+            if amount > 0.0 {
+                let target = (px[1] as f32 + px[2] as f32) / 2.0;
+                px[0] = (px[0] as f32 + (target - px[0] as f32) * amount).round() as u8;
+            }
+        }
+    }
+}
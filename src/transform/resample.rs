@@ -0,0 +1,52 @@
+// src/transform/resample.rs
+//
+// Resizing in linear light (`--linear-resize`). Filtering the stored,
+// gamma-encoded values darkens fine bright-on-dark detail such as text,
+// stars and thin lines when downscaling; decoding to linear floats first
+// and re-encoding afterwards keeps their brightness.
+
+use super::tonemap::{self, linear_to_srgb, srgb_to_linear};
+use image::imageops::{self, FilterType};
+use image::{ColorType, DynamicImage, Rgba32FImage};
+#[cfg(feature = "native")]
+use rayon::prelude::*;
+
+/// Lanczos3 resize to exactly `width` x `height` in linear light, keeping
+/// the image's color type
+pub fn resize_linear(img: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    // Floating-point sources are scene-linear already
+    if tonemap::is_hdr(img) {
+        return img.resize_exact(width, height, FilterType::Lanczos3);
+    }
+
+    let mut pixels = img.to_rgba32f();
+    map_color(&mut pixels, srgb_to_linear);
+    let mut resized = imageops::resize(&pixels, width, height, FilterType::Lanczos3);
+    // Lanczos overshoots around edges
+    map_color(&mut resized, |v| linear_to_srgb(v.clamp(0.0, 1.0)));
+
+    with_color_type(DynamicImage::ImageRgba32F(resized), img.color())
+}
+
+/// Applies `f` to the color channels, leaving alpha as is
+fn map_color(pixels: &mut Rgba32FImage, f: impl Fn(f32) -> f32 + Send + Sync) {
+    let map_pixel = |px: &mut [f32]| px[..3].iter_mut().for_each(|c| *c = f(*c));
+    #[cfg(feature = "native")]
+    pixels.par_chunks_mut(4).for_each(map_pixel);
+    #[cfg(not(feature = "native"))]
+    pixels.chunks_mut(4).for_each(map_pixel);
+}
+
+/// Converts back to the source's color type (8/16-bit, gray, alpha)
+fn with_color_type(img: DynamicImage, color: ColorType) -> DynamicImage {
+    match color {
+        ColorType::L8 => DynamicImage::ImageLuma8(img.to_luma8()),
+        ColorType::La8 => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+        ColorType::Rgb8 => DynamicImage::ImageRgb8(img.to_rgb8()),
+        ColorType::L16 => DynamicImage::ImageLuma16(img.to_luma16()),
+        ColorType::La16 => DynamicImage::ImageLumaA16(img.to_luma_alpha16()),
+        ColorType::Rgb16 => DynamicImage::ImageRgb16(img.to_rgb16()),
+        ColorType::Rgba16 => DynamicImage::ImageRgba16(img.to_rgba16()),
+        _ => DynamicImage::ImageRgba8(img.to_rgba8()),
+    }
+}
@@ -0,0 +1,193 @@
+// src/transform/rotate.rs
+//
+// Rotation by arbitrary angles (`--rotate-deg`, `--auto-straighten`), with
+// bilinear sampling. The corners a rotation uncovers are filled with a color,
+// cropped away, or made room for by enlarging the canvas.
+//
+// Straightening looks for the dominant near-horizontal edges, such as a
+// horizon or a table edge, and finds the angle that lines them up best.
+
+use super::color::Color;
+use super::geometry::RotateEdges;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, Rgba, RgbaImage};
+
+/// Largest tilt `--auto-straighten` corrects, in degrees either way
+const MAX_TILT: f32 = 10.0;
+/// Longest side of the copy the tilt is measured on
+const TILT_SIZE: u32 = 512;
+/// Smallest tilt worth resampling the image for
+const MIN_TILT: f32 = 0.1;
+/// How much better the tilt must line edges up than the average angle
+const MIN_CONTRAST: f32 = 1.5;
+/// Share of the strongest edge pixels that vote on the tilt
+const STRONG_EDGES: f32 = 0.02;
+
+/// Rotates clockwise by `degrees` around the center; `edges` says what
+/// happens to the uncovered corners, filled with `fill` unless cropped
+pub fn rotate(img: &DynamicImage, degrees: f32, edges: RotateEdges, fill: Color) -> DynamicImage {
+    let rgba = img.to_rgba8();
+    let (width, height) = (rgba.width() as f32, rgba.height() as f32);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (abs_sin, abs_cos) = (sin.abs(), cos.abs());
+
+    let (out_w, out_h) = match edges {
+        RotateEdges::Fill => (width, height),
+        // Bounding box of the rotated image
+        RotateEdges::Expand => (
+            width * abs_cos + height * abs_sin,
+            width * abs_sin + height * abs_cos,
+        ),
+        // Largest rectangle of the same shape whose rotated-back bounding
+        // box still fits in the source
+        RotateEdges::Crop => {
+            let scale = (width / (width * abs_cos + height * abs_sin))
+                .min(height / (width * abs_sin + height * abs_cos));
+            (width * scale, height * scale)
+        }
+    };
+    let (out_w, out_h) = ((out_w.round() as u32).max(1), (out_h.round() as u32).max(1));
+
+    // Each output pixel samples the source point rotated back onto it
+    let (cx, cy) = (width / 2.0, height / 2.0);
+    let (ox, oy) = (out_w as f32 / 2.0, out_h as f32 / 2.0);
+    let out = RgbaImage::from_fn(out_w, out_h, |x, y| {
+        let (dx, dy) = (x as f32 + 0.5 - ox, y as f32 + 0.5 - oy);
+        let sx = cx + dx * cos + dy * sin - 0.5;
+        let sy = cy - dx * sin + dy * cos - 0.5;
+        sample(&rgba, sx, sy, fill.0)
+    });
+
+    let opaque = edges == RotateEdges::Crop || fill.is_opaque();
+    if opaque && !img.color().has_alpha() {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(out).to_rgb8())
+    } else {
+        DynamicImage::ImageRgba8(out)
+    }
+}
+
+/// Bilinear sample at (`x`, `y`); neighbors outside the image count as
+/// `fill`, except right at the edges, which are extended half a pixel
+fn sample(img: &RgbaImage, x: f32, y: f32, fill: Rgba<u8>) -> Rgba<u8> {
+    let (max_x, max_y) = (img.width() as f32 - 1.0, img.height() as f32 - 1.0);
+    if x < -0.5 || y < -0.5 || x > max_x + 0.5 || y > max_y + 0.5 {
+        return fill;
+    }
+    let (x, y) = (x.clamp(0.0, max_x), y.clamp(0.0, max_y));
+
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = (
+        (x0 + 1).min(img.width() - 1),
+        (y0 + 1).min(img.height() - 1),
+    );
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let (a, b) = (img.get_pixel(x0, y0), img.get_pixel(x1, y0));
+    let (c, d) = (img.get_pixel(x0, y1), img.get_pixel(x1, y1));
+    Rgba(std::array::from_fn(|i| {
+        let top = a[i] as f32 * (1.0 - fx) + b[i] as f32 * fx;
+        let bottom = c[i] as f32 * (1.0 - fx) + d[i] as f32 * fx;
+        (top * (1.0 - fy) + bottom * fy).round() as u8
+    }))
+}
+
+/// Clockwise tilt of the dominant straight edges in degrees, 0 when no
+/// clear edges are found
+pub fn detect_tilt(img: &DynamicImage) -> f32 {
+    let small = if img.width().max(img.height()) > TILT_SIZE {
+        img.resize(TILT_SIZE, TILT_SIZE, FilterType::Triangle)
+    } else {
+        img.clone()
+    };
+    // Blurred so grain and texture don't outvote the real edges
+    let gray = image::imageops::blur(&small.to_luma8(), 1.5);
+    let (width, height) = gray.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+
+    // The strongest mostly-horizontal edges vote; the angle that lines them
+    // up into the fewest rows is the tilt
+    let gradients = sobel(&gray);
+    let mut magnitudes: Vec<f32> = gradients.iter().map(|g| g.2).collect();
+    let strong = ((1.0 - STRONG_EDGES) * magnitudes.len() as f32) as usize;
+    let (_, &mut threshold, _) = magnitudes.select_nth_unstable_by(strong, f32::total_cmp);
+    let points: Vec<(f32, f32, f32)> = gradients
+        .iter()
+        .enumerate()
+        .filter(|(_, (gx, gy, magnitude))| *magnitude >= threshold.max(1.0) && gy.abs() > gx.abs())
+        .map(|(i, &(_, _, magnitude))| {
+            let (x, y) = (i as u32 % (width - 2) + 1, i as u32 / (width - 2) + 1);
+            (x as f32, y as f32, magnitude)
+        })
+        .collect();
+
+    match line_angle(&points, (width, height), MAX_TILT) {
+        Some((tilt, contrast)) if contrast >= MIN_CONTRAST && tilt.abs() >= MIN_TILT => tilt,
+        _ => 0.0,
+    }
+}
+
+/// The clockwise angle in degrees, within `max` either way, at which
+/// weighted points line up into the sharpest row profile, and how much
+/// sharper that profile is than the average over all angles
+pub(super) fn line_angle(
+    points: &[(f32, f32, f32)],
+    (width, height): (u32, u32),
+    max: f32,
+) -> Option<(f32, f32)> {
+    if points.is_empty() {
+        return None;
+    }
+
+    // Rows of the sheared image, offset so every point lands in range
+    let offset = width as f32 * (max + 1.0).to_radians().tan();
+    let rows = (height as f32 + 2.0 * offset) as usize + 2;
+    let score = |degrees: f32| {
+        let slope = degrees.to_radians().tan();
+        let mut profile = vec![0f64; rows];
+        for &(x, y, weight) in points {
+            let row = ((y - x * slope + offset).max(0.0) as usize).min(rows - 1);
+            profile[row] += weight as f64;
+        }
+        profile.iter().map(|n| n * n).sum::<f64>()
+    };
+    let scan = |from: f32, to: f32, step: f32| {
+        let steps = ((to - from) / step).round() as i32;
+        (0..=steps)
+            .map(|i| {
+                let angle = from + i as f32 * step;
+                (angle, score(angle))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let coarse = scan(-max, max, 0.5);
+    let average = coarse.iter().map(|c| c.1).sum::<f64>() / coarse.len() as f64;
+    let (best, _) = coarse.iter().copied().max_by(|a, b| a.1.total_cmp(&b.1))?;
+    let (angle, sharpness) = scan(best - 0.5, best + 0.5, 0.05)
+        .into_iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    Some((angle, (sharpness / average.max(f64::MIN_POSITIVE)) as f32))
+}
+
+/// Horizontal and vertical Sobel responses and their magnitude per interior
+/// pixel
+fn sobel(gray: &GrayImage) -> Vec<(f32, f32, f32)> {
+    let (width, height) = gray.dimensions();
+    let at = |x: u32, y: u32| gray.get_pixel(x, y)[0] as f32;
+    let mut out = Vec::with_capacity(((width - 2) * (height - 2)) as usize);
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x, y - 1)
+                - at(x + 1, y - 1);
+            out.push((gx, gy, (gx * gx + gy * gy).sqrt()));
+        }
+    }
+    out
+}
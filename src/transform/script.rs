@@ -0,0 +1,658 @@
+// src/transform/script.rs
+//
+// `--pipeline`: an ordered chain of operations written as
+//
+//   resize(50%) | sharpen(0.5) | watermark(logo.png, se) | encode(webp, 75)
+//
+// Steps run left to right on each source (after the command-line transforms
+// that run before scaling); every `encode` writes the image as it is at that
+// point, so one chain can produce several outputs:
+//
+//   resize(1600x) | encode(jpg, 85) | resize(50%) | encode(webp) | encode(png)
+//
+// Operations:
+//   resize(50%) resize(WxH) resize(Wx) resize(xH)   percent, or fit within (never enlarged)
+//   resize(WxH<) resize(WxH^) resize(WxH!)          enlarge only, cover, or exact (as in --resize)
+//   crop(X,Y,WxH)  aspect(16:9[, gravity])  carve(16:9)  pad(16:9[, gravity])
+//   trim([fuzz])  extent(WxH[, gravity])
+//   rotate(90|180|270)  flip(h|v)  grayscale  blur(sigma)  sharpen(sigma)
+//   denoise([strength])  redeye  enhance  vignette([strength])  posterize(levels)  lut(file.cube)
+//   watermark(file[, gravity[, opacity%]])
+//   encode(format[, quality])
+
+use super::color::Color;
+use super::geometry::{AspectRatio, Gravity, Rect, ResizeMode, parse_size};
+use super::lut::Lut3d;
+use super::{canvas, crop, denoise, enhance, posterize, redeye, seam, trim, vignette};
+use crate::decode;
+use crate::exit::InvalidConfig;
+use crate::i18n::{Msg, tr};
+use anyhow::Result;
+use clap::ValueEnum;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImageView};
+use std::path::Path;
+use std::sync::Arc;
+
+/// A parsed `--pipeline` chain
+pub struct Script {
+    steps: Vec<Step>,
+    /// Output name template, for chains that name outputs other than by scale
+    output_name: Option<String>,
+    /// Encodes of the same size and format give identical outputs, so all
+    /// but the first can be left out (everything but pipelines, whose later
+    /// steps may change the pixels without changing the size)
+    skip_repeats: bool,
+}
+
+/// An image produced by an `encode` step
+pub struct Variant {
+    pub image: DynamicImage,
+    /// Output width as a percentage of the source width, for output names
+    pub scale: u32,
+    pub format: String,
+    /// Overrides the `--quality` setting
+    pub quality: Option<u8>,
+    /// What the variant is for, for output names (`{label}`)
+    pub label: Option<String>,
+}
+
+enum Step {
+    Resize(Resize),
+    Crop(Rect),
+    Aspect(AspectRatio, Gravity),
+    Carve(AspectRatio),
+    Pad(AspectRatio, Gravity, Color),
+    Trim(f32),
+    Extent((u32, u32), Gravity),
+    Rotate(u32),
+    Flip(bool),
+    Grayscale,
+    Blur(f32),
+    Sharpen(f32),
+    Redeye,
+    Enhance,
+    Denoise(f32),
+    Vignette(f32),
+    Posterize(u8),
+    Lut(Box<Lut3d>),
+    Watermark(Arc<DynamicImage>, Gravity, f32),
+    Encode(String, Option<u8>),
+    /// Remembers the current image for `Restore` (not part of the syntax)
+    Checkpoint,
+    Restore,
+    /// Names the following encodes' variants (not part of the syntax)
+    Label(String),
+}
+
+/// Largest percentage a resize takes (10x)
+const MAX_PERCENT: u32 = 1000;
+
+enum Resize {
+    Percent(u32),
+    /// Fit within the given width and/or height, or cover them
+    Fit {
+        width: Option<u32>,
+        height: Option<u32>,
+        cover: bool,
+        only: Only,
+    },
+    /// Exactly the given size, ignoring the aspect ratio
+    Exact {
+        width: u32,
+        height: u32,
+        only: Only,
+    },
+}
+
+/// Which sources a resize applies to (the `>` and `<` geometry flags)
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Only {
+    Any,
+    /// Larger than the size
+    Shrink,
+    /// Smaller than the size
+    Enlarge,
+}
+
+impl Script {
+    /// Parses a chain, or `@file` to read the chain from a file
+    ///
+    /// Files named by steps (`lut`, `watermark`) are loaded here, relative
+    /// to the working directory.
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = match text.strip_prefix('@') {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|err| InvalidConfig(tr!(Msg::PipelineRead, path, err)))?,
+            None => text.to_string(),
+        };
+
+        let steps = text
+            .split(['|', '\n'])
+            .map(str::trim)
+            .filter(|step| !step.is_empty() && !step.starts_with('#'))
+            .map(|step| {
+                Step::parse(step).map_err(|err| {
+                    anyhow::Error::new(InvalidConfig(tr!(Msg::PipelineStep, step, err)))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let script = Self {
+            steps,
+            output_name: None,
+            skip_repeats: false,
+        };
+        if script.outputs() == 0 {
+            return Err(InvalidConfig(tr!(Msg::PipelineNoEncode)).into());
+        }
+        Ok(script)
+    }
+
+    /// A chain that crops the source once, then encodes every format at each
+    /// size, resizing from the crop each time (`--jobs` rows, `--name`)
+    pub fn variants(
+        crop: Option<Rect>,
+        sizes: &[&str],
+        formats: &[String],
+        quality: Option<u8>,
+    ) -> Result<Self, String> {
+        let mut steps: Vec<Step> = crop.map(Step::Crop).into_iter().collect();
+        steps.push(Step::Checkpoint);
+        for size in sizes {
+            steps.push(Step::Restore);
+            steps.push(Step::Resize(Resize::parse(size, Only::Shrink)?));
+            for format in formats {
+                steps.push(Step::Encode(format.to_lowercase(), quality));
+            }
+        }
+        Ok(Self {
+            steps,
+            output_name: None,
+            skip_repeats: true,
+        })
+    }
+
+    /// A chain encoding every format at each ImageMagick geometry (`--resize`)
+    ///
+    /// Unlike pipeline sizes, a bare `WxH` fits both ways, and percentages
+    /// can enlarge; outputs are named `{stem}_{width}x{height}`.
+    pub fn geometries(geometries: &[String], formats: &[String]) -> Result<Self, String> {
+        let mut steps = vec![Step::Checkpoint];
+        for geometry in geometries {
+            steps.push(Step::Restore);
+            steps.push(Step::Resize(Resize::parse(geometry, Only::Any)?));
+            for format in formats {
+                steps.push(Step::Encode(format.to_lowercase(), None));
+            }
+        }
+        Ok(Self {
+            steps,
+            output_name: Some("{stem}_{width}x{height}".into()),
+            skip_repeats: true,
+        })
+    }
+
+    /// A chain encoding every format at each width, never enlarging
+    /// (`--breakpoints`)
+    ///
+    /// Outputs are named `{stem}_{width}w`; widths beyond the source's
+    /// collapse into a single output at the source width.
+    pub fn breakpoints(widths: &[u32], formats: &[String]) -> Self {
+        let mut steps = vec![Step::Checkpoint];
+        for &width in widths {
+            steps.push(Step::Restore);
+            steps.push(Step::Resize(Resize::Fit {
+                width: Some(width),
+                height: None,
+                cover: false,
+                only: Only::Shrink,
+            }));
+            for format in formats {
+                steps.push(Step::Encode(format.to_lowercase(), None));
+            }
+        }
+        Self {
+            steps,
+            output_name: Some("{stem}_{width}w".into()),
+            skip_repeats: true,
+        }
+    }
+
+    /// A chain bringing the source to each of `targets`, as (label, width,
+    /// height), by `mode` (cropping by `gravity`, carving, or padding with
+    /// `background`) and then resizing to exactly that size, encoding every
+    /// format (`--preset social`)
+    ///
+    /// Outputs are named `{stem}_{label}`.
+    pub fn targets(
+        targets: &[(&str, u32, u32)],
+        formats: &[String],
+        mode: ResizeMode,
+        gravity: Gravity,
+        background: Color,
+    ) -> Self {
+        let mut steps = vec![Step::Checkpoint];
+        for &(label, width, height) in targets {
+            let aspect = AspectRatio { width, height };
+            steps.push(Step::Restore);
+            steps.push(Step::Label(label.to_string()));
+            steps.push(match mode {
+                ResizeMode::Crop => Step::Aspect(aspect, gravity),
+                ResizeMode::Seam => Step::Carve(aspect),
+                ResizeMode::Pad => Step::Pad(aspect, gravity, background),
+            });
+            steps.push(Step::Resize(Resize::Exact {
+                width,
+                height,
+                only: Only::Any,
+            }));
+            for format in formats {
+                steps.push(Step::Encode(format.to_lowercase(), None));
+            }
+        }
+        Self {
+            steps,
+            output_name: Some("{stem}_{label}".into()),
+            skip_repeats: true,
+        }
+    }
+
+    /// Output name template (`{stem}`, `{scale}`, `{width}`, `{height}`,
+    /// `{exif_date}`, `{camera}`, `{iso}`, `{focal}`), when outputs aren't
+    /// named after their scale
+    pub fn output_name(&self) -> Option<&str> {
+        self.output_name.as_deref()
+    }
+
+    /// Names outputs after `template` (`--name`)
+    pub fn with_output_name(mut self, template: String) -> Self {
+        self.output_name = Some(template);
+        self
+    }
+
+    /// Number of `encode` steps, i.e. outputs per source
+    pub fn outputs(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| matches!(step, Step::Encode(..)))
+            .count()
+    }
+
+    /// Formats of the `encode` steps, in order
+    pub fn formats(&self) -> impl Iterator<Item = &str> {
+        self.steps.iter().filter_map(|step| match step {
+            Step::Encode(format, _) => Some(format.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Whether encodes repeating an earlier one's size, format and quality
+    /// can be left out
+    pub fn skips_repeats(&self) -> bool {
+        self.skip_repeats
+    }
+
+    /// Runs the chain on one source (resizing in linear light if `linear`),
+    /// handing each encoded variant to `emit`
+    pub fn run(
+        &self,
+        mut img: DynamicImage,
+        linear: bool,
+        mut emit: impl FnMut(Variant) -> Result<()>,
+    ) -> Result<()> {
+        let source_width = img.width().max(1);
+        let mut saved = None;
+        let mut label = None;
+
+        for step in &self.steps {
+            img = match step {
+                Step::Encode(format, quality) => {
+                    let scale = (img.width() as u64 * 100 / source_width as u64) as u32;
+                    emit(Variant {
+                        image: img.clone(),
+                        scale: scale.max(1),
+                        format: format.clone(),
+                        quality: *quality,
+                        label: label.clone(),
+                    })?;
+                    continue;
+                }
+                Step::Label(name) => {
+                    label = Some(name.clone());
+                    continue;
+                }
+                Step::Checkpoint => {
+                    saved = Some(img.clone());
+                    continue;
+                }
+                Step::Restore => saved.clone().unwrap_or(img),
+                Step::Resize(resize) => resize.apply(&img, linear)?,
+                Step::Crop(rect) => crop::crop_rect(&img, *rect)?,
+                Step::Aspect(aspect, gravity) => crop::crop_aspect(&img, *aspect, *gravity),
+                Step::Carve(aspect) => seam::carve_aspect(&img, *aspect),
+                Step::Pad(aspect, gravity, background) => {
+                    canvas::pad_aspect(&img, *aspect, *gravity, *background)
+                }
+                Step::Trim(fuzz) => trim::trim(&img, *fuzz),
+                Step::Extent(size, gravity) => canvas::extend(&img, *size, *gravity, Color::WHITE),
+                Step::Rotate(90) => img.rotate90(),
+                Step::Rotate(180) => img.rotate180(),
+                Step::Rotate(_) => img.rotate270(),
+                Step::Flip(true) => img.fliph(),
+                Step::Flip(false) => img.flipv(),
+                Step::Grayscale => img.grayscale(),
+                Step::Blur(sigma) => img.blur(*sigma),
+                Step::Sharpen(sigma) => img.unsharpen(*sigma, 1),
+                Step::Denoise(strength) => denoise::denoise(&img, *strength),
+                Step::Redeye => redeye::fix_redeye(&img, None),
+                Step::Enhance => enhance::auto_enhance(&img),
+                Step::Vignette(strength) => vignette::vignette(&img, *strength),
+                Step::Posterize(levels) => posterize::posterize(&img, *levels),
+                Step::Lut(lut) => lut.apply(&img),
+                Step::Watermark(mark, gravity, opacity) => {
+                    watermark(&img, mark, *gravity, *opacity)
+                }
+            };
+        }
+        Ok(())
+    }
+}
+
+impl Step {
+    fn parse(step: &str) -> Result<Self, String> {
+        let (name, args) = match step.split_once('(') {
+            Some((name, rest)) => {
+                let args = rest
+                    .strip_suffix(')')
+                    .ok_or_else(|| "missing ')'".to_string())?;
+                let args: Vec<&str> = args.split(',').map(str::trim).collect();
+                (name.trim(), args)
+            }
+            None => (step, Vec::new()),
+        };
+        let args: Vec<&str> = args.into_iter().filter(|a| !a.is_empty()).collect();
+
+        let arity = |min: usize, max: usize| {
+            if (min..=max).contains(&args.len()) {
+                Ok(())
+            } else if min == max {
+                Err(format!("expected {min} argument(s)"))
+            } else {
+                Err(format!("expected {min} to {max} arguments"))
+            }
+        };
+        let number = |i: usize, default: f32, range: (f32, f32)| -> Result<f32, String> {
+            let Some(arg) = args.get(i) else {
+                return Ok(default);
+            };
+            arg.parse::<f32>()
+                .ok()
+                .filter(|v| (range.0..=range.1).contains(v))
+                .ok_or_else(|| format!("'{arg}' is not a number in {}-{}", range.0, range.1))
+        };
+        let gravity = |i: usize| -> Result<Gravity, String> {
+            args.get(i).map_or(Ok(Gravity::Center), |arg| {
+                Gravity::from_str(arg, true).map_err(|_| format!("unknown gravity '{arg}'"))
+            })
+        };
+
+        match name.to_lowercase().as_str() {
+            "resize" => {
+                arity(1, 1)?;
+                Resize::parse(args[0], Only::Shrink).map(Step::Resize)
+            }
+            "crop" => {
+                arity(3, 3)?;
+                args.join(",").parse().map(Step::Crop)
+            }
+            "aspect" => {
+                arity(1, 2)?;
+                Ok(Step::Aspect(args[0].parse()?, gravity(1)?))
+            }
+            "carve" => {
+                arity(1, 1)?;
+                args[0].parse().map(Step::Carve)
+            }
+            "pad" => {
+                arity(1, 2)?;
+                Ok(Step::Pad(args[0].parse()?, gravity(1)?, Color::WHITE))
+            }
+            "trim" => {
+                arity(0, 1)?;
+                Ok(Step::Trim(number(0, 0.0, (0.0, 100.0))?))
+            }
+            "extent" => {
+                arity(1, 2)?;
+                let size =
+                    parse_size(args[0]).ok_or_else(|| format!("invalid size '{}'", args[0]))?;
+                Ok(Step::Extent(size, gravity(1)?))
+            }
+            "rotate" => {
+                arity(1, 1)?;
+                match args[0] {
+                    "90" | "180" | "270" => {
+                        Ok(Step::Rotate(args[0].parse().expect("checked above")))
+                    }
+                    other => Err(format!("can only rotate by 90, 180 or 270, not '{other}'")),
+                }
+            }
+            "flip" => {
+                arity(1, 1)?;
+                match args[0] {
+                    "h" | "horizontal" => Ok(Step::Flip(true)),
+                    "v" | "vertical" => Ok(Step::Flip(false)),
+                    other => Err(format!("expected h or v, not '{other}'")),
+                }
+            }
+            "grayscale" | "greyscale" => {
+                arity(0, 0)?;
+                Ok(Step::Grayscale)
+            }
+            "blur" => {
+                arity(1, 1)?;
+                Ok(Step::Blur(number(0, 0.0, (0.0, 100.0))?))
+            }
+            "sharpen" => {
+                arity(1, 1)?;
+                Ok(Step::Sharpen(number(0, 0.0, (0.0, 100.0))?))
+            }
+            "denoise" => {
+                arity(0, 1)?;
+                Ok(Step::Denoise(number(0, 50.0, (1.0, 100.0))?))
+            }
+            "redeye" => {
+                arity(0, 0)?;
+                Ok(Step::Redeye)
+            }
+            "enhance" => {
+                arity(0, 0)?;
+                Ok(Step::Enhance)
+            }
+            "vignette" => {
+                arity(0, 1)?;
+                Ok(Step::Vignette(number(0, 40.0, (0.0, 100.0))?))
+            }
+            "posterize" => {
+                arity(1, 1)?;
+                Ok(Step::Posterize(number(0, 0.0, (2.0, 255.0))? as u8))
+            }
+            "lut" => {
+                arity(1, 1)?;
+                let lut = Lut3d::load(Path::new(args[0])).map_err(|err| format!("{err:#}"))?;
+                Ok(Step::Lut(Box::new(lut)))
+            }
+            "watermark" => {
+                arity(1, 3)?;
+                let mark =
+                    decode::load_image(Path::new(args[0])).map_err(|err| format!("{err:#}"))?;
+                let opacity = number(2, 100.0, (0.0, 100.0))? / 100.0;
+                Ok(Step::Watermark(Arc::new(mark), gravity(1)?, opacity))
+            }
+            "encode" => {
+                arity(1, 2)?;
+                let quality = match args.get(1) {
+                    None => None,
+                    Some(q) => Some(
+                        q.parse::<u8>()
+                            .ok()
+                            .filter(|q| *q <= 100)
+                            .ok_or_else(|| format!("invalid quality '{q}'"))?,
+                    ),
+                };
+                Ok(Step::Encode(args[0].to_lowercase(), quality))
+            }
+            other => Err(format!("unknown operation '{other}'")),
+        }
+    }
+}
+
+impl Resize {
+    /// Parses a percentage or an ImageMagick-style geometry (`WxH`, `Wx`,
+    /// `xH` or `W`, optionally followed by `>`, `<`, `^` or `!`); a geometry
+    /// without `>` or `<` applies to the sources `bare` selects
+    fn parse(arg: &str, bare: Only) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "invalid size '{arg}' (expected N%, WxH, Wx, xH or W, optionally followed by >, <, ^ or !)"
+            )
+        };
+        let arg = arg.trim();
+
+        if let Some(percent) = arg.strip_suffix('%') {
+            return percent
+                .trim()
+                .parse()
+                .ok()
+                .filter(|p| (1..=MAX_PERCENT).contains(p))
+                .map(Resize::Percent)
+                .ok_or_else(invalid);
+        }
+        let size = arg.trim_end_matches(['>', '<', '^', '!']);
+        let flags = &arg[size.len()..];
+        let flag = |c: char| flags.contains(c);
+        if (flag('>') && flag('<')) || (flag('^') && flag('!')) {
+            return Err(invalid());
+        }
+        let only = match (flag('>'), flag('<')) {
+            (true, _) => Only::Shrink,
+            (_, true) => Only::Enlarge,
+            _ => bare,
+        };
+
+        let (w, h) = size.split_once(['x', 'X']).unwrap_or((size, ""));
+        let side = |s: &str| -> Result<Option<u32>, String> {
+            match s.trim() {
+                "" => Ok(None),
+                s => s
+                    .parse()
+                    .ok()
+                    .filter(|&v| v > 0)
+                    .map(Some)
+                    .ok_or_else(invalid),
+            }
+        };
+        match (side(w)?, side(h)?) {
+            (None, None) => Err(invalid()),
+            (Some(width), Some(height)) if flag('!') => Ok(Resize::Exact {
+                width,
+                height,
+                only,
+            }),
+            _ if flag('!') => Err(format!("'{arg}': '!' needs both a width and a height")),
+            (width, height) => Ok(Resize::Fit {
+                width,
+                height,
+                cover: flag('^'),
+                only,
+            }),
+        }
+    }
+
+    fn apply(&self, img: &DynamicImage, linear: bool) -> Result<DynamicImage> {
+        let (source_width, source_height) = img.dimensions();
+        match *self {
+            Resize::Percent(percent) => crate::pipeline::resize_image(img, percent, linear),
+            Resize::Fit {
+                width,
+                height,
+                cover,
+                only,
+            } => {
+                let fx = width.map(|w| w as f64 / source_width as f64);
+                let fy = height.map(|h| h as f64 / source_height as f64);
+                let factor = match (fx, fy) {
+                    (Some(fx), Some(fy)) if cover => fx.max(fy),
+                    (Some(fx), Some(fy)) => fx.min(fy),
+                    (Some(f), None) | (None, Some(f)) => f,
+                    (None, None) => 1.0,
+                };
+                let applies = match only {
+                    Only::Any => factor != 1.0,
+                    Only::Shrink => factor < 1.0,
+                    Only::Enlarge => factor > 1.0,
+                };
+                if !applies {
+                    return Ok(img.clone());
+                }
+                let new_width = ((source_width as f64 * factor).round() as u32).max(1);
+                let new_height = ((source_height as f64 * factor).round() as u32).max(1);
+                Ok(crate::pipeline::resize_to(
+                    img, new_width, new_height, linear,
+                ))
+            }
+            Resize::Exact {
+                width,
+                height,
+                only,
+            } => {
+                let applies = match only {
+                    Only::Any => true,
+                    Only::Shrink => source_width > width || source_height > height,
+                    Only::Enlarge => source_width < width && source_height < height,
+                };
+                if !applies || (source_width, source_height) == (width, height) {
+                    return Ok(img.clone());
+                }
+                Ok(crate::pipeline::resize_to(img, width, height, linear))
+            }
+        }
+    }
+}
+
+/// Overlays `mark` at the gravity anchor with a small margin, shrinking it
+/// to fit if it's larger than the image
+fn watermark(
+    img: &DynamicImage,
+    mark: &DynamicImage,
+    gravity: Gravity,
+    opacity: f32,
+) -> DynamicImage {
+    let (width, height) = img.dimensions();
+    let margin = width.min(height) / 50;
+    let room = (
+        width.saturating_sub(2 * margin).max(1),
+        height.saturating_sub(2 * margin).max(1),
+    );
+
+    let mut mark = if mark.width() > room.0 || mark.height() > room.1 {
+        mark.resize(room.0, room.1, FilterType::Lanczos3).to_rgba8()
+    } else {
+        mark.to_rgba8()
+    };
+    if opacity < 1.0 {
+        for pixel in mark.pixels_mut() {
+            pixel[3] = (pixel[3] as f32 * opacity).round() as u8;
+        }
+    }
+
+    let (x, y) = gravity.offset(room, mark.dimensions());
+    let mut canvas = img.to_rgba8();
+    imageops::overlay(&mut canvas, &mark, (x + margin) as i64, (y + margin) as i64);
+
+    if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(canvas)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+    }
+}
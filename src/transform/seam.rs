@@ -0,0 +1,142 @@
+// src/transform/seam.rs
+//
+// Seam carving (`--resize-mode seam`, experimental): reaching an aspect
+// ratio by repeatedly removing the connected path of pixels, one per row,
+// that crosses the least detail. Unlike cropping it keeps subjects near
+// both edges, and unlike stretching it doesn't distort them.
+//
+// Seams are found on a copy reduced to at most WORK_SIZE pixels so large
+// photos carve in reasonable time; each seam then removes a band of the
+// matching full-resolution columns.
+
+use super::geometry::AspectRatio;
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, RgbaImage};
+
+/// Longest side of the image seams are searched on
+const WORK_SIZE: u32 = 512;
+
+/// Removes seams until the image has the given aspect ratio
+pub fn carve_aspect(img: &DynamicImage, aspect: AspectRatio) -> DynamicImage {
+    let (width, height) = (img.width() as u64, img.height() as u64);
+    let (aw, ah) = (aspect.width as u64, aspect.height as u64);
+
+    // Too wide: carve vertical seams; too tall: carve horizontal ones by
+    // carving the rotated image
+    let carved = if width * ah > height * aw {
+        let target = (height * aw / ah).max(1) as u32;
+        carve_columns(&img.to_rgba8(), img.width() - target)
+    } else if width * ah < height * aw {
+        let target = (width * ah / aw).max(1) as u32;
+        let rotated = image::imageops::rotate90(&img.to_rgba8());
+        image::imageops::rotate270(&carve_columns(&rotated, img.height() - target))
+    } else {
+        return img.clone();
+    };
+
+    if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(carved)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(carved).to_rgb8())
+    }
+}
+
+/// Removes `remove` columns' worth of vertical seams
+fn carve_columns(img: &RgbaImage, remove: u32) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    let factor = width.max(height).div_ceil(WORK_SIZE).max(1);
+    let work = DynamicImage::ImageRgba8(img.clone())
+        .resize_exact(
+            (width / factor).max(1),
+            (height / factor).max(1),
+            FilterType::Triangle,
+        )
+        .to_luma8();
+    let (work_w, work_h) = work.dimensions();
+
+    // Each work seam removes `factor` full-resolution columns per row, the
+    // last one only what's left over
+    let seams = remove.div_ceil(factor).min(work_w.saturating_sub(1));
+    let mut removed = vec![Vec::with_capacity(seams as usize); work_h as usize];
+    let mut band = vec![factor; seams as usize];
+    if let Some(last) = band.last_mut()
+        && !remove.is_multiple_of(factor)
+    {
+        *last = remove % factor;
+    }
+
+    // Work columns still present in each row, as indices into `work`
+    let mut columns: Vec<Vec<u32>> = vec![(0..work_w).collect(); work_h as usize];
+    for &count in &band {
+        let path = find_seam(&work, &columns);
+        for (y, &x) in path.iter().enumerate() {
+            removed[y].push((columns[y].remove(x), count));
+        }
+    }
+
+    let new_width = width - band.iter().sum::<u32>();
+    let mut out = RgbaImage::new(new_width, height);
+    let mut drop = vec![false; width as usize];
+    for y in 0..height {
+        drop.fill(false);
+        let work_y = (y / factor).min(work_h - 1) as usize;
+        for &(x, count) in &removed[work_y] {
+            let start = x * factor;
+            for column in start..(start + count).min(width) {
+                drop[column as usize] = true;
+            }
+        }
+        let kept = (0..width).filter(|&x| !drop[x as usize]);
+        for (out_x, x) in kept.take(new_width as usize).enumerate() {
+            out.put_pixel(out_x as u32, y, *img.get_pixel(x, y));
+        }
+    }
+    out
+}
+
+/// Lowest-energy 8-connected top-to-bottom path through the remaining
+/// columns, as positions within `columns`
+fn find_seam(work: &GrayImage, columns: &[Vec<u32>]) -> Vec<usize> {
+    let width = columns[0].len();
+    let luma = |y: usize, i: usize| work.get_pixel(columns[y][i], y as u32)[0] as f32;
+
+    // Cumulative minimum energy, row by row
+    let mut cost = vec![vec![0f32; width]; columns.len()];
+    for y in 0..columns.len() {
+        for i in 0..width {
+            let left = luma(y, i.saturating_sub(1));
+            let right = luma(y, (i + 1).min(width - 1));
+            let up = luma(y.saturating_sub(1), i);
+            let down = luma((y + 1).min(columns.len() - 1), i);
+            let energy = (right - left).abs() + (down - up).abs();
+
+            let above = if y == 0 {
+                0.0
+            } else {
+                let row = &cost[y - 1];
+                row[i.saturating_sub(1)..=(i + 1).min(width - 1)]
+                    .iter()
+                    .copied()
+                    .fold(f32::MAX, f32::min)
+            };
+            cost[y][i] = energy + above;
+        }
+    }
+
+    // Walk back up from the cheapest end
+    let mut path = vec![0; columns.len()];
+    let last = cost.len() - 1;
+    path[last] = min_index(&cost[last], 0, width - 1);
+    for y in (0..last).rev() {
+        let x = path[y + 1];
+        path[y] = min_index(&cost[y], x.saturating_sub(1), (x + 1).min(width - 1));
+    }
+    path
+}
+
+/// Index of the smallest value in `row[from..=to]`
+fn min_index(row: &[f32], from: usize, to: usize) -> usize {
+    (from..=to)
+        .min_by(|&a, &b| row[a].total_cmp(&row[b]))
+        .unwrap_or(from)
+}
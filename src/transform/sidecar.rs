@@ -0,0 +1,290 @@
+// src/transform/sidecar.rs
+//
+// `--xmp-sidecars`: the crop and rotation a photographer already set in
+// Lightroom or darktable, read from the XMP sidecar beside each source and
+// applied before rsimg's own transforms, so outputs match the edit without
+// exporting intermediates. The sidecar is looked for as darktable names it
+// (`photo.cr2.xmp`), then as Lightroom does (`photo.xmp`); the source is
+// never written to.
+//
+// Lightroom and Camera Raw keep the orientation (`tiff:Orientation`) and,
+// with `crs:HasCrop`, the straightening angle (`crs:CropAngle`) and the
+// crop's edges as fractions (`crs:CropLeft` ...), both of the image as
+// stored, before it's turned upright. darktable keeps a history of module
+// parameters: the enabled `flip`, `clipping` (angle and crop) and `crop`
+// entries up to `history_end` are applied, to the image as flipped.
+// Parameters darktable stored compressed (`gz...`) aren't read, nor is
+// anything else in the sidecar, exposure and color included.
+
+use super::color::Color;
+use super::geometry::RotateEdges;
+use super::rotate;
+use crate::decode;
+use crate::paths::long_path;
+use image::DynamicImage;
+use image::metadata::Orientation;
+use std::path::Path;
+
+/// Crop and rotation read from a sidecar
+#[derive(Debug, Default)]
+struct Edits {
+    /// Orientation replacing the source's own
+    orientation: Option<Orientation>,
+    /// Clockwise straightening in degrees, about the center
+    angle: f32,
+    /// Left, top, right and bottom edges as fractions of the straightened
+    /// image
+    crop: Option<[f32; 4]>,
+    /// Whether `angle` and `crop` are of the image as stored (Lightroom)
+    /// rather than as oriented (darktable)
+    stored: bool,
+}
+
+/// `img`, decoded from `source`, with the edits of its sidecar applied;
+/// as it was without a sidecar. Uncovered corners take `background`.
+pub(super) fn apply(mut img: DynamicImage, source: &Path, background: Color) -> DynamicImage {
+    let Some(edits) = read(source) else {
+        return img;
+    };
+    let decoded = decode::load_orientation(source);
+    let orientation = edits.orientation.unwrap_or(decoded);
+    if orientation != decoded {
+        img.apply_orientation(inverse(decoded));
+        img.apply_orientation(orientation);
+    }
+
+    // Lightroom's angle and crop are of the stored image; mirrored, it
+    // turns the other way
+    let (angle, crop) = match edits.stored {
+        true => (
+            if mirrored(orientation) {
+                -edits.angle
+            } else {
+                edits.angle
+            },
+            edits.crop.map(|crop| orient(crop, orientation)),
+        ),
+        false => (edits.angle, edits.crop),
+    };
+    if angle != 0.0 {
+        img = rotate::rotate(&img, angle, RotateEdges::Fill, background);
+    }
+    if let Some([left, top, right, bottom]) = crop {
+        let (width, height) = (img.width() as f32, img.height() as f32);
+        let edge = |fraction: f32, size: f32| (fraction.clamp(0.0, 1.0) * size).round() as u32;
+        let (x, y) = (edge(left, width), edge(top, height));
+        let (right, bottom) = (edge(right, width), edge(bottom, height));
+        if right > x && bottom > y && (right - x, bottom - y) != (img.width(), img.height()) {
+            img = img.crop_imm(x, y, right - x, bottom - y);
+        }
+    }
+    img
+}
+
+/// The edits in the sidecar of `source`, if it has one with any
+fn read(source: &Path) -> Option<Edits> {
+    let xmp = read_xmp(source)?;
+    darktable(&xmp).or_else(|| lightroom(&xmp))
+}
+
+/// The sidecar of `source`: darktable's `photo.cr2.xmp`, else Lightroom's
+/// `photo.xmp`
+pub(crate) fn read_xmp(source: &Path) -> Option<String> {
+    let name = source.file_name()?.to_string_lossy();
+    let stem = source.file_stem()?.to_string_lossy();
+    [
+        format!("{name}.xmp"),
+        format!("{name}.XMP"),
+        format!("{stem}.xmp"),
+        format!("{stem}.XMP"),
+    ]
+    .iter()
+    .find_map(|sidecar| std::fs::read_to_string(long_path(&source.with_file_name(sidecar))).ok())
+}
+
+fn lightroom(xmp: &str) -> Option<Edits> {
+    let number = |name: &str| property(xmp, name)?.parse::<f32>().ok();
+    let orientation = property(xmp, "tiff:Orientation")
+        .and_then(|value| Orientation::from_exif(value.parse().ok()?));
+    let cropped =
+        property(xmp, "crs:HasCrop").is_some_and(|value| value.eq_ignore_ascii_case("true"));
+    let crop = match cropped {
+        true => Some([
+            number("crs:CropLeft")?,
+            number("crs:CropTop")?,
+            number("crs:CropRight")?,
+            number("crs:CropBottom")?,
+        ]),
+        false => None,
+    };
+    if orientation.is_none() && crop.is_none() {
+        return None;
+    }
+    Some(Edits {
+        orientation,
+        angle: match cropped {
+            true => number("crs:CropAngle").unwrap_or(0.0),
+            false => 0.0,
+        },
+        crop,
+        stored: true,
+    })
+}
+
+fn darktable(xmp: &str) -> Option<Edits> {
+    let start = xmp.find("<darktable:history>")?;
+    let history = &xmp[start..];
+    let history = &history[..history.find("</darktable:history>")?];
+    let end = attribute(xmp, "darktable:history_end").and_then(|end| end.parse::<usize>().ok());
+
+    // Later entries of a module replace earlier ones
+    let (mut flip, mut clipping, mut crop) = (None, None, None);
+    for (n, entry) in history.split("<rdf:li").skip(1).enumerate() {
+        let num = attribute(entry, "darktable:num")
+            .and_then(|num| num.parse().ok())
+            .unwrap_or(n);
+        if end.is_some_and(|end| num >= end) || attribute(entry, "darktable:enabled") != Some("1") {
+            continue;
+        }
+        let Some(params) = attribute(entry, "darktable:params").and_then(hex) else {
+            continue;
+        };
+        let float = |i: usize| {
+            let bytes = params.get(i * 4..i * 4 + 4)?;
+            Some(f32::from_le_bytes(bytes.try_into().ok()?))
+        };
+        match attribute(entry, "darktable:operation") {
+            Some("flip") => {
+                let bytes = params.get(..4).and_then(|bytes| bytes.try_into().ok());
+                flip = bytes.map(|bytes| flip_orientation(i32::from_le_bytes(bytes)));
+            }
+            // The crop's flips (negative width or height) aren't applied
+            Some("clipping") => {
+                clipping = (|| {
+                    let [angle, left, top, right, bottom] = [0, 1, 2, 3, 4].map(float);
+                    Some((angle?, [left?, top?, right?.abs(), bottom?.abs()]))
+                })();
+            }
+            Some("crop") => {
+                crop = (|| Some([float(0)?, float(1)?, float(2)?, float(3)?]))();
+            }
+            _ => {}
+        }
+    }
+
+    // The modules run flip, clipping, crop
+    let crop = match (clipping.map(|(_, crop)| crop), crop) {
+        (Some(outer), Some(inner)) => Some(within(outer, inner)),
+        (outer, inner) => outer.or(inner),
+    };
+    Some(Edits {
+        orientation: flip.flatten(),
+        angle: clipping.map_or(0.0, |(angle, _)| angle),
+        crop,
+        stored: false,
+    })
+}
+
+/// The orientation darktable's `flip` sets, as flip and swap bits; `None`
+/// (-1) keeps the source's own
+fn flip_orientation(bits: i32) -> Option<Orientation> {
+    match bits {
+        0 => Some(Orientation::NoTransforms),
+        1 => Some(Orientation::FlipVertical),
+        2 => Some(Orientation::FlipHorizontal),
+        3 => Some(Orientation::Rotate180),
+        4 => Some(Orientation::Rotate90FlipH),
+        5 => Some(Orientation::Rotate90),
+        6 => Some(Orientation::Rotate270),
+        7 => Some(Orientation::Rotate270FlipH),
+        _ => None,
+    }
+}
+
+/// `inner`, given as fractions of the `outer` crop, as fractions of the
+/// whole image
+fn within(outer: [f32; 4], inner: [f32; 4]) -> [f32; 4] {
+    let [left, top, right, bottom] = outer;
+    let (width, height) = (right - left, bottom - top);
+    [
+        left + inner[0] * width,
+        top + inner[1] * height,
+        left + inner[2] * width,
+        top + inner[3] * height,
+    ]
+}
+
+/// The orientation undoing `orientation`
+fn inverse(orientation: Orientation) -> Orientation {
+    match orientation {
+        Orientation::Rotate90 => Orientation::Rotate270,
+        Orientation::Rotate270 => Orientation::Rotate90,
+        other => other,
+    }
+}
+
+fn mirrored(orientation: Orientation) -> bool {
+    matches!(
+        orientation,
+        Orientation::FlipHorizontal
+            | Orientation::FlipVertical
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH
+    )
+}
+
+/// Edges given as fractions of the stored image, as fractions of the image
+/// turned by `orientation`
+fn orient([left, top, right, bottom]: [f32; 4], orientation: Orientation) -> [f32; 4] {
+    let point = |x: f32, y: f32| match orientation {
+        Orientation::NoTransforms => (x, y),
+        Orientation::FlipHorizontal => (1.0 - x, y),
+        Orientation::Rotate180 => (1.0 - x, 1.0 - y),
+        Orientation::FlipVertical => (x, 1.0 - y),
+        Orientation::Rotate90FlipH => (y, x),
+        Orientation::Rotate90 => (1.0 - y, x),
+        Orientation::Rotate270FlipH => (1.0 - y, 1.0 - x),
+        Orientation::Rotate270 => (y, 1.0 - x),
+    };
+    let (x0, y0) = point(left, top);
+    let (x1, y1) = point(right, bottom);
+    [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]
+}
+
+/// The value of an XMP property, written as an attribute or an element
+pub(crate) fn property<'a>(xmp: &'a str, name: &str) -> Option<&'a str> {
+    attribute(xmp, name).or_else(|| {
+        let open = format!("<{name}>");
+        let start = xmp.find(&open)? + open.len();
+        let end = xmp[start..].find(&format!("</{name}>"))?;
+        Some(xmp[start..start + end].trim())
+    })
+}
+
+/// The value of the first `name="..."` attribute in `text`
+fn attribute<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!("{name}=\"");
+    let mut from = 0;
+    while let Some(at) = text[from..].find(&pattern) {
+        let start = from + at;
+        // Not the end of a longer name
+        if text[..start].ends_with(char::is_whitespace) {
+            let value = start + pattern.len();
+            let end = text[value..].find('"')?;
+            return Some(&text[value..value + end]);
+        }
+        from = start + pattern.len();
+    }
+    None
+}
+
+/// Bytes written as hex, as darktable stores small parameters
+fn hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,103 @@
+// src/transform/simulate.rs
+//
+// `--simulate`: how an image looks to viewers with a color vision
+// deficiency, written as extra outputs for accessibility reviews of charts
+// and screenshots. Each pixel goes through the matrix of Machado, Oliveira
+// and Fernandes (2009) for a full deficiency, in linear light; alpha is
+// kept as is.
+
+use clap::ValueEnum;
+use image::DynamicImage;
+use std::sync::OnceLock;
+
+/// A color vision deficiency to simulate
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Deficiency {
+    /// No green cones (red-green, the most common)
+    Deuteranopia,
+    /// No red cones (red-green, reds darken)
+    Protanopia,
+    /// No blue cones (blue-yellow)
+    Tritanopia,
+}
+
+impl Deficiency {
+    /// Name added to the output names of its variants
+    pub fn name(self) -> &'static str {
+        match self {
+            Deficiency::Deuteranopia => "deuteranopia",
+            Deficiency::Protanopia => "protanopia",
+            Deficiency::Tritanopia => "tritanopia",
+        }
+    }
+
+    /// Linear RGB to linear RGB as seen with the deficiency
+    fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            Deficiency::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Deficiency::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Deficiency::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+/// `img` as seen with `deficiency`, in 8 bits
+pub fn simulate(img: &DynamicImage, deficiency: Deficiency) -> DynamicImage {
+    let matrix = deficiency.matrix();
+    let linear = linear_table();
+    let convert = |[r, g, b]: [u8; 3]| {
+        let rgb = [r, g, b].map(|v| linear[usize::from(v)]);
+        matrix.map(|row| encode(row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2]))
+    };
+    if img.color().has_alpha() {
+        let mut rgba = img.to_rgba8();
+        for pixel in rgba.pixels_mut() {
+            let [r, g, b, a] = pixel.0;
+            let [r, g, b] = convert([r, g, b]);
+            pixel.0 = [r, g, b, a];
+        }
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        let mut rgb = img.to_rgb8();
+        for pixel in rgb.pixels_mut() {
+            pixel.0 = convert(pixel.0);
+        }
+        DynamicImage::ImageRgb8(rgb)
+    }
+}
+
+/// Linear light of every sRGB byte
+fn linear_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        std::array::from_fn(|v| {
+            let v = v as f32 / 255.0;
+            match v <= 0.04045 {
+                true => v / 12.92,
+                false => ((v + 0.055) / 1.055).powf(2.4),
+            }
+        })
+    })
+}
+
+/// The sRGB byte of linear light `v`
+fn encode(v: f32) -> u8 {
+    let v = v.clamp(0.0, 1.0);
+    let v = match v <= 0.0031308 {
+        true => v * 12.92,
+        false => 1.055 * v.powf(1.0 / 2.4) - 0.055,
+    };
+    (v * 255.0).round() as u8
+}
@@ -0,0 +1,84 @@
+// src/transform/tonemap.rs
+//
+// Tone mapping of floating-point (HDR) sources such as OpenEXR renders and
+// Radiance .hdr files into displayable 8-bit sRGB.
+
+use clap::ValueEnum;
+use image::{DynamicImage, Rgba32FImage, RgbaImage};
+#[cfg(feature = "native")]
+use rayon::prelude::*;
+
+/// Curve compressing scene-linear values into the displayable range
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Tonemap {
+    /// ACES filmic approximation (Narkowicz)
+    Aces,
+    /// Reinhard `x / (1 + x)`
+    Reinhard,
+    /// Clip values above 1.0
+    Clamp,
+}
+
+impl Tonemap {
+    fn map(self, x: f32) -> f32 {
+        match self {
+            Tonemap::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+            Tonemap::Reinhard => x / (1.0 + x),
+            Tonemap::Clamp => x,
+        }
+    }
+}
+
+/// True for sources decoded with floating-point samples
+pub fn is_hdr(img: &DynamicImage) -> bool {
+    matches!(
+        img,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    )
+}
+
+/// Applies exposure (in stops) and the tone curve, then encodes to sRGB
+pub fn tonemap(img: &DynamicImage, exposure: f32, operator: Tonemap) -> DynamicImage {
+    let has_alpha = img.color().has_alpha();
+    let src: Rgba32FImage = img.to_rgba32f();
+    let gain = 2f32.powf(exposure);
+
+    let mut out = RgbaImage::new(src.width(), src.height());
+    let map_pixel = |(dst, px): (&mut [u8], &[f32])| {
+        for c in 0..3 {
+            let mapped = operator.map((px[c] * gain).max(0.0)).clamp(0.0, 1.0);
+            dst[c] = (linear_to_srgb(mapped) * 255.0).round() as u8;
+        }
+        dst[3] = (px[3].clamp(0.0, 1.0) * 255.0).round() as u8;
+    };
+    #[cfg(feature = "native")]
+    out.par_chunks_mut(4)
+        .zip(src.par_chunks(4))
+        .for_each(map_pixel);
+    #[cfg(not(feature = "native"))]
+    out.chunks_mut(4).zip(src.chunks(4)).for_each(map_pixel);
+
+    if has_alpha {
+        DynamicImage::ImageRgba8(out)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(out).to_rgb8())
+    }
+}
+
+/// sRGB transfer function
+pub(super) fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of the sRGB transfer function
+pub(super) fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.040_45 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
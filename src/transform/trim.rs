@@ -0,0 +1,48 @@
+// src/transform/trim.rs
+//
+// Trimming of uniform borders (scanner margins, letterbox bars).
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+/// Removes borders matching the top-left pixel color within `fuzz` percent.
+/// A completely uniform image is returned unchanged.
+pub fn trim(img: &DynamicImage, fuzz: f32) -> DynamicImage {
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    if width == 0 || height == 0 {
+        return img.clone();
+    }
+
+    let reference = *rgba.get_pixel(0, 0);
+    let tolerance = (fuzz.clamp(0.0, 100.0) / 100.0 * 255.0).round() as u8;
+    let row_uniform = |y: u32| (0..width).all(|x| matches(&rgba, x, y, reference, tolerance));
+    let col_uniform = |x: u32, top: u32, bottom: u32| {
+        (top..bottom).all(|y| matches(&rgba, x, y, reference, tolerance))
+    };
+
+    let Some(top) = (0..height).find(|&y| !row_uniform(y)) else {
+        return img.clone();
+    };
+    let bottom = (top..height)
+        .rev()
+        .find(|&y| !row_uniform(y))
+        .map_or(height, |y| y + 1);
+    let left = (0..width)
+        .find(|&x| !col_uniform(x, top, bottom))
+        .unwrap_or(0);
+    let right = (left..width)
+        .rev()
+        .find(|&x| !col_uniform(x, top, bottom))
+        .map_or(width, |x| x + 1);
+
+    img.crop_imm(left, top, right - left, bottom - top)
+}
+
+/// Whether a pixel lies within the tolerance of the border color on every channel
+fn matches(img: &RgbaImage, x: u32, y: u32, reference: Rgba<u8>, tolerance: u8) -> bool {
+    img.get_pixel(x, y)
+        .0
+        .iter()
+        .zip(reference.0)
+        .all(|(&c, r)| c.abs_diff(r) <= tolerance)
+}
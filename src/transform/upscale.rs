@@ -0,0 +1,114 @@
+// src/transform/upscale.rs
+//
+// Super-resolution upscaling (`--upscale 2x --upscale-model esrgan.onnx`,
+// feature `onnx`) for producing prints and hero images from small sources.
+// ESRGAN-style models map a 1x3xHxW RGB tensor to 1x3x(kH)x(kW); the image
+// is run through them in overlapping tiles of a fixed size, keeping only
+// each tile's center so seams between tiles don't show. Alpha is scaled
+// conventionally.
+//
+// Runs as a custom transform before scaling, so `--scales` then apply to the
+// upscaled image.
+
+use super::{Transform, TransformContext};
+use crate::onnx::OnnxModel;
+use anyhow::Result;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, Rgb, RgbImage, RgbaImage};
+use std::path::Path;
+
+/// Tile edge fed to the model
+const TILE: u32 = 128;
+/// Margin of each tile discarded to hide seams
+const OVERLAP: u32 = 8;
+
+/// Clap value parser for `2x`-style factors
+pub fn parse_factor_arg(s: &str) -> Result<u32, String> {
+    let digits = s.trim().trim_end_matches(['x', 'X']);
+    digits
+        .parse()
+        .ok()
+        .filter(|factor| (2..=8).contains(factor))
+        .ok_or_else(|| format!("invalid upscale factor '{s}' (expected 2x to 8x)"))
+}
+
+/// A super-resolution model and the factor it enlarges by
+pub struct Upscale {
+    model: OnnxModel,
+    factor: u32,
+}
+
+impl Upscale {
+    pub fn load(path: &Path, factor: u32) -> Result<Self> {
+        Ok(Self {
+            model: OnnxModel::load(path, (TILE, TILE))?,
+            factor,
+        })
+    }
+
+    /// Upscales one tile whose top-left corner is at (`x0`, `y0`), possibly
+    /// outside the image (edges are repeated)
+    fn tile(&self, rgb: &RgbImage, x0: i64, y0: i64) -> Result<RgbImage> {
+        let (width, height) = rgb.dimensions();
+        let input = RgbImage::from_fn(TILE, TILE, |x, y| {
+            let sx = (x0 + x as i64).clamp(0, width as i64 - 1) as u32;
+            let sy = (y0 + y as i64).clamp(0, height as i64 - 1) as u32;
+            *rgb.get_pixel(sx, sy)
+        });
+
+        let output = self.model.infer(&input)?;
+        let size = (TILE * self.factor) as usize;
+        let shape = output.shape();
+        if shape.len() != 4 || shape[1] < 3 || shape[2] != size || shape[3] != size {
+            anyhow::bail!(
+                "Upscaling model output has shape {shape:?}, expected [1, 3, {size}, {size}] for {}x",
+                self.factor
+            );
+        }
+        Ok(RgbImage::from_fn(size as u32, size as u32, |x, y| {
+            let value =
+                |c: usize| (output[[0, c, y as usize, x as usize]].clamp(0.0, 1.0) * 255.0).round();
+            Rgb([value(0) as u8, value(1) as u8, value(2) as u8])
+        }))
+    }
+}
+
+impl Transform for Upscale {
+    fn apply(&self, img: DynamicImage, _ctx: &TransformContext) -> Result<DynamicImage> {
+        let rgb = img.to_rgb8();
+        let (width, height) = rgb.dimensions();
+        let k = self.factor;
+        let mut out = RgbImage::new(width * k, height * k);
+
+        let step = TILE - 2 * OVERLAP;
+        for ty in (0..height).step_by(step as usize) {
+            for tx in (0..width).step_by(step as usize) {
+                let tile =
+                    self.tile(&rgb, tx as i64 - OVERLAP as i64, ty as i64 - OVERLAP as i64)?;
+                // The tile's center covers source pixels tx..tx + step
+                let keep_w = step.min(width - tx) * k;
+                let keep_h = step.min(height - ty) * k;
+                let center = imageops::crop_imm(&tile, OVERLAP * k, OVERLAP * k, keep_w, keep_h);
+                imageops::replace(&mut out, &*center, (tx * k) as i64, (ty * k) as i64);
+            }
+        }
+
+        if !img.color().has_alpha() {
+            return Ok(DynamicImage::ImageRgb8(out));
+        }
+        let alpha = imageops::resize(
+            &img.to_luma_alpha8(),
+            width * k,
+            height * k,
+            FilterType::Lanczos3,
+        );
+        Ok(DynamicImage::ImageRgba8(RgbaImage::from_fn(
+            width * k,
+            height * k,
+            |x, y| {
+                let [r, g, b] = out.get_pixel(x, y).0;
+                image::Rgba([r, g, b, alpha.get_pixel(x, y)[1]])
+            },
+        )))
+    }
+}
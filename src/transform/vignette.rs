@@ -0,0 +1,34 @@
+// src/transform/vignette.rs
+//
+// Radial darkening toward the image corners.
+
+use image::DynamicImage;
+
+/// Darkens the corners by up to `strength` percent with a smooth falloff
+pub fn vignette(img: &DynamicImage, strength: f32) -> DynamicImage {
+    let has_alpha = img.color().has_alpha();
+    let mut rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+
+    let amount = strength.clamp(0.0, 100.0) / 100.0;
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let radius = (cx * cx + cy * cy).sqrt().max(1.0);
+
+    for (x, y, pixel) in rgba.enumerate_pixels_mut() {
+        let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+        let d = (dx * dx + dy * dy).sqrt() / radius;
+
+        // Untouched center, smoothstep falloff from 40% of the radius outward
+        let t = ((d - 0.4) / 0.6).clamp(0.0, 1.0);
+        let factor = 1.0 - amount * t * t * (3.0 - 2.0 * t);
+        for c in 0..3 {
+            pixel[c] = (pixel[c] as f32 * factor).round() as u8;
+        }
+    }
+
+    if has_alpha {
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba).to_rgb8())
+    }
+}
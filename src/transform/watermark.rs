@@ -0,0 +1,192 @@
+// src/transform/watermark.rs
+//
+// `--invisible-watermark`: a mark hidden in every output so a leaked copy
+// can be traced to who it was issued to. The ID is hashed to a 64-bit
+// fingerprint, and each 8×8 block of the luma carries one of its bits,
+// blocks being spread over the bits by their position: the bit is the
+// sign of the difference between two mid-frequency DCT coefficients of
+// the block, (1,2) and (2,1), pushed at least `STRENGTH` apart. JPEG
+// quantizes the two alike, so the mark survives recompression down to low
+// qualities; flat areas change by a few levels, which lossy WebP smooths
+// away where there's no texture to hide them in. It's embedded after the
+// other color effects of each scaled variant, on the variant's own block
+// grid: resizing or cropping an output afterwards shifts the grid and
+// loses it.
+//
+// Detection reads the bit of every block whose coefficients are clearly
+// apart and takes each fingerprint bit by majority; how consistently blocks
+// agree tells a marked image (close to all) from an unmarked one (about
+// half, or no votes at all when flat).
+
+use image::{DynamicImage, GenericImageView, ImageBuffer};
+use std::f32::consts::PI;
+use std::sync::OnceLock;
+
+/// Least difference the two coefficients of a block are pushed apart by
+const STRENGTH: f32 = 24.0;
+/// Least difference a block's coefficients must be apart by to vote: flat
+/// blocks of an unmarked image would all read as zero
+const MIN_DIFFERENCE: f32 = STRENGTH / 3.0;
+/// Share of blocks that must agree with their bit for a mark to be found
+const MIN_AGREEMENT: f32 = 0.7;
+/// Most fingerprint bits a detected mark may differ in and match an ID
+const MAX_BIT_ERRORS: u32 = 6;
+
+/// A mark carrying the fingerprint of an ID
+#[derive(Clone, Copy, Debug)]
+pub struct Watermark {
+    payload: u64,
+}
+
+/// What `detect` read from an image
+#[derive(Clone, Copy, Debug)]
+pub struct Detection {
+    /// Fingerprint bits by majority
+    pub payload: u64,
+    /// Share of the voting blocks agreeing with their bit, 0.5 to 1 (0
+    /// when none vote)
+    pub agreement: f32,
+}
+
+impl Watermark {
+    pub fn new(id: &str) -> Self {
+        Self {
+            payload: fingerprint(id),
+        }
+    }
+
+    /// `img` carrying the mark, in 8 bits
+    pub fn embed(&self, img: &DynamicImage) -> DynamicImage {
+        let has_alpha = img.color().has_alpha();
+        let (width, height) = img.dimensions();
+        let (channels, mut bytes) = match has_alpha {
+            true => (4, img.to_rgba8().into_raw()),
+            false => (3, img.to_rgb8().into_raw()),
+        };
+        let luma = luma(img);
+        let (b12, b21) = (basis(1, 2), basis(2, 1));
+        for by in 0..height / 8 {
+            for bx in 0..width / 8 {
+                let bit = self.payload >> bit_index(bx, by) & 1 == 1;
+                let block = |x: u32, y: u32| luma[((by * 8 + y) * width + bx * 8 + x) as usize];
+                let difference = coefficient(&block, &b12) - coefficient(&block, &b21);
+                let target = if bit { STRENGTH } else { -STRENGTH };
+                let shift = match bit {
+                    true if difference < target => target - difference,
+                    false if difference > target => target - difference,
+                    _ => continue,
+                };
+                // Moves (1,2) up and (2,1) down by half the shift each
+                for y in 0..8 {
+                    for x in 0..8 {
+                        let i = (y * 8 + x) as usize;
+                        let delta = shift / 2.0 * (b12[i] - b21[i]);
+                        let at = ((by * 8 + y) * width + bx * 8 + x) as usize * channels;
+                        for value in &mut bytes[at..at + 3] {
+                            *value = (f32::from(*value) + delta).round().clamp(0.0, 255.0) as u8;
+                        }
+                    }
+                }
+            }
+        }
+        match has_alpha {
+            true => DynamicImage::ImageRgba8(
+                ImageBuffer::from_raw(width, height, bytes).expect("same size"),
+            ),
+            false => DynamicImage::ImageRgb8(
+                ImageBuffer::from_raw(width, height, bytes).expect("same size"),
+            ),
+        }
+    }
+}
+
+impl Detection {
+    /// Whether the blocks agree enough for this to be a mark
+    pub fn is_marked(&self) -> bool {
+        self.agreement >= MIN_AGREEMENT
+    }
+
+    /// Whether the mark carries `id`
+    pub fn carries(&self, id: &str) -> bool {
+        self.is_marked() && (self.payload ^ fingerprint(id)).count_ones() <= MAX_BIT_ERRORS
+    }
+}
+
+/// Reads the mark `img` may carry
+pub fn detect(img: &DynamicImage) -> Detection {
+    let (width, height) = img.dimensions();
+    let luma = luma(img);
+    let (b12, b21) = (basis(1, 2), basis(2, 1));
+    // Blocks voting one and zero for each bit
+    let mut votes = [[0u32; 2]; 64];
+    for by in 0..height / 8 {
+        for bx in 0..width / 8 {
+            let block = |x: u32, y: u32| luma[((by * 8 + y) * width + bx * 8 + x) as usize];
+            let difference = coefficient(&block, &b12) - coefficient(&block, &b21);
+            if difference.abs() >= MIN_DIFFERENCE {
+                votes[bit_index(bx, by) as usize][usize::from(difference > 0.0)] += 1;
+            }
+        }
+    }
+    let mut payload = 0;
+    let (mut agreeing, mut total) = (0, 0);
+    for (bit, [zeros, ones]) in votes.into_iter().enumerate() {
+        payload |= u64::from(ones > zeros) << bit;
+        agreeing += zeros.max(ones);
+        total += zeros + ones;
+    }
+    Detection {
+        payload,
+        agreement: match total {
+            0 => 0.0,
+            total => agreeing as f32 / total as f32,
+        },
+    }
+}
+
+/// The 64-bit fingerprint an ID is carried as (FNV-1a)
+pub fn fingerprint(id: &str) -> u64 {
+    id.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Which fingerprint bit the block at (`bx`, `by`) carries, scattered so
+/// neighbouring blocks carry different bits
+fn bit_index(bx: u32, by: u32) -> u32 {
+    let mixed = u64::from(bx).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ u64::from(by).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+    (mixed.rotate_left(17) >> 58) as u32
+}
+
+/// Luma of every pixel, row by row
+fn luma(img: &DynamicImage) -> Vec<f32> {
+    img.to_rgb8()
+        .pixels()
+        .map(|p| 0.299 * f32::from(p[0]) + 0.587 * f32::from(p[1]) + 0.114 * f32::from(p[2]))
+        .collect()
+}
+
+/// The DCT coefficient of `block` (pixel at x, y) for a `basis`
+fn coefficient(block: &impl Fn(u32, u32) -> f32, basis: &[f32; 64]) -> f32 {
+    let mut sum = 0.0;
+    for y in 0..8 {
+        for x in 0..8 {
+            sum += block(x, y) * basis[(y * 8 + x) as usize];
+        }
+    }
+    sum
+}
+
+/// The orthonormal 8×8 DCT-II basis image of vertical frequency `u` and
+/// horizontal frequency `v`
+fn basis(u: usize, v: usize) -> [f32; 64] {
+    static COSINES: OnceLock<[[f32; 8]; 8]> = OnceLock::new();
+    let cosines = COSINES.get_or_init(|| {
+        std::array::from_fn(|k| {
+            let scale = if k == 0 { (1.0f32 / 8.0).sqrt() } else { 0.5 };
+            std::array::from_fn(|n| scale * ((2 * n + 1) as f32 * k as f32 * PI / 16.0).cos())
+        })
+    });
+    std::array::from_fn(|i| cosines[u][i / 8] * cosines[v][i % 8])
+}
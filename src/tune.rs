@@ -0,0 +1,238 @@
+// src/tune.rs
+//
+// `rsimg tune`: encodes one image at several qualities and formats and lays
+// the same region of every result side by side in a labeled grid, so the
+// quality setting can be picked by eye instead of by trial and error.
+
+use crate::font;
+use anyhow::{Context, Result};
+use image::{DynamicImage, GenericImage, Rgba, RgbaImage};
+use owo_colors::OwoColorize;
+use rsimg::budget::format_size;
+use rsimg::decode;
+use rsimg::encode::{EncodeOptions, save_image};
+use rsimg::exit::InvalidConfig;
+use rsimg::i18n::{self, Msg, tr};
+use rsimg::scratch::ScratchDir;
+use rsimg::transform::crop;
+use rsimg::transform::geometry::Rect;
+use std::path::{Path, PathBuf};
+
+/// Formats whose output depends on the quality setting
+const LOSSY_FORMATS: &[&str] = &["jpg", "jpeg", "webp"];
+/// Side of the default comparison region
+const DEFAULT_REGION: u32 = 256;
+
+const LABEL_SCALE: u32 = 2;
+const PADDING: u32 = 6;
+const BACKGROUND: Rgba<u8> = Rgba([32, 32, 32, 255]);
+const TEXT: Rgba<u8> = Rgba([240, 240, 240, 255]);
+
+#[derive(clap::Args)]
+pub struct TuneArgs {
+    /// Image to compare encodings of
+    #[arg(value_name = "INPUT", help = "Input image")]
+    pub input: PathBuf,
+
+    /// Qualities to compare (comma-separated)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = vec![60, 70, 80, 90],
+        value_name = "QUALITIES",
+        help = "Qualities to compare"
+    )]
+    pub qualities: Vec<u8>,
+
+    /// Formats to compare (comma-separated); lossless ones get a single tile
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = vec!["jpg".to_string(), "webp".to_string()],
+        value_name = "FORMATS",
+        help = "Formats to compare"
+    )]
+    pub formats: Vec<String>,
+
+    /// Region shown in every tile (default: 256x256 at the center)
+    #[arg(long, value_name = "X,Y,WxH", help = "Region to compare")]
+    pub crop: Option<Rect>,
+
+    /// Grid image to write (default: <input>_tune.png next to the input)
+    #[arg(short, long, value_name = "FILE", help = "Grid image path")]
+    pub output: Option<PathBuf>,
+}
+
+/// One encoded variant: its label, encoded size and decoded region
+struct Tile {
+    label: String,
+    size: u64,
+    region: DynamicImage,
+}
+
+/// Runs the `tune` subcommand
+pub fn run(args: TuneArgs, tmpdir: &Path) -> Result<()> {
+    if let Some(q) = args.qualities.iter().find(|&&q| q > 100) {
+        return Err(InvalidConfig(tr!(Msg::QualityInvalid, q)).into());
+    }
+
+    let img = decode::load_image(&args.input)?;
+    let source_size = std::fs::metadata(&args.input)
+        .with_context(|| format!("Failed to read file: {}", args.input.display()))?
+        .len();
+    let rect = args.crop.unwrap_or_else(|| centered(&img));
+
+    let scratch = ScratchDir::new(tmpdir, "rsimg-tune")?;
+    let rows = encode_all(&img, &args, rect, scratch.path());
+    drop(scratch);
+    let rows = rows?;
+
+    let original = Tile {
+        label: tr!(Msg::Original),
+        size: source_size,
+        region: crop::crop_rect(&img, rect)?,
+    };
+
+    println!(
+        "{}",
+        format!("\n=== RSIMG — {} ===\n", i18n::text(Msg::TuneTitle))
+            .bold()
+            .cyan()
+    );
+    for tile in rows.iter().flatten() {
+        println!(
+            "  {:<16} {:>10}  {}",
+            tile.label.bright_white(),
+            format_size(tile.size).bright_yellow(),
+            tr!(
+                Msg::OfSource,
+                format!("{:.0}", tile.size as f64 * 100.0 / source_size as f64)
+            )
+            .dimmed()
+        );
+    }
+
+    let grid = grid(&original, &rows);
+    let output = match args.output {
+        Some(path) => path,
+        None => default_output(&args.input)?,
+    };
+    grid.save(&output)
+        .with_context(|| format!("Failed to save grid: {}", output.display()))?;
+    println!(
+        "\n  {} {}: {}\n",
+        "💾".bright_white(),
+        i18n::text(Msg::Grid),
+        output.display().to_string().bright_yellow()
+    );
+
+    Ok(())
+}
+
+/// The default comparison region: a square at the image center
+fn centered(img: &DynamicImage) -> Rect {
+    let width = img.width().min(DEFAULT_REGION);
+    let height = img.height().min(DEFAULT_REGION);
+    Rect {
+        x: (img.width() - width) / 2,
+        y: (img.height() - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Encodes the full image for every format and quality, one row per format
+fn encode_all(
+    img: &DynamicImage,
+    args: &TuneArgs,
+    rect: Rect,
+    scratch: &Path,
+) -> Result<Vec<Vec<Tile>>> {
+    let mut rows = Vec::with_capacity(args.formats.len());
+
+    for fmt in &args.formats {
+        let fmt = fmt.to_lowercase();
+        let lossy = LOSSY_FORMATS.contains(&fmt.as_str());
+        let qualities = if lossy {
+            args.qualities.as_slice()
+        } else {
+            &args.qualities[..1.min(args.qualities.len())]
+        };
+
+        let mut row = Vec::with_capacity(qualities.len());
+        for &quality in qualities {
+            let path = scratch.join(format!("q{quality}.{fmt}"));
+            let opts = EncodeOptions {
+                quality,
+                ..Default::default()
+            };
+            save_image(img, &path, &fmt, &opts)
+                .with_context(|| format!("Error encoding {fmt} at quality {quality}"))?;
+
+            let size = std::fs::metadata(&path)
+                .with_context(|| format!("Failed to read file: {}", path.display()))?
+                .len();
+            let decoded = decode::load_image(&path)
+                .with_context(|| format!("Can't preview {fmt} outputs"))?;
+
+            row.push(Tile {
+                label: if lossy {
+                    format!("{fmt} q{quality}")
+                } else {
+                    fmt.clone()
+                },
+                size,
+                region: crop::crop_rect(&decoded, rect)?,
+            });
+        }
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+/// Lays tiles out with the original first in every row, labels underneath
+fn grid(original: &Tile, rows: &[Vec<Tile>]) -> RgbaImage {
+    let (tile_w, tile_h) = (original.region.width(), original.region.height());
+    let label_h = font::text_size("", LABEL_SCALE).1 + 2 * PADDING;
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0) as u32 + 1;
+    let cell_w = tile_w + PADDING;
+    let cell_h = tile_h + label_h;
+
+    let mut grid = RgbaImage::from_pixel(
+        columns * cell_w + PADDING,
+        rows.len().max(1) as u32 * cell_h + PADDING,
+        BACKGROUND,
+    );
+
+    for (r, row) in rows.iter().enumerate() {
+        let y = PADDING + r as u32 * cell_h;
+        for (c, tile) in std::iter::once(original).chain(row).enumerate() {
+            let x = PADDING + c as u32 * cell_w;
+            let _ = grid.copy_from(&tile.region.to_rgba8(), x, y);
+            let mut label = format!("{} {}", tile.label, format_size(tile.size));
+            while font::text_size(&label, LABEL_SCALE).0 > tile_w {
+                label.pop();
+            }
+            font::draw_text(
+                &mut grid,
+                x,
+                y + tile_h + PADDING,
+                &label,
+                LABEL_SCALE,
+                TEXT,
+            );
+        }
+    }
+
+    grid
+}
+
+/// `photo.jpg` → `photo_tune.png` in the same directory
+fn default_output(input: &Path) -> Result<PathBuf> {
+    let stem = input
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Invalid filename: {}", input.display()))?;
+    Ok(input.with_file_name(format!("{stem}_tune.png")))
+}
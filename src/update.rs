@@ -0,0 +1,233 @@
+// src/update.rs
+//
+// `rsimg self-update` and the opt-in new-version notice, for the static
+// binaries published as GitHub releases rather than installed with cargo.
+// Each release carries one binary per target, `rsimg-{target}` (`.exe` on
+// Windows), next to its SHA-256 in `rsimg-{target}.sha256`; self-update
+// downloads the newest, checks the digest and swaps it in for the running
+// executable. The notice (--check-updates or RSIMG_CHECK_UPDATES=1) asks
+// GitHub at most once a day, remembering when in the user cache directory,
+// and stays silent when anything goes wrong.
+
+use anyhow::{Context, Result};
+use rsimg::i18n::{Msg, tr};
+use rsimg::paths::long_path;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/pankaspe/rsimg/releases/latest";
+/// Target triple this binary was built for, as used in asset names
+const TARGET: &str = env!("RSIMG_TARGET");
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// How often the new-version notice asks GitHub
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Largest binary accepted from a release
+const MAX_BINARY_BYTES: u64 = 256 * 1024 * 1024;
+
+#[derive(clap::Args)]
+pub struct SelfUpdateArgs {
+    /// Only report whether a newer release is out
+    #[arg(long, help = "Check for a newer release without installing it")]
+    pub check: bool,
+}
+
+/// The newest published release
+struct Release {
+    version: (u64, u64, u64),
+    tag: String,
+    /// Asset names and download URLs
+    assets: Vec<(String, String)>,
+}
+
+impl Release {
+    fn asset(&self, name: &str) -> Option<&str> {
+        self.assets
+            .iter()
+            .find(|(asset, _)| asset == name)
+            .map(|(_, url)| url.as_str())
+    }
+}
+
+/// Runs the `self-update` subcommand
+pub fn run(args: SelfUpdateArgs) -> Result<()> {
+    let release = latest_release(Duration::from_secs(30))?;
+    if release.version <= current_version() {
+        println!("{}", tr!(Msg::UpToDate, VERSION));
+        return Ok(());
+    }
+    if args.check {
+        println!("{}", tr!(Msg::UpdateAvailable, release.tag, VERSION));
+        return Ok(());
+    }
+
+    let name = format!("rsimg-{TARGET}{}", std::env::consts::EXE_SUFFIX);
+    let url = release
+        .asset(&name)
+        .with_context(|| format!("Release {} has no binary for {TARGET}", release.tag))?;
+    let checksum_url = release
+        .asset(&format!("{name}.sha256"))
+        .with_context(|| format!("Release {} has no checksum for {name}", release.tag))?;
+
+    let binary = download(url, MAX_BINARY_BYTES)?;
+    let checksum = String::from_utf8(download(checksum_url, 1024)?)
+        .with_context(|| format!("Invalid checksum file for {name}"))?;
+    // `sha256sum` output: the digest, then the file name
+    let expected = checksum.split_whitespace().next().unwrap_or_default();
+    let actual: String = Sha256::digest(&binary)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    if !expected.eq_ignore_ascii_case(&actual) {
+        anyhow::bail!("Checksum mismatch for {name}: expected {expected}, got {actual}");
+    }
+
+    let exe = std::env::current_exe().context("Failed to locate the running executable")?;
+    replace_executable(&exe, &binary)?;
+    println!("{}", tr!(Msg::Updated, VERSION, release.tag));
+    Ok(())
+}
+
+/// Prints a notice on stderr when a newer release is out, asking GitHub at
+/// most once per `CHECK_INTERVAL`
+pub fn notify_if_outdated() {
+    let Some(stamp) = cache_dir().map(|dir| dir.join("update-check")) else {
+        return;
+    };
+    let checked = std::fs::metadata(&stamp)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+    if checked.is_some_and(|elapsed| elapsed < CHECK_INTERVAL) {
+        return;
+    }
+    // Stamped before asking, so an unreachable GitHub isn't retried every run
+    if let Some(dir) = stamp.parent()
+        && std::fs::create_dir_all(dir).is_err()
+    {
+        return;
+    }
+    if std::fs::write(&stamp, VERSION).is_err() {
+        return;
+    }
+
+    if let Ok(release) = latest_release(Duration::from_secs(3))
+        && release.version > current_version()
+    {
+        eprintln!("  {}", tr!(Msg::UpdateAvailable, release.tag, VERSION));
+    }
+}
+
+/// Asks GitHub for the latest release
+fn latest_release(timeout: Duration) -> Result<Release> {
+    let text = get(LATEST_RELEASE, timeout)?
+        .body_mut()
+        .read_to_string()
+        .context("Failed to read the latest release")?;
+    let json: Value = serde_json::from_str(&text).context("Invalid release metadata")?;
+
+    let tag = json["tag_name"]
+        .as_str()
+        .context("Release metadata has no tag")?
+        .to_string();
+    let version =
+        parse_version(&tag).with_context(|| format!("Release tag {tag} isn't a version"))?;
+    let assets = json["assets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|asset| {
+            let name = asset["name"].as_str()?;
+            let url = asset["browser_download_url"].as_str()?;
+            Some((name.to_string(), url.to_string()))
+        })
+        .collect();
+    Ok(Release {
+        version,
+        tag,
+        assets,
+    })
+}
+
+/// Downloads a release asset of at most `limit` bytes
+fn download(url: &str, limit: u64) -> Result<Vec<u8>> {
+    get(url, Duration::from_secs(300))?
+        .body_mut()
+        .with_config()
+        .limit(limit)
+        .read_to_vec()
+        .with_context(|| format!("Failed to download {url}"))
+}
+
+fn get(url: &str, timeout: Duration) -> Result<ureq::http::Response<ureq::Body>> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(timeout))
+        .build()
+        .into();
+    agent
+        .get(url)
+        .header("User-Agent", concat!("rsimg/", env!("CARGO_PKG_VERSION")))
+        .header("Accept", "application/vnd.github+json")
+        .call()
+        .with_context(|| format!("Failed to fetch {url}"))
+}
+
+/// Writes `binary` next to `exe` and moves it in place
+///
+/// Windows can't replace a running executable, but can rename it: the old
+/// one is moved aside to `rsimg.old.exe`, and removed by the next update.
+fn replace_executable(exe: &Path, binary: &[u8]) -> Result<()> {
+    let temp = exe.with_extension("new");
+    std::fs::write(long_path(&temp), binary)
+        .with_context(|| format!("Failed to write {}", temp.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("Failed to make {} executable", temp.display()))?;
+    }
+
+    #[cfg(windows)]
+    {
+        let old = exe.with_extension("old.exe");
+        let _ = std::fs::remove_file(long_path(&old));
+        std::fs::rename(long_path(exe), long_path(&old))
+            .with_context(|| format!("Failed to move {} aside", exe.display()))?;
+    }
+
+    if let Err(err) = std::fs::rename(long_path(&temp), long_path(exe)) {
+        let _ = std::fs::remove_file(long_path(&temp));
+        return Err(err).with_context(|| format!("Failed to replace {}", exe.display()));
+    }
+    Ok(())
+}
+
+fn current_version() -> (u64, u64, u64) {
+    parse_version(VERSION).expect("package version is semver")
+}
+
+/// `major.minor.patch` of a tag such as `v1.2.3` or `1.2.3-rc.1`
+fn parse_version(tag: &str) -> Option<(u64, u64, u64)> {
+    let version = tag.trim_start_matches('v');
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(version)
+}
+
+/// Per-user cache directory for rsimg
+fn cache_dir() -> Option<PathBuf> {
+    let env = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+    let base = if cfg!(windows) {
+        env("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|home| PathBuf::from(home).join("Library/Caches"))
+    } else {
+        env("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    };
+    base.map(|base| base.join("rsimg"))
+}
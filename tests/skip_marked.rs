@@ -0,0 +1,94 @@
+// tests/skip_marked.rs
+//
+// Outputs of the lossless JPEG and APNG paths carry the rsimg marker, so a
+// second `--skip-marked` run over them leaves them alone.
+
+use image::codecs::gif::GifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::{Delay, Frame, Rgb, RgbImage, Rgba, RgbaImage};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A fresh directory under the system temp dir
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rsimg-skip-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("in")).unwrap();
+    dir
+}
+
+/// Runs rsimg over `input` into `output` with `--skip-marked`
+fn run(input: &Path, output: &Path, format: &str, extra: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_rsimg"))
+        .arg(input)
+        .args(["-o".as_ref(), output.as_os_str()])
+        .args(["--formats", format, "--scales", "100", "--skip-marked"])
+        .args(extra)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success());
+}
+
+/// Files written into `dir`, none when it wasn't created
+fn outputs(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| entries.map(|entry| entry.unwrap().path()).collect())
+        .unwrap_or_default()
+}
+
+#[test]
+fn lossless_jpeg_outputs_are_skipped() {
+    let dir = scratch("jpeg");
+    let image = RgbImage::from_fn(64, 48, |x, y| Rgb([x as u8 * 4, y as u8 * 5, 128]));
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, 90)
+        .encode_image(&image)
+        .unwrap();
+    fs::write(dir.join("in/photo.jpg"), jpeg).unwrap();
+
+    run(
+        &dir.join("in"),
+        &dir.join("first"),
+        "jpg",
+        &["--lossless-jpeg"],
+    );
+    assert_eq!(outputs(&dir.join("first")).len(), 1);
+    run(
+        &dir.join("first"),
+        &dir.join("second"),
+        "jpg",
+        &["--lossless-jpeg"],
+    );
+    assert!(outputs(&dir.join("second")).is_empty());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn apng_outputs_are_skipped() {
+    let dir = scratch("apng");
+    let mut gif = Vec::new();
+    {
+        let mut encoder = GifEncoder::new(&mut gif);
+        for shade in [0, 255] {
+            let image = RgbaImage::from_pixel(16, 16, Rgba([shade, 64, 128, 255]));
+            let frame = Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(100, 1));
+            encoder.encode_frame(frame).unwrap();
+        }
+    }
+    fs::write(dir.join("in/anim.gif"), gif).unwrap();
+
+    run(&dir.join("in"), &dir.join("first"), "apng", &[]);
+    let first = outputs(&dir.join("first"));
+    assert_eq!(first.len(), 1);
+    assert!(
+        fs::read(&first[0])
+            .unwrap()
+            .windows(4)
+            .any(|w| w == b"acTL")
+    );
+    run(&dir.join("first"), &dir.join("second"), "apng", &[]);
+    assert!(outputs(&dir.join("second")).is_empty());
+    fs::remove_dir_all(dir).unwrap();
+}